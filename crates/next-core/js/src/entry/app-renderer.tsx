@@ -8,14 +8,17 @@ type FileType =
   | "loading"
   | "not-found"
   | "head";
+type LayoutInfo = ({
+  segment: string;
+  page?: { module: any; chunks: string[] };
+  // the layout info of the parallel routes, by name of the slot
+  slots?: Record<string, LayoutInfo[]>;
+} & {
+  [componentKey in FileType]?: { module: any; chunks: string[] };
+})[];
 declare global {
   // an array of all layouts and the page
-  const LAYOUT_INFO: ({
-    segment: string;
-    page?: { module: any; chunks: string[] };
-  } & {
-    [componentKey in FileType]?: { module: any; chunks: string[] };
-  })[];
+  const LAYOUT_INFO: LayoutInfo;
   // array of chunks for the bootstrap script
   const BOOTSTRAP: string[];
   const IPC: Ipc<unknown, unknown>;
@@ -107,26 +110,48 @@ type ServerComponentsManifestModule = {
   [exportName: string]: { id: string; chunks: string[]; name: string };
 };
 
-async function runOperation(renderData: RenderData) {
-  const layoutInfoChunks: Record<string, string[]> = {};
-  const pageItem = LAYOUT_INFO[LAYOUT_INFO.length - 1];
-  const pageModule = pageItem.page!.module;
-  const Page = pageModule.default;
-  let tree: LoaderTree = ["", {}, { page: [() => Page, "page.js"] }];
-  layoutInfoChunks["page.js"] = pageItem.page!.chunks;
-  for (let i = LAYOUT_INFO.length - 2; i >= 0; i--) {
-    const info = LAYOUT_INFO[i];
+function createLoaderTree(
+  layoutInfo: LayoutInfo,
+  prefix: string,
+  layoutInfoChunks: Record<string, string[]>
+): LoaderTree {
+  const pageItem = layoutInfo[layoutInfo.length - 1];
+  const Page = pageItem.page!.module.default;
+  let tree: LoaderTree = ["", {}, { page: [() => Page, `${prefix}page.js`] }];
+  layoutInfoChunks[`${prefix}page.js`] = pageItem.page!.chunks;
+  for (let i = layoutInfo.length - 2; i >= 0; i--) {
+    const info = layoutInfo[i];
     const components: ComponentsType = {};
+    const parallelRoutes: LoaderTree[1] = { children: tree };
     for (const key of Object.keys(info)) {
       if (key === "segment") {
         continue;
       }
+      if (key === "slots") {
+        // Slots are passed as props with the name of the slot to the layout.
+        for (const [slot, slotInfo] of Object.entries(info.slots!)) {
+          parallelRoutes[slot] = createLoaderTree(
+            slotInfo,
+            `${prefix}@${slot}${i}/`,
+            layoutInfoChunks
+          );
+        }
+        continue;
+      }
       const k = key as FileType;
-      components[k] = [() => info[k]!.module.default, `${k}${i}.js`];
-      layoutInfoChunks[`${k}${i}.js`] = info[k]!.chunks;
+      components[k] = [() => info[k]!.module.default, `${prefix}${k}${i}.js`];
+      layoutInfoChunks[`${prefix}${k}${i}.js`] = info[k]!.chunks;
     }
-    tree = [info.segment, { children: tree }, components];
+    tree = [info.segment, parallelRoutes, components];
   }
+  return tree;
+}
+
+async function runOperation(renderData: RenderData) {
+  const layoutInfoChunks: Record<string, string[]> = {};
+  const pageItem = LAYOUT_INFO[LAYOUT_INFO.length - 1];
+  const pageModule = pageItem.page!.module;
+  const tree = createLoaderTree(LAYOUT_INFO, "", layoutInfoChunks);
 
   const proxyMethodsForModule = (
     id: string,
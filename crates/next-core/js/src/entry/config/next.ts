@@ -0,0 +1,79 @@
+import IPC, { Ipc } from "@vercel/turbopack-next/internal/ipc";
+
+import { PHASE_DEVELOPMENT_SERVER } from "next/dist/shared/lib/constants";
+import { defaultConfig } from "next/dist/server/config-shared";
+
+import * as configModule from ".";
+
+type IpcIncomingMessage = {
+  type: "evaluate";
+};
+
+type IpcOutgoingMessage = {
+  type: "jsonValue";
+  data: string;
+};
+
+const ipc = IPC as Ipc<IpcIncomingMessage, IpcOutgoingMessage>;
+
+(async () => {
+  while (true) {
+    const msg = await ipc.recv();
+
+    switch (msg.type) {
+      case "evaluate": {
+        let config;
+        try {
+          config = await loadNextConfig();
+        } catch (e) {
+          return ipc.sendError(e as Error);
+        }
+        await ipc.send({
+          type: "jsonValue",
+          data: JSON.stringify(config),
+        });
+        break;
+      }
+      default: {
+        console.error("unexpected message type", msg.type);
+        process.exit(1);
+      }
+    }
+  }
+})();
+
+/**
+ * Evaluates the user's next.config.js and resolves everything that is
+ * computed by functions, so the result can be serialized to JSON.
+ */
+async function loadNextConfig() {
+  const exported =
+    "default" in configModule ? (configModule as any).default : configModule;
+
+  const userConfig =
+    typeof exported === "function"
+      ? await exported(PHASE_DEVELOPMENT_SERVER, { defaultConfig })
+      : await exported;
+
+  const config = { ...userConfig };
+
+  const [rewrites, redirects, headers] = await Promise.all([
+    typeof config.rewrites === "function" ? config.rewrites() : [],
+    typeof config.redirects === "function" ? config.redirects() : [],
+    typeof config.headers === "function" ? config.headers() : [],
+  ]);
+
+  // Rewrites can either be a list, which is applied after the filesystem, or
+  // an object with explicit phases.
+  config.rewrites = Array.isArray(rewrites)
+    ? { beforeFiles: [], afterFiles: rewrites, fallback: [] }
+    : {
+        beforeFiles: rewrites.beforeFiles ?? [],
+        afterFiles: rewrites.afterFiles ?? [],
+        fallback: rewrites.fallback ?? [],
+      };
+  config.redirects = redirects;
+  config.headers = headers;
+
+  return config;
+}
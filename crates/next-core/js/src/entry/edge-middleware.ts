@@ -0,0 +1,124 @@
+import { NextRequest } from "next/dist/server/web/spec-extension/request";
+import { NextFetchEvent } from "next/dist/server/web/spec-extension/fetch-event";
+import { pathToRegexp } from "next/dist/compiled/path-to-regexp";
+import type { MiddlewareResult, RenderData } from "types/turbopack";
+
+import * as middlewareModule from ".";
+
+type MiddlewareConfig = {
+  matcher?: string | Array<string | { source: string }>;
+};
+
+const { middleware, default: defaultExport, config } = middlewareModule as any;
+const handler = middleware ?? defaultExport;
+const matchers = getMatchers(config);
+
+// The node.js host runs the middleware through the entry of the sandbox.
+(self as any)._ENTRIES = {
+  ...(self as any)._ENTRIES,
+  middleware: runOperation,
+};
+
+function getMatchers(config: MiddlewareConfig | undefined): RegExp[] | null {
+  const matcher = config?.matcher;
+  if (matcher == null) {
+    return null;
+  }
+  return (Array.isArray(matcher) ? matcher : [matcher]).map((m) =>
+    pathToRegexp(typeof m === "string" ? m : m.source)
+  );
+}
+
+async function runOperation(
+  renderData: RenderData,
+  body: ArrayBuffer
+): Promise<MiddlewareResult> {
+  if (typeof handler !== "function") {
+    throw new Error(
+      "The middleware must export a `middleware` or a `default` function"
+    );
+  }
+
+  if (matchers != null && !matchers.some((m) => m.test(renderData.path))) {
+    return { type: "next", headers: [], requestHeaders: null };
+  }
+
+  const host = renderData.headers.host ?? "localhost";
+  const url = new URL(renderData.url, `http://${host}`);
+  const headers = new Headers();
+  for (const [name, value] of Object.entries(renderData.headers)) {
+    for (const v of Array.isArray(value) ? value : [value]) {
+      headers.append(name, v);
+    }
+  }
+  const request = new NextRequest(url, {
+    method: renderData.method,
+    headers,
+    body:
+      renderData.method === "GET" || renderData.method === "HEAD"
+        ? undefined
+        : body,
+  });
+  const event = new NextFetchEvent({ request, page: "/" });
+
+  const response: Response | undefined = await handler(request, event);
+  if (response == null) {
+    return { type: "next", headers: [], requestHeaders: null };
+  }
+
+  const responseHeaders: string[] = [];
+  response.headers.forEach((value, name) => {
+    if (!name.startsWith("x-middleware-")) {
+      responseHeaders.push(name, value);
+    }
+  });
+
+  const requestHeaders = getRequestHeaders(response);
+
+  const rewrite = response.headers.get("x-middleware-rewrite");
+  if (rewrite != null) {
+    const rewriteUrl = new URL(rewrite, url);
+    if (rewriteUrl.origin !== url.origin) {
+      throw new Error(
+        `Rewriting to an external url (${rewriteUrl}) is not supported`
+      );
+    }
+    return {
+      type: "rewrite",
+      path: rewriteUrl.pathname,
+      search: rewriteUrl.search,
+      headers: responseHeaders,
+      requestHeaders,
+    };
+  }
+
+  if (response.headers.get("x-middleware-next") === "1") {
+    return { type: "next", headers: responseHeaders, requestHeaders };
+  }
+
+  return {
+    type: "response",
+    status: response.status,
+    headers: responseHeaders,
+    body: await response.arrayBuffer(),
+  };
+}
+
+// `NextResponse.next({ request: { headers } })` lists the names of all headers
+// of the request in `x-middleware-override-headers` and passes their values
+// as `x-middleware-request-<name>`.
+function getRequestHeaders(response: Response): string[] | null {
+  const names = response.headers.get("x-middleware-override-headers");
+  if (names == null) {
+    return null;
+  }
+  const requestHeaders: string[] = [];
+  for (const name of names.split(",")) {
+    const key = name.trim();
+    const value = response.headers.get(`x-middleware-request-${key}`);
+    if (key !== "" && value != null) {
+      requestHeaders.push(key, value);
+    }
+  }
+  return requestHeaders;
+}
@@ -0,0 +1,90 @@
+import IPC, { Ipc } from "@vercel/turbopack-next/internal/ipc";
+import type { MiddlewareResult, RenderData } from "types/turbopack";
+
+import { Buffer } from "node:buffer";
+
+const ipc = IPC as Ipc<IpcIncomingMessage, IpcOutgoingMessage>;
+
+type IpcIncomingMessage =
+  | {
+      type: "headers";
+      data: RenderData;
+    }
+  | {
+      type: "bodyChunk";
+      data: Array<number>;
+    }
+  | { type: "bodyEnd" };
+
+// The response body is sent base64 encoded.
+type IpcOutgoingMessage =
+  | Exclude<MiddlewareResult, { type: "response" }>
+  | {
+      type: "response";
+      status: number;
+      headers: string[];
+      body: string;
+    };
+
+// The middleware runs in the edge sandbox, which is created before this entry
+// is evaluated.
+declare const EDGE_SANDBOX: {
+  _ENTRIES: {
+    middleware: (
+      renderData: RenderData,
+      body: ArrayBuffer
+    ) => Promise<MiddlewareResult>;
+  };
+};
+
+(async () => {
+  while (true) {
+    const msg = await ipc.recv();
+
+    let renderData: RenderData;
+    switch (msg.type) {
+      case "headers": {
+        renderData = msg.data;
+        break;
+      }
+      default: {
+        console.error("unexpected message type", msg.type);
+        process.exit(1);
+      }
+    }
+
+    let body = Buffer.alloc(0);
+    loop: while (true) {
+      const msg = await ipc.recv();
+
+      switch (msg.type) {
+        case "bodyChunk": {
+          body = Buffer.concat([body, Buffer.from(msg.data)]);
+          break;
+        }
+        case "bodyEnd": {
+          break loop;
+        }
+        default: {
+          console.error("unexpected message type", msg.type);
+          process.exit(1);
+        }
+      }
+    }
+
+    const result = await EDGE_SANDBOX._ENTRIES.middleware(
+      renderData,
+      body.buffer.slice(body.byteOffset, body.byteOffset + body.byteLength)
+    );
+    if (result.type === "response") {
+      ipc.send({
+        ...result,
+        body: Buffer.from(result.body).toString("base64"),
+      });
+    } else {
+      ipc.send(result);
+    }
+  }
+})().catch((err) => {
+  ipc.sendError(err);
+});
@@ -16,10 +16,25 @@ import Component, * as otherExports from ".";
 ("TURBOPACK { transition: next-client }");
 import chunkGroup from ".";
 import type { BuildManifest } from "next/dist/server/get-page-files";
+import type { ImageConfigComplete } from "next/dist/shared/lib/image-config";
 import type { ChunkGroup } from "types/next";
 
 const ipc = IPC as Ipc<IpcIncomingMessage, IpcOutgoingMessage>;
 
+/**
+ * The render options that depend on the next.config.js. They are passed in by
+ * turbopack as part of the env.
+ */
+type ConfigRenderOptions = {
+  basePath: string;
+  images: ImageConfigComplete;
+};
+
+const configRenderOptions: ConfigRenderOptions | null = process.env
+  .__TURBOPACK_NEXT_RENDER_OPTIONS
+  ? JSON.parse(process.env.__TURBOPACK_NEXT_RENDER_OPTIONS)
+  : null;
+
 type IpcIncomingMessage = {
   type: "headers";
   data: RenderData;
@@ -109,25 +124,33 @@ async function runOperation(
       previewModeEncryptionKey: "",
       previewModeSigningKey: "",
     },
-    basePath: "",
+    basePath: configRenderOptions?.basePath ?? "",
     optimizeFonts: false,
     optimizeCss: false,
     nextScriptWorkers: false,
-    images: {
-      deviceSizes: [],
-      imageSizes: [],
-      loader: "default",
-      path: "",
-      loaderFile: "",
-      domains: [],
-      disableStaticImages: false,
-      minimumCacheTTL: 0,
-      formats: [],
-      dangerouslyAllowSVG: false,
-      contentSecurityPolicy: "",
-      remotePatterns: [],
-      unoptimized: true,
-    },
+    images: configRenderOptions
+      ? {
+          ...configRenderOptions.images,
+          // Turbopack doesn't serve the default image optimizer yet.
+          unoptimized:
+            configRenderOptions.images.unoptimized ||
+            configRenderOptions.images.loader === "default",
+        }
+      : {
+          deviceSizes: [],
+          imageSizes: [],
+          loader: "default",
+          path: "",
+          loaderFile: "",
+          domains: [],
+          disableStaticImages: false,
+          minimumCacheTTL: 0,
+          formats: [],
+          dangerouslyAllowSVG: false,
+          contentSecurityPolicy: "",
+          remotePatterns: [],
+          unoptimized: true,
+        },
   };
 
   if ("getStaticProps" in otherExports) {
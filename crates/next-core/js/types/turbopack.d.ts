@@ -6,3 +6,23 @@ export type RenderData = {
   query: NextParsedUrlQuery;
   headers: Record<string, HeaderValue>;
 };
+
+export type MiddlewareResult =
+  | {
+      type: "next";
+      headers: string[];
+      requestHeaders: string[] | null;
+    }
+  | {
+      type: "rewrite";
+      path: string;
+      search: string;
+      headers: string[];
+      requestHeaders: string[] | null;
+    }
+  | {
+      type: "response";
+      status: number;
+      headers: string[];
+      body: ArrayBuffer;
+    };
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use turbo_tasks_fs::FileSystemPathVc;
 
@@ -8,6 +8,9 @@ pub mod next_layout_entry_transition;
 pub struct LayoutSegment {
     pub files: HashMap<String, FileSystemPathVc>,
     pub target: FileSystemPathVc,
+    /// The `@slot` directories next to the layout. Their content is passed as
+    /// prop with the name of the slot to the layout.
+    pub slots: BTreeMap<String, FileSystemPathVc>,
 }
 
 #[turbo_tasks::value(transparent)]
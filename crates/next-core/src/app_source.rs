@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     io::Write,
 };
 
@@ -27,9 +27,9 @@ use turbopack_core::{
 use turbopack_dev_server::{
     html::DevHtmlAssetVc,
     source::{
-        combined::{CombinedContentSource, CombinedContentSourceVc},
-        specificity::SpecificityVc,
-        ContentSourceData, ContentSourceVc, NoContentSourceVc,
+        combined::CombinedContentSource, specificity::SpecificityVc, ContentSource,
+        ContentSourceContent, ContentSourceData, ContentSourceDataFilter, ContentSourceDataVary,
+        ContentSourceResultVc, ContentSourceVc, NoContentSourceVc,
     },
 };
 use turbopack_ecmascript::{
@@ -40,9 +40,11 @@ use turbopack_env::ProcessEnvAssetVc;
 
 use crate::{
     app_render::{
-        next_layout_entry_transition::NextLayoutEntryTransition, LayoutSegment, LayoutSegmentsVc,
+        next_layout_entry_transition::NextLayoutEntryTransition, LayoutSegment,
+        LayoutSegmentReadRef, LayoutSegmentsVc,
     },
     embed_js::{next_js_file, wrap_with_next_js_fs},
+    env::env_for_js,
     fallback::get_fallback_page,
     next_client::{
         context::{
@@ -56,6 +58,7 @@ use crate::{
         server_to_client_transition::NextServerToClientTransition,
         ssr_client_module_transition::NextSSRClientModuleTransition,
     },
+    next_config::NextConfigVc,
     next_server::{
         get_server_environment, get_server_module_options_context,
         get_server_resolve_options_context, ServerContextType,
@@ -126,6 +129,7 @@ fn next_ssr_client_module_transition(
     project_root: FileSystemPathVc,
     app_dir: FileSystemPathVc,
     process_env: ProcessEnvVc,
+    next_config: NextConfigVc,
     externals: StringsVc,
 ) -> TransitionVc {
     let ty = Value::new(ServerContextType::AppSSR { app_dir });
@@ -134,6 +138,7 @@ fn next_ssr_client_module_transition(
         ssr_resolve_options_context: get_server_resolve_options_context(
             project_root,
             ty,
+            next_config,
             externals,
         ),
         ssr_environment: get_server_environment(ty, process_env),
@@ -148,12 +153,13 @@ fn next_layout_entry_transition(
     app_dir: FileSystemPathVc,
     server_root: FileSystemPathVc,
     process_env: ProcessEnvVc,
+    next_config: NextConfigVc,
     externals: StringsVc,
 ) -> TransitionVc {
     let ty = Value::new(ServerContextType::AppRSC { app_dir });
     let rsc_environment = get_server_environment(ty, process_env);
     let rsc_resolve_options_context =
-        get_server_resolve_options_context(project_root, ty, externals);
+        get_server_resolve_options_context(project_root, ty, next_config, externals);
     let rsc_module_options_context = get_server_module_options_context(ty);
 
     NextLayoutEntryTransition {
@@ -171,9 +177,11 @@ fn app_context(
     project_root: FileSystemPathVc,
     server_root: FileSystemPathVc,
     app_dir: FileSystemPathVc,
-    env: ProcessEnvVc,
+    client_env: ProcessEnvVc,
+    server_env: ProcessEnvVc,
     browserslist_query: &str,
     ssr: bool,
+    next_config: NextConfigVc,
     externals: StringsVc,
 ) -> AssetContextVc {
     let next_server_to_client_transition = NextServerToClientTransition { ssr }.cell().into();
@@ -181,7 +189,14 @@ fn app_context(
     let mut transitions = HashMap::new();
     transitions.insert(
         "next-layout-entry".to_string(),
-        next_layout_entry_transition(project_root, app_dir, server_root, env, externals),
+        next_layout_entry_transition(
+            project_root,
+            app_dir,
+            server_root,
+            server_env,
+            next_config,
+            externals,
+        ),
     );
     transitions.insert(
         "server-to-client".to_string(),
@@ -189,7 +204,13 @@ fn app_context(
     );
     transitions.insert(
         "next-client".to_string(),
-        next_client_transition(project_root, server_root, app_dir, env, browserslist_query),
+        next_client_transition(
+            project_root,
+            server_root,
+            app_dir,
+            client_env,
+            browserslist_query,
+        ),
     );
     transitions.insert(
         "next-client-chunks".to_string(),
@@ -197,15 +218,21 @@ fn app_context(
     );
     transitions.insert(
         "next-ssr-client-module".to_string(),
-        next_ssr_client_module_transition(project_root, app_dir, env, externals),
+        next_ssr_client_module_transition(
+            project_root,
+            app_dir,
+            server_env,
+            next_config,
+            externals,
+        ),
     );
 
     let ssr_ty = Value::new(ServerContextType::AppSSR { app_dir });
     ModuleAssetContextVc::new(
         TransitionsByNameVc::cell(transitions),
-        get_server_environment(ssr_ty, env),
+        get_server_environment(ssr_ty, server_env),
         get_server_module_options_context(ssr_ty),
-        get_server_resolve_options_context(project_root, ssr_ty, externals),
+        get_server_resolve_options_context(project_root, ssr_ty, next_config, externals),
     )
     .into()
 }
@@ -219,6 +246,7 @@ pub async fn create_app_source(
     server_root: FileSystemPathVc,
     env: ProcessEnvVc,
     browserslist_query: &str,
+    next_config: NextConfigVc,
     externals: StringsVc,
 ) -> Result<ContentSourceVc> {
     let project_root = wrap_with_next_js_fs(project_root);
@@ -233,31 +261,39 @@ pub async fn create_app_source(
         return Ok(NoContentSourceVc::new().into());
     };
 
+    let client_env = env_for_js(env, true, next_config);
+    let server_env = env_for_js(env, false, next_config);
+
     let context_ssr = app_context(
         project_root,
         server_root,
         app_dir,
-        env,
+        client_env,
+        server_env,
         browserslist_query,
         true,
+        next_config,
         externals,
     );
     let context = app_context(
         project_root,
         server_root,
         app_dir,
-        env,
+        client_env,
+        server_env,
         browserslist_query,
         false,
+        next_config,
         externals,
     );
 
     let server_runtime_entries =
-        vec![ProcessEnvAssetVc::new(project_root, env).as_ecmascript_chunk_placeable()];
+        vec![ProcessEnvAssetVc::new(project_root, server_env).as_ecmascript_chunk_placeable()];
 
-    let fallback_page = get_fallback_page(project_root, server_root, env, browserslist_query);
+    let fallback_page =
+        get_fallback_page(project_root, server_root, client_env, browserslist_query);
 
-    Ok(create_app_source_for_directory(
+    let sources = create_app_source_for_directory(
         context_ssr,
         context,
         project_root,
@@ -271,7 +307,39 @@ pub async fn create_app_source(
         LayoutSegmentsVc::cell(Vec::new()),
         output_path,
     )
-    .into())
+    .await?;
+    let source = CombinedContentSource {
+        sources: sources.routes.clone(),
+    }
+    .cell()
+    .into();
+    let source = if sources.intercepting.is_empty() {
+        source
+    } else {
+        InterceptingContentSource {
+            intercepting: CombinedContentSource {
+                sources: sources.intercepting.clone(),
+            }
+            .cell()
+            .into(),
+            fallback: source,
+        }
+        .cell()
+        .into()
+    };
+
+    Ok(source)
+}
+
+/// The content sources created for a directory of the app dir.
+#[turbo_tasks::value]
+struct AppDirectorySources {
+    /// Serves the routes of the directory and its subdirectories.
+    routes: Vec<ContentSourceVc>,
+    /// Serves the intercepting routes (`(.)`, `(..)` and `(...)` directories)
+    /// within the directory. They take precedence over [Self::routes] for soft
+    /// navigations only.
+    intercepting: Vec<ContentSourceVc>,
 }
 
 #[turbo_tasks::function]
@@ -288,11 +356,14 @@ async fn create_app_source_for_directory(
     target: FileSystemPathVc,
     layouts: LayoutSegmentsVc,
     intermediate_output_path: FileSystemPathVc,
-) -> Result<CombinedContentSourceVc> {
+) -> Result<AppDirectorySourcesVc> {
     let mut layouts = layouts;
     let mut sources = Vec::new();
+    let mut intercepting = Vec::new();
     let mut page = None;
+    let mut default = None;
     let mut files = HashMap::new();
+    let mut slots = BTreeMap::new();
     if let DirectoryContent::Entries(entries) = &*input_dir.read_dir().await? {
         for (name, entry) in entries.iter() {
             if let &DirectoryEntry::File(file) = entry {
@@ -301,6 +372,9 @@ async fn create_app_source_for_directory(
                         "page" => {
                             page = Some(file);
                         }
+                        "default" => {
+                            default = Some(file);
+                        }
                         "layout" | "error" | "loading" | "template" | "not-found" | "head" => {
                             files.insert(name.to_string(), file);
                         }
@@ -353,8 +427,21 @@ async fn create_app_source_for_directory(
             .emit();
         }
 
+        for (name, entry) in entries.iter() {
+            if let (Some(slot), DirectoryEntry::Directory(dir)) = (name.strip_prefix('@'), entry) {
+                slots.insert(slot.to_string(), *dir);
+            }
+        }
+
         let mut list = layouts.await?.clone_value();
-        list.push(LayoutSegment { files, target }.cell());
+        list.push(
+            LayoutSegment {
+                files,
+                target,
+                slots,
+            }
+            .cell(),
+        );
         layouts = LayoutSegmentsVc::cell(list);
         if let Some(page_path) = page {
             sources.push(create_node_rendered_source(
@@ -370,6 +457,7 @@ async fn create_app_source_for_directory(
                     target,
                     project_root,
                     intermediate_output_path,
+                    intercepting: false,
                 }
                 .cell()
                 .into(),
@@ -378,43 +466,309 @@ async fn create_app_source_for_directory(
             ));
         }
         for (name, entry) in entries.iter() {
-            if let DirectoryEntry::Directory(dir) = entry {
-                let intermediate_output_path = intermediate_output_path.join(name);
-                let specificity = if name.starts_with("[[") || name.starts_with("[...") {
-                    specificity.with_catch_all(position)
-                } else if name.starts_with('[') {
-                    specificity.with_dynamic_segment(position)
-                } else {
-                    specificity
-                };
-                let (new_target, position) = if name.starts_with('(') && name.ends_with(')') {
-                    // This doesn't affect the url
-                    (target, position)
+            if let (Some(slot), DirectoryEntry::Directory(dir)) = (name.strip_prefix('@'), entry) {
+                // Intercepting routes within a slot render the slot with the
+                // intercepting page, and the children with the default of this
+                // directory.
+                let intercepted_targets =
+                    slot_intercepted_targets(*dir, target, server_root, false).await?;
+                if intercepted_targets.is_empty() {
+                    continue;
+                }
+                let children = if let Some(children) = default.or(page) {
+                    children
                 } else {
-                    // This adds to the url
-                    (target.join(name), position + 1)
+                    AppSourceIssue {
+                        severity: IssueSeverity::Warning.into(),
+                        path: *dir,
+                        message: StringVc::cell(format!(
+                            "The parallel route @{slot} contains intercepting routes, but there \
+                             is neither a default nor a page for the children of {}, so they are \
+                             not rendered.",
+                            target.to_string().await?,
+                        )),
+                    }
+                    .cell()
+                    .as_issue()
+                    .emit();
+                    continue;
                 };
-                sources.push(
-                    create_app_source_for_directory(
-                        context_ssr,
-                        context,
-                        project_root,
+                for &intercepted_target in intercepted_targets.iter() {
+                    let path = server_root
+                        .await?
+                        .get_path_to(&*intercepted_target.await?)
+                        .unwrap_or_default()
+                        .to_string();
+                    let mut specificity = SpecificityVc::exact();
+                    for (position, name) in path.split('/').filter(|s| !s.is_empty()).enumerate() {
+                        specificity = match parse_segment(name) {
+                            Segment::CatchAll | Segment::OptionalCatchAll => {
+                                specificity.with_catch_all(position as u32)
+                            }
+                            Segment::Dynamic => specificity.with_dynamic_segment(position as u32),
+                            _ => specificity,
+                        };
+                    }
+                    intercepting.push(create_node_rendered_source(
                         specificity,
-                        position,
-                        *dir,
                         server_root,
+                        regular_expression_for_path(server_root, intercepted_target, false),
+                        AppRenderer {
+                            context_ssr,
+                            context,
+                            server_root,
+                            layout_path: layouts,
+                            page_path: children,
+                            target: intercepted_target,
+                            project_root,
+                            intermediate_output_path: intermediate_output_path
+                                .join(name)
+                                .join(&path),
+                            intercepting: true,
+                        }
+                        .cell()
+                        .into(),
                         runtime_entries,
                         fallback_page,
-                        new_target,
-                        layouts,
-                        intermediate_output_path,
-                    )
-                    .into(),
-                );
+                    ));
+                }
+            }
+        }
+        for (name, entry) in entries.iter() {
+            if let DirectoryEntry::Directory(dir) = entry {
+                if name.starts_with('@') {
+                    // Slots are rendered as part of the routes of this directory.
+                    continue;
+                }
+                let intermediate_output_path = intermediate_output_path.join(name);
+                let segment = parse_segment(name);
+                let specificity = match segment {
+                    Segment::CatchAll | Segment::OptionalCatchAll => {
+                        specificity.with_catch_all(position)
+                    }
+                    Segment::Dynamic => specificity.with_dynamic_segment(position),
+                    _ => specificity,
+                };
+                let (new_target, position) = match segment {
+                    Segment::Intercepting { levels, name } => {
+                        // The route is matched at the url it intercepts
+                        let new_target = intercepted_target(levels, name, target, server_root);
+                        let new_position = server_root
+                            .await?
+                            .get_path_to(&*new_target.await?)
+                            .map_or(0, |path| path.split('/').filter(|s| !s.is_empty()).count());
+                        (new_target, new_position as u32)
+                    }
+                    // This doesn't affect the url
+                    Segment::Group => (target, position),
+                    // This adds to the url
+                    _ => (target.join(name), position + 1),
+                };
+                let dir_sources = create_app_source_for_directory(
+                    context_ssr,
+                    context,
+                    project_root,
+                    specificity,
+                    position,
+                    *dir,
+                    server_root,
+                    runtime_entries,
+                    fallback_page,
+                    new_target,
+                    layouts,
+                    intermediate_output_path,
+                )
+                .await?;
+                let routes = CombinedContentSource {
+                    sources: dir_sources.routes.clone(),
+                }
+                .cell()
+                .into();
+                if matches!(segment, Segment::Intercepting { .. }) {
+                    intercepting.push(routes);
+                } else {
+                    sources.push(routes);
+                }
+                intercepting.extend(dir_sources.intercepting.iter().copied());
+            }
+        }
+    }
+    Ok(AppDirectorySources {
+        routes: sources,
+        intercepting,
+    }
+    .cell())
+}
+
+/// How a directory of the app dir matches url segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment<'a> {
+    /// A plain directory matches the url segment of the same name.
+    Static(&'a str),
+    /// `[param]` matches a single url segment.
+    Dynamic,
+    /// `[...param]` matches one or more url segments.
+    CatchAll,
+    /// `[[...param]]` matches zero or more url segments.
+    OptionalCatchAll,
+    /// `(group)` doesn't affect the url.
+    Group,
+    /// `(.)name`, `(..)name` or `(...)name` intercepts the route `name` next to
+    /// the directory, `levels` segments above it, or at the root when `levels`
+    /// is `None`.
+    Intercepting {
+        levels: Option<usize>,
+        name: &'a str,
+    },
+}
+
+impl<'a> Segment<'a> {
+    /// Directories with a lower priority are tried first when matching urls.
+    fn priority(&self) -> u8 {
+        match self {
+            Segment::Static(_) | Segment::Intercepting { .. } => 0,
+            Segment::Group => 1,
+            Segment::Dynamic => 2,
+            Segment::CatchAll => 3,
+            Segment::OptionalCatchAll => 4,
+        }
+    }
+
+    /// Returns the numbers of url segments at the start of `remaining` the
+    /// directory matches, longest first. Intercepting routes are matched by
+    /// their target instead, so they never match here.
+    fn matches(&self, remaining: &[String]) -> Vec<usize> {
+        match self {
+            Segment::Static(name) => {
+                if remaining.first().map(|s| s.as_str()) == Some(*name) {
+                    vec![1]
+                } else {
+                    Vec::new()
+                }
             }
+            Segment::Dynamic => {
+                if remaining.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![1]
+                }
+            }
+            Segment::CatchAll => (1..=remaining.len()).rev().collect(),
+            Segment::OptionalCatchAll => (0..=remaining.len()).rev().collect(),
+            Segment::Group => vec![0],
+            Segment::Intercepting { .. } => Vec::new(),
         }
     }
-    Ok(CombinedContentSource { sources }.cell())
+}
+
+fn parse_segment(name: &str) -> Segment<'_> {
+    if let Some(rest) = name.strip_prefix("(...)") {
+        return Segment::Intercepting {
+            levels: None,
+            name: rest,
+        };
+    }
+    if let Some(rest) = name.strip_prefix("(.)") {
+        return Segment::Intercepting {
+            levels: Some(0),
+            name: rest,
+        };
+    }
+    if let Some(mut rest) = name.strip_prefix("(..)") {
+        let mut levels = 1;
+        while let Some(next) = rest.strip_prefix("(..)") {
+            rest = next;
+            levels += 1;
+        }
+        return Segment::Intercepting {
+            levels: Some(levels),
+            name: rest,
+        };
+    }
+    if name.starts_with('(') && name.ends_with(')') {
+        Segment::Group
+    } else if name.starts_with("[[") {
+        Segment::OptionalCatchAll
+    } else if name.starts_with("[...") {
+        Segment::CatchAll
+    } else if name.starts_with('[') {
+        Segment::Dynamic
+    } else {
+        Segment::Static(name)
+    }
+}
+
+/// Returns the url a [Segment::Intercepting] directory at `target`
+/// intercepts.
+fn intercepted_target(
+    levels: Option<usize>,
+    name: &str,
+    target: FileSystemPathVc,
+    server_root: FileSystemPathVc,
+) -> FileSystemPathVc {
+    let mut new_target = match levels {
+        Some(_) => target,
+        None => server_root,
+    };
+    for _ in 0..levels.unwrap_or_default() {
+        new_target = new_target.parent();
+    }
+    new_target.join(name)
+}
+
+/// Serves intercepting routes for soft navigations of the client router, which
+/// are the requests with a `Next-Url` header. All other requests are served by
+/// the routes they would have intercepted.
+#[turbo_tasks::value(shared)]
+struct InterceptingContentSource {
+    intercepting: ContentSourceVc,
+    fallback: ContentSourceVc,
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for InterceptingContentSource {
+    #[turbo_tasks::function]
+    async fn get(
+        self_vc: InterceptingContentSourceVc,
+        path: &str,
+        data: Value<ContentSourceData>,
+    ) -> Result<ContentSourceResultVc> {
+        let this = self_vc.await?;
+        if let Some(headers) = &data.headers {
+            if headers.contains_key("next-url") {
+                let result = this.intercepting.get(path, data.clone());
+                if !matches!(
+                    &*result.await?.content.await?,
+                    ContentSourceContent::NotFound
+                ) {
+                    return Ok(result);
+                }
+            }
+            return Ok(this.fallback.get(path, data));
+        }
+        // Only ask for the headers when there is a route that could intercept
+        // the request.
+        let result = this.intercepting.get(path, Value::new(Default::default()));
+        if matches!(
+            &*result.await?.content.await?,
+            ContentSourceContent::NotFound
+        ) {
+            return Ok(this.fallback.get(path, data));
+        }
+        Ok(ContentSourceResultVc::exact(
+            ContentSourceContent::NeedData {
+                source: self_vc.into(),
+                path: path.to_string(),
+                vary: ContentSourceDataVary {
+                    headers: Some(ContentSourceDataFilter::Subset(HashSet::from([
+                        "next-url".to_string()
+                    ]))),
+                    ..Default::default()
+                },
+            }
+            .cell(),
+        ))
+    }
 }
 
 #[turbo_tasks::value]
@@ -427,6 +781,9 @@ struct AppRenderer {
     target: FileSystemPathVc,
     project_root: FileSystemPathVc,
     intermediate_output_path: FileSystemPathVc,
+    /// Set for the routes of intercepting routes within slots, which are only
+    /// served for soft navigations. Their slots render intercepting routes.
+    intercepting: bool,
 }
 
 #[turbo_tasks::value_impl]
@@ -442,6 +799,7 @@ impl NodeEntry for AppRenderer {
         let page = self.page_path;
         let path = page.parent();
         let path_value = &*path.await?;
+        let target = &*self.target.await?;
         let layout_and_page = layout_path
             .iter()
             .copied()
@@ -449,74 +807,66 @@ impl NodeEntry for AppRenderer {
                 LayoutSegment {
                     files: HashMap::from([("page".to_string(), page)]),
                     target: self.target,
+                    slots: BTreeMap::new(),
                 }
                 .cell(),
             ))
             .try_join()
             .await?;
-        let segments: Vec<_> = layout_and_page
-            .into_iter()
-            .fold(
-                (self.server_root, Vec::new()),
-                |(last_path, mut futures), segment| {
-                    (segment.target, {
-                        futures.push(async move {
-                            let target = &*segment.target.await?;
-                            let segment_path =
-                                last_path.await?.get_path_to(target).unwrap_or_default();
-                            let mut imports = BTreeMap::new();
-                            for (key, file) in segment.files.iter() {
-                                let file_str = file.to_string().await?;
-                                let identifier = magic_identifier::encode(&format!(
-                                    "imported namespace {}",
-                                    file_str
-                                ));
-                                let chunks_identifier = magic_identifier::encode(&format!(
-                                    "client chunks for {}",
-                                    file_str
-                                ));
-                                if let Some(p) = path_value.get_relative_path_to(&*file.await?) {
-                                    imports.insert(
-                                        key.to_string(),
-                                        (p, identifier, chunks_identifier),
-                                    );
-                                } else {
-                                    return Err(anyhow!(
-                                        "Unable to generate import as there
-                                is no relative path to the layout module {} from context
-                                path {}",
-                                        file_str,
-                                        path.to_string().await?
-                                    ));
-                                }
-                            }
-                            Ok((stringify_str(segment_path), imports))
-                        });
-                        futures
-                    })
-                },
-            )
-            .1
-            .into_iter()
-            .try_join()
-            .await?;
+        let mut segments = segment_imports(&layout_and_page, self.server_root, path).await?;
+        for (segment, imports) in layout_and_page.iter().zip(segments.iter_mut()) {
+            // The url segments of the page below this layout, which the slots
+            // need to match.
+            let remaining: Vec<String> = segment
+                .target
+                .await?
+                .get_path_to(target)
+                .unwrap_or_default()
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+                .collect();
+            for (name, &slot_dir) in segment.slots.iter() {
+                let slot_segments = slot_layout_segments(
+                    slot_dir,
+                    segment.target,
+                    segment.target,
+                    remaining.clone(),
+                    0,
+                    self.intercepting,
+                    self.server_root,
+                )
+                .await?
+                .iter()
+                .copied()
+                .try_join()
+                .await?;
+                if slot_segments.is_empty() {
+                    AppSourceIssue {
+                        severity: IssueSeverity::Warning.into(),
+                        path: slot_dir,
+                        message: StringVc::cell(format!(
+                            "The parallel route @{name} has neither a page nor a default for {}, \
+                             so it is not rendered.",
+                            self.target.to_string().await?,
+                        )),
+                    }
+                    .cell()
+                    .as_issue()
+                    .emit();
+                    continue;
+                }
+                imports.slots.push((
+                    name.clone(),
+                    segment_imports(&slot_segments, segment.target, path).await?,
+                ));
+            }
+        }
         let mut result = RopeBuilder::from(
             "import IPC, { Ipc } from \"@vercel/turbopack-next/internal/ipc\";\n",
         );
 
-        for (_, imports) in segments.iter() {
-            for (p, identifier, chunks_identifier) in imports.values() {
-                result += r#"("TURBOPACK { transition: next-layout-entry; chunking-type: parallel }");
-"#;
-                writeln!(
-                    result,
-                    "import {}, {{ chunks as {} }} from {};\n",
-                    identifier,
-                    chunks_identifier,
-                    stringify_str(p)
-                )?
-            }
-        }
+        write_imports(&mut result, &segments)?;
         if let Some(page) = path_value.get_relative_path_to(&*page.await?) {
             writeln!(
                 result,
@@ -527,19 +877,9 @@ import BOOTSTRAP from {};
             )?;
         }
 
-        result += "const LAYOUT_INFO = [";
-        for (segment_str_lit, imports) in segments.iter() {
-            writeln!(result, "  {{\n    segment: {segment_str_lit},")?;
-            for (key, (_, identifier, chunks_identifier)) in imports {
-                writeln!(
-                    result,
-                    "    {key}: {{ module: {identifier}, chunks: {chunks_identifier} }},",
-                    key = stringify_str(key),
-                )?;
-            }
-            result += "  },";
-        }
-        result += "];\n\n";
+        result += "const LAYOUT_INFO = ";
+        write_layout_info(&mut result, &segments)?;
+        result += ";\n\n";
 
         let base_code = next_js_file("entry/app-renderer.tsx");
         if let FileContent::Content(base_file) = &*base_code.await? {
@@ -582,6 +922,301 @@ import BOOTSTRAP from {};
     }
 }
 
+/// The url segment of a [LayoutSegment] and the imports of its files. The
+/// imports are tuples of the relative path, the identifier of the module and
+/// the identifier of its client chunks.
+struct SegmentImports {
+    segment: String,
+    imports: BTreeMap<String, (String, String, String)>,
+    slots: Vec<(String, Vec<SegmentImports>)>,
+}
+
+/// Computes the [SegmentImports] of a list of segments. The url segment of
+/// each one is relative to the previous one, starting at `start`.
+async fn segment_imports(
+    segments: &[LayoutSegmentReadRef],
+    start: FileSystemPathVc,
+    path: FileSystemPathVc,
+) -> Result<Vec<SegmentImports>> {
+    let path_value = &*path.await?;
+    let mut last_path = start;
+    segments
+        .iter()
+        .map(|segment| {
+            let parent_path = last_path;
+            last_path = segment.target;
+            async move {
+                let target = &*segment.target.await?;
+                let segment_path = parent_path.await?.get_path_to(target).unwrap_or_default();
+                let mut imports = BTreeMap::new();
+                for (key, file) in segment.files.iter() {
+                    let file_str = file.to_string().await?;
+                    let identifier =
+                        magic_identifier::encode(&format!("imported namespace {}", file_str));
+                    let chunks_identifier =
+                        magic_identifier::encode(&format!("client chunks for {}", file_str));
+                    if let Some(p) = path_value.get_relative_path_to(&*file.await?) {
+                        imports.insert(key.to_string(), (p, identifier, chunks_identifier));
+                    } else {
+                        return Err(anyhow!(
+                            "Unable to generate import as there
+                                is no relative path to the layout module {} from context
+                                path {}",
+                            file_str,
+                            path.to_string().await?
+                        ));
+                    }
+                }
+                Ok(SegmentImports {
+                    segment: stringify_str(segment_path),
+                    imports,
+                    slots: Vec::new(),
+                })
+            }
+        })
+        .try_join()
+        .await
+}
+
+fn write_imports(result: &mut RopeBuilder, segments: &[SegmentImports]) -> Result<()> {
+    for segment in segments {
+        for (p, identifier, chunks_identifier) in segment.imports.values() {
+            *result += r#"("TURBOPACK { transition: next-layout-entry; chunking-type: parallel }");
+"#;
+            writeln!(
+                result,
+                "import {}, {{ chunks as {} }} from {};\n",
+                identifier,
+                chunks_identifier,
+                stringify_str(p)
+            )?
+        }
+        for (_, slot_segments) in segment.slots.iter() {
+            write_imports(result, slot_segments)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_layout_info(result: &mut RopeBuilder, segments: &[SegmentImports]) -> Result<()> {
+    *result += "[";
+    for segment in segments {
+        writeln!(result, "  {{\n    segment: {},", segment.segment)?;
+        for (key, (_, identifier, chunks_identifier)) in segment.imports.iter() {
+            writeln!(
+                result,
+                "    {key}: {{ module: {identifier}, chunks: {chunks_identifier} }},",
+                key = stringify_str(key),
+            )?;
+        }
+        if !segment.slots.is_empty() {
+            *result += "    slots: {\n";
+            for (name, slot_segments) in segment.slots.iter() {
+                write!(result, "    {}: ", stringify_str(name))?;
+                write_layout_info(result, slot_segments)?;
+                *result += ",\n";
+            }
+            *result += "    },\n";
+        }
+        *result += "  },";
+    }
+    *result += "]";
+    Ok(())
+}
+
+/// Finds the segments a parallel route slot renders for a page at the url
+/// segments `remaining` below `root`, the target of the slot's layout. `dir` is
+/// a directory of the slot at the url `target`, which matched the first
+/// `matched` segments. Uses the `page` matching the url, or otherwise the
+/// closest `default`. Returns an empty list when there is neither.
+///
+/// Intercepting routes within the slot are only matched when `intercepting`
+/// is set.
+#[turbo_tasks::function]
+async fn slot_layout_segments(
+    dir: FileSystemPathVc,
+    root: FileSystemPathVc,
+    target: FileSystemPathVc,
+    remaining: Vec<String>,
+    matched: u32,
+    intercepting: bool,
+    server_root: FileSystemPathVc,
+) -> Result<LayoutSegmentsVc> {
+    let matched = matched as usize;
+    let mut files = HashMap::new();
+    let mut candidates = Vec::new();
+    if let DirectoryContent::Entries(entries) = &*dir.read_dir().await? {
+        for (name, entry) in entries.iter() {
+            match entry {
+                DirectoryEntry::File(file) => {
+                    if let Some((name, _)) = name.rsplit_once('.') {
+                        if matches!(
+                            name,
+                            "page"
+                                | "default"
+                                | "layout"
+                                | "error"
+                                | "loading"
+                                | "template"
+                                | "not-found"
+                                | "head"
+                        ) {
+                            files.insert(name.to_string(), *file);
+                        }
+                    }
+                }
+                DirectoryEntry::Directory(child) => {
+                    if name.starts_with('@') {
+                        continue;
+                    }
+                    let segment = parse_segment(name);
+                    if let Segment::Intercepting { levels, name } = segment {
+                        if !intercepting {
+                            continue;
+                        }
+                        // The intercepted url needs to be a prefix of the url
+                        // of the page
+                        let child_target = intercepted_target(levels, name, target, server_root);
+                        let root = root.await?;
+                        if let Some(path) = root.get_path_to(&*child_target.await?) {
+                            let segments: Vec<&str> =
+                                path.split('/').filter(|s| !s.is_empty()).collect();
+                            if segments.len() <= remaining.len()
+                                && segments
+                                    .iter()
+                                    .zip(remaining.iter())
+                                    .all(|(a, b)| *a == b.as_str())
+                            {
+                                candidates.push((
+                                    segment.priority(),
+                                    *child,
+                                    child_target,
+                                    segments.len(),
+                                ));
+                            }
+                        }
+                        continue;
+                    }
+                    let child_target = if segment == Segment::Group {
+                        target
+                    } else {
+                        target.join(name)
+                    };
+                    for count in segment.matches(&remaining[matched..]) {
+                        candidates.push((
+                            segment.priority(),
+                            *child,
+                            child_target,
+                            matched + count,
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    candidates.sort_by_key(|(priority, ..)| *priority);
+
+    let level = || {
+        let mut files = files.clone();
+        files.remove("page");
+        files.remove("default");
+        LayoutSegment {
+            files,
+            target,
+            slots: BTreeMap::new(),
+        }
+        .cell()
+    };
+    let leaf = |file: FileSystemPathVc| {
+        LayoutSegment {
+            files: HashMap::from([("page".to_string(), file)]),
+            target,
+            slots: BTreeMap::new(),
+        }
+        .cell()
+    };
+
+    if matched == remaining.len() {
+        if let Some(&page) = files.get("page") {
+            return Ok(LayoutSegmentsVc::cell(vec![level(), leaf(page)]));
+        }
+    }
+    for (_, child, child_target, child_matched) in candidates {
+        let segments = slot_layout_segments(
+            child,
+            root,
+            child_target,
+            remaining.clone(),
+            child_matched as u32,
+            intercepting,
+            server_root,
+        )
+        .await?;
+        if !segments.is_empty() {
+            let mut list = vec![level()];
+            list.extend(segments.iter().copied());
+            return Ok(LayoutSegmentsVc::cell(list));
+        }
+    }
+    if let Some(&default) = files.get("default") {
+        return Ok(LayoutSegmentsVc::cell(vec![level(), leaf(default)]));
+    }
+    Ok(LayoutSegmentsVc::cell(Vec::new()))
+}
+
+#[turbo_tasks::value(transparent)]
+struct InterceptedTargets(Vec<FileSystemPathVc>);
+
+/// Finds the urls of the intercepting routes within the directory `dir` of a
+/// parallel route slot at the url `target`. These are the pages below `(.)`,
+/// `(..)` and `(...)` directories, and `intercepting` is set below them.
+#[turbo_tasks::function]
+async fn slot_intercepted_targets(
+    dir: FileSystemPathVc,
+    target: FileSystemPathVc,
+    server_root: FileSystemPathVc,
+    intercepting: bool,
+) -> Result<InterceptedTargetsVc> {
+    let mut targets = Vec::new();
+    if let DirectoryContent::Entries(entries) = &*dir.read_dir().await? {
+        for (name, entry) in entries.iter() {
+            match entry {
+                DirectoryEntry::File(_) => {
+                    if intercepting && matches!(name.rsplit_once('.'), Some(("page", _))) {
+                        targets.push(target);
+                    }
+                }
+                DirectoryEntry::Directory(child) => {
+                    if name.starts_with('@') {
+                        continue;
+                    }
+                    let (child_target, child_intercepting) = match parse_segment(name) {
+                        Segment::Intercepting { levels, name } => {
+                            (intercepted_target(levels, name, target, server_root), true)
+                        }
+                        Segment::Group => (target, intercepting),
+                        _ => (target.join(name), intercepting),
+                    };
+                    targets.extend(
+                        slot_intercepted_targets(
+                            *child,
+                            child_target,
+                            server_root,
+                            child_intercepting,
+                        )
+                        .await?
+                        .iter()
+                        .copied(),
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(InterceptedTargetsVc::cell(targets))
+}
+
 #[turbo_tasks::value(shared)]
 struct AppSourceIssue {
     pub severity: IssueSeverityVc,
@@ -618,3 +1253,80 @@ impl Issue for AppSourceIssue {
         self.message
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments(path: &str) -> Vec<String> {
+        path.split('/').map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_segments() {
+        assert_eq!(parse_segment("photo"), Segment::Static("photo"));
+        assert_eq!(parse_segment("[id]"), Segment::Dynamic);
+        assert_eq!(parse_segment("[...slug]"), Segment::CatchAll);
+        assert_eq!(parse_segment("[[...slug]]"), Segment::OptionalCatchAll);
+        assert_eq!(parse_segment("(marketing)"), Segment::Group);
+        assert_eq!(
+            parse_segment("(.)photo"),
+            Segment::Intercepting {
+                levels: Some(0),
+                name: "photo"
+            }
+        );
+        assert_eq!(
+            parse_segment("(..)(..)photo"),
+            Segment::Intercepting {
+                levels: Some(2),
+                name: "photo"
+            }
+        );
+        assert_eq!(
+            parse_segment("(...)photo"),
+            Segment::Intercepting {
+                levels: None,
+                name: "photo"
+            }
+        );
+    }
+
+    #[test]
+    fn matches_segments() {
+        let remaining = segments("photo/[id]/comments");
+        assert_eq!(Segment::Static("photo").matches(&remaining), vec![1]);
+        assert_eq!(
+            Segment::Static("feed").matches(&remaining),
+            Vec::<usize>::new()
+        );
+        assert_eq!(Segment::Dynamic.matches(&remaining), vec![1]);
+        assert_eq!(Segment::Dynamic.matches(&[]), Vec::<usize>::new());
+        assert_eq!(Segment::CatchAll.matches(&remaining), vec![3, 2, 1]);
+        assert_eq!(Segment::CatchAll.matches(&[]), Vec::<usize>::new());
+        assert_eq!(Segment::OptionalCatchAll.matches(&[]), vec![0]);
+        assert_eq!(Segment::Group.matches(&remaining), vec![0]);
+    }
+
+    #[test]
+    fn prefers_static_segments() {
+        let mut segments = vec![
+            parse_segment("[[...slug]]"),
+            parse_segment("[id]"),
+            parse_segment("(group)"),
+            parse_segment("[...slug]"),
+            parse_segment("photo"),
+        ];
+        segments.sort_by_key(|segment| segment.priority());
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Static("photo"),
+                Segment::Group,
+                Segment::Dynamic,
+                Segment::CatchAll,
+                Segment::OptionalCatchAll,
+            ]
+        );
+    }
+}
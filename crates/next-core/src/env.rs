@@ -1,8 +1,12 @@
 use anyhow::Result;
-use turbo_tasks_env::{CommandLineProcessEnvVc, FilterProcessEnvVc, ProcessEnvVc};
+use turbo_tasks_env::{
+    CommandLineProcessEnvVc, CustomProcessEnvVc, FilterProcessEnvVc, ProcessEnvVc,
+};
 use turbo_tasks_fs::FileSystemPathVc;
 use turbopack_env::TryDotenvProcessEnvVc;
 
+use crate::next_config::NextConfigVc;
+
 /// Loads a series of dotenv files according to the precedence rules set by
 /// https://nextjs.org/docs/basic-features/environment-variables#environment-variable-load-order
 #[turbo_tasks::function]
@@ -35,3 +39,15 @@ pub async fn load_env(project_path: FileSystemPathVc) -> Result<ProcessEnvVc> {
 pub fn filter_for_client(env: ProcessEnvVc) -> ProcessEnvVc {
     FilterProcessEnvVc::new(env, "NEXT_PUBLIC_".to_string()).into()
 }
+
+/// Computes the env that is exposed to the code, extended by the env variables
+/// of the `next.config.js`. The client only receives `NEXT_PUBLIC_` variables
+/// of the process env.
+#[turbo_tasks::function]
+pub fn env_for_js(env: ProcessEnvVc, client: bool, next_config: NextConfigVc) -> ProcessEnvVc {
+    if client {
+        CustomProcessEnvVc::new(filter_for_client(env), next_config.env()).into()
+    } else {
+        CustomProcessEnvVc::new(env, next_config.server_env()).into()
+    }
+}
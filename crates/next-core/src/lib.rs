@@ -6,8 +6,11 @@ mod app_source;
 mod embed_js;
 pub mod env;
 mod fallback;
+mod middleware;
 pub mod next_client;
 mod next_client_component;
+pub mod next_config;
+pub mod next_config_source;
 mod next_import_map;
 pub mod next_server;
 mod nodejs;
@@ -20,8 +23,9 @@ mod util;
 mod web_entry_source;
 
 pub use app_source::create_app_source;
+pub use middleware::create_middleware_source;
 pub use server_rendered_source::create_server_rendered_source;
-pub use web_entry_source::create_web_entry_source;
+pub use web_entry_source::{create_web_entry_asset, create_web_entry_source};
 
 pub fn register() {
     turbo_tasks::register();
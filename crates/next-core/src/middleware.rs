@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use turbo_tasks::{primitives::StringsVc, Value};
+use turbo_tasks_env::ProcessEnvVc;
+use turbo_tasks_fs::{FileSystemEntryType, FileSystemPathVc};
+use turbopack::{
+    module_options::ModuleOptionsContext, resolve_options_context::ResolveOptionsContext,
+    transition::TransitionsByNameVc, ModuleAssetContextVc,
+};
+use turbopack_core::{
+    chunk::dev::DevChunkingContextVc,
+    context::AssetContextVc,
+    environment::{EnvironmentIntention, EnvironmentVc, ExecutionEnvironment, NodeJsEnvironmentVc},
+    source_asset::SourceAssetVc,
+    virtual_asset::VirtualAssetVc,
+};
+use turbopack_dev_server::source::ContentSourceVc;
+use turbopack_ecmascript::{
+    chunk::EcmascriptChunkPlaceablesVc, EcmascriptInputTransform, EcmascriptInputTransformsVc,
+    EcmascriptModuleAssetType, EcmascriptModuleAssetVc,
+};
+use turbopack_env::ProcessEnvAssetVc;
+
+use crate::{
+    embed_js::{next_js_file, wrap_with_next_js_fs},
+    env::env_for_js,
+    next_config::NextConfigVc,
+    next_import_map::get_next_build_import_map,
+    next_server::{
+        get_server_environment, get_server_module_options_context,
+        get_server_resolve_options_context, ServerContextType,
+    },
+    nodejs::middleware::MiddlewareContentSource,
+};
+
+/// Wraps the `inner` content source with the `middleware.ts` or `middleware.js`
+/// of the project (or its `src` directory), so it runs before every request.
+/// Returns `inner` unchanged when the project has no middleware.
+#[turbo_tasks::function]
+pub async fn create_middleware_source(
+    project_root: FileSystemPathVc,
+    output_path: FileSystemPathVc,
+    server_root: FileSystemPathVc,
+    inner: ContentSourceVc,
+    env: ProcessEnvVc,
+    next_config: NextConfigVc,
+) -> Result<ContentSourceVc> {
+    let project_path = wrap_with_next_js_fs(project_root);
+
+    let mut middleware_path = None;
+    'search: for dir in [project_path, project_path.join("src")] {
+        for name in ["middleware.ts", "middleware.js"] {
+            let path = dir.join(name);
+            if *path.get_type().await? == FileSystemEntryType::File {
+                middleware_path = Some(path);
+                break 'search;
+            }
+        }
+    }
+    let middleware_path = if let Some(middleware_path) = middleware_path {
+        middleware_path
+    } else {
+        return Ok(inner);
+    };
+
+    let ty = Value::new(ServerContextType::Middleware);
+    let server_env = env_for_js(env, false, next_config);
+    let context: AssetContextVc = ModuleAssetContextVc::new(
+        TransitionsByNameVc::cell(HashMap::new()),
+        get_server_environment(ty, server_env),
+        get_server_module_options_context(ty),
+        get_server_resolve_options_context(project_path, ty, next_config, StringsVc::empty()),
+    )
+    .into();
+
+    let middleware_asset = context.process(SourceAssetVc::new(middleware_path).into());
+    let edge_entry = VirtualAssetVc::new(
+        middleware_asset.path().join("edge-middleware.ts"),
+        next_js_file("entry/edge-middleware.ts").into(),
+    );
+    let edge_module = EcmascriptModuleAssetVc::new(
+        edge_entry.into(),
+        context,
+        Value::new(EcmascriptModuleAssetType::Typescript),
+        EcmascriptInputTransformsVc::cell(vec![EcmascriptInputTransform::TypeScript]),
+        context.environment(),
+    );
+
+    let host_context = middleware_host_context(project_path, env);
+    let entry = VirtualAssetVc::new(
+        middleware_asset.path().join("server-middleware.ts"),
+        next_js_file("entry/server-middleware.ts").into(),
+    );
+    let module = EcmascriptModuleAssetVc::new(
+        entry.into(),
+        host_context,
+        Value::new(EcmascriptModuleAssetType::Typescript),
+        EcmascriptInputTransformsVc::cell(vec![EcmascriptInputTransform::TypeScript]),
+        host_context.environment(),
+    );
+
+    let chunking_context = DevChunkingContextVc::builder(
+        project_path,
+        output_path,
+        output_path.join("chunks"),
+        output_path.join("assets"),
+    )
+    .build();
+    let edge_chunking_context = DevChunkingContextVc::builder(
+        project_path,
+        output_path,
+        output_path.join("chunks"),
+        output_path.join("assets"),
+    )
+    .layer("edge")
+    .build();
+    let runtime_entries =
+        EcmascriptChunkPlaceablesVc::cell(vec![
+            ProcessEnvAssetVc::new(project_path, server_env).as_ecmascript_chunk_placeable()
+        ]);
+
+    Ok(MiddlewareContentSource {
+        inner,
+        server_root,
+        module,
+        edge_module,
+        runtime_entries,
+        chunking_context,
+        edge_chunking_context,
+        intermediate_output_path: output_path,
+    }
+    .cell()
+    .into())
+}
+
+/// The context of the node.js host that runs the sandboxed middleware.
+#[turbo_tasks::function]
+fn middleware_host_context(project_path: FileSystemPathVc, env: ProcessEnvVc) -> AssetContextVc {
+    ModuleAssetContextVc::new(
+        TransitionsByNameVc::cell(HashMap::new()),
+        EnvironmentVc::new(
+            Value::new(ExecutionEnvironment::NodeJsLambda(
+                NodeJsEnvironmentVc::current(env),
+            )),
+            Value::new(EnvironmentIntention::Middleware),
+        ),
+        ModuleOptionsContext {
+            enable_typescript_transform: true,
+            ..Default::default()
+        }
+        .cell(),
+        ResolveOptionsContext {
+            enable_typescript: true,
+            enable_node_modules: true,
+            enable_node_externals: true,
+            custom_conditions: vec!["development".to_string()],
+            import_map: Some(get_next_build_import_map(project_path)),
+            module: true,
+            ..Default::default()
+        }
+        .cell(),
+    )
+    .into()
+}
@@ -25,7 +25,6 @@ use turbopack_env::ProcessEnvAssetVc;
 
 use crate::{
     embed_js::attached_next_js_package_path,
-    env::filter_for_client,
     next_client::runtime_entry::{RuntimeEntriesVc, RuntimeEntry},
     next_import_map::{
         get_next_client_fallback_import_map, get_next_client_import_map,
@@ -189,6 +188,8 @@ pub fn get_client_assets_path(
     }
 }
 
+/// Computes the runtime entries of the client. `env` is exposed as is, so it
+/// needs to be filtered for the client already.
 #[turbo_tasks::function]
 pub async fn get_client_runtime_entries(
     project_root: FileSystemPathVc,
@@ -201,10 +202,8 @@ pub async fn get_client_runtime_entries(
             .await?
             .as_request();
 
-    let mut runtime_entries = vec![RuntimeEntry::Ecmascript(
-        ProcessEnvAssetVc::new(project_root, filter_for_client(env)).into(),
-    )
-    .cell()];
+    let mut runtime_entries =
+        vec![RuntimeEntry::Ecmascript(ProcessEnvAssetVc::new(project_root, env).into()).cell()];
 
     // It's important that React Refresh come before the regular bootstrap file,
     // because the bootstrap contains JSX which requires Refresh's global
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use turbo_tasks::{
+    primitives::{StringVc, StringsVc},
+    trace::TraceRawVcs,
+    Value,
+};
+use turbo_tasks_env::{EnvMapVc, ProcessEnvVc};
+use turbo_tasks_fs::{FileSystemEntryType, FileSystemPathVc};
+use turbopack::{
+    module_options::ModuleOptionsContext, resolve_options_context::ResolveOptionsContext,
+    transition::TransitionsByNameVc, ModuleAssetContextVc,
+};
+use turbopack_core::{
+    context::AssetContextVc,
+    environment::{EnvironmentIntention, EnvironmentVc, ExecutionEnvironment, NodeJsEnvironmentVc},
+    issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
+    source_asset::SourceAssetVc,
+    virtual_asset::VirtualAssetVc,
+};
+use turbopack_ecmascript::{
+    chunk::EcmascriptChunkPlaceablesVc, EcmascriptInputTransform, EcmascriptInputTransformsVc,
+    EcmascriptModuleAssetType, EcmascriptModuleAssetVc,
+};
+use turbopack_env::ProcessEnvAssetVc;
+
+use crate::{
+    embed_js::{next_js_file, wrap_with_next_js_fs},
+    next_config_source::source_path_regex,
+    next_import_map::get_next_build_import_map,
+    nodejs::evaluate::evaluate,
+};
+
+/// The options of a `next.config.js` that are applied by turbopack. Everything
+/// that is computed by a function in the config (e. g. `rewrites()`) has
+/// already been resolved by the loader.
+#[turbo_tasks::value]
+#[derive(Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NextConfig {
+    pub base_path: String,
+    pub env: IndexMap<String, serde_json::Value>,
+    pub images: ImagesConfig,
+    pub rewrites: Rewrites,
+    pub redirects: Vec<Redirect>,
+    pub headers: Vec<Header>,
+    pub experimental: ExperimentalConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImagesConfig {
+    pub device_sizes: Vec<u16>,
+    pub image_sizes: Vec<u16>,
+    pub path: String,
+    pub loader: String,
+    pub loader_file: String,
+    pub domains: Vec<String>,
+    pub disable_static_images: bool,
+    #[serde(rename = "minimumCacheTTL")]
+    pub minimum_cache_ttl: u32,
+    pub formats: Vec<String>,
+    #[serde(rename = "dangerouslyAllowSVG")]
+    pub dangerously_allow_svg: bool,
+    pub content_security_policy: String,
+    pub remote_patterns: Vec<RemotePattern>,
+    pub unoptimized: bool,
+}
+
+/// Mirrors `imageConfigDefault` of Next.js.
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        Self {
+            device_sizes: vec![640, 750, 828, 1080, 1200, 1920, 2048, 3840],
+            image_sizes: vec![16, 32, 48, 64, 96, 128, 256, 384],
+            path: "/_next/image".to_string(),
+            loader: "default".to_string(),
+            loader_file: String::new(),
+            domains: vec![],
+            disable_static_images: false,
+            minimum_cache_ttl: 60,
+            formats: vec!["image/webp".to_string()],
+            dangerously_allow_svg: false,
+            content_security_policy: "script-src 'none'; frame-src 'none'; sandbox;".to_string(),
+            remote_patterns: vec![],
+            unoptimized: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct RemotePattern {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    pub hostname: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pathname: Option<String>,
+}
+
+/// The rewrites of the config, grouped by the phase in which they are applied.
+/// The loader normalizes the plain array form into `after_files`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Rewrites {
+    pub before_files: Vec<Rewrite>,
+    pub after_files: Vec<Rewrite>,
+    pub fallback: Vec<Rewrite>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct Rewrite {
+    pub source: String,
+    pub destination: String,
+    /// `Some(false)` disables prefixing `source` and `destination` with the
+    /// base path.
+    #[serde(default)]
+    pub base_path: Option<bool>,
+    /// `has` and `missing` conditions, which are not supported yet. Routes
+    /// with conditions are ignored.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub has: Vec<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct Redirect {
+    pub source: String,
+    pub destination: String,
+    #[serde(default)]
+    pub permanent: bool,
+    #[serde(default)]
+    pub status_code: Option<u16>,
+    #[serde(default)]
+    pub base_path: Option<bool>,
+    /// `has` and `missing` conditions, which are not supported yet. Routes
+    /// with conditions are ignored.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub has: Vec<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct Header {
+    pub source: String,
+    pub headers: Vec<HeaderKeyValue>,
+    #[serde(default)]
+    pub base_path: Option<bool>,
+    /// `has` and `missing` conditions, which are not supported yet. Routes
+    /// with conditions are ignored.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub has: Vec<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+pub struct HeaderKeyValue {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExperimentalConfig {
+    pub server_components_external_packages: Vec<String>,
+}
+
+impl NextConfig {
+    /// Removes the rewrites, redirects and headers that can't be applied and
+    /// returns the reasons.
+    fn remove_unsupported_routes(&mut self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        let mut is_supported =
+            |kind: &str, source: &str, has: &[serde_json::Value], missing: &[serde_json::Value]| {
+                let reason = if !has.is_empty() || !missing.is_empty() {
+                    "`has` and `missing` conditions are not supported yet".to_string()
+                } else if let Err(err) = source_path_regex(source) {
+                    format!("{err:#}")
+                } else {
+                    return true;
+                };
+                reasons.push(format!("The {kind} of {source} has been ignored: {reason}"));
+                false
+            };
+        for rewrites in [
+            &mut self.rewrites.before_files,
+            &mut self.rewrites.after_files,
+            &mut self.rewrites.fallback,
+        ] {
+            rewrites.retain(|r| is_supported("rewrite", &r.source, &r.has, &r.missing));
+        }
+        self.redirects
+            .retain(|r| is_supported("redirect", &r.source, &r.has, &r.missing));
+        self.headers
+            .retain(|h| is_supported("headers", &h.source, &h.has, &h.missing));
+        reasons
+    }
+}
+
+/// The name of the env variable the server renderer reads the config dependent
+/// render options from.
+const RENDER_OPTIONS_ENV: &str = "__TURBOPACK_NEXT_RENDER_OPTIONS";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RenderOptions<'a> {
+    base_path: &'a str,
+    images: &'a ImagesConfig,
+}
+
+#[turbo_tasks::value_impl]
+impl NextConfigVc {
+    /// The env variables that the config adds to the code, including the ones
+    /// Next.js defines for its own runtime.
+    #[turbo_tasks::function]
+    pub async fn env(self) -> Result<EnvMapVc> {
+        let this = self.await?;
+        let mut env = IndexMap::new();
+        for (key, value) in this.env.iter() {
+            let value = match value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            env.insert(key.clone(), value);
+        }
+        env.insert("__NEXT_ROUTER_BASEPATH".to_string(), this.base_path.clone());
+        Ok(EnvMapVc::cell(env))
+    }
+
+    /// Like [NextConfigVc::env], but also contains the options the server
+    /// renderer needs to pass to Next.js.
+    #[turbo_tasks::function]
+    pub async fn server_env(self) -> Result<EnvMapVc> {
+        let this = self.await?;
+        let mut env = self.env().await?.clone_value();
+        env.insert(
+            RENDER_OPTIONS_ENV.to_string(),
+            serde_json::to_string(&RenderOptions {
+                base_path: &this.base_path,
+                images: &this.images,
+            })?,
+        );
+        Ok(EnvMapVc::cell(env))
+    }
+
+    #[turbo_tasks::function]
+    pub async fn server_component_externals(self) -> Result<StringsVc> {
+        Ok(StringsVc::cell(
+            self.await?
+                .experimental
+                .server_components_external_packages
+                .clone(),
+        ))
+    }
+}
+
+#[turbo_tasks::function]
+fn next_config_context(project_path: FileSystemPathVc, env: ProcessEnvVc) -> AssetContextVc {
+    ModuleAssetContextVc::new(
+        TransitionsByNameVc::cell(HashMap::new()),
+        EnvironmentVc::new(
+            Value::new(ExecutionEnvironment::NodeJsBuildTime(
+                NodeJsEnvironmentVc::current(env),
+            )),
+            Value::new(EnvironmentIntention::Data),
+        ),
+        ModuleOptionsContext {
+            enable_typescript_transform: true,
+            ..Default::default()
+        }
+        .cell(),
+        ResolveOptionsContext {
+            enable_typescript: true,
+            enable_node_modules: true,
+            enable_node_externals: true,
+            enable_node_native_modules: true,
+            custom_conditions: vec!["development".to_string()],
+            import_map: Some(get_next_build_import_map(project_path)),
+            module: true,
+            ..Default::default()
+        }
+        .cell(),
+    )
+    .into()
+}
+
+/// Loads `next.config.mjs` or `next.config.js` from the project directory by
+/// evaluating it in node.js. Falls back to the default config when there is no
+/// config file or when it can't be loaded.
+#[turbo_tasks::function]
+pub async fn load_next_config(
+    project_path: FileSystemPathVc,
+    intermediate_output_path: FileSystemPathVc,
+    env: ProcessEnvVc,
+) -> Result<NextConfigVc> {
+    let project_path = wrap_with_next_js_fs(project_path);
+
+    let mut config_path = None;
+    for name in ["next.config.mjs", "next.config.js"] {
+        let path = project_path.join(name);
+        if *path.get_type().await? == FileSystemEntryType::File {
+            config_path = Some(path);
+            break;
+        }
+    }
+    let config_path = if let Some(config_path) = config_path {
+        config_path
+    } else {
+        return Ok(NextConfig::default().cell());
+    };
+
+    let context = next_config_context(project_path, env);
+    let config_asset = context.process(SourceAssetVc::new(config_path).into());
+    let loader = VirtualAssetVc::new(
+        config_asset.path().join("config-loader.ts"),
+        next_js_file("entry/config/next.ts").into(),
+    );
+    let module = EcmascriptModuleAssetVc::new(
+        loader.into(),
+        context,
+        Value::new(EcmascriptModuleAssetType::Typescript),
+        EcmascriptInputTransformsVc::cell(vec![EcmascriptInputTransform::TypeScript]),
+        context.environment(),
+    );
+    let runtime_entries =
+        EcmascriptChunkPlaceablesVc::cell(vec![
+            ProcessEnvAssetVc::new(project_path, env).as_ecmascript_chunk_placeable()
+        ]);
+
+    let value = evaluate(
+        project_path,
+        config_path,
+        module,
+        runtime_entries,
+        intermediate_output_path,
+    )
+    .await?;
+    if value.is_null() {
+        // The evaluation has already reported an issue.
+        return Ok(NextConfig::default().cell());
+    }
+    match serde_json::from_value::<NextConfig>((*value).clone()) {
+        Ok(mut config) => {
+            for reason in config.remove_unsupported_routes() {
+                NextConfigIssue {
+                    severity: IssueSeverity::Warning.into(),
+                    path: config_path,
+                    message: StringVc::cell(reason),
+                }
+                .cell()
+                .as_issue()
+                .emit();
+            }
+            Ok(config.cell())
+        }
+        Err(err) => {
+            NextConfigIssue {
+                severity: IssueSeverity::Error.into(),
+                path: config_path,
+                message: StringVc::cell(format!(
+                    "The config is invalid and has been ignored: {err}"
+                )),
+            }
+            .cell()
+            .as_issue()
+            .emit();
+            Ok(NextConfig::default().cell())
+        }
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct NextConfigIssue {
+    pub severity: IssueSeverityVc,
+    pub path: FileSystemPathVc,
+    pub message: StringVc,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for NextConfigIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        self.severity
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("An issue occurred while loading the Next.js config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("next config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        self.message
+    }
+}
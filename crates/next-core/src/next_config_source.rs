@@ -0,0 +1,599 @@
+use std::mem::take;
+
+use anyhow::{bail, Context, Result};
+use indexmap::IndexMap;
+use turbo_tasks::Value;
+use turbo_tasks_fs::{rope::Rope, FileContent};
+use turbopack_core::asset::AssetContent;
+use turbopack_dev_server::source::{
+    query::Query, ContentSource, ContentSourceContent, ContentSourceData, ContentSourceResult,
+    ContentSourceResultVc, ContentSourceVc, ProxyResult,
+};
+
+use crate::{
+    next_config::{NextConfigVc, Rewrite},
+    path_regex::{PathRegex, PathRegexBuilder, PathRegexVc},
+};
+
+/// Applies the `headers` and `redirects` of the `next.config.js` to requests.
+/// It wraps the middleware, as Next.js applies them before the middleware runs.
+#[turbo_tasks::value(shared)]
+pub struct NextConfigContentSource {
+    pub inner: ContentSourceVc,
+    pub next_config: NextConfigVc,
+}
+
+#[turbo_tasks::value_impl]
+impl NextConfigContentSourceVc {
+    #[turbo_tasks::function]
+    pub fn new(inner: ContentSourceVc, next_config: NextConfigVc) -> Self {
+        NextConfigContentSource { inner, next_config }.cell()
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for NextConfigContentSource {
+    #[turbo_tasks::function]
+    async fn get(
+        &self,
+        path: &str,
+        data: Value<ContentSourceData>,
+    ) -> Result<ContentSourceResultVc> {
+        let config = self.next_config.await?;
+        let base_path = config.base_path.trim_start_matches('/');
+        let stripped = strip_base_path(path, base_path);
+        let rule_path = |rule_base_path: Option<bool>| {
+            if rule_base_path == Some(false) {
+                Some(path)
+            } else {
+                stripped
+            }
+        };
+
+        let mut headers = Vec::new();
+        for header in config.headers.iter() {
+            if let Some(rule_path) = rule_path(header.base_path) {
+                if let Some(params) = match_source(&header.source, rule_path).await? {
+                    for entry in header.headers.iter() {
+                        headers.push(entry.key.clone());
+                        headers.push(substitute_params(&entry.value, &params));
+                    }
+                }
+            }
+        }
+
+        for redirect in config.redirects.iter() {
+            let rule_path = if let Some(rule_path) = rule_path(redirect.base_path) {
+                rule_path
+            } else {
+                continue;
+            };
+            if let Some(params) = match_source(&redirect.source, rule_path).await? {
+                let mut location = substitute_params(&redirect.destination, &params);
+                if redirect.base_path != Some(false)
+                    && !base_path.is_empty()
+                    && location.starts_with('/')
+                {
+                    location = format!("/{base_path}{location}");
+                }
+                let default_status = if redirect.permanent { 308 } else { 307 };
+                let status = redirect.status_code.unwrap_or(default_status);
+                headers.push("location".to_string());
+                headers.push(location);
+                return Ok(ContentSourceResultVc::exact(
+                    ContentSourceContent::HttpProxy(
+                        ProxyResult {
+                            status,
+                            headers,
+                            body: Rope::default(),
+                        }
+                        .cell(),
+                    )
+                    .cell(),
+                ));
+            }
+        }
+
+        let result = self.inner.get(path, data);
+        if headers.is_empty() || stripped.unwrap_or(path).starts_with("_next/") {
+            Ok(result)
+        } else {
+            Ok(add_headers(result, headers))
+        }
+    }
+}
+
+/// Applies the `basePath` and the `rewrites` of the `next.config.js` to
+/// requests before they reach the inner content source. It's wrapped by the
+/// middleware, as Next.js applies rewrites after the middleware has run.
+///
+/// `beforeFiles` rewrites are applied before the inner source is asked,
+/// `afterFiles` and `fallback` rewrites only when it has nothing to serve for
+/// the original path. The query of the destination is added to the query of
+/// the request.
+#[turbo_tasks::value(shared)]
+pub struct NextRewritesContentSource {
+    pub inner: ContentSourceVc,
+    pub next_config: NextConfigVc,
+}
+
+#[turbo_tasks::value_impl]
+impl NextRewritesContentSourceVc {
+    #[turbo_tasks::function]
+    pub fn new(inner: ContentSourceVc, next_config: NextConfigVc) -> Self {
+        NextRewritesContentSource { inner, next_config }.cell()
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for NextRewritesContentSource {
+    #[turbo_tasks::function]
+    async fn get(
+        &self,
+        path: &str,
+        data: Value<ContentSourceData>,
+    ) -> Result<ContentSourceResultVc> {
+        let config = self.next_config.await?;
+        let base_path = config.base_path.trim_start_matches('/');
+        let stripped = strip_base_path(path, base_path);
+        // Assets are requested without the base path, as chunk urls don't
+        // include it.
+        let inner_path = stripped.or_else(|| path.starts_with("_next/").then_some(path));
+
+        let mut result =
+            match rewrite(&config.rewrites.before_files, path, stripped, base_path).await? {
+                Some((rewritten, query)) => get_with_query(self.inner, rewritten, query, &data),
+                None => match inner_path {
+                    Some(inner_path) => self.inner.get(inner_path, data.clone()),
+                    // Only rewrites without the base path apply to paths outside
+                    // of it
+                    None => ContentSourceResultVc::not_found(),
+                },
+            };
+
+        for rewrites in [&config.rewrites.after_files, &config.rewrites.fallback] {
+            if !is_not_found(result).await? {
+                break;
+            }
+            if let Some((rewritten, query)) = rewrite(rewrites, path, stripped, base_path).await? {
+                result = get_with_query(self.inner, rewritten, query, &data);
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Removes the base path from a path. Returns `None` when the path is not
+/// within the base path.
+fn strip_base_path<'a>(path: &'a str, base_path: &str) -> Option<&'a str> {
+    if base_path.is_empty() {
+        return Some(path);
+    }
+    let rest = path.strip_prefix(base_path)?;
+    if rest.is_empty() {
+        Some(rest)
+    } else {
+        rest.strip_prefix('/')
+    }
+}
+
+async fn is_not_found(result: ContentSourceResultVc) -> Result<bool> {
+    Ok(matches!(
+        &*result.await?.content.await?,
+        ContentSourceContent::NotFound
+    ))
+}
+
+/// Returns the path of the first matching rewrite, relative to the server
+/// root, and the query of its destination. Rewrites to external urls can't be
+/// served by the dev server and are skipped.
+async fn rewrite(
+    rewrites: &[Rewrite],
+    path: &str,
+    stripped: Option<&str>,
+    base_path: &str,
+) -> Result<Option<(String, String)>> {
+    for rewrite in rewrites {
+        let rule_path = if rewrite.base_path == Some(false) {
+            path
+        } else if let Some(stripped) = stripped {
+            stripped
+        } else {
+            continue;
+        };
+        if rewrite.destination.starts_with("http://") || rewrite.destination.starts_with("https://")
+        {
+            continue;
+        }
+        if let Some(params) = match_source(&rewrite.source, rule_path).await? {
+            let destination = substitute_params(&rewrite.destination, &params);
+            let (destination, query) = destination
+                .split_once('?')
+                .unwrap_or((destination.as_str(), ""));
+            let destination = destination.trim_matches('/');
+            let destination = if rewrite.base_path == Some(false) {
+                strip_base_path(destination, base_path).unwrap_or(destination)
+            } else {
+                destination
+            };
+            return Ok(Some((destination.to_string(), query.to_string())));
+        }
+    }
+    Ok(None)
+}
+
+/// Gets the rewritten path from the inner source, with the query of the
+/// rewrite destination added to the query of the request.
+fn get_with_query(
+    inner: ContentSourceVc,
+    path: String,
+    query: String,
+    data: &ContentSourceData,
+) -> ContentSourceResultVc {
+    if query.is_empty() {
+        return inner.get(&path, Value::new(data.clone()));
+    }
+    let source: ContentSourceVc = QueryContentSource { inner, query }.cell().into();
+    source.get(&path, Value::new(data.clone()))
+}
+
+async fn match_source(source: &str, path: &str) -> Result<Option<IndexMap<String, String>>> {
+    Ok(source_regex(source).await?.get_matches(&format!("/{path}")))
+}
+
+#[turbo_tasks::function]
+fn source_regex(source: &str) -> Result<PathRegexVc> {
+    Ok(PathRegexVc::cell(source_path_regex(source)?))
+}
+
+/// Converts a `source` of the config (e. g. `/blog/:slug*` or
+/// `/:id(\d+)`) into a regular expression like path-to-regexp does for
+/// Next.js. It matches paths with their leading slash.
+pub(crate) fn source_path_regex(source: &str) -> Result<PathRegex> {
+    let mut path_regex = PathRegexBuilder::new();
+    let mut literal = String::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        let (name, pattern) = match c {
+            '\\' => {
+                let escaped = rest
+                    .chars()
+                    .next()
+                    .with_context(|| format!("{source} ends with an escape character"))?;
+                literal.push(escaped);
+                rest = &rest[escaped.len_utf8()..];
+                continue;
+            }
+            ':' => {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                if end == 0 {
+                    bail!("{source} contains a param without a name");
+                }
+                let name = &rest[..end];
+                rest = &rest[end..];
+                let pattern = match rest.strip_prefix('(') {
+                    Some(after) => {
+                        let (pattern, after) = param_pattern(source, after)?;
+                        rest = after;
+                        pattern
+                    }
+                    None => "[^/#?]+?",
+                };
+                (name, pattern)
+            }
+            // Unnamed params are matched, but can't be used in destinations
+            '(' => {
+                let (pattern, after) = param_pattern(source, rest)?;
+                rest = after;
+                ("", pattern)
+            }
+            c => {
+                literal.push(c);
+                continue;
+            }
+        };
+        let modifier = match rest.chars().next() {
+            Some(modifier @ ('?' | '*' | '+')) => {
+                rest = &rest[1..];
+                Some(modifier)
+            }
+            _ => None,
+        };
+        let prefix = if literal.ends_with('/') {
+            literal.pop();
+            "/"
+        } else {
+            ""
+        };
+        path_regex.push_literal(take(&mut literal));
+        path_regex.push_custom_param(name, prefix, pattern, modifier);
+    }
+    path_regex.push_literal(literal);
+    path_regex
+        .build()
+        .with_context(|| format!("{source} is not a valid source"))
+}
+
+/// Splits the custom regular expression of a param (e. g. `\d+` of
+/// `:id(\d+)`) from the rest of the source, which starts after the opening
+/// parenthesis.
+fn param_pattern<'a>(source: &str, rest: &'a str) -> Result<(&'a str, &'a str)> {
+    let mut depth = 1;
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '(' => {
+                // The captures of the regular expression are the params
+                if !rest[i + 1..].starts_with('?') {
+                    bail!("{source} contains a capturing group in the pattern of a param");
+                }
+                depth += 1;
+            }
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    if i == 0 {
+                        bail!("{source} contains a param with an empty pattern");
+                    }
+                    return Ok((&rest[..i], &rest[i + 1..]));
+                }
+            }
+            _ => {}
+        }
+    }
+    bail!("{source} contains an unbalanced pattern")
+}
+
+/// Replaces the `:param` placeholders in a destination or header value with
+/// the matched values.
+fn substitute_params(template: &str, params: &IndexMap<String, String>) -> String {
+    let mut params = params.iter().collect::<Vec<_>>();
+    // Longer names first, so `:slug` doesn't replace the start of `:slugs`.
+    params.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+    let mut result = template.to_string();
+    for (name, value) in params {
+        for suffix in ["*", "+", "?", ""] {
+            result = result.replace(&format!(":{name}{suffix}"), value);
+        }
+    }
+    result
+}
+
+/// Adds headers to the response of a content source. Static content is
+/// converted into a proxied response, so it can carry the headers.
+#[turbo_tasks::function]
+pub(crate) async fn add_headers(
+    result: ContentSourceResultVc,
+    headers: Vec<String>,
+) -> Result<ContentSourceResultVc> {
+    let result_value = result.await?;
+    let proxy_result = match &*result_value.content.await? {
+        ContentSourceContent::NotFound => return Ok(result),
+        ContentSourceContent::NeedData { source, path, vary } => {
+            return Ok(ContentSourceResult {
+                specificity: result_value.specificity,
+                content: ContentSourceContent::NeedData {
+                    source: HeadersContentSource {
+                        inner: *source,
+                        headers,
+                    }
+                    .cell()
+                    .into(),
+                    path: path.clone(),
+                    vary: vary.clone(),
+                }
+                .cell(),
+            }
+            .cell());
+        }
+        ContentSourceContent::HttpProxy(proxy_result) => {
+            let proxy_result = proxy_result.await?;
+            let mut all_headers = proxy_result.headers.clone();
+            all_headers.extend(headers);
+            ProxyResult {
+                status: proxy_result.status,
+                headers: all_headers,
+                body: proxy_result.body.clone(),
+            }
+        }
+        ContentSourceContent::Static(content) => {
+            let file = if let AssetContent::File(file) = &*content.content().await? {
+                file.await?
+            } else {
+                return Ok(result);
+            };
+            let file = if let FileContent::Content(file) = &*file {
+                file
+            } else {
+                return Ok(result);
+            };
+            let mut all_headers = Vec::new();
+            if let Some(content_type) = file.content_type() {
+                all_headers.push("content-type".to_string());
+                all_headers.push(content_type.to_string());
+            }
+            all_headers.extend(headers);
+            ProxyResult {
+                status: 200,
+                headers: all_headers,
+                body: file.content().clone(),
+            }
+        }
+    };
+    Ok(ContentSourceResult {
+        specificity: result_value.specificity,
+        content: ContentSourceContent::HttpProxy(proxy_result.cell()).cell(),
+    }
+    .cell())
+}
+
+/// Adds the headers matched by [NextConfigContentSource] to content that is
+/// only available once the request data is known.
+#[turbo_tasks::value(shared)]
+struct HeadersContentSource {
+    inner: ContentSourceVc,
+    headers: Vec<String>,
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for HeadersContentSource {
+    #[turbo_tasks::function]
+    fn get(&self, path: &str, data: Value<ContentSourceData>) -> ContentSourceResultVc {
+        add_headers(self.inner.get(path, data), self.headers.clone())
+    }
+}
+
+/// Adds the query of a rewrite destination to the query of the request.
+#[turbo_tasks::value(shared)]
+struct QueryContentSource {
+    inner: ContentSourceVc,
+    query: String,
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for QueryContentSource {
+    #[turbo_tasks::function]
+    async fn get(
+        &self,
+        path: &str,
+        data: Value<ContentSourceData>,
+    ) -> Result<ContentSourceResultVc> {
+        let mut data = data.into_value();
+        // The query is only available when the inner source has asked for it
+        if let Some(query) = &mut data.query {
+            let destination_query: Query = serde_qs::from_str(&self.query)?;
+            query.extend(
+                destination_query
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone())),
+            );
+        }
+        let result = self.inner.get(path, Value::new(data));
+        let result_value = result.await?;
+        if let ContentSourceContent::NeedData { source, path, vary } =
+            &*result_value.content.await?
+        {
+            return Ok(ContentSourceResult {
+                specificity: result_value.specificity,
+                content: ContentSourceContent::NeedData {
+                    source: QueryContentSource {
+                        inner: *source,
+                        query: self.query.clone(),
+                    }
+                    .cell()
+                    .into(),
+                    path: path.clone(),
+                    vary: vary.clone(),
+                }
+                .cell(),
+            }
+            .cell());
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    fn matches(source: &str, path: &str) -> Option<Vec<(String, String)>> {
+        source_path_regex(source)
+            .unwrap()
+            .get_matches(path)
+            .map(|params| params.into_iter().collect())
+    }
+
+    fn params(params: &[(&str, &str)]) -> IndexMap<String, String> {
+        params
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::root("/", "/", Some(vec![]))]
+    #[case::static_path("/about", "/about", Some(vec![]))]
+    #[case::static_mismatch("/about", "/about/team", None)]
+    #[case::param("/blog/:slug", "/blog/hello", Some(vec![("slug", "hello")]))]
+    #[case::param_single_segment("/blog/:slug", "/blog/a/b", None)]
+    #[case::optional_present("/blog/:slug?", "/blog/hello", Some(vec![("slug", "hello")]))]
+    #[case::optional_missing("/blog/:slug?", "/blog", Some(vec![]))]
+    #[case::zero_or_more("/blog/:slug*", "/blog/a/b", Some(vec![("slug", "a/b")]))]
+    #[case::zero_or_more_missing("/blog/:slug*", "/blog", Some(vec![]))]
+    #[case::one_or_more("/blog/:slug+", "/blog/a/b", Some(vec![("slug", "a/b")]))]
+    #[case::one_or_more_missing("/blog/:slug+", "/blog", None)]
+    #[case::custom_regex("/post/:id(\\d{1,})", "/post/123", Some(vec![("id", "123")]))]
+    #[case::custom_regex_mismatch("/post/:id(\\d{1,})", "/post/abc", None)]
+    #[case::custom_regex_with_slash("/docs/:path(.*)", "/docs/a/b", Some(vec![("path", "a/b")]))]
+    #[case::unnamed("/(.*)", "/a/b", Some(vec![]))]
+    #[case::unnamed_root("/(.*)", "/", Some(vec![]))]
+    #[case::escaped("/a\\:b", "/a:b", Some(vec![]))]
+    #[case::literal_suffix("/:name.html", "/index.html", Some(vec![("name", "index")]))]
+    fn matches_sources(
+        #[case] source: &str,
+        #[case] path: &str,
+        #[case] expected: Option<Vec<(&str, &str)>>,
+    ) {
+        let expected = expected.map(|params| {
+            params
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        });
+        assert_eq!(matches(source, path), expected);
+    }
+
+    #[rstest]
+    #[case::missing_name("/:")]
+    #[case::capturing_group("/:id((a|b))")]
+    #[case::empty_pattern("/:id()")]
+    #[case::unbalanced("/:id(a")]
+    #[case::trailing_escape("/a\\")]
+    #[case::invalid_regex("/:id([)")]
+    fn rejects_invalid_sources(#[case] source: &str) {
+        assert!(source_path_regex(source).is_err());
+    }
+
+    #[rstest]
+    #[case::param("/news/:slug", &[("slug", "hello")], "/news/hello")]
+    #[case::modifier("/news/:slug*", &[("slug", "a/b")], "/news/a/b")]
+    #[case::longer_name_first(
+        "/:slug/:slugs",
+        &[("slug", "a"), ("slugs", "b")],
+        "/a/b"
+    )]
+    #[case::query("/search?q=:q", &[("q", "rust")], "/search?q=rust")]
+    #[case::unused("/static", &[("slug", "a")], "/static")]
+    fn substitutes_params(
+        #[case] template: &str,
+        #[case] values: &[(&str, &str)],
+        #[case] expected: &str,
+    ) {
+        assert_eq!(substitute_params(template, &params(values)), expected);
+    }
+
+    #[rstest]
+    #[case::no_base_path("docs/a", "", Some("docs/a"))]
+    #[case::within("docs/a", "docs", Some("a"))]
+    #[case::base_path_itself("docs", "docs", Some(""))]
+    #[case::prefix_of_segment("docsite/a", "docs", None)]
+    #[case::outside("blog/a", "docs", None)]
+    #[case::nested_base_path("a/b/c", "a/b", Some("c"))]
+    fn strips_base_path(
+        #[case] path: &str,
+        #[case] base_path: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(strip_base_path(path, base_path), expected);
+    }
+}
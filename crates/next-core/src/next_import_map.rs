@@ -8,6 +8,7 @@ use turbopack_core::resolve::options::{
 use crate::{
     embed_js::{attached_next_js_package_path, VIRTUAL_PACKAGE_NAME},
     next_client::context::ContextType,
+    next_config::NextConfigVc,
     next_server::ServerContextType,
 };
 
@@ -99,6 +100,7 @@ pub fn get_next_client_fallback_import_map(ty: Value<ContextType>) -> ImportMapV
 pub async fn get_next_server_import_map(
     project_path: FileSystemPathVc,
     ty: Value<ServerContextType>,
+    next_config: NextConfigVc,
     externals: StringsVc,
 ) -> Result<ImportMapVc> {
     let mut import_map = ImportMap::empty();
@@ -153,7 +155,9 @@ pub async fn get_next_server_import_map(
                 request_to_import_mapping(app_dir, "next/dist/compiled/react-dom/*"),
             );
 
-            for external in externals.await?.iter() {
+            let externals = externals.await?;
+            let config_externals = next_config.server_component_externals().await?;
+            for external in externals.iter().chain(config_externals.iter()) {
                 import_map.insert_exact_alias(external, ImportMapping::External(None).into());
                 import_map.insert_wildcard_alias(
                     format!("{external}/"),
@@ -161,11 +165,28 @@ pub async fn get_next_server_import_map(
                 );
             }
         }
+        // Edge functions can't require node.js modules, so `next` is bundled.
+        ServerContextType::Middleware => {}
     }
 
     Ok(import_map.cell())
 }
 
+/// Computes the Next-specific import map for code that is evaluated in
+/// node.js at build time, like the `next.config.js`.
+#[turbo_tasks::function]
+pub fn get_next_build_import_map(project_path: FileSystemPathVc) -> ImportMapVc {
+    let mut import_map = ImportMap::empty();
+    let package_root = attached_next_js_package_path(project_path);
+
+    insert_next_shared_aliases(&mut import_map, package_root);
+
+    import_map.insert_exact_alias("next", ImportMapping::External(None).into());
+    import_map.insert_wildcard_alias("next/", ImportMapping::External(None).into());
+
+    import_map.cell()
+}
+
 pub fn get_next_client_resolved_map(
     context: FileSystemPathVc,
     root: FileSystemPathVc,
@@ -9,11 +9,12 @@ use turbopack::{
     resolve_options_context::{ResolveOptionsContext, ResolveOptionsContextVc},
 };
 use turbopack_core::environment::{
-    EnvironmentIntention, EnvironmentVc, ExecutionEnvironment, NodeJsEnvironmentVc,
+    EdgeFunctionEnvironment, EnvironmentIntention, EnvironmentVc, ExecutionEnvironment,
+    NodeJsEnvironmentVc, EDGE_RESOLVE_CONDITIONS,
 };
 use turbopack_ecmascript::EcmascriptInputTransform;
 
-use crate::next_import_map::get_next_server_import_map;
+use crate::{next_config::NextConfigVc, next_import_map::get_next_server_import_map};
 
 #[turbo_tasks::value(serialization = "auto_for_input")]
 #[derive(Debug, Copy, Clone, Hash, PartialOrd, Ord)]
@@ -21,15 +22,18 @@ pub enum ServerContextType {
     Pages { pages_dir: FileSystemPathVc },
     AppSSR { app_dir: FileSystemPathVc },
     AppRSC { app_dir: FileSystemPathVc },
+    Middleware,
 }
 
 #[turbo_tasks::function]
 pub fn get_server_resolve_options_context(
     project_path: FileSystemPathVc,
     ty: Value<ServerContextType>,
+    next_config: NextConfigVc,
     externals: StringsVc,
 ) -> ResolveOptionsContextVc {
-    let next_server_import_map = get_next_server_import_map(project_path, ty, externals);
+    let next_server_import_map =
+        get_next_server_import_map(project_path, ty, next_config, externals);
     match ty.into_value() {
         ServerContextType::Pages { .. } | ServerContextType::AppSSR { .. } => {
             ResolveOptionsContext {
@@ -55,6 +59,19 @@ pub fn get_server_resolve_options_context(
             module: true,
             ..Default::default()
         },
+        ServerContextType::Middleware => ResolveOptionsContext {
+            enable_typescript: true,
+            enable_react: true,
+            enable_node_modules: true,
+            custom_conditions: ["development"]
+                .into_iter()
+                .chain(EDGE_RESOLVE_CONDITIONS)
+                .map(|condition| condition.to_string())
+                .collect(),
+            import_map: Some(next_server_import_map),
+            module: true,
+            ..Default::default()
+        },
     }
     .cell()
 }
@@ -64,16 +81,28 @@ pub fn get_server_environment(
     ty: Value<ServerContextType>,
     process_env: ProcessEnvVc,
 ) -> EnvironmentVc {
-    EnvironmentVc::new(
+    let node_js = || {
         Value::new(ExecutionEnvironment::NodeJsLambda(
             NodeJsEnvironmentVc::current(process_env),
-        )),
-        match ty.into_value() {
-            ServerContextType::Pages { .. } => Value::new(EnvironmentIntention::ServerRendering),
-            ServerContextType::AppSSR { .. } => Value::new(EnvironmentIntention::Prerendering),
-            ServerContextType::AppRSC { .. } => Value::new(EnvironmentIntention::ServerRendering),
-        },
-    )
+        ))
+    };
+    match ty.into_value() {
+        ServerContextType::Pages { .. } => {
+            EnvironmentVc::new(node_js(), Value::new(EnvironmentIntention::ServerRendering))
+        }
+        ServerContextType::AppSSR { .. } => {
+            EnvironmentVc::new(node_js(), Value::new(EnvironmentIntention::Prerendering))
+        }
+        ServerContextType::AppRSC { .. } => {
+            EnvironmentVc::new(node_js(), Value::new(EnvironmentIntention::ServerRendering))
+        }
+        ServerContextType::Middleware => EnvironmentVc::new(
+            Value::new(ExecutionEnvironment::EdgeFunction(
+                EdgeFunctionEnvironment::default().into(),
+            )),
+            Value::new(EnvironmentIntention::Middleware),
+        ),
+    }
 }
 
 #[turbo_tasks::function]
@@ -96,6 +125,10 @@ pub fn get_server_module_options_context(ty: Value<ServerContextType>) -> Module
             )],
             ..Default::default()
         },
+        ServerContextType::Middleware => ModuleOptionsContext {
+            enable_typescript_transform: true,
+            ..Default::default()
+        },
     }
     .cell()
 }
@@ -1,11 +1,14 @@
-use std::fmt::Write;
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::Write,
+};
 
 use anyhow::Result;
 use turbo_tasks_fs::{File, FileSystemPathVc};
 use turbopack::ecmascript::utils::stringify_str;
 use turbopack_core::{
     asset::{Asset, AssetContentVc, AssetVc},
-    chunk::{ChunkGroupVc, ChunkReferenceVc},
+    chunk::{ChunkGroupVc, ChunkReferenceVc, ChunkVc},
     reference::AssetReferencesVc,
 };
 
@@ -52,3 +55,151 @@ impl Asset for NodeJsBootstrapAsset {
         Ok(AssetReferencesVc::cell(references))
     }
 }
+
+/// The Web APIs of the host that are exposed to edge functions.
+const EDGE_GLOBALS: &[&str] = &[
+    "AbortController",
+    "AbortSignal",
+    "atob",
+    "Blob",
+    "btoa",
+    "clearInterval",
+    "clearTimeout",
+    "console",
+    "crypto",
+    "CryptoKey",
+    "DOMException",
+    "Event",
+    "EventTarget",
+    "fetch",
+    "FormData",
+    "Headers",
+    "queueMicrotask",
+    "ReadableStream",
+    "Request",
+    "Response",
+    "setInterval",
+    "setTimeout",
+    "structuredClone",
+    "TextDecoder",
+    "TextEncoder",
+    "TransformStream",
+    "URL",
+    "URLSearchParams",
+    "WebAssembly",
+    "WritableStream",
+];
+
+/// Returns the chunks of all chunk groups that are loaded on demand by the
+/// `chunk_group`, e. g. for `import()`, transitively.
+async fn async_chunks(chunk_group: ChunkGroupVc) -> Result<Vec<ChunkVc>> {
+    let entry_chunks = chunk_group.chunks().await?;
+    let mut visited: HashSet<ChunkVc> = entry_chunks.iter().copied().collect();
+    let mut queue: VecDeque<ChunkVc> = entry_chunks.iter().copied().collect();
+    let mut chunks = Vec::new();
+    while let Some(chunk) = queue.pop_front() {
+        for reference in chunk.references().await?.iter() {
+            for asset in reference.resolve_reference().primary_assets().await?.iter() {
+                if let Some(chunk) = ChunkVc::resolve_from(asset).await? {
+                    if visited.insert(chunk) {
+                        chunks.push(chunk);
+                        queue.push_back(chunk);
+                    }
+                }
+            }
+        }
+    }
+    Ok(chunks)
+}
+
+/// Bootstraps an edge function in node.js. The chunks of the edge function are
+/// evaluated in a sandbox that only provides Web APIs and is exposed as
+/// `EDGE_SANDBOX` to the `chunk_group` of the host, which runs in node.js.
+///
+/// Edge functions can't load chunks at runtime, so the chunks of their async
+/// chunk groups are evaluated upfront, before the entry. They only register
+/// their modules, which `import()` picks up later.
+#[turbo_tasks::value(shared)]
+pub(super) struct EdgeBootstrapAsset {
+    pub(super) path: FileSystemPathVc,
+    pub(super) chunk_group: ChunkGroupVc,
+    pub(super) edge_chunk_group: ChunkGroupVc,
+}
+
+#[turbo_tasks::value_impl]
+impl Asset for EdgeBootstrapAsset {
+    #[turbo_tasks::function]
+    fn path(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    async fn content(&self) -> Result<AssetContentVc> {
+        let context_path = self.path.parent().await?;
+
+        let mut output = "Error.stackTraceLimit = 100;\nglobal.self = global;\n".to_string();
+        writeln!(
+            &mut output,
+            r#"const fs = require("fs");
+const path = require("path");
+const vm = require("vm");
+const sandbox = {{ EdgeRuntime: "edge-runtime", process: {{ env: process.env }} }};
+for (const name of [{}]) {{
+  if (name in globalThis) sandbox[name] = globalThis[name];
+}}
+sandbox.self = sandbox;
+vm.createContext(sandbox);
+function evaluate(chunkPath) {{
+  const filename = path.join(__dirname, chunkPath);
+  vm.runInContext(fs.readFileSync(filename, "utf-8"), sandbox, {{ filename }});
+}}"#,
+            EDGE_GLOBALS
+                .iter()
+                .map(|name| stringify_str(name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+
+        let async_chunks = async_chunks(self.edge_chunk_group).await?;
+        for chunk in async_chunks
+            .iter()
+            .chain(self.edge_chunk_group.chunks().await?.iter())
+        {
+            let path = &*chunk.path().await?;
+            if let Some(p) = context_path.get_relative_path_to(path) {
+                if p.ends_with(".js") {
+                    writeln!(&mut output, "evaluate({});", stringify_str(&p))?;
+                }
+            }
+        }
+
+        output.push_str("global.EDGE_SANDBOX = sandbox;\n");
+
+        for chunk in self.chunk_group.chunks().await?.iter() {
+            let path = &*chunk.path().await?;
+            if let Some(p) = context_path.get_relative_path_to(path) {
+                if p.ends_with(".js") {
+                    writeln!(&mut output, "require({});", stringify_str(&p))?;
+                }
+            }
+        }
+
+        Ok(File::from(output).into())
+    }
+
+    #[turbo_tasks::function]
+    async fn references(&self) -> Result<AssetReferencesVc> {
+        let mut references = Vec::new();
+        for chunk in self
+            .chunk_group
+            .chunks()
+            .await?
+            .iter()
+            .chain(self.edge_chunk_group.chunks().await?.iter())
+            .chain(async_chunks(self.edge_chunk_group).await?.iter())
+        {
+            references.push(ChunkReferenceVc::new(*chunk).into());
+        }
+        Ok(AssetReferencesVc::cell(references))
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use turbo_tasks::primitives::{JsonValueVc, StringVc};
+use turbo_tasks_fs::FileSystemPathVc;
+use turbopack_core::{asset::AssetVc, chunk::dev::DevChunkingContextVc};
+use turbopack_ecmascript::{chunk::EcmascriptChunkPlaceablesVc, EcmascriptModuleAssetVc};
+
+use super::{
+    get_intermediate_asset, get_renderer_pool, issue::EvaluationIssue, pool::NodeJsOperation,
+    trace_stack, StructuredError,
+};
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum EvalJavaScriptOutgoingMessage {
+    Evaluate,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum EvalJavaScriptIncomingMessage {
+    JsonValue { data: String },
+    Error(StructuredError),
+}
+
+/// Evaluates a module in a node.js process and returns the JSON value it
+/// responds with. The module is expected to answer an `evaluate` message with
+/// a `jsonValue` message.
+///
+/// Errors are reported as issue at `issue_context` and result in a `null`
+/// value.
+#[turbo_tasks::function]
+pub async fn evaluate(
+    context_path: FileSystemPathVc,
+    issue_context: FileSystemPathVc,
+    module: EcmascriptModuleAssetVc,
+    runtime_entries: EcmascriptChunkPlaceablesVc,
+    intermediate_output_path: FileSystemPathVc,
+) -> Result<JsonValueVc> {
+    let chunking_context = DevChunkingContextVc::builder(
+        context_path,
+        intermediate_output_path,
+        intermediate_output_path.join("chunks"),
+        intermediate_output_path.join("assets"),
+    )
+    .build();
+    let intermediate_asset = get_intermediate_asset(
+        module,
+        runtime_entries,
+        chunking_context,
+        intermediate_output_path,
+    );
+    let renderer_pool = get_renderer_pool(intermediate_asset, intermediate_output_path);
+    // Read this strongly consistent, since we don't want to run inconsistent
+    // node.js code.
+    let pool = renderer_pool.strongly_consistent().await?;
+    let mut operation = match pool.operation().await {
+        Ok(operation) => operation,
+        Err(err) => return evaluation_error(issue_context, err, None).await,
+    };
+
+    match run_evaluate_operation(&mut operation, intermediate_asset, intermediate_output_path).await
+    {
+        Ok(value) => Ok(value),
+        Err(err) => evaluation_error(issue_context, err, Some(operation)).await,
+    }
+}
+
+async fn run_evaluate_operation(
+    operation: &mut NodeJsOperation,
+    intermediate_asset: AssetVc,
+    intermediate_output_path: FileSystemPathVc,
+) -> Result<JsonValueVc> {
+    operation
+        .send(EvalJavaScriptOutgoingMessage::Evaluate)
+        .await
+        .context("sending evaluate request to node.js process")?;
+    match operation
+        .recv()
+        .await
+        .context("receiving from node.js process")?
+    {
+        EvalJavaScriptIncomingMessage::JsonValue { data } => Ok(JsonValueVc::cell(
+            serde_json::from_str(&data).context("parsing the evaluation result")?,
+        )),
+        EvalJavaScriptIncomingMessage::Error(error) => {
+            bail!(trace_stack(error, intermediate_asset, intermediate_output_path).await?)
+        }
+    }
+}
+
+async fn evaluation_error(
+    issue_context: FileSystemPathVc,
+    error: anyhow::Error,
+    operation: Option<NodeJsOperation>,
+) -> Result<JsonValueVc> {
+    let status = match operation {
+        Some(operation) => Some(operation.wait_or_kill().await?),
+        None => None,
+    };
+
+    EvaluationIssue {
+        context: issue_context,
+        message: StringVc::cell(format!("{error:?}")),
+        status: status.and_then(|status| status.code()),
+    }
+    .cell()
+    .as_issue()
+    .emit();
+
+    Ok(JsonValueVc::cell(serde_json::Value::Null))
+}
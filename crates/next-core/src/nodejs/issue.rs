@@ -46,3 +46,45 @@ impl Issue for RenderingIssue {
 
     // TODO parse stack trace into source location
 }
+
+#[turbo_tasks::value(shared)]
+#[derive(Copy, Clone)]
+pub(super) struct EvaluationIssue {
+    pub context: FileSystemPathVc,
+    pub message: StringVc,
+    pub status: Option<i32>,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for EvaluationIssue {
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("Error evaluating Node.js code".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("build".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.context
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        self.message
+    }
+
+    #[turbo_tasks::function]
+    async fn detail(&self) -> Result<StringVc> {
+        let mut details = vec![];
+
+        if let Some(status) = self.status {
+            details.push(format!("Node.js exit code: {status}"));
+        }
+
+        Ok(StringVc::cell(details.join("\n")))
+    }
+}
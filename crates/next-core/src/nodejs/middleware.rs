@@ -0,0 +1,408 @@
+use std::collections::{btree_map::Entry, BTreeMap};
+
+use anyhow::{anyhow, bail, Context, Result};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use turbo_tasks::Value;
+use turbo_tasks_fs::FileSystemPathVc;
+use turbopack_core::{asset::AssetVc, chunk::ChunkingContextVc};
+use turbopack_dev_server::source::{
+    BodyVc, ContentSource, ContentSourceContent, ContentSourceData, ContentSourceDataFilter,
+    ContentSourceDataVary, ContentSourceResultVc, ContentSourceVc, HeaderValue, ProxyResult,
+    ProxyResultVc,
+};
+use turbopack_ecmascript::{chunk::EcmascriptChunkPlaceablesVc, EcmascriptModuleAssetVc};
+
+use super::{
+    get_edge_intermediate_asset, get_renderer_pool, pool::NodeJsOperation, proxy_error,
+    trace_stack, RenderData, RenderDataVc, RenderProxyOutgoingMessage, StructuredError,
+};
+use crate::next_config_source::add_headers;
+
+/// Routes of Next.js and of the dev server that never reach the middleware,
+/// e. g. assets, HMR and introspection.
+const INTERNAL_ROUTES: &[&str] = &[
+    "_next/",
+    "__nextjs_original-stack-frame",
+    "__turbopack__/",
+    "__turbo_tasks__/",
+];
+
+/// A content source that runs a middleware for every request before it is
+/// passed to the `inner` content source. The middleware can continue with the
+/// request, rewrite it to another path of the `inner` source or respond itself.
+#[turbo_tasks::value(shared)]
+pub struct MiddlewareContentSource {
+    pub inner: ContentSourceVc,
+    pub server_root: FileSystemPathVc,
+    /// The node.js entry that drives the `edge_module`.
+    pub module: EcmascriptModuleAssetVc,
+    /// The entry of the middleware, which runs in an edge sandbox.
+    pub edge_module: EcmascriptModuleAssetVc,
+    pub runtime_entries: EcmascriptChunkPlaceablesVc,
+    pub chunking_context: ChunkingContextVc,
+    pub edge_chunking_context: ChunkingContextVc,
+    pub intermediate_output_path: FileSystemPathVc,
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for MiddlewareContentSource {
+    #[turbo_tasks::function]
+    async fn get(
+        self_vc: MiddlewareContentSourceVc,
+        path: &str,
+        data: Value<ContentSourceData>,
+    ) -> Result<ContentSourceResultVc> {
+        let this = self_vc.await?;
+        if INTERNAL_ROUTES.iter().any(|route| path.starts_with(route)) {
+            return Ok(this.inner.get(path, data));
+        }
+        if data.method.is_none()
+            || data.url.is_none()
+            || data.headers.is_none()
+            || data.query.is_none()
+            || data.body.is_none()
+        {
+            return Ok(ContentSourceResultVc::exact(
+                ContentSourceContent::NeedData {
+                    source: self_vc.into(),
+                    path: path.to_string(),
+                    vary: ContentSourceDataVary {
+                        method: true,
+                        url: true,
+                        headers: Some(ContentSourceDataFilter::All),
+                        query: Some(ContentSourceDataFilter::All),
+                        body: true,
+                        // The middleware has to run for every request.
+                        cache_buster: true,
+                        ..Default::default()
+                    },
+                }
+                .cell(),
+            ));
+        }
+
+        let result = run_middleware(
+            this.server_root.join(path),
+            this.module,
+            this.edge_module,
+            this.runtime_entries,
+            this.chunking_context,
+            this.edge_chunking_context,
+            this.intermediate_output_path,
+            RenderData {
+                params: IndexMap::new(),
+                method: data
+                    .method
+                    .clone()
+                    .ok_or_else(|| anyhow!("method needs to be provided"))?,
+                url: data
+                    .url
+                    .clone()
+                    .ok_or_else(|| anyhow!("url needs to be provided"))?,
+                query: data
+                    .query
+                    .clone()
+                    .ok_or_else(|| anyhow!("query needs to be provided"))?,
+                headers: data
+                    .headers
+                    .clone()
+                    .ok_or_else(|| anyhow!("headers needs to be provided"))?,
+                path: format!("/{path}"),
+            }
+            .cell(),
+            data.body
+                .ok_or_else(|| anyhow!("body needs to be provided"))?,
+        );
+        let result = result.await?;
+        let (inner_result, headers) = match &*result {
+            MiddlewareResult::Next {
+                headers,
+                request_headers,
+            } => {
+                let data = override_headers(data.into_value(), request_headers);
+                (get_with_data(this.inner, path, &data).await?, headers)
+            }
+            MiddlewareResult::Rewrite {
+                path,
+                search,
+                headers,
+                request_headers,
+            } => {
+                let data = ContentSourceData {
+                    url: Some(format!("{path}{search}")),
+                    query: Some(serde_qs::from_str(search.trim_start_matches('?'))?),
+                    ..override_headers(data.into_value(), request_headers)
+                };
+                (
+                    get_with_data(this.inner, path.trim_start_matches('/'), &data).await?,
+                    headers,
+                )
+            }
+            MiddlewareResult::Response(proxy_result) => {
+                return Ok(ContentSourceResultVc::exact(
+                    ContentSourceContent::HttpProxy(*proxy_result).cell(),
+                ));
+            }
+        };
+        if headers.is_empty() {
+            Ok(inner_result)
+        } else {
+            Ok(add_headers(inner_result, headers.clone()))
+        }
+    }
+}
+
+/// Gets the content at `path` from `source`. The data the source asks for is
+/// taken from `data`, since the request has been consumed by the middleware
+/// and might have been rewritten.
+async fn get_with_data(
+    mut source: ContentSourceVc,
+    path: &str,
+    data: &ContentSourceData,
+) -> Result<ContentSourceResultVc> {
+    let mut path = path.to_string();
+    let mut source_data = ContentSourceData::default();
+    loop {
+        let result = source.get(&path, Value::new(source_data));
+        if let ContentSourceContent::NeedData {
+            source: next_source,
+            path: next_path,
+            vary,
+        } = &*result.await?.content.await?
+        {
+            source = *next_source;
+            path = next_path.clone();
+            source_data = filter_data(data, vary);
+        } else {
+            return Ok(result);
+        }
+    }
+}
+
+/// Replaces the headers of the request with the ones the middleware has set
+/// with `NextResponse.next({ request: { headers } })`, if any.
+fn override_headers(
+    data: ContentSourceData,
+    request_headers: &Option<Vec<String>>,
+) -> ContentSourceData {
+    let request_headers = match request_headers {
+        Some(request_headers) => request_headers,
+        None => return data,
+    };
+    let mut headers = BTreeMap::new();
+    for pair in request_headers.chunks_exact(2) {
+        let (name, value) = (pair[0].clone(), pair[1].clone());
+        match headers.entry(name) {
+            Entry::Vacant(e) => {
+                e.insert(HeaderValue::SingleString(value));
+            }
+            Entry::Occupied(mut e) => {
+                e.get_mut().extend_with_string(value);
+            }
+        }
+    }
+    ContentSourceData {
+        headers: Some(headers),
+        ..data
+    }
+}
+
+/// Picks the parts of `data` that are requested by `vary`.
+fn filter_data(data: &ContentSourceData, vary: &ContentSourceDataVary) -> ContentSourceData {
+    ContentSourceData {
+        method: if vary.method {
+            data.method.clone()
+        } else {
+            None
+        },
+        url: if vary.url { data.url.clone() } else { None },
+        query: vary.query.as_ref().and_then(|filter| {
+            let mut query = data.query.clone()?;
+            query.filter_with(filter);
+            Some(query)
+        }),
+        headers: vary.headers.as_ref().and_then(|filter| {
+            let headers = data.headers.as_ref()?;
+            Some(
+                headers
+                    .iter()
+                    .filter(|(name, _)| filter.contains(name))
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect(),
+            )
+        }),
+        body: if vary.body { data.body } else { None },
+        cache_buster: if vary.cache_buster {
+            data.cache_buster
+        } else {
+            0
+        },
+    }
+}
+
+/// The outcome of running a middleware for a request.
+#[turbo_tasks::value(shared)]
+pub enum MiddlewareResult {
+    /// The request continues to the next content source. The headers are
+    /// added to its response. The `request_headers` replace the headers of the
+    /// request, if the middleware has overridden them.
+    Next {
+        headers: Vec<String>,
+        request_headers: Option<Vec<String>>,
+    },
+    /// The request is served from another path and query string (`search`,
+    /// including the leading `?`). The headers are added to its response, the
+    /// `request_headers` replace the headers of the request like for `Next`.
+    Rewrite {
+        path: String,
+        search: String,
+        headers: Vec<String>,
+        request_headers: Option<Vec<String>>,
+    },
+    /// The middleware responded to the request itself, e. g. with a redirect.
+    Response(ProxyResultVc),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum MiddlewareIncomingMessage {
+    #[serde(rename_all = "camelCase")]
+    Next {
+        headers: Vec<String>,
+        request_headers: Option<Vec<String>>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Rewrite {
+        path: String,
+        search: String,
+        headers: Vec<String>,
+        request_headers: Option<Vec<String>>,
+    },
+    Response {
+        status: u16,
+        headers: Vec<String>,
+        /// The base64 encoded body.
+        body: String,
+    },
+    Error(StructuredError),
+}
+
+/// Runs a middleware module for a request in an edge sandbox of a node.js
+/// process.
+#[turbo_tasks::function]
+pub async fn run_middleware(
+    path: FileSystemPathVc,
+    module: EcmascriptModuleAssetVc,
+    edge_module: EcmascriptModuleAssetVc,
+    runtime_entries: EcmascriptChunkPlaceablesVc,
+    chunking_context: ChunkingContextVc,
+    edge_chunking_context: ChunkingContextVc,
+    intermediate_output_path: FileSystemPathVc,
+    data: RenderDataVc,
+    body: BodyVc,
+) -> Result<MiddlewareResultVc> {
+    let intermediate_asset = get_edge_intermediate_asset(
+        module,
+        edge_module,
+        runtime_entries,
+        chunking_context,
+        edge_chunking_context,
+        intermediate_output_path,
+    );
+    let renderer_pool = get_renderer_pool(intermediate_asset, intermediate_output_path);
+    // Read this strongly consistent, since we don't want to run inconsistent
+    // node.js code.
+    let pool = renderer_pool.strongly_consistent().await?;
+    let mut operation = match pool.operation().await {
+        Ok(operation) => operation,
+        Err(err) => {
+            return Ok(MiddlewareResult::Response(proxy_error(path, err, None).await?).cell());
+        }
+    };
+
+    match run_middleware_operation(
+        &mut operation,
+        data,
+        body,
+        intermediate_asset,
+        intermediate_output_path,
+    )
+    .await
+    {
+        Ok(result) => Ok(result.cell()),
+        Err(err) => {
+            Ok(MiddlewareResult::Response(proxy_error(path, err, Some(operation)).await?).cell())
+        }
+    }
+}
+
+async fn run_middleware_operation(
+    operation: &mut NodeJsOperation,
+    data: RenderDataVc,
+    body: BodyVc,
+    intermediate_asset: AssetVc,
+    intermediate_output_path: FileSystemPathVc,
+) -> Result<MiddlewareResult> {
+    let data = data.await?;
+    operation
+        .send(RenderProxyOutgoingMessage::Headers { data: &data })
+        .await
+        .context("sending headers to node.js process")?;
+    let body = body.await?;
+    for chunk in body.chunks() {
+        operation
+            .send(RenderProxyOutgoingMessage::BodyChunk {
+                data: chunk.as_bytes(),
+            })
+            .await
+            .context("sending body to node.js process")?;
+    }
+    operation
+        .send(RenderProxyOutgoingMessage::BodyEnd)
+        .await
+        .context("sending body to node.js process")?;
+    Ok(
+        match operation
+            .recv()
+            .await
+            .context("receiving from node.js process")?
+        {
+            MiddlewareIncomingMessage::Next {
+                headers,
+                request_headers,
+            } => MiddlewareResult::Next {
+                headers,
+                request_headers,
+            },
+            MiddlewareIncomingMessage::Rewrite {
+                path,
+                search,
+                headers,
+                request_headers,
+            } => MiddlewareResult::Rewrite {
+                path,
+                search,
+                headers,
+                request_headers,
+            },
+            MiddlewareIncomingMessage::Response {
+                status,
+                headers,
+                body,
+            } => MiddlewareResult::Response(
+                ProxyResult {
+                    status,
+                    headers,
+                    body: base64::decode(body)
+                        .context("decoding the middleware response body")?
+                        .into(),
+                }
+                .cell(),
+            ),
+            MiddlewareIncomingMessage::Error(error) => {
+                bail!(trace_stack(error, intermediate_asset, intermediate_output_path).await?)
+            }
+        },
+    )
+}
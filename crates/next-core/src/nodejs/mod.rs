@@ -27,14 +27,16 @@ use turbopack_dev_server::{
 use turbopack_ecmascript::{chunk::EcmascriptChunkPlaceablesVc, EcmascriptModuleAssetVc};
 
 use self::{
-    bootstrap::NodeJsBootstrapAsset,
+    bootstrap::{EdgeBootstrapAsset, NodeJsBootstrapAsset},
     issue::RenderingIssue,
     pool::{NodeJsOperation, NodeJsPool, NodeJsPoolVc},
 };
 use crate::source_map::{SourceMapTraceVc, StackFrame, TraceResult};
 
 pub(crate) mod bootstrap;
+pub(crate) mod evaluate;
 pub(crate) mod issue;
+pub(crate) mod middleware;
 pub(crate) mod node_api_source;
 pub(crate) mod node_entry;
 pub(crate) mod node_rendered_source;
@@ -228,6 +230,30 @@ pub async fn get_intermediate_asset(
     .into())
 }
 
+/// Creates a node.js renderer asset for an edge function. The `edge_module`
+/// runs sandboxed with only Web APIs and is driven by the `entry_module`, which
+/// runs in node.js. Both need to be chunked by different chunking contexts, as
+/// they share modules that are compiled for different environments.
+#[turbo_tasks::function]
+pub async fn get_edge_intermediate_asset(
+    entry_module: EcmascriptModuleAssetVc,
+    edge_module: EcmascriptModuleAssetVc,
+    runtime_entries: EcmascriptChunkPlaceablesVc,
+    chunking_context: ChunkingContextVc,
+    edge_chunking_context: ChunkingContextVc,
+    intermediate_output_path: FileSystemPathVc,
+) -> Result<AssetVc> {
+    let chunk = entry_module.as_evaluated_chunk(chunking_context, None);
+    let edge_chunk = edge_module.as_evaluated_chunk(edge_chunking_context, Some(runtime_entries));
+    Ok(EdgeBootstrapAsset {
+        path: intermediate_output_path.join("index.js"),
+        chunk_group: ChunkGroupVc::from_chunk(chunk),
+        edge_chunk_group: ChunkGroupVc::from_chunk(edge_chunk),
+    }
+    .cell()
+    .into())
+}
+
 #[turbo_tasks::value(shared)]
 pub(super) struct RenderData {
     params: IndexMap<String, String>,
@@ -124,6 +124,38 @@ impl PathRegexBuilder {
         self.push_str(&regex::escape(segment.as_ref()));
     }
 
+    /// Pushes a literal part of a path, which is matched as it is.
+    pub fn push_literal<S>(&mut self, literal: S)
+    where
+        S: AsRef<str>,
+    {
+        self.push_str(&regex::escape(literal.as_ref()));
+    }
+
+    /// Pushes a param matching the regular expression `pattern`, like
+    /// path-to-regexp does for the routes of the `next.config.js`. The
+    /// `prefix` is repeated with the param for the `*` and `+` modifiers and is
+    /// optional with it for the `?` and `*` modifiers.
+    pub fn push_custom_param<N>(
+        &mut self,
+        name: N,
+        prefix: &str,
+        pattern: &str,
+        modifier: Option<char>,
+    ) where
+        N: Into<String>,
+    {
+        let prefix = regex::escape(prefix);
+        let repeated = format!("(?:{pattern})(?:{prefix}(?:{pattern}))*");
+        self.push_str(&match modifier {
+            Some('?') => format!("(?:{prefix}((?:{pattern})))?"),
+            Some('*') => format!("(?:{prefix}({repeated}))?"),
+            Some('+') => format!("{prefix}({repeated})"),
+            _ => format!("{prefix}((?:{pattern}))"),
+        });
+        self.named_params.push(name.into());
+    }
+
     /// Builds and returns the [PathRegex].
     pub fn build(mut self) -> Result<PathRegex> {
         self.regex_str += "$";
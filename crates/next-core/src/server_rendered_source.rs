@@ -32,6 +32,7 @@ use turbopack_env::ProcessEnvAssetVc;
 
 use crate::{
     embed_js::{next_js_file, wrap_with_next_js_fs},
+    env::env_for_js,
     fallback::get_fallback_page,
     next_client::{
         context::{
@@ -41,6 +42,7 @@ use crate::{
         },
         NextClientTransition,
     },
+    next_config::NextConfigVc,
     next_server::{
         get_server_environment, get_server_module_options_context,
         get_server_resolve_options_context, ServerContextType,
@@ -62,6 +64,7 @@ pub async fn create_server_rendered_source(
     server_root: FileSystemPathVc,
     env: ProcessEnvVc,
     browserslist_query: &str,
+    next_config: NextConfigVc,
 ) -> Result<ContentSourceVc> {
     let project_path = wrap_with_next_js_fs(project_root);
 
@@ -78,6 +81,9 @@ pub async fn create_server_rendered_source(
     let ty = Value::new(ContextType::Pages { pages_dir });
     let server_ty = Value::new(ServerContextType::Pages { pages_dir });
 
+    let client_env = env_for_js(env, true, next_config);
+    let server_env = env_for_js(env, false, next_config);
+
     let client_chunking_context = get_client_chunking_context(project_path, server_root, ty);
     let client_environment = get_client_environment(browserslist_query);
     let client_module_options_context =
@@ -86,7 +92,7 @@ pub async fn create_server_rendered_source(
         add_next_transforms_to_pages(client_module_options_context, pages_dir);
     let client_resolve_options_context = get_client_resolve_options_context(project_path, ty);
 
-    let client_runtime_entries = get_client_runtime_entries(project_path, env, ty);
+    let client_runtime_entries = get_client_runtime_entries(project_path, client_env, ty);
 
     let next_client_transition = NextClientTransition {
         is_app: false,
@@ -104,16 +110,22 @@ pub async fn create_server_rendered_source(
     transitions.insert("next-client".to_string(), next_client_transition);
     let context: AssetContextVc = ModuleAssetContextVc::new(
         TransitionsByNameVc::cell(transitions),
-        get_server_environment(server_ty, env),
+        get_server_environment(server_ty, server_env),
         get_server_module_options_context(server_ty),
-        get_server_resolve_options_context(project_path, server_ty, StringsVc::empty()),
+        get_server_resolve_options_context(
+            project_path,
+            server_ty,
+            next_config,
+            StringsVc::empty(),
+        ),
     )
     .into();
 
     let server_runtime_entries =
-        vec![ProcessEnvAssetVc::new(project_path, env).as_ecmascript_chunk_placeable()];
+        vec![ProcessEnvAssetVc::new(project_path, server_env).as_ecmascript_chunk_placeable()];
 
-    let fallback_page = get_fallback_page(project_path, server_root, env, browserslist_query);
+    let fallback_page =
+        get_fallback_page(project_path, server_root, client_env, browserslist_query);
 
     let server_rendered_source = create_server_rendered_source_for_directory(
         project_path,
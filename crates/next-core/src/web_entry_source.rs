@@ -4,6 +4,7 @@ use turbo_tasks_env::ProcessEnvVc;
 use turbo_tasks_fs::FileSystemPathVc;
 use turbopack::ecmascript::EcmascriptModuleAssetVc;
 use turbopack_core::{
+    asset::AssetVc,
     chunk::{ChunkGroupVc, ChunkableAssetVc},
     resolve::{origin::PlainResolveOriginVc, parse::RequestVc},
 };
@@ -14,27 +15,28 @@ use turbopack_dev_server::{
 
 use crate::{
     embed_js::wrap_with_next_js_fs,
+    env::filter_for_client,
     next_client::context::{
         get_client_asset_context, get_client_chunking_context, get_client_runtime_entries,
         ContextType,
     },
 };
 
+/// Creates the html asset that bootstraps the client entries.
 #[turbo_tasks::function]
-pub async fn create_web_entry_source(
+pub async fn create_web_entry_asset(
     project_root: FileSystemPathVc,
     entry_requests: Vec<RequestVc>,
     server_root: FileSystemPathVc,
     env: ProcessEnvVc,
-    eager_compile: bool,
     browserslist_query: &str,
-) -> Result<ContentSourceVc> {
+) -> Result<AssetVc> {
     let project_root = wrap_with_next_js_fs(project_root);
 
     let ty = Value::new(ContextType::Other);
     let context = get_client_asset_context(project_root, browserslist_query, ty);
     let chunking_context = get_client_chunking_context(project_root, server_root, ty);
-    let entries = get_client_runtime_entries(project_root, env, ty);
+    let entries = get_client_runtime_entries(project_root, filter_for_client(env), ty);
 
     let runtime_entries = entries.resolve_entries(context);
 
@@ -74,17 +76,20 @@ pub async fn create_web_entry_source(
         .try_join()
         .await?;
 
-    let entry_asset = DevHtmlAssetVc::new(
+    Ok(DevHtmlAssetVc::new(
         server_root.join("index.html"),
         chunks.into_iter().map(ChunkGroupVc::from_chunk).collect(),
     )
-    .into();
+    .into())
+}
 
-    let graph = if eager_compile {
-        AssetGraphContentSourceVc::new_eager(server_root, entry_asset)
-    } else {
-        AssetGraphContentSourceVc::new_lazy(server_root, entry_asset)
-    }
-    .into();
-    Ok(graph)
+/// Serves the assets of the client entries. Assets are only compiled when
+/// they are requested, eager compilation is done outside of requests, see
+/// `next-dev`.
+#[turbo_tasks::function]
+pub fn create_web_entry_source(
+    server_root: FileSystemPathVc,
+    entry_asset: AssetVc,
+) -> ContentSourceVc {
+    AssetGraphContentSourceVc::new_lazy(server_root, entry_asset).into()
 }
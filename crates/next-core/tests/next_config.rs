@@ -0,0 +1,322 @@
+#![feature(min_specialization)]
+
+use anyhow::{bail, Result};
+use next_core::{
+    next_config::{Header, HeaderKeyValue, NextConfig, NextConfigVc, Redirect, Rewrite, Rewrites},
+    next_config_source::{NextConfigContentSourceVc, NextRewritesContentSourceVc},
+};
+use turbo_tasks::{TurboTasks, Value};
+use turbo_tasks_fs::rope::Rope;
+use turbo_tasks_memory::MemoryBackend;
+use turbopack_dev_server::source::{
+    ContentSource, ContentSourceContent, ContentSourceData, ContentSourceDataFilter,
+    ContentSourceDataVary, ContentSourceResultVc, ContentSourceVc, ProxyResult,
+};
+
+fn register() {
+    next_core::register();
+    include!(concat!(env!("OUT_DIR"), "/register_test_next_config.rs"));
+}
+
+/// Serves the given pages with the path and the query of the request as body.
+#[turbo_tasks::value(shared)]
+struct PagesContentSource {
+    pages: Vec<String>,
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for PagesContentSource {
+    #[turbo_tasks::function]
+    async fn get(
+        self_vc: PagesContentSourceVc,
+        path: &str,
+        data: Value<ContentSourceData>,
+    ) -> Result<ContentSourceResultVc> {
+        if !self_vc.await?.pages.iter().any(|page| page == path) {
+            return Ok(ContentSourceResultVc::not_found());
+        }
+        let content = match &data.query {
+            Some(query) => ContentSourceContent::HttpProxy(
+                ProxyResult {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: Rope::from(format!("{path}?{}", serde_qs::to_string(query)?)),
+                }
+                .cell(),
+            ),
+            None => ContentSourceContent::NeedData {
+                source: self_vc.into(),
+                path: path.to_string(),
+                vary: ContentSourceDataVary {
+                    query: Some(ContentSourceDataFilter::All),
+                    ..Default::default()
+                },
+            },
+        };
+        Ok(ContentSourceResultVc::exact(content.cell()))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+/// Requests a path with a query string like the dev server does.
+async fn get(source: ContentSourceVc, path: &str, query: &str) -> Result<Option<Response>> {
+    let mut source = source;
+    let mut path = path.to_string();
+    let mut data = ContentSourceData::default();
+    loop {
+        let result = source.get(&path, Value::new(data)).await?;
+        match &*result.content.await? {
+            ContentSourceContent::NotFound => return Ok(None),
+            ContentSourceContent::NeedData {
+                source: needed_source,
+                path: needed_path,
+                vary,
+            } => {
+                source = *needed_source;
+                path = needed_path.clone();
+                data = ContentSourceData {
+                    query: match vary.query {
+                        Some(_) => Some(serde_qs::from_str(query)?),
+                        None => None,
+                    },
+                    ..Default::default()
+                };
+            }
+            ContentSourceContent::HttpProxy(proxy_result) => {
+                let proxy_result = proxy_result.await?;
+                return Ok(Some(Response {
+                    status: proxy_result.status,
+                    headers: proxy_result
+                        .headers
+                        .chunks(2)
+                        .map(|pair| (pair[0].clone(), pair[1].clone()))
+                        .collect(),
+                    body: proxy_result.body.to_str()?.to_string(),
+                }));
+            }
+            ContentSourceContent::Static(_) => bail!("unexpected static content"),
+        }
+    }
+}
+
+/// The sources wrapped like next-dev does, without a middleware.
+fn source(pages: &[&str], config: NextConfig) -> ContentSourceVc {
+    let next_config = NextConfigVc::cell(config);
+    let pages = PagesContentSource {
+        pages: pages.iter().map(|page| page.to_string()).collect(),
+    }
+    .cell()
+    .into();
+    let rewrites = NextRewritesContentSourceVc::new(pages, next_config).into();
+    NextConfigContentSourceVc::new(rewrites, next_config).into()
+}
+
+fn rewrite(source: &str, destination: &str) -> Rewrite {
+    Rewrite {
+        source: source.to_string(),
+        destination: destination.to_string(),
+        base_path: None,
+        has: Vec::new(),
+        missing: Vec::new(),
+    }
+}
+
+fn ok(body: &str) -> Option<Response> {
+    Some(Response {
+        status: 200,
+        headers: Vec::new(),
+        body: body.to_string(),
+    })
+}
+
+async fn run(test: impl std::future::Future<Output = Result<()>> + Send + 'static) {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(test).await.unwrap();
+}
+
+#[tokio::test]
+async fn applies_rewrites_by_phase() {
+    run(async {
+        let source = source(
+            &["about", "blog/post", "page"],
+            NextConfig {
+                rewrites: Rewrites {
+                    before_files: vec![rewrite("/about", "/page")],
+                    after_files: vec![
+                        rewrite("/blog/post", "/page"),
+                        rewrite("/news/:slug", "/blog/:slug"),
+                    ],
+                    fallback: vec![rewrite("/:path*", "/page")],
+                },
+                ..Default::default()
+            },
+        );
+        // `beforeFiles` shadow pages
+        assert_eq!(get(source, "about", "").await?, ok("page?"));
+        // `afterFiles` don't
+        assert_eq!(get(source, "blog/post", "").await?, ok("blog/post?"));
+        assert_eq!(get(source, "news/post", "").await?, ok("blog/post?"));
+        // `fallback` applies when nothing else matched
+        assert_eq!(get(source, "missing/page", "").await?, ok("page?"));
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn forwards_the_query_of_rewrites() {
+    run(async {
+        let source = source(
+            &["search"],
+            NextConfig {
+                rewrites: Rewrites {
+                    before_files: vec![rewrite("/find/:term", "/search?q=:term&page=1")],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            get(source, "find/rust", "sort=asc&page=2").await?,
+            ok("search?sort=asc&page=1&q=rust")
+        );
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn redirects() {
+    run(async {
+        let source = source(
+            &["page"],
+            NextConfig {
+                redirects: vec![
+                    Redirect {
+                        source: "/old/:slug".to_string(),
+                        destination: "/new/:slug".to_string(),
+                        permanent: true,
+                        status_code: None,
+                        base_path: None,
+                        has: Vec::new(),
+                        missing: Vec::new(),
+                    },
+                    Redirect {
+                        source: "/temporary".to_string(),
+                        destination: "https://example.com/".to_string(),
+                        permanent: false,
+                        status_code: Some(302),
+                        base_path: None,
+                        has: Vec::new(),
+                        missing: Vec::new(),
+                    },
+                ],
+                ..Default::default()
+            },
+        );
+        let redirect = |status, location: &str| {
+            Some(Response {
+                status,
+                headers: vec![("location".to_string(), location.to_string())],
+                body: String::new(),
+            })
+        };
+        assert_eq!(
+            get(source, "old/post", "").await?,
+            redirect(308, "/new/post")
+        );
+        assert_eq!(
+            get(source, "temporary", "").await?,
+            redirect(302, "https://example.com/")
+        );
+        assert_eq!(get(source, "page", "").await?, ok("page?"));
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn adds_headers() {
+    run(async {
+        let source = source(
+            &["blog/post", "page"],
+            NextConfig {
+                headers: vec![Header {
+                    source: "/blog/:slug".to_string(),
+                    headers: vec![HeaderKeyValue {
+                        key: "x-slug".to_string(),
+                        value: ":slug".to_string(),
+                    }],
+                    base_path: None,
+                    has: Vec::new(),
+                    missing: Vec::new(),
+                }],
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            get(source, "blog/post", "").await?,
+            Some(Response {
+                status: 200,
+                headers: vec![("x-slug".to_string(), "post".to_string())],
+                body: "blog/post?".to_string(),
+            })
+        );
+        assert_eq!(get(source, "page", "").await?, ok("page?"));
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn applies_the_base_path() {
+    run(async {
+        let source = source(
+            &["page", "other"],
+            NextConfig {
+                base_path: "/docs".to_string(),
+                rewrites: Rewrites {
+                    before_files: vec![
+                        rewrite("/rewritten", "/page"),
+                        Rewrite {
+                            base_path: Some(false),
+                            ..rewrite("/outside", "/docs/other")
+                        },
+                    ],
+                    ..Default::default()
+                },
+                redirects: vec![Redirect {
+                    source: "/old".to_string(),
+                    destination: "/page".to_string(),
+                    permanent: false,
+                    status_code: None,
+                    base_path: None,
+                    has: Vec::new(),
+                    missing: Vec::new(),
+                }],
+                ..Default::default()
+            },
+        );
+        assert_eq!(get(source, "docs/page", "").await?, ok("page?"));
+        assert_eq!(get(source, "page", "").await?, None);
+        assert_eq!(get(source, "docs/rewritten", "").await?, ok("page?"));
+        assert_eq!(get(source, "outside", "").await?, ok("other?"));
+        assert_eq!(
+            get(source, "docs/old", "").await?,
+            Some(Response {
+                status: 307,
+                headers: vec![("location".to_string(), "/docs/page".to_string())],
+                body: String::new(),
+            })
+        );
+        Ok(())
+    })
+    .await
+}
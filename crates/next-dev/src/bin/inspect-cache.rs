@@ -0,0 +1,134 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use turbo_tasks::{persisted_graph::TaskCell, TurboTasks};
+use turbo_tasks_memory::MemoryBackend;
+use turbo_tasks_rocksdb::{
+    inspect::{debug_cell, CacheInspector},
+    private::CFStats,
+};
+
+/// Inspects and maintains the persistent cache written by next-dev with
+/// `--cache`, when it has been built without the `persistent_cache_logdb`
+/// feature.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Directory of the cache, as passed to next-dev with `--cache`
+    #[clap(value_parser)]
+    path: PathBuf,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Shows the size of all column families
+    Stats,
+    /// Lists the tasks in the cache
+    Tasks {
+        /// Only lists tasks whose name contains this
+        #[clap(long, value_parser)]
+        function: Option<String>,
+
+        /// Only lists dirty tasks
+        #[clap(long, value_parser, default_value_t = false)]
+        dirty: bool,
+
+        /// Only lists active tasks
+        #[clap(long, value_parser, default_value_t = false)]
+        active: bool,
+    },
+    /// Shows the persisted data of a task
+    Cells {
+        /// Id of the task in the cache
+        #[clap(value_parser)]
+        id: usize,
+    },
+    /// Removes deleted and overwritten entries from disk
+    Compact,
+    /// Removes inactive tasks that haven't been used for some days
+    Prune {
+        #[clap(long, value_parser)]
+        days: u64,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // All crates that next-dev uses might have written to the cache
+    next_dev::register();
+
+    let args = Args::parse();
+    let inspector = CacheInspector::open(&args.path)?;
+    println!(
+        "{} (version {:?})",
+        args.path.display(),
+        inspector.version().unwrap_or_default()
+    );
+    if !inspector.is_consistent() {
+        println!("the last session hasn't been stopped cleanly, the cache will be cleared");
+    }
+
+    match args.command {
+        Command::Stats => {
+            let stats = inspector.stats()?;
+            for cf in stats.iter() {
+                println!("{}", cf);
+            }
+            println!("{}", CFStats::total("ALL", &stats));
+        }
+        Command::Tasks {
+            function,
+            dirty,
+            active,
+        } => {
+            let mut count = 0;
+            for task in inspector.tasks()? {
+                if matches!(&function, Some(function) if !task.name.contains(function))
+                    || (dirty && !task.dirty)
+                    || (active && !task.active)
+                {
+                    continue;
+                }
+                println!("{}", task);
+                count += 1;
+            }
+            println!("{count} tasks");
+        }
+        Command::Cells { id } => {
+            let data = inspector
+                .task_data(id)?
+                .ok_or_else(|| anyhow!("task {id} is not persisted"))?;
+            println!("output: {:?}", data.output);
+            println!("children: {:?}", data.children);
+            println!("dependencies: {:?}", data.dependencies);
+            let tt = TurboTasks::new(MemoryBackend::new());
+            tt.run_once(async move {
+                for (index, cell) in data.cells {
+                    match cell {
+                        TaskCell::Content(content) => match content.0 {
+                            Some(content) => println!("{index}: {}", debug_cell(content).await),
+                            None => println!("{index}: empty"),
+                        },
+                        TaskCell::NeedComputation => println!("{index}: not persisted"),
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+        }
+        Command::Compact => {
+            inspector.compact()?;
+            println!("compacted");
+        }
+        Command::Prune { days } => {
+            let pruned = inspector.prune(Duration::from_secs(days * 24 * 60 * 60))?;
+            println!("pruned {pruned} tasks");
+            inspector.compact()?;
+        }
+    }
+    Ok(())
+}
@@ -48,6 +48,74 @@ pub struct DevServerOptions {
     #[cfg_attr(feature = "serializable", serde(default))]
     pub eager_compile: bool,
 
+    /// Memory limit in megabytes. When exceeded, the least recently used
+    /// results that are not needed currently are unloaded. They are recomputed
+    /// when needed again.
+    #[cfg_attr(feature = "cli", clap(long, value_parser))]
+    #[cfg_attr(feature = "serializable", serde(default))]
+    pub memory_limit: Option<usize>,
+
+    /// How many seconds a request waits for its content to be recomputed
+    /// before the content of an earlier request is served instead. 0 always
+    /// waits for the recomputation. Defaults to 30 seconds.
+    #[cfg_attr(feature = "cli", clap(long, value_parser))]
+    #[cfg_attr(feature = "serializable", serde(default))]
+    pub content_timeout: Option<u64>,
+
+    /// Write a trace of all function executions to this file. It can be
+    /// opened with chrome://tracing or https://ui.perfetto.dev.
+    #[cfg_attr(feature = "cli", clap(long, value_parser))]
+    #[cfg_attr(feature = "serializable", serde(default))]
+    pub trace: Option<PathBuf>,
+
+    /// Persist the task graph to this directory to speed up the next start.
+    /// Files that changed in the meantime are detected on startup. The cache
+    /// is discarded when the process didn't shut down cleanly or the version
+    /// changed.
+    #[cfg(any(feature = "persistent_cache", feature = "persistent_cache_logdb"))]
+    #[cfg_attr(feature = "cli", clap(long, value_parser))]
+    #[cfg_attr(feature = "serializable", serde(default))]
+    pub cache: Option<PathBuf>,
+
+    /// Record all function executions to this file, to replay them later with
+    /// `--replay`. The recording is completed when the server is stopped.
+    #[cfg_attr(feature = "cli", clap(long, value_parser))]
+    #[cfg_attr(feature = "serializable", serde(default))]
+    pub record: Option<PathBuf>,
+
+    /// Replay a recording written with `--record` instead of starting the
+    /// server, and report all executions that yield different results. These
+    /// point to non-deterministic functions.
+    #[cfg_attr(feature = "cli", clap(long, value_parser))]
+    #[cfg_attr(feature = "serializable", serde(default))]
+    pub replay: Option<PathBuf>,
+
+    /// Milliseconds to wait for further changes of the same file before
+    /// recomputing. Defaults to 1.
+    #[cfg_attr(feature = "cli", clap(long, value_parser))]
+    #[cfg_attr(feature = "serializable", serde(default))]
+    pub watch_debounce: Option<u64>,
+
+    /// Poll the project for changes in this interval in milliseconds instead
+    /// of using native file system events, e. g. for network shares or some
+    /// container mounts.
+    #[cfg_attr(feature = "cli", clap(long, value_parser))]
+    #[cfg_attr(feature = "serializable", serde(default))]
+    pub watch_poll: Option<u64>,
+
+    /// Don't watch paths ignored by `.gitignore` files and the `.git`
+    /// directory. Reads of ignored files are not updated when they change,
+    /// e. g. when packages are installed into an ignored `node_modules`.
+    #[cfg_attr(feature = "cli", clap(long))]
+    #[cfg_attr(feature = "serializable", serde(default))]
+    pub watch_gitignore: bool,
+
+    /// Don't watch paths relative to the root that match this glob. Can be
+    /// passed multiple times.
+    #[cfg_attr(feature = "cli", clap(long))]
+    #[cfg_attr(feature = "serializable", serde(default))]
+    pub watch_ignore: Vec<String>,
+
     /// Display version of the binary. Noop if used in library mode.
     #[cfg_attr(feature = "cli", clap(long))]
     #[cfg_attr(feature = "serializable", serde(default))]
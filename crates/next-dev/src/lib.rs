@@ -2,33 +2,44 @@
 #![feature(min_specialization)]
 
 pub mod devserver_options;
+mod trace;
 mod turbo_tasks_viz;
 
 use std::{
+    any::Any,
     collections::HashSet,
     env::current_dir,
     future::{join, Future},
     net::{IpAddr, SocketAddr},
     path::MAIN_SEPARATOR,
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Context, Result};
 use devserver_options::DevServerOptions;
 use next_core::{
-    create_app_source, create_server_rendered_source, create_web_entry_source, env::load_env,
+    create_app_source, create_middleware_source, create_server_rendered_source,
+    create_web_entry_asset, create_web_entry_source,
+    env::load_env,
+    next_config::load_next_config,
+    next_config_source::{NextConfigContentSourceVc, NextRewritesContentSourceVc},
     source_map::NextSourceMapTraceContentSourceVc,
 };
 use owo_colors::OwoColorize;
+use trace::init_chrome_trace;
+use tracing_chrome::FlushGuard;
 use turbo_tasks::{
-    primitives::StringsVc, util::FormatDuration, RawVc, TransientInstance, TransientValue,
-    TurboTasks, Value,
+    backend::Backend, primitives::StringsVc, run_once_with_priority, util::FormatDuration,
+    CompletionVc, RawVc, TaskPriority, TransientInstance, TransientValue, TryJoinIterExt,
+    TurboTasks, TurboTasksApi, Value,
 };
-use turbo_tasks_fs::{DiskFileSystemVc, FileSystemVc};
+use turbo_tasks_fs::{DiskFileSystemVc, FileSystemVc, WatchOptions};
 use turbo_tasks_memory::MemoryBackend;
 use turbopack_cli_utils::issue::{ConsoleUi, ConsoleUiVc, LogOptions};
-use turbopack_core::{issue::IssueSeverity, resolve::parse::RequestVc};
+use turbopack_core::{
+    asset::AssetVc, issue::IssueSeverity, reference::all_assets, resolve::parse::RequestVc,
+};
 use turbopack_dev_server::{
     fs::DevServerFileSystemVc,
     introspect::IntrospectionSource,
@@ -36,11 +47,13 @@ use turbopack_dev_server::{
         combined::CombinedContentSource, router::RouterContentSource,
         static_assets::StaticAssetsContentSourceVc, ContentSourceVc,
     },
-    DevServer,
+    DevServer, DEFAULT_CONTENT_TIMEOUT,
 };
 
 pub struct NextDevServerBuilder {
-    turbo_tasks: Arc<TurboTasks<MemoryBackend>>,
+    turbo_tasks: Arc<dyn TurboTasksApi>,
+    /// Only the [MemoryBackend] can be visualized at `/__turbo_tasks__/`.
+    memory_turbo_tasks: Option<Arc<TurboTasks<MemoryBackend>>>,
     project_dir: String,
     root_dir: String,
     entry_requests: Vec<String>,
@@ -53,16 +66,22 @@ pub struct NextDevServerBuilder {
     show_all: bool,
     log_detail: bool,
     allow_retry: bool,
+    content_timeout: Option<Duration>,
+    watch_options: WatchOptions,
 }
 
 impl NextDevServerBuilder {
-    pub fn new(
-        turbo_tasks: Arc<TurboTasks<MemoryBackend>>,
+    pub fn new<B: Backend + 'static>(
+        turbo_tasks: Arc<TurboTasks<B>>,
         project_dir: String,
         root_dir: String,
     ) -> NextDevServerBuilder {
+        let memory_turbo_tasks = (turbo_tasks.clone() as Arc<dyn Any + Send + Sync>)
+            .downcast::<TurboTasks<MemoryBackend>>()
+            .ok();
         NextDevServerBuilder {
             turbo_tasks,
+            memory_turbo_tasks,
             project_dir,
             root_dir,
             entry_requests: vec![],
@@ -77,6 +96,8 @@ impl NextDevServerBuilder {
             show_all: false,
             log_detail: false,
             allow_retry: false,
+            content_timeout: Some(DEFAULT_CONTENT_TIMEOUT),
+            watch_options: WatchOptions::default(),
         }
     }
 
@@ -130,8 +151,23 @@ impl NextDevServerBuilder {
         self
     }
 
+    /// How long a request waits for its content to be recomputed before the
+    /// content of an earlier request is served instead. `None` always waits.
+    pub fn content_timeout(mut self, content_timeout: Option<Duration>) -> NextDevServerBuilder {
+        self.content_timeout = content_timeout;
+        self
+    }
+
+    /// Configures how the project and output directories are watched for
+    /// changes.
+    pub fn watch_options(mut self, watch_options: WatchOptions) -> NextDevServerBuilder {
+        self.watch_options = watch_options;
+        self
+    }
+
     pub async fn build(self) -> Result<DevServer> {
         let turbo_tasks = self.turbo_tasks;
+        let memory_turbo_tasks = Arc::new(self.memory_turbo_tasks);
 
         let project_dir = self.project_dir;
         let root_dir = self.root_dir;
@@ -141,6 +177,7 @@ impl NextDevServerBuilder {
         let show_all = self.show_all;
         let log_detail = self.log_detail;
         let browserslist_query = self.browserslist_query;
+        let watch_options = Arc::new(self.watch_options);
         let log_options = LogOptions {
             current_dir: current_dir().unwrap(),
             show_all,
@@ -155,19 +192,36 @@ impl NextDevServerBuilder {
 
         let mut err: Option<anyhow::Error> = None;
 
-        let tasks = turbo_tasks.clone();
-        let source = move || {
-            source(
+        let sources = move || {
+            project_sources(
                 root_dir.clone(),
                 project_dir.clone(),
                 entry_requests.clone(),
-                eager_compile,
-                turbo_tasks.clone().into(),
+                memory_turbo_tasks.clone().into(),
                 console_ui.clone().into(),
                 browserslist_query.clone(),
                 server_component_externals.clone(),
+                watch_options.clone().into(),
             )
         };
+        let source = {
+            let sources = sources.clone();
+            move || sources().source()
+        };
+
+        if eager_compile {
+            // Compiles the entry in the background. Requests have a higher
+            // priority, so they don't wait for the whole app to be compiled.
+            let turbo_tasks = turbo_tasks.clone();
+            tokio::spawn(async move {
+                // Errors are reported when the content is requested
+                let _ = run_once_with_priority(turbo_tasks, TaskPriority::Low, async move {
+                    sources().eager_compile().strongly_consistent().await?;
+                    Ok(())
+                })
+                .await;
+            });
+        }
 
         // Retry to listen on the different port if the port is already in use.
         for retry_count in 0..10 {
@@ -175,10 +229,11 @@ impl NextDevServerBuilder {
             let addr = SocketAddr::new(host, current_port);
 
             let listen_result = DevServer::listen(
-                tasks.clone(),
+                turbo_tasks.clone(),
                 source.clone(),
                 addr,
                 console_ui_to_dev_server.clone(),
+                self.content_timeout,
             );
 
             match listen_result {
@@ -238,35 +293,75 @@ async fn handle_issues<T: Into<RawVc>>(source: T, console_ui: ConsoleUiVc) -> Re
 }
 
 #[turbo_tasks::function]
-async fn project_fs(project_dir: &str, console_ui: ConsoleUiVc) -> Result<FileSystemVc> {
+async fn project_fs(
+    project_dir: &str,
+    console_ui: ConsoleUiVc,
+    watch_options: TransientInstance<WatchOptions>,
+) -> Result<FileSystemVc> {
     let disk_fs = DiskFileSystemVc::new("project".to_string(), project_dir.to_string());
     handle_issues(disk_fs, console_ui).await?;
-    disk_fs.await?.start_watching()?;
+    disk_fs
+        .await?
+        .start_watching_with_options((*watch_options).clone())?;
     Ok(disk_fs.into())
 }
 
 #[turbo_tasks::function]
-async fn output_fs(project_dir: &str, console_ui: ConsoleUiVc) -> Result<FileSystemVc> {
+async fn output_fs(
+    project_dir: &str,
+    console_ui: ConsoleUiVc,
+    watch_options: TransientInstance<WatchOptions>,
+) -> Result<FileSystemVc> {
     let disk_fs = DiskFileSystemVc::new("output".to_string(), project_dir.to_string());
     handle_issues(disk_fs, console_ui).await?;
-    disk_fs.await?.start_watching()?;
+    disk_fs
+        .await?
+        .start_watching_with_options((*watch_options).clone())?;
     Ok(disk_fs.into())
 }
 
+/// The content source of the project, and the entry asset that is compiled
+/// in the background when eager compilation is enabled.
+#[turbo_tasks::value(shared)]
+struct ProjectSources {
+    source: ContentSourceVc,
+    web_entry: AssetVc,
+}
+
+#[turbo_tasks::value_impl]
+impl ProjectSourcesVc {
+    #[turbo_tasks::function]
+    async fn source(self) -> Result<ContentSourceVc> {
+        Ok(self.await?.source)
+    }
+
+    /// Compiles all assets referenced by the web entry.
+    #[turbo_tasks::function]
+    async fn eager_compile(self) -> Result<CompletionVc> {
+        let assets = all_assets(self.await?.web_entry).await?;
+        assets
+            .iter()
+            .map(|asset| asset.content().resolve())
+            .try_join()
+            .await?;
+        Ok(CompletionVc::new())
+    }
+}
+
 #[turbo_tasks::function]
-async fn source(
+async fn project_sources(
     root_dir: String,
     project_dir: String,
     entry_requests: Vec<String>,
-    eager_compile: bool,
-    turbo_tasks: TransientInstance<TurboTasks<MemoryBackend>>,
+    memory_turbo_tasks: TransientInstance<Option<Arc<TurboTasks<MemoryBackend>>>>,
     console_ui: TransientInstance<ConsoleUi>,
     browserslist_query: String,
     server_component_externals: Vec<String>,
-) -> Result<ContentSourceVc> {
+    watch_options: TransientInstance<WatchOptions>,
+) -> Result<ProjectSourcesVc> {
     let console_ui = (*console_ui).clone().cell();
-    let output_fs = output_fs(&project_dir, console_ui);
-    let fs = project_fs(&root_dir, console_ui);
+    let output_fs = output_fs(&project_dir, console_ui, watch_options.clone());
+    let fs = project_fs(&root_dir, console_ui, watch_options);
     let project_relative = project_dir.strip_prefix(&root_dir).unwrap();
     let project_relative = project_relative
         .strip_prefix(MAIN_SEPARATOR)
@@ -277,10 +372,12 @@ async fn source(
 
     let output_root = output_fs.root().join("/.next/server");
 
+    let next_config = load_next_config(project_path, output_root.join("config"), env);
+
     let dev_server_fs = DevServerFileSystemVc::new().as_file_system();
     let dev_server_root = dev_server_fs.root();
 
-    let web_source = create_web_entry_source(
+    let web_entry = create_web_entry_asset(
         project_path,
         entry_requests
             .iter()
@@ -288,15 +385,16 @@ async fn source(
             .collect(),
         dev_server_root,
         env,
-        eager_compile,
         &browserslist_query,
     );
+    let web_source = create_web_entry_source(dev_server_root, web_entry);
     let rendered_source = create_server_rendered_source(
         project_path,
         output_root.join("pages"),
         dev_server_root,
         env,
         &browserslist_query,
+        next_config,
     );
     let app_source = create_app_source(
         project_path,
@@ -304,13 +402,9 @@ async fn source(
         dev_server_root,
         env,
         &browserslist_query,
+        next_config,
         StringsVc::cell(server_component_externals),
     );
-    let viz = turbo_tasks_viz::TurboTasksSource {
-        turbo_tasks: turbo_tasks.into(),
-    }
-    .cell()
-    .into();
     let static_source =
         StaticAssetsContentSourceVc::new(String::new(), project_path.join("public")).into();
     let main_source = CombinedContentSource {
@@ -323,16 +417,31 @@ async fn source(
     .cell()
     .into();
     let source_map_trace = NextSourceMapTraceContentSourceVc::new(main_source.into()).into();
+    // Like in Next.js, headers and redirects are applied before the
+    // middleware runs, and rewrites afterwards.
+    let main_source = create_middleware_source(
+        project_path,
+        output_root.join("middleware"),
+        dev_server_root,
+        NextRewritesContentSourceVc::new(main_source.into(), next_config).into(),
+        env,
+        next_config,
+    );
+    let main_source = NextConfigContentSourceVc::new(main_source, next_config).into();
+    let mut routes = vec![
+        ("__turbopack__/".to_string(), introspect),
+        (
+            "__nextjs_original-stack-frame".to_string(),
+            source_map_trace,
+        ),
+    ];
+    if let Some(turbo_tasks) = &*memory_turbo_tasks {
+        let viz = turbo_tasks_viz::TurboTasksSourceVc::new(turbo_tasks.clone()).into();
+        routes.push(("__turbo_tasks__/".to_string(), viz));
+    }
     let source = RouterContentSource {
-        routes: vec![
-            ("__turbopack__/".to_string(), introspect),
-            ("__turbo_tasks__/".to_string(), viz),
-            (
-                "__nextjs_original-stack-frame".to_string(),
-                source_map_trace,
-            ),
-        ],
-        fallback: main_source.into(),
+        routes,
+        fallback: main_source,
     }
     .cell()
     .into();
@@ -341,7 +450,7 @@ async fn source(
     handle_issues(web_source, console_ui).await?;
     handle_issues(rendered_source, console_ui).await?;
 
-    Ok(source)
+    Ok(ProjectSources { source, web_entry }.cell())
 }
 
 pub fn register() {
@@ -349,12 +458,36 @@ pub fn register() {
     include!(concat!(env!("OUT_DIR"), "/register.rs"));
 }
 
+fn watch_options(options: &DevServerOptions) -> Result<WatchOptions> {
+    let mut watch_options = WatchOptions::default();
+    if let Some(debounce) = options.watch_debounce {
+        watch_options = watch_options.with_debounce(Duration::from_millis(debounce));
+    }
+    if let Some(interval) = options.watch_poll {
+        watch_options = watch_options.with_polling(Duration::from_millis(interval));
+    }
+    if options.watch_gitignore {
+        watch_options = watch_options.with_gitignore();
+    }
+    for glob in options.watch_ignore.iter() {
+        watch_options = watch_options
+            .with_ignore(glob)
+            .with_context(|| format!("parsing --watch-ignore {}", glob))?;
+    }
+    Ok(watch_options)
+}
+
 /// Start a devserver with the given options.
 pub async fn start_server(options: &DevServerOptions) -> Result<()> {
     let start = Instant::now();
 
     #[cfg(feature = "tokio_console")]
     console_subscriber::init();
+    let trace_guard = options
+        .trace
+        .as_deref()
+        .map(init_chrome_trace)
+        .transpose()?;
     register();
 
     let dir = options
@@ -377,8 +510,83 @@ pub async fn start_server(options: &DevServerOptions) -> Result<()> {
         dir.clone()
     };
 
-    let tt = TurboTasks::new(MemoryBackend::new());
+    #[cfg(any(feature = "persistent_cache", feature = "persistent_cache_logdb"))]
+    if let Some(cache) = &options.cache {
+        use turbo_tasks::registry::get_registry_hash;
+        use turbo_tasks_fs::DiskFileSystemStateCheck;
+        use turbo_tasks_memory::MemoryBackendWithPersistedGraph;
+
+        if options.memory_limit.is_some() {
+            return Err(anyhow!(
+                "--memory-limit can't be used together with --cache"
+            ));
+        }
+        // The cache is cleared for every build, as the implementation of
+        // functions might have changed, and when functions or value types
+        // have been added or removed, as the persisted graph refers to them
+        let version = format!(
+            "{}-{}-{:016x}",
+            env!("CARGO_PKG_VERSION"),
+            option_env!("VERGEN_BUILD_TIMESTAMP").unwrap_or("unknown"),
+            get_registry_hash()
+        );
+        #[cfg(feature = "persistent_cache_logdb")]
+        let pg = turbo_tasks_logdb::LogDbPersistedGraph::with_version(
+            cache.join("turbo-tasks.log"),
+            &version,
+        );
+        #[cfg(not(feature = "persistent_cache_logdb"))]
+        let pg = turbo_tasks_rocksdb::RocksDbPersistedGraph::with_version(cache, &version);
+        let pg = pg.context("persistent cache can't be opened")?;
+        let backend = MemoryBackendWithPersistedGraph::with_external_state_check(
+            pg,
+            DiskFileSystemStateCheck,
+        );
+        let tt = new_turbo_tasks(backend, options)?;
+        return run_server(tt, options, dir, root_dir, start, trace_guard).await;
+    }
+
+    let tt = new_turbo_tasks(
+        match options.memory_limit {
+            Some(memory_limit) => MemoryBackend::with_memory_limit(memory_limit * 1024 * 1024),
+            None => MemoryBackend::new(),
+        },
+        options,
+    )?;
+    run_server(tt, options, dir, root_dir, start, trace_guard).await
+}
+
+/// Creates the turbo-tasks instance, which records all executions when
+/// `--record` is set.
+fn new_turbo_tasks<B: Backend + 'static>(
+    backend: B,
+    options: &DevServerOptions,
+) -> Result<Arc<TurboTasks<B>>> {
+    match &options.record {
+        Some(path) => {
+            TurboTasks::with_recording(backend, path).context("recording can't be written")
+        }
+        None => Ok(TurboTasks::new(backend)),
+    }
+}
+
+async fn run_server<B: Backend + 'static>(
+    tt: Arc<TurboTasks<B>>,
+    options: &DevServerOptions,
+    dir: String,
+    root_dir: String,
+    start: Instant,
+    trace_guard: Option<FlushGuard>,
+) -> Result<()> {
+    if let Some(replay) = &options.replay {
+        let report = tt.replay(replay).await?;
+        print!("{report}");
+        tt.stop_and_wait().await;
+        return Ok(());
+    }
+
     let tt_clone = tt.clone();
+    handle_shutdown(tt.clone(), trace_guard);
 
     #[allow(unused_mut)]
     let mut server = NextDevServerBuilder::new(tt, dir, root_dir)
@@ -388,11 +596,17 @@ pub async fn start_server(options: &DevServerOptions) -> Result<()> {
         .port(options.port)
         .log_detail(options.log_detail)
         .show_all(options.show_all)
+        .content_timeout(match options.content_timeout {
+            Some(0) => None,
+            Some(seconds) => Some(Duration::from_secs(seconds)),
+            None => Some(DEFAULT_CONTENT_TIMEOUT),
+        })
         .log_level(
             options
                 .log_level
                 .map_or_else(|| IssueSeverity::Warning, |l| l.0),
-        );
+        )
+        .watch_options(watch_options(options)?);
 
     #[cfg(feature = "serializable")]
     {
@@ -450,10 +664,36 @@ pub async fn start_server(options: &DevServerOptions) -> Result<()> {
     Ok(())
 }
 
+/// Stops the turbo-tasks backend and completes the trace file when the process
+/// is stopped with ctrl-c. A persistent cache is only reused when the backend
+/// has been stopped, and a recording is only complete then. A second ctrl-c
+/// exits without waiting for that, but still completes the trace file.
+fn handle_shutdown<B: Backend + 'static>(tt: Arc<TurboTasks<B>>, trace_guard: Option<FlushGuard>) {
+    let trace_guard = Arc::new(Mutex::new(trace_guard));
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            let forced_trace_guard = trace_guard.clone();
+            tokio::spawn(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    drop(forced_trace_guard.lock().unwrap().take());
+                    std::process::exit(0);
+                }
+            });
+            tt.stop_and_wait().await;
+            drop(trace_guard.lock().unwrap().take());
+            if let Err(err) = tt.recording_result() {
+                eprintln!("{} - {:#}", "error".red(), err);
+                std::process::exit(1);
+            }
+            std::process::exit(0);
+        }
+    });
+}
+
 #[cfg(feature = "profile")]
 // When profiling, exits the process when no new updates have been received for
 // a given timeout and there are no more tasks in progress.
-async fn profile_timeout<T>(tt: &TurboTasks<MemoryBackend>, future: impl Future<Output = T>) -> T {
+async fn profile_timeout<B: Backend, T>(tt: &TurboTasks<B>, future: impl Future<Output = T>) -> T {
     /// How long to wait in between updates before force-exiting the process
     /// during profiling.
     const PROFILE_EXIT_TIMEOUT: Duration = Duration::from_secs(5);
@@ -472,8 +712,8 @@ async fn profile_timeout<T>(tt: &TurboTasks<MemoryBackend>, future: impl Future<
 }
 
 #[cfg(not(feature = "profile"))]
-fn profile_timeout<T>(
-    _tt: &TurboTasks<MemoryBackend>,
+fn profile_timeout<B: Backend, T>(
+    _tt: &TurboTasks<B>,
     future: impl Future<Output = T>,
 ) -> impl Future<Output = T> {
     future
@@ -0,0 +1,81 @@
+use std::{fmt::Debug, path::Path};
+
+use anyhow::{Context as _, Result};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id},
+    Subscriber,
+};
+use tracing_chrome::{ChromeLayerBuilder, EventOrSpan, FlushGuard};
+use tracing_subscriber::{
+    layer::{Context, Layered, SubscriberExt},
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    Layer, Registry,
+};
+
+/// Records all tracing spans, which includes every turbo-tasks function
+/// execution, into a chrome trace file. The file is completed when the
+/// returned guard is dropped.
+pub fn init_chrome_trace(path: &Path) -> Result<FlushGuard> {
+    let (layer, guard) = ChromeLayerBuilder::<Layered<NameLayer, Registry>>::new()
+        .file(path)
+        .include_args(true)
+        .name_fn(Box::new(
+            |event_or_span: &EventOrSpan<'_, '_, _>| match event_or_span {
+                EventOrSpan::Event(event) => {
+                    let mut visitor = NameVisitor::default();
+                    event.record(&mut visitor);
+                    visitor
+                        .0
+                        .unwrap_or_else(|| event.metadata().name().to_string())
+                }
+                EventOrSpan::Span(span) => match span.extensions().get::<Name>() {
+                    Some(name) => name.0.clone(),
+                    None => span.name().to_string(),
+                },
+            },
+        ))
+        .build();
+    tracing_subscriber::registry()
+        .with(NameLayer)
+        .with(layer)
+        .try_init()
+        .context("failed to install the tracing subscriber")?;
+    Ok(guard)
+}
+
+/// The `name` field of a span. Spans share a static name, e. g.
+/// `turbo_tasks::function`, so the trace uses this field instead.
+struct Name(String);
+
+/// Stores the `name` field of new spans as [Name], as the fields of a span
+/// aren't accessible later on.
+pub struct NameLayer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for NameLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = NameVisitor::default();
+        attrs.record(&mut visitor);
+        if let (Some(name), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(Name(name));
+        }
+    }
+}
+
+#[derive(Default)]
+struct NameVisitor(Option<String>);
+
+impl Visit for NameVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "name" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
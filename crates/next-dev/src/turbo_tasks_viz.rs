@@ -45,6 +45,23 @@ impl ContentSource for TurboTasksSource {
                 invalidator.invalidate();
             }
         });
+        if path == "stats.json" {
+            let mut stats = Stats::new();
+            let b = tt.backend();
+            b.with_all_cached_tasks(|task| {
+                stats.add_id(b, task);
+            });
+            let json = serde_json::to_string_pretty(&stats.to_json())?;
+            return Ok(ContentSourceResultVc::exact(
+                ContentSourceContent::Static(
+                    AssetContentVc::from(
+                        File::from(json).with_content_type(mime::APPLICATION_JSON),
+                    )
+                    .into(),
+                )
+                .cell(),
+            ));
+        }
         let html = match path {
             "graph" => {
                 let mut stats = Stats::new();
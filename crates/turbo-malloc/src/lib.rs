@@ -1,43 +1,121 @@
-use std::alloc::{GlobalAlloc, Layout};
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    cell::Cell,
+    sync::atomic::{AtomicIsize, Ordering},
+};
+
+/// Number of bytes allocated through [TurboMalloc] that have been flushed from
+/// the per-thread counters.
+static ALLOCATED: AtomicIsize = AtomicIsize::new(0);
+
+/// Allocations are counted per thread and only flushed to [ALLOCATED] when
+/// the thread's counter exceeds this many bytes. This avoids contention on a
+/// global counter on every allocation.
+const FLUSH_THRESHOLD: isize = 64 * 1024;
+
+thread_local! {
+    static THREAD_ALLOCATED: Cell<isize> = const { Cell::new(0) };
+}
 
 /// Turbo's preferred global allocator. This is a new type instead of a type
 /// alias because you can't use type aliases to instantiate unit types (E0423).
 pub struct TurboMalloc;
 
+impl TurboMalloc {
+    /// Returns the number of bytes currently allocated. This is always 0 when
+    /// [TurboMalloc] is not the global allocator. It's off by up to 64 KiB per
+    /// thread, as allocations are counted per thread.
+    pub fn memory_usage() -> usize {
+        ALLOCATED.load(Ordering::Relaxed).max(0) as usize
+    }
+}
+
+fn update(delta: isize) {
+    let flushed = THREAD_ALLOCATED.try_with(|allocated| {
+        let value = allocated.get() + delta;
+        if value.abs() > FLUSH_THRESHOLD {
+            ALLOCATED.fetch_add(value, Ordering::Relaxed);
+            allocated.set(0);
+        } else {
+            allocated.set(value);
+        }
+    });
+    if flushed.is_err() {
+        // The thread local has already been destroyed
+        ALLOCATED.fetch_add(delta, Ordering::Relaxed);
+    }
+}
+
+fn add(size: usize) {
+    update(size as isize);
+}
+
+fn remove(size: usize) {
+    update(-(size as isize));
+}
+
 #[cfg(not(all(target_os = "linux", target_arch = "aarch64")))]
 unsafe impl GlobalAlloc for TurboMalloc {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        mimalloc::MiMalloc.alloc(layout)
+        let ret = mimalloc::MiMalloc.alloc(layout);
+        if !ret.is_null() {
+            add(layout.size());
+        }
+        ret
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        mimalloc::MiMalloc.dealloc(ptr, layout)
+        mimalloc::MiMalloc.dealloc(ptr, layout);
+        remove(layout.size());
     }
 
     unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
-        mimalloc::MiMalloc.alloc_zeroed(layout)
+        let ret = mimalloc::MiMalloc.alloc_zeroed(layout);
+        if !ret.is_null() {
+            add(layout.size());
+        }
+        ret
     }
 
     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
-        mimalloc::MiMalloc.realloc(ptr, layout, new_size)
+        let ret = mimalloc::MiMalloc.realloc(ptr, layout, new_size);
+        if !ret.is_null() {
+            remove(layout.size());
+            add(new_size);
+        }
+        ret
     }
 }
 
 #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
 unsafe impl GlobalAlloc for TurboMalloc {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        std::alloc::System.alloc(layout)
+        let ret = std::alloc::System.alloc(layout);
+        if !ret.is_null() {
+            add(layout.size());
+        }
+        ret
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        std::alloc::System.dealloc(ptr, layout)
+        std::alloc::System.dealloc(ptr, layout);
+        remove(layout.size());
     }
 
     unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
-        std::alloc::System.alloc_zeroed(layout)
+        let ret = std::alloc::System.alloc_zeroed(layout);
+        if !ret.is_null() {
+            add(layout.size());
+        }
+        ret
     }
 
     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
-        std::alloc::System.realloc(ptr, layout, new_size)
+        let ret = std::alloc::System.realloc(ptr, layout, new_size);
+        if !ret.is_null() {
+            remove(layout.size());
+            add(new_size);
+        }
+        ret
     }
 }
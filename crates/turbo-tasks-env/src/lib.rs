@@ -1,6 +1,7 @@
 #![feature(min_specialization)]
 
 mod command_line;
+mod custom;
 mod dotenv;
 mod filter;
 
@@ -11,7 +12,8 @@ use indexmap::IndexMap;
 use turbo_tasks::primitives::OptionStringVc;
 
 pub use self::{
-    command_line::CommandLineProcessEnvVc, dotenv::DotenvProcessEnvVc, filter::FilterProcessEnvVc,
+    command_line::CommandLineProcessEnvVc, custom::CustomProcessEnvVc, dotenv::DotenvProcessEnvVc,
+    filter::FilterProcessEnvVc,
 };
 
 #[turbo_tasks::value(transparent)]
@@ -46,6 +48,17 @@ pub trait ProcessEnv {
     }
 }
 
+/// Reads a single value from an [EnvMap], ignoring the casing of the name.
+#[turbo_tasks::function]
+pub async fn case_insensitive_read(map: EnvMapVc, name: &str) -> Result<OptionStringVc> {
+    Ok(OptionStringVc::cell(
+        to_uppercase_map(map)
+            .await?
+            .get(&name.to_uppercase())
+            .cloned(),
+    ))
+}
+
 #[turbo_tasks::function]
 async fn to_uppercase_map(map: EnvMapVc) -> Result<EnvMapVc> {
     let map = &*map.await?;
@@ -0,0 +1,678 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use flate2::read::GzDecoder;
+use tar::EntryType;
+use turbo_tasks::{primitives::StringVc, CompletionVc, ValueToString, ValueToStringVc};
+use zip::ZipArchive;
+
+use crate::{
+    rope::Rope,
+    util::{join_path, normalize_path},
+    DirectoryContentVc, DirectoryEntry, File, FileContent, FileContentVc, FileMeta, FileMetaVc,
+    FileSystem, FileSystemPathVc, FileSystemVc, LinkContent, LinkContentVc, LinkType, Permissions,
+};
+
+/// Maximum number of symlinks followed while resolving a path, like `ELOOP`
+/// on Linux.
+const MAX_SYMLINKS: usize = 40;
+
+enum FileData {
+    /// Index of the file in the zip archive, decompressed on read
+    Zip(usize),
+    /// Content of the file, read while streaming the tar archive
+    Tar(Bytes),
+}
+
+enum ArchiveEntry {
+    File {
+        data: FileData,
+        permissions: Permissions,
+    },
+    Directory,
+    Symlink(String),
+}
+
+fn permissions_from_mode(mode: u32) -> Permissions {
+    if mode & 0o111 != 0 {
+        Permissions::Executable
+    } else {
+        Permissions::Writable
+    }
+}
+
+/// The entries of an archive by normalized path
+struct ArchiveIndex {
+    entries: HashMap<String, ArchiveEntry>,
+    /// Names of the entries of each directory
+    children: HashMap<String, Vec<String>>,
+    /// Only entries inside of this directory are visible
+    subdirectory: String,
+}
+
+impl ArchiveIndex {
+    fn new(subdirectory: String) -> Self {
+        Self {
+            entries: HashMap::from([(String::new(), ArchiveEntry::Directory)]),
+            children: HashMap::new(),
+            subdirectory,
+        }
+    }
+
+    fn insert(&mut self, path: &str, entry: ArchiveEntry) {
+        // Entries pointing outside of the archive are skipped
+        let path = match normalize_path(path) {
+            Some(path) => path,
+            None => return,
+        };
+        let path = if self.subdirectory.is_empty() {
+            path
+        } else {
+            match path
+                .strip_prefix(&self.subdirectory)
+                .and_then(|path| path.strip_prefix('/'))
+            {
+                Some(path) => path.to_string(),
+                None => return,
+            }
+        };
+        if path.is_empty() {
+            return;
+        }
+
+        // Archives don't need to contain entries for all directories
+        let mut parent = String::new();
+        for segment in path.split('/') {
+            let current = join_path(&parent, segment).unwrap_or_default();
+            if !self.entries.contains_key(&current) {
+                self.children
+                    .entry(parent)
+                    .or_default()
+                    .push(segment.to_string());
+                self.entries
+                    .insert(current.clone(), ArchiveEntry::Directory);
+            }
+            parent = current;
+        }
+        if !matches!(entry, ArchiveEntry::Directory) {
+            self.entries.insert(path, entry);
+        }
+    }
+
+    fn read_zip(&mut self, data: Bytes) -> Result<ZipArchive<Cursor<Bytes>>> {
+        let mut archive = ZipArchive::new(Cursor::new(data))?;
+        for i in 0..archive.len() {
+            let mut file = archive
+                .by_index(i)
+                .with_context(|| format!("reading entry {} of zip archive", i))?;
+            let name = file.name().to_string();
+            let mode = file.unix_mode();
+            let entry = if file.is_dir() {
+                ArchiveEntry::Directory
+            } else if mode.map_or(false, |mode| mode & 0o170000 == 0o120000) {
+                // The content of a symlink is its target
+                let mut target = String::new();
+                file.read_to_string(&mut target)
+                    .with_context(|| format!("reading symlink {} of zip archive", name))?;
+                ArchiveEntry::Symlink(target)
+            } else {
+                ArchiveEntry::File {
+                    data: FileData::Zip(i),
+                    permissions: mode.map(permissions_from_mode).unwrap_or_default(),
+                }
+            };
+            self.insert(&name, entry);
+        }
+        Ok(archive)
+    }
+
+    /// Reads the entries of a tar archive. The archive is streamed, so a
+    /// compressed archive is never held uncompressed in memory as a whole.
+    fn read_tar(&mut self, reader: impl Read) -> Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+            let entry = match entry.header().entry_type() {
+                EntryType::Regular | EntryType::Continuous => {
+                    let permissions = entry
+                        .header()
+                        .mode()
+                        .map(permissions_from_mode)
+                        .unwrap_or_default();
+                    let mut content = Vec::new();
+                    entry
+                        .read_to_end(&mut content)
+                        .with_context(|| format!("reading entry {} of tar archive", name))?;
+                    ArchiveEntry::File {
+                        data: FileData::Tar(Bytes::from(content)),
+                        permissions,
+                    }
+                }
+                EntryType::Directory => ArchiveEntry::Directory,
+                EntryType::Symlink => match entry.link_name_bytes() {
+                    Some(target) => {
+                        ArchiveEntry::Symlink(String::from_utf8_lossy(&target).to_string())
+                    }
+                    None => continue,
+                },
+                // Hard links, devices and other special files are not supported
+                _ => continue,
+            };
+            self.insert(&name, entry);
+        }
+        Ok(())
+    }
+
+    /// Resolves all symlinks in `path`, including the last segment when
+    /// `follow_last` is set. Returns `None` when a symlink points outside of
+    /// the archive or there are too many symlinks.
+    fn resolve(&self, path: &str, follow_last: bool) -> Option<String> {
+        let mut path = path.to_string();
+        for _ in 0..MAX_SYMLINKS {
+            let segments = path
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>();
+            let mut resolved = String::new();
+            let mut redirect = None;
+            for (i, segment) in segments.iter().enumerate() {
+                let current = join_path(&resolved, segment)?;
+                let is_last = i + 1 == segments.len();
+                if let Some(ArchiveEntry::Symlink(target)) = self.entries.get(&current) {
+                    if !is_last || follow_last {
+                        // Absolute targets are relative to the root of the archive
+                        let base = if target.starts_with('/') {
+                            ""
+                        } else {
+                            &resolved
+                        };
+                        let target = join_path(base, target)?;
+                        redirect = Some(join_path(&target, &segments[i + 1..].join("/"))?);
+                        break;
+                    }
+                }
+                resolved = current;
+            }
+            match redirect {
+                Some(target) => path = target,
+                None => return Some(resolved),
+            }
+        }
+        None
+    }
+}
+
+/// A read-only [FileSystem] serving the files of a `.zip`, `.tgz` or `.tar`
+/// archive without extracting it, e. g. npm package tarballs or the zip
+/// cache of Yarn PnP.
+///
+/// Packages are resolved from archives with `ResolveModules::Archives` of
+/// turbopack-core. It can also be attached with an [AttachedFileSystem] to make
+/// a package available in `node_modules`.
+///
+/// [AttachedFileSystem]: crate::attach::AttachedFileSystem
+#[turbo_tasks::value(cell = "new", eq = "manual", serialization = "none")]
+pub struct ArchiveFileSystem {
+    archive: FileSystemPathVc,
+    #[turbo_tasks(debug_ignore, trace_ignore)]
+    contents: ArchiveContents,
+}
+
+/// The parsed entries of an archive
+struct ArchiveContents {
+    name: String,
+    index: Arc<ArchiveIndex>,
+    /// Zip archives are decompressed file by file, which needs the archive
+    zip: Option<ZipArchive<Cursor<Bytes>>>,
+}
+
+#[turbo_tasks::value_impl]
+impl ArchiveFileSystemVc {
+    /// Opens the archive at `archive`. Only the contents of `subdirectory`
+    /// are visible, e. g. `package` for npm tarballs. The format is detected
+    /// from the content.
+    #[turbo_tasks::function]
+    pub async fn new(archive: FileSystemPathVc, subdirectory: &str) -> Result<Self> {
+        let name = archive.to_string().await?.clone_value();
+        let content = archive.read().await?;
+        let file = match &*content {
+            FileContent::Content(file) => file,
+            FileContent::NotFound => bail!("archive {} not found", name),
+        };
+        let subdirectory = normalize_path(subdirectory)
+            .ok_or_else(|| anyhow!("invalid subdirectory {}", subdirectory))?;
+        Ok(Self::cell(ArchiveFileSystem {
+            archive,
+            contents: ArchiveContents::open(name, file.content(), subdirectory)?,
+        }))
+    }
+
+    /// The archive file, e. g. to resolve the dependencies of a package in
+    /// the archive from the archives next to it.
+    #[turbo_tasks::function]
+    pub async fn archive(self) -> Result<FileSystemPathVc> {
+        Ok(self.await?.archive)
+    }
+}
+
+impl ArchiveContents {
+    fn open(name: String, content: &Rope, subdirectory: String) -> Result<Self> {
+        let mut magic = Vec::with_capacity(2);
+        content.read().take(2).read_to_end(&mut magic)?;
+
+        let mut index = ArchiveIndex::new(subdirectory);
+        let zip = if magic == b"PK" {
+            // Zip archives need to be seekable, so they are kept in memory
+            let mut data = Vec::with_capacity(content.len());
+            content.read().read_to_end(&mut data)?;
+            Some(
+                index
+                    .read_zip(Bytes::from(data))
+                    .with_context(|| format!("reading zip archive {}", name))?,
+            )
+        } else {
+            let result = if magic == [0x1f, 0x8b] {
+                index.read_tar(GzDecoder::new(content.read()))
+            } else {
+                index.read_tar(content.read())
+            };
+            result.with_context(|| format!("reading tar archive {}", name))?;
+            None
+        };
+
+        Ok(ArchiveContents {
+            name,
+            index: Arc::new(index),
+            zip,
+        })
+    }
+
+    /// Resolves symlinks in `path` and returns the entry it points to
+    fn lookup(&self, path: &str, follow_last: bool) -> Option<(String, &ArchiveEntry)> {
+        let resolved = self.index.resolve(path, follow_last)?;
+        let entry = self.index.entries.get(&resolved)?;
+        Some((resolved, entry))
+    }
+
+    fn content(&self, data: &FileData) -> Result<Rope> {
+        match data {
+            FileData::Tar(bytes) => Ok(Rope::from(bytes.clone())),
+            FileData::Zip(index) => {
+                // Clones of the archive share the parsed central directory
+                let mut archive = self
+                    .zip
+                    .clone()
+                    .context("zip entry in an archive that is not a zip file")?;
+                let mut file = archive.by_index(*index)?;
+                let mut content = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut content)?;
+                Ok(Rope::from(content))
+            }
+        }
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl FileSystem for ArchiveFileSystem {
+    #[turbo_tasks::function]
+    async fn read(&self, fs_path: FileSystemPathVc) -> Result<FileContentVc> {
+        let path = &fs_path.await?.path;
+        Ok(match self.contents.lookup(path, true) {
+            Some((_, ArchiveEntry::File { data, permissions })) => File {
+                meta: FileMeta {
+                    permissions: *permissions,
+                    content_type: None,
+                },
+                content: self
+                    .contents
+                    .content(data)
+                    .with_context(|| format!("reading {} from {}", path, self.contents.name))?,
+            }
+            .into(),
+            Some((path, ArchiveEntry::Directory)) => {
+                bail!("reading file {}: is a directory", path)
+            }
+            Some((_, ArchiveEntry::Symlink(_))) | None => FileContent::NotFound.cell(),
+        })
+    }
+
+    #[turbo_tasks::function]
+    async fn read_link(&self, fs_path: FileSystemPathVc) -> Result<LinkContentVc> {
+        let path = &fs_path.await?.path;
+        let target = match self.contents.lookup(path, false) {
+            Some((_, ArchiveEntry::Symlink(target))) => target,
+            _ => return Ok(LinkContent::NotFound.cell()),
+        };
+        let mut link_type = LinkType::UNSET;
+        if target.starts_with('/') {
+            link_type |= LinkType::ABSOLUTE;
+        }
+        if let Some((_, ArchiveEntry::Directory)) = self.contents.lookup(path, true) {
+            link_type |= LinkType::DIRECTORY;
+        }
+        Ok(LinkContent::Link {
+            target: target.clone(),
+            link_type,
+        }
+        .cell())
+    }
+
+    #[turbo_tasks::function]
+    async fn read_dir(&self, fs_path: FileSystemPathVc) -> Result<DirectoryContentVc> {
+        let fs_path = fs_path.await?;
+        let resolved = match self.contents.lookup(&fs_path.path, true) {
+            Some((resolved, ArchiveEntry::Directory)) => resolved,
+            _ => return Ok(DirectoryContentVc::not_found()),
+        };
+
+        // Entries are listed by the requested path, not the resolved one
+        let entries = self
+            .contents
+            .index
+            .children
+            .get(&resolved)
+            .into_iter()
+            .flatten()
+            .filter_map(|name| {
+                let entry = self
+                    .contents
+                    .index
+                    .entries
+                    .get(&join_path(&resolved, name).unwrap_or_default())?;
+                let entry_path = FileSystemPathVc::new_normalized(
+                    fs_path.fs,
+                    join_path(&fs_path.path, name).unwrap_or_default(),
+                );
+                let entry = match entry {
+                    ArchiveEntry::File { .. } => DirectoryEntry::File(entry_path),
+                    ArchiveEntry::Directory => DirectoryEntry::Directory(entry_path),
+                    ArchiveEntry::Symlink(_) => DirectoryEntry::Symlink(entry_path),
+                };
+                Some((name.clone(), entry))
+            })
+            .collect();
+
+        Ok(DirectoryContentVc::new(entries))
+    }
+
+    #[turbo_tasks::function]
+    fn write(&self, _fs_path: FileSystemPathVc, _content: FileContentVc) -> Result<CompletionVc> {
+        bail!("Writing is not possible to the archive filesystem")
+    }
+
+    #[turbo_tasks::function]
+    fn write_link(
+        &self,
+        _fs_path: FileSystemPathVc,
+        _target: LinkContentVc,
+    ) -> Result<CompletionVc> {
+        bail!("Writing is not possible to the archive filesystem")
+    }
+
+    #[turbo_tasks::function]
+    async fn metadata(&self, fs_path: FileSystemPathVc) -> Result<FileMetaVc> {
+        let path = &fs_path.await?.path;
+        Ok(match self.contents.lookup(path, true) {
+            Some((_, ArchiveEntry::File { permissions, .. })) => FileMeta {
+                permissions: *permissions,
+                content_type: None,
+            }
+            .cell(),
+            Some(_) => FileMeta::default().cell(),
+            None => bail!("path {} not found, can't read metadata", path),
+        })
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ValueToString for ArchiveFileSystem {
+    #[turbo_tasks::function]
+    fn to_string(&self) -> StringVc {
+        StringVc::cell(self.contents.name.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use anyhow::Result;
+    use flate2::{write::GzEncoder, Compression};
+    use rstest::*;
+    use tar::{EntryType, Header};
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::{ArchiveContents, ArchiveEntry, MAX_SYMLINKS};
+    use crate::{rope::Rope, Permissions};
+
+    /// Writes the names as they are, as `Header::set_path` refuses to write
+    /// the `..` segments of malicious archives
+    fn append(
+        builder: &mut tar::Builder<impl Write>,
+        entry_type: EntryType,
+        path: &str,
+        link_name: &str,
+        content: &[u8],
+        mode: u32,
+    ) -> Result<()> {
+        let mut header = Header::new_old();
+        let old = header.as_old_mut();
+        old.name[..path.len()].copy_from_slice(path.as_bytes());
+        old.linkname[..link_name.len()].copy_from_slice(link_name.as_bytes());
+        header.set_entry_type(entry_type);
+        header.set_size(content.len() as u64);
+        header.set_mode(mode);
+        header.set_cksum();
+        builder.append(&header, content)?;
+        Ok(())
+    }
+
+    fn append_file(builder: &mut tar::Builder<impl Write>, path: &str, mode: u32) -> Result<()> {
+        let content = format!("content of {}", path);
+        append(
+            builder,
+            EntryType::Regular,
+            path,
+            "",
+            content.as_bytes(),
+            mode,
+        )
+    }
+
+    fn append_symlink(
+        builder: &mut tar::Builder<impl Write>,
+        path: &str,
+        target: &str,
+    ) -> Result<()> {
+        append(builder, EntryType::Symlink, path, target, &[], 0o777)
+    }
+
+    /// An npm package tarball, which doesn't contain entries for directories
+    fn tgz() -> Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        append_file(&mut builder, "package/package.json", 0o644)?;
+        append_file(&mut builder, "package/lib/index.js", 0o644)?;
+        append_file(&mut builder, "package/bin/cli.js", 0o755)?;
+        append_symlink(&mut builder, "package/main.js", "lib/index.js")?;
+        append_symlink(&mut builder, "package/dist", "/package/lib")?;
+        append_symlink(&mut builder, "package/loop", "loop")?;
+        append_symlink(&mut builder, "package/outside", "../../x")?;
+        append_file(&mut builder, "../escaped.js", 0o644)?;
+        append_file(&mut builder, "other/file.js", 0o644)?;
+        Ok(builder.into_inner()?.finish()?)
+    }
+
+    /// A package in the zip cache of Yarn PnP
+    fn zip() -> Result<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default();
+        writer.add_directory("node_modules/pkg/", options)?;
+        writer.add_directory("node_modules/pkg/lib/", options)?;
+        writer.start_file("node_modules/pkg/lib/index.js", options)?;
+        writer.write_all(b"content of lib/index.js")?;
+        writer.start_file(
+            "node_modules/pkg/bin/cli.js",
+            options.unix_permissions(0o755),
+        )?;
+        writer.write_all(b"content of bin/cli.js")?;
+        Ok(writer.finish()?.into_inner())
+    }
+
+    fn open(data: Vec<u8>, subdirectory: &str) -> Result<ArchiveContents> {
+        ArchiveContents::open(
+            "archive".to_string(),
+            &Rope::from(data),
+            subdirectory.to_string(),
+        )
+    }
+
+    fn read(fs: &ArchiveContents, path: &str) -> Result<Option<String>> {
+        Ok(match fs.lookup(path, true) {
+            Some((_, ArchiveEntry::File { data, .. })) => {
+                Some(fs.content(data)?.to_str()?.to_string())
+            }
+            _ => None,
+        })
+    }
+
+    fn children(fs: &ArchiveContents, path: &str) -> Vec<String> {
+        let mut children = fs.index.children.get(path).cloned().unwrap_or_default();
+        children.sort();
+        children
+    }
+
+    #[test]
+    fn reads_tgz() -> Result<()> {
+        let fs = open(tgz()?, "package")?;
+        assert!(fs.zip.is_none());
+        assert_eq!(
+            read(&fs, "lib/index.js")?.as_deref(),
+            Some("content of package/lib/index.js")
+        );
+        assert_eq!(read(&fs, "missing.js")?, None);
+        assert_eq!(
+            children(&fs, ""),
+            [
+                "bin",
+                "dist",
+                "lib",
+                "loop",
+                "main.js",
+                "outside",
+                "package.json"
+            ]
+        );
+        assert_eq!(children(&fs, "lib"), ["index.js"]);
+        assert!(matches!(
+            fs.lookup("bin/cli.js", true),
+            Some((
+                _,
+                ArchiveEntry::File {
+                    permissions: Permissions::Executable,
+                    ..
+                }
+            ))
+        ));
+        assert!(matches!(
+            fs.lookup("lib", true),
+            Some((_, ArchiveEntry::Directory))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn reads_uncompressed_tar() -> Result<()> {
+        let mut builder = tar::Builder::new(Vec::new());
+        append_file(&mut builder, "package/index.js", 0o644)?;
+        let fs = open(builder.into_inner()?, "package")?;
+        assert_eq!(
+            read(&fs, "index.js")?.as_deref(),
+            Some("content of package/index.js")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reads_zip() -> Result<()> {
+        let fs = open(zip()?, "node_modules/pkg")?;
+        assert!(fs.zip.is_some());
+        assert_eq!(
+            read(&fs, "lib/index.js")?.as_deref(),
+            Some("content of lib/index.js")
+        );
+        assert_eq!(children(&fs, ""), ["bin", "lib"]);
+        assert!(matches!(
+            fs.lookup("bin/cli.js", true),
+            Some((
+                _,
+                ArchiveEntry::File {
+                    permissions: Permissions::Executable,
+                    ..
+                }
+            ))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn skips_entries_outside_of_subdirectory() -> Result<()> {
+        let fs = open(tgz()?, "package")?;
+        assert_eq!(read(&fs, "escaped.js")?, None);
+        assert_eq!(read(&fs, "../escaped.js")?, None);
+        assert_eq!(read(&fs, "other/file.js")?, None);
+
+        let fs = open(tgz()?, "")?;
+        assert_eq!(children(&fs, ""), ["other", "package"]);
+        Ok(())
+    }
+
+    #[test]
+    fn fails_for_invalid_archives() {
+        assert!(open(b"PK not a zip".to_vec(), "").is_err());
+        assert!(open(vec![0x1f, 0x8b, 0, 0], "").is_err());
+    }
+
+    #[rstest]
+    #[case::no_link("lib/index.js", true, Some("lib/index.js"))]
+    #[case::missing("lib/missing.js", true, Some("lib/missing.js"))]
+    #[case::file_link("main.js", true, Some("lib/index.js"))]
+    #[case::dir_link("dist/index.js", true, Some("lib/index.js"))]
+    #[case::last_not_followed("dist", false, Some("dist"))]
+    #[case::last_followed("dist", true, Some("lib"))]
+    #[case::cycle("loop", true, None)]
+    #[case::outside("outside", true, None)]
+    fn resolve(#[case] path: &str, #[case] follow_last: bool, #[case] expected: Option<&str>) {
+        // The absolute link points to `/package/lib`, which only exists when
+        // the whole archive is visible
+        let fs = open(tgz().unwrap(), "").unwrap();
+        let path = format!("package/{}", path);
+        let expected = expected.map(|expected| format!("package/{}", expected));
+        assert_eq!(fs.index.resolve(&path, follow_last), expected);
+    }
+
+    #[test]
+    fn resolve_gives_up_after_max_symlinks() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for i in 0..MAX_SYMLINKS {
+            append_symlink(
+                &mut builder,
+                &format!("link{}", i),
+                &format!("link{}", i + 1),
+            )
+            .unwrap();
+        }
+        append_file(&mut builder, &format!("link{}", MAX_SYMLINKS), 0o644).unwrap();
+        let fs = open(builder.into_inner().unwrap(), "").unwrap();
+        assert_eq!(
+            fs.index.resolve("link1", true),
+            Some(format!("link{}", MAX_SYMLINKS))
+        );
+        assert_eq!(fs.index.resolve("link0", true), None);
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+};
+
+use turbo_tasks_hash::Xxh3Hash64Hasher;
+
+use crate::{path_to_key, rope::Rope, File, Permissions};
+
+/// Remembers hashes of the files that have been read, so that changes that
+/// leave the content untouched (e. g. an editor saving without modifications,
+/// or `git checkout` touching files) don't need to invalidate the reads.
+#[derive(Default)]
+pub struct ContentHashes {
+    map: Mutex<HashMap<String, u64>>,
+}
+
+pub(crate) fn hash_content(content: &Rope, permissions: Permissions) -> u64 {
+    let mut hasher = Xxh3Hash64Hasher::new();
+    hasher.write_ref(content);
+    hasher.write_value(permissions as u8);
+    hasher.finish()
+}
+
+impl ContentHashes {
+    pub fn insert(&self, key: String, file: &File) {
+        let hash = hash_content(&file.content, file.meta.permissions);
+        self.map.lock().unwrap().insert(key, hash);
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.map.lock().unwrap().remove(key);
+    }
+
+    /// Checks if the file at `path` still has the content that has been read.
+    /// Reads the file from disk, so it shouldn't be called while holding
+    /// other locks.
+    pub fn is_unchanged(&self, key: &str, path: &Path) -> bool {
+        let hash = match self.map.lock().unwrap().get(key) {
+            Some(hash) => *hash,
+            None => return false,
+        };
+        let current = fs::symlink_metadata(path).ok().and_then(|metadata| {
+            // Symlinks share the invalidators of the path, so they are always
+            // considered to be changed
+            if !metadata.is_file() {
+                return None;
+            }
+            let content = fs::read(path).ok()?;
+            Some(hash_content(
+                &Rope::from(content),
+                metadata.permissions().into(),
+            ))
+        });
+        if current == Some(hash) {
+            true
+        } else {
+            // The invalidated read will remember the new content
+            self.remove(key);
+            false
+        }
+    }
+
+    /// Returns the `paths` whose content differs from the content that has
+    /// been read. A `git checkout` can touch many files at once, so the files
+    /// are hashed on multiple threads.
+    pub fn changed(&self, paths: impl Iterator<Item = PathBuf>) -> Vec<PathBuf> {
+        // Hashing a few files isn't worth spawning threads
+        const MIN_PATHS_PER_THREAD: usize = 16;
+
+        let paths = paths.collect::<Vec<_>>();
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = (paths.len() / threads + 1).max(MIN_PATHS_PER_THREAD);
+        let is_changed = |path: &PathBuf| !self.is_unchanged(&path_to_key(path), path);
+        if paths.len() <= chunk_size {
+            return paths.into_iter().filter(is_changed).collect();
+        }
+        thread::scope(|scope| {
+            let handles = paths
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .filter(|path| is_changed(path))
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::ContentHashes;
+    use crate::{path_to_key, File};
+
+    fn insert(hashes: &ContentHashes, path: &PathBuf) {
+        let content = fs::read(path).unwrap();
+        hashes.insert(path_to_key(path), &File::from(content));
+    }
+
+    #[test]
+    fn detects_changed_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "content").unwrap();
+        let hashes = ContentHashes::default();
+        insert(&hashes, &path);
+
+        // Rewriting the same content, e. g. by `git checkout`
+        fs::remove_file(&path).unwrap();
+        fs::write(&path, "content").unwrap();
+        assert!(hashes.is_unchanged(&path_to_key(&path), &path));
+
+        fs::write(&path, "changed").unwrap();
+        assert!(!hashes.is_unchanged(&path_to_key(&path), &path));
+        // The hash is forgotten until the file is read again
+        fs::write(&path, "content").unwrap();
+        assert!(!hashes.is_unchanged(&path_to_key(&path), &path));
+    }
+
+    #[test]
+    fn detects_changes_in_parallel() {
+        let dir = tempfile::tempdir().unwrap();
+        let hashes = ContentHashes::default();
+        let paths = (0..100)
+            .map(|i| {
+                let path = dir.path().join(format!("{i}.txt"));
+                fs::write(&path, i.to_string()).unwrap();
+                insert(&hashes, &path);
+                path
+            })
+            .collect::<Vec<_>>();
+        for path in paths.iter().step_by(3) {
+            fs::write(path, "changed").unwrap();
+        }
+
+        let mut changed = hashes.changed(paths.clone().into_iter());
+        changed.sort();
+        let mut expected = paths.into_iter().step_by(3).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(changed, expected);
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use turbo_tasks::{
+    persisted_graph::ExternalStateCheck, FunctionId, RawVc, SharedReference, TaskInput,
+};
+
+use crate::{
+    content_hashes::hash_content,
+    rope::Rope,
+    util::{sys_to_unix, unix_to_sys},
+    DirectoryContent, DirectoryEntry, DiskFileSystem, FileContent, FileMeta, FileSystemPath,
+    LinkContent, LinkType, DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_METADATA_FUNCTION_ID,
+    DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_READ_DIR_FUNCTION_ID,
+    DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_READ_FUNCTION_ID,
+    DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_READ_LINK_FUNCTION_ID,
+};
+
+/// Checks the persisted reads of a [DiskFileSystem] against the disk on
+/// startup. Files are compared by the hash of their content, directories by
+/// their entries, so only reads of files that changed while no session was
+/// running are recomputed.
+pub struct DiskFileSystemStateCheck;
+
+fn read_cell<T: Send + Sync + 'static>(
+    read_cell: &dyn Fn(RawVc) -> Option<SharedReference>,
+    input: Option<&TaskInput>,
+) -> Option<Arc<T>> {
+    let vc = match input? {
+        TaskInput::TaskCell(task, index) => RawVc::TaskCell(*task, *index),
+        TaskInput::TaskOutput(task) => RawVc::TaskOutput(*task),
+        _ => return None,
+    };
+    read_cell(vc)?.downcast::<T>()
+}
+
+impl ExternalStateCheck for DiskFileSystemStateCheck {
+    fn functions(&self) -> Vec<FunctionId> {
+        vec![
+            *DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_READ_FUNCTION_ID,
+            *DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_READ_DIR_FUNCTION_ID,
+            *DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_READ_LINK_FUNCTION_ID,
+            *DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_METADATA_FUNCTION_ID,
+        ]
+    }
+
+    fn is_outdated(
+        &self,
+        function: FunctionId,
+        inputs: &[TaskInput],
+        output: RawVc,
+        read: &dyn Fn(RawVc) -> Option<SharedReference>,
+    ) -> Result<bool> {
+        let (fs, fs_path, output) = match (
+            read_cell::<DiskFileSystem>(read, inputs.first()),
+            read_cell::<FileSystemPath>(read, inputs.get(1)),
+            read(output),
+        ) {
+            (Some(fs), Some(fs_path), Some(output)) => (fs, fs_path, output),
+            _ => return Ok(true),
+        };
+        let root = Path::new(&fs.root);
+        let path = root.join(&*unix_to_sys(&fs_path.path));
+        let unchanged = if function == *DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_READ_FUNCTION_ID {
+            output
+                .downcast::<FileContent>()
+                .map_or(false, |content| is_file_unchanged(&path, &content))
+        } else if function == *DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_READ_DIR_FUNCTION_ID {
+            output
+                .downcast::<DirectoryContent>()
+                .map_or(false, |content| is_dir_unchanged(&path, &content))
+        } else if function == *DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_READ_LINK_FUNCTION_ID {
+            output
+                .downcast::<LinkContent>()
+                .map_or(false, |content| is_link_unchanged(root, &path, &content))
+        } else if function == *DISKFILESYSTEM_IMPL_TRAIT_FILESYSTEM_METADATA_FUNCTION_ID {
+            match (output.downcast::<FileMeta>(), fs::metadata(&path)) {
+                (Some(persisted), Ok(meta)) => *persisted == FileMeta::from(meta),
+                _ => false,
+            }
+        } else {
+            false
+        };
+        Ok(!unchanged)
+    }
+}
+
+fn is_file_unchanged(path: &Path, persisted: &FileContent) -> bool {
+    match (persisted, fs::read(path)) {
+        (FileContent::NotFound, Err(e)) => e.kind() == ErrorKind::NotFound,
+        (FileContent::Content(file), Ok(content)) => match fs::metadata(path) {
+            Ok(metadata) => {
+                hash_content(&file.content, file.meta.permissions)
+                    == hash_content(&Rope::from(content), metadata.permissions().into())
+            }
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// The kind of a [DirectoryEntry], without the path.
+fn entry_kind(entry: &DirectoryEntry) -> u8 {
+    match entry {
+        DirectoryEntry::File(_) => 0,
+        DirectoryEntry::Directory(_) => 1,
+        DirectoryEntry::Symlink(_) => 2,
+        DirectoryEntry::Other(_) => 3,
+        DirectoryEntry::Error => 4,
+    }
+}
+
+fn is_dir_unchanged(path: &Path, persisted: &DirectoryContent) -> bool {
+    let read_dir = match fs::read_dir(path) {
+        Ok(read_dir) => read_dir,
+        Err(e) => {
+            return matches!(persisted, DirectoryContent::NotFound)
+                && matches!(
+                    e.kind(),
+                    ErrorKind::NotFound | ErrorKind::NotADirectory | ErrorKind::InvalidFilename
+                )
+        }
+    };
+    let entries = match persisted {
+        DirectoryContent::Entries(entries) => entries,
+        DirectoryContent::NotFound => return false,
+    };
+    let mut current = HashMap::new();
+    for entry in read_dir {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => return false,
+        };
+        // Non unicode names are skipped when reading the directory as well
+        let name = match entry.file_name().to_str() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let kind = match entry.file_type() {
+            Ok(t) if t.is_file() => 0,
+            Ok(t) if t.is_dir() => 1,
+            Ok(t) if t.is_symlink() => 2,
+            Ok(_) => 3,
+            Err(_) => return false,
+        };
+        current.insert(name, kind);
+    }
+    current.len() == entries.len()
+        && entries
+            .iter()
+            .all(|(name, entry)| current.get(name) == Some(&entry_kind(entry)))
+}
+
+fn is_link_unchanged(root: &Path, path: &Path, persisted: &LinkContent) -> bool {
+    match (persisted, fs::read_link(path)) {
+        (LinkContent::NotFound, Err(_)) => true,
+        (LinkContent::Link { target, link_type }, Ok(link)) => {
+            // The type of the target might have changed without changing the
+            // link
+            let is_dir = fs::metadata(path).map_or(false, |meta| meta.is_dir());
+            if link_type.contains(LinkType::DIRECTORY) != is_dir {
+                return false;
+            }
+            // Absolute links are stored relative to the root
+            let link: PathBuf = if link.is_absolute() {
+                match link.strip_prefix(root) {
+                    Ok(link) => link.to_path_buf(),
+                    Err(_) => return false,
+                }
+            } else {
+                link
+            };
+            sys_to_unix(&link.to_string_lossy()) == target.as_str()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{is_dir_unchanged, is_file_unchanged, is_link_unchanged};
+    use crate::{DirectoryContent, File, FileContent, LinkContent, LinkType};
+
+    #[test]
+    fn detects_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "content").unwrap();
+        let persisted = FileContent::Content(File::from("content"));
+        assert!(is_file_unchanged(&path, &persisted));
+
+        fs::write(&path, "changed").unwrap();
+        assert!(!is_file_unchanged(&path, &persisted));
+
+        fs::remove_file(&path).unwrap();
+        assert!(!is_file_unchanged(&path, &persisted));
+        assert!(is_file_unchanged(&path, &FileContent::NotFound));
+    }
+
+    #[test]
+    fn detects_created_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dir");
+        assert!(is_dir_unchanged(&path, &DirectoryContent::NotFound));
+
+        fs::create_dir(&path).unwrap();
+        assert!(!is_dir_unchanged(&path, &DirectoryContent::NotFound));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn detects_changed_links() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        fs::write(dir.path().join("b.txt"), "b").unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink("a.txt", &link).unwrap();
+        let persisted = LinkContent::Link {
+            target: "a.txt".to_string(),
+            link_type: LinkType::UNSET,
+        };
+        assert!(is_link_unchanged(dir.path(), &link, &persisted));
+
+        fs::remove_file(&link).unwrap();
+        std::os::unix::fs::symlink("b.txt", &link).unwrap();
+        assert!(!is_link_unchanged(dir.path(), &link, &persisted));
+    }
+}
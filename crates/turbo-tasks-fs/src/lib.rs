@@ -5,14 +5,20 @@
 #![feature(io_error_more)]
 #![feature(main_separator_str)]
 
+pub mod archive;
 pub mod attach;
+mod content_hashes;
 pub mod embed;
+mod external_state;
 pub mod glob;
 mod invalidator_map;
+pub mod overlay;
 mod read_glob;
 mod retry;
 pub mod rope;
 pub mod util;
+pub mod virtual_fs;
+mod watch_options;
 
 use std::{
     collections::{HashMap, HashSet},
@@ -23,18 +29,19 @@ use std::{
     path::{Path, PathBuf, MAIN_SEPARATOR},
     sync::{
         mpsc::{channel, RecvError, TryRecvError},
-        Arc, Mutex,
+        Arc, Mutex, Weak,
     },
-    time::Duration,
 };
 
 use anyhow::{anyhow, bail, Context, Result};
 use bitflags::bitflags;
+use content_hashes::ContentHashes;
+pub use external_state::DiskFileSystemStateCheck;
 use glob::GlobVc;
 use invalidator_map::InvalidatorMap;
 use jsonc_parser::{parse_to_serde_value, ParseOptions};
 use mime::Mime;
-use notify::{watcher, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{watcher, DebouncedEvent, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
 use read_glob::read_glob;
 pub use read_glob::{ReadGlobResult, ReadGlobResultVc};
 use serde::{Deserialize, Serialize};
@@ -48,6 +55,8 @@ use turbo_tasks::{
 };
 use turbo_tasks_hash::hash_xxh3_hash64;
 use util::{join_path, normalize_path, sys_to_unix, unix_to_sys};
+use watch_options::WatchFilter;
+pub use watch_options::WatchOptions;
 
 #[cfg(target_family = "windows")]
 use crate::util::is_windows_raw_path;
@@ -80,7 +89,73 @@ pub struct DiskFileSystem {
     dir_invalidator_map: Arc<InvalidatorMap>,
     #[turbo_tasks(debug_ignore, trace_ignore)]
     #[serde(skip)]
-    watcher: Mutex<Option<RecommendedWatcher>>,
+    content_hashes: Arc<ContentHashes>,
+    #[turbo_tasks(debug_ignore, trace_ignore)]
+    #[serde(skip)]
+    watcher: Arc<Mutex<Option<DiskWatcher>>>,
+}
+
+/// Keeps the watcher alive, events stop when it's dropped
+enum DiskWatcher {
+    Native(RecommendedWatcher),
+    Polling(PollWatcher),
+}
+
+impl DiskWatcher {
+    fn watch(&mut self, path: &Path, mode: RecursiveMode) -> Result<()> {
+        match self {
+            DiskWatcher::Native(watcher) => watcher.watch(path, mode)?,
+            DiskWatcher::Polling(watcher) => watcher.watch(path, mode)?,
+        }
+        Ok(())
+    }
+
+    /// Watches the root. When paths are ignored, only the directories that
+    /// are not ignored are watched, so that e. g. `node_modules` isn't
+    /// traversed.
+    fn watch_root(&mut self, root: &Path, filter: &mut WatchFilter) -> Result<()> {
+        if filter.ignores_nothing() {
+            self.watch(root, RecursiveMode::Recursive)
+        } else {
+            filter.scan(root, &mut |dir| {
+                self.watch(dir, RecursiveMode::NonRecursive)
+            })
+        }
+    }
+}
+
+/// Directories are watched one by one when paths are ignored, so directories
+/// that have been created need to be watched too. A changed `.gitignore` file
+/// is read again and its directory is scanned for directories that are no
+/// longer ignored.
+fn update_watched_path(
+    watcher: &Weak<Mutex<Option<DiskWatcher>>>,
+    filter: &mut WatchFilter,
+    path: &Path,
+) -> Result<()> {
+    if filter.ignores_nothing() {
+        return Ok(());
+    }
+    let dir = if filter.is_gitignore_file(path) {
+        let dir = path.parent().unwrap();
+        filter.load_gitignore(dir)?;
+        dir
+    } else if path.is_dir() && !filter.is_ignored(path) {
+        path
+    } else {
+        return Ok(());
+    };
+    let watcher = match watcher.upgrade() {
+        Some(watcher) => watcher,
+        None => return Ok(()),
+    };
+    let mut watcher = watcher.lock().unwrap();
+    match &mut *watcher {
+        Some(watcher) => filter.scan(dir, &mut |dir| {
+            watcher.watch(dir, RecursiveMode::NonRecursive)
+        }),
+        None => Ok(()),
+    }
 }
 
 impl DiskFileSystem {
@@ -106,21 +181,33 @@ impl DiskFileSystem {
     }
 
     pub fn start_watching(&self) -> Result<()> {
+        self.start_watching_with_options(WatchOptions::default())
+    }
+
+    /// Starts watching with custom ignore rules, debounce or polling. Does
+    /// nothing when already watching.
+    pub fn start_watching_with_options(&self, options: WatchOptions) -> Result<()> {
         let mut watcher_guard = self.watcher.lock().unwrap();
         if watcher_guard.is_some() {
             return Ok(());
         }
         let invalidator_map = self.invalidator_map.clone();
         let dir_invalidator_map = self.dir_invalidator_map.clone();
+        let content_hashes = self.content_hashes.clone();
         let root = self.root.clone();
+        let mut filter = WatchFilter::new(&root, &options);
         // Create a channel to receive the events.
         let (tx, rx) = channel();
         // Create a watcher object, delivering debounced events.
-        // The notification back-end is selected based on the platform.
-        let mut watcher = watcher(tx, Duration::from_millis(1))?;
+        // The notification back-end is selected based on the platform, unless
+        // polling is requested.
+        let mut watcher = match options.poll_interval {
+            Some(interval) => DiskWatcher::Polling(PollWatcher::new(tx, interval)?),
+            None => DiskWatcher::Native(watcher(tx, options.debounce)?),
+        };
         // Add a path to be watched. All files and directories at that path and
         // below will be monitored for changes.
-        watcher.watch(&root, RecursiveMode::Recursive)?;
+        watcher.watch_root(Path::new(&root), &mut filter)?;
 
         // We need to invalidate all reads that happened before watching
         // Best is to start_watching before starting to read
@@ -132,37 +219,79 @@ impl DiskFileSystem {
         }
 
         watcher_guard.replace(watcher);
+        // The thread must not keep the watcher alive, as it only exits when
+        // the watcher has been dropped
+        let weak_watcher = Arc::downgrade(&self.watcher);
 
         spawn_thread(move || {
             let mut batched_invalidate_path = HashSet::new();
             let mut batched_invalidate_path_dir = HashSet::new();
             let mut batched_invalidate_path_and_children = HashSet::new();
             let mut batched_invalidate_path_and_children_dir = HashSet::new();
+            // Paths that are files now, but might have been directories before
+            let mut batched_invalidate_children = HashSet::new();
 
             'outer: loop {
                 let mut event = rx.recv().map_err(|e| match e {
                     RecvError => TryRecvError::Disconnected,
                 });
                 loop {
+                    let changed_paths = match &event {
+                        Ok(DebouncedEvent::Write(path))
+                        | Ok(DebouncedEvent::Create(path))
+                        | Ok(DebouncedEvent::Remove(path)) => vec![path],
+                        Ok(DebouncedEvent::Rename(source, destination)) => {
+                            vec![source, destination]
+                        }
+                        _ => Vec::new(),
+                    };
+                    for path in changed_paths {
+                        if let Err(err) = update_watched_path(&weak_watcher, &mut filter, path) {
+                            println!("watch error ({:?}): {:?} ", path, err);
+                        }
+                    }
                     match event {
+                        Ok(DebouncedEvent::Write(path))
+                        | Ok(DebouncedEvent::Create(path))
+                        | Ok(DebouncedEvent::Remove(path))
+                            if filter.is_ignored(&path) =>
+                        {
+                            // ignored
+                        }
                         Ok(DebouncedEvent::Write(path)) => {
                             batched_invalidate_path.insert(path);
                         }
                         Ok(DebouncedEvent::Create(path)) | Ok(DebouncedEvent::Remove(path)) => {
-                            batched_invalidate_path_and_children.insert(path.clone());
-                            batched_invalidate_path_and_children_dir.insert(path.clone());
                             if let Some(parent) = path.parent() {
                                 batched_invalidate_path_dir.insert(PathBuf::from(parent));
                             }
+                            if path.is_file() {
+                                // A file that has been replaced, e. g. by `git
+                                // checkout`, is checked for unchanged content
+                                // like a write
+                                batched_invalidate_path.insert(path.clone());
+                                batched_invalidate_children.insert(path);
+                            } else {
+                                batched_invalidate_path_and_children.insert(path.clone());
+                                batched_invalidate_path_and_children_dir.insert(path);
+                            }
                         }
                         Ok(DebouncedEvent::Rename(source, destination)) => {
-                            batched_invalidate_path_and_children.insert(source.clone());
-                            if let Some(parent) = source.parent() {
-                                batched_invalidate_path_dir.insert(PathBuf::from(parent));
-                            }
-                            batched_invalidate_path_and_children.insert(destination.clone());
-                            if let Some(parent) = destination.parent() {
-                                batched_invalidate_path_dir.insert(PathBuf::from(parent));
+                            for path in [source, destination] {
+                                if filter.is_ignored(&path) {
+                                    continue;
+                                }
+                                if let Some(parent) = path.parent() {
+                                    batched_invalidate_path_dir.insert(PathBuf::from(parent));
+                                }
+                                if path.is_file() {
+                                    // Atomic saves rename a temporary file
+                                    // over the original one
+                                    batched_invalidate_path.insert(path.clone());
+                                    batched_invalidate_children.insert(path);
+                                } else {
+                                    batched_invalidate_path_and_children.insert(path);
+                                }
                             }
                         }
                         Ok(DebouncedEvent::Rescan) => {
@@ -225,13 +354,37 @@ impl DiskFileSystem {
                     }
                     paths.clear()
                 }
+                fn invalidate_children_execute(
+                    invalidator_map: &mut HashMap<String, HashSet<Invalidator>>,
+                    paths: &mut HashSet<PathBuf>,
+                ) {
+                    let prefixes = paths
+                        .drain()
+                        .map(|path| format!("{}{}", path_to_key(path), MAIN_SEPARATOR))
+                        .collect::<Vec<_>>();
+                    if prefixes.is_empty() {
+                        return;
+                    }
+                    for (_, invalidators) in invalidator_map
+                        .drain_filter(|key, _| prefixes.iter().any(|p| key.starts_with(p)))
+                    {
+                        invalidators.into_iter().for_each(|i| i.invalidate());
+                    }
+                }
+                // Files are hashed before locking, as the reads would block
+                // the registration of new invalidators
+                let changed_paths = content_hashes.changed(batched_invalidate_path.drain());
                 {
                     let mut invalidator_map = invalidator_map.lock().unwrap();
-                    invalidate_path(&mut invalidator_map, batched_invalidate_path.drain());
+                    invalidate_path(&mut invalidator_map, changed_paths.into_iter());
                     invalidate_path_and_children_execute(
                         &mut invalidator_map,
                         &mut batched_invalidate_path_and_children,
                     );
+                    invalidate_children_execute(
+                        &mut invalidator_map,
+                        &mut batched_invalidate_children,
+                    );
                 }
                 {
                     let mut dir_invalidator_map = dir_invalidator_map.lock().unwrap();
@@ -278,7 +431,8 @@ impl DiskFileSystemVc {
             root,
             invalidator_map: Arc::new(InvalidatorMap::new()),
             dir_invalidator_map: Arc::new(InvalidatorMap::new()),
-            watcher: Mutex::new(None),
+            content_hashes: Default::default(),
+            watcher: Default::default(),
         };
 
         Ok(Self::cell(instance))
@@ -299,8 +453,14 @@ impl FileSystem for DiskFileSystem {
         self.register_invalidator(&full_path, true);
 
         let content = match retry_future(|| File::from_path(full_path.clone())).await {
-            Ok(file) => FileContent::new(file),
-            Err(e) if e.kind() == ErrorKind::NotFound => FileContent::NotFound,
+            Ok(file) => {
+                self.content_hashes.insert(path_to_key(&full_path), &file);
+                FileContent::new(file)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                self.content_hashes.remove(&path_to_key(&full_path));
+                FileContent::NotFound
+            }
             Err(e) => {
                 bail!(anyhow!(e).context(format!("reading file {}", full_path.display())))
             }
@@ -1605,3 +1765,17 @@ pub fn register() {
     turbo_tasks::register();
     include!(concat!(env!("OUT_DIR"), "/register.rs"));
 }
+
+#[cfg(test)]
+mod tests {
+    use turbo_tasks::heap_size::HeapSize;
+
+    use super::{File, FileContent};
+
+    #[test]
+    fn file_content_heap_size() {
+        let content = FileContent::Content(File::from(vec![0u8; 1000]));
+        assert!(content.heap_size() >= 1000);
+        assert_eq!(FileContent::NotFound.heap_size(), 0);
+    }
+}
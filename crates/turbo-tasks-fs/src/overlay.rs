@@ -0,0 +1,293 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{bail, Result};
+use turbo_tasks::{primitives::StringVc, CompletionVc, ValueToString, ValueToStringVc};
+
+use crate::{
+    util::join_path, DirectoryContent, DirectoryContentVc, DirectoryEntry, File, FileContent,
+    FileContentVc, FileMetaVc, FileSystem, FileSystemEntryType, FileSystemPathVc, FileSystemVc,
+    LinkContent, LinkContentVc,
+};
+
+/// Files in an upper layer with this prefix hide the entry with the rest of
+/// the name in lower layers, like in overlayfs and OCI images
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// A directory in an upper layer containing this file hides the contents of
+/// the directory in lower layers
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+
+/// A [FileSystem] which stacks multiple [FileSystem]s on top of each other,
+/// e. g. unsaved editor buffers over the project on disk.
+///
+/// Reads are served by the topmost layer that contains the path, directory
+/// listings are merged from all layers and writes go to the topmost layer.
+/// An entry in an upper layer shadows the entry in lower layers, even when
+/// it's of a different type, e. g. a directory over a file.
+///
+/// Entries of lower layers are hidden by whiteouts and opaque directories in
+/// upper layers, like in overlayfs: A `.wh.<name>` file hides `<name>` and a
+/// `.wh..wh..opq` file hides the contents of its directory. Removing a file or
+/// link that is still contained in a lower layer writes a whiteout.
+#[turbo_tasks::value]
+pub struct OverlayFileSystem {
+    /// The layers, topmost first
+    layers: Vec<FileSystemVc>,
+}
+
+#[turbo_tasks::value_impl]
+impl OverlayFileSystemVc {
+    /// Creates a new [OverlayFileSystem] from `layers`, where earlier layers
+    /// shadow later ones.
+    #[turbo_tasks::function]
+    pub fn new(layers: Vec<FileSystemVc>) -> Self {
+        OverlayFileSystem { layers }.cell()
+    }
+
+    /// Converts a path on a layer to a path on this [FileSystem]
+    #[turbo_tasks::function]
+    pub async fn convert_path(self, layer_path: FileSystemPathVc) -> Result<FileSystemPathVc> {
+        let layer_path = layer_path.await?;
+        if !self.await?.layers.contains(&layer_path.fs) {
+            bail!(
+                "path {} is not part of a layer",
+                layer_path.fs.to_string().await?
+            );
+        }
+        Ok(self.root().join(&layer_path.path))
+    }
+}
+
+impl OverlayFileSystem {
+    async fn top_layer_path(&self, path: &str) -> Result<FileSystemPathVc> {
+        match self.layers.first() {
+            Some(layer) => Ok(layer.root().join(path)),
+            None => bail!("an overlay file system without layers can't be written to"),
+        }
+    }
+
+    /// Returns the topmost layer path that contains `path` and its type,
+    /// starting at the layer with index `start`. Returns `None` when no layer
+    /// contains it or it's hidden.
+    async fn find_layer(
+        &self,
+        path: &str,
+        start: usize,
+    ) -> Result<Option<(FileSystemPathVc, FileSystemEntryType)>> {
+        let name = path.rsplit('/').next().unwrap_or_default();
+        if name.starts_with(WHITEOUT_PREFIX) {
+            return Ok(None);
+        }
+        for layer in self.layers.iter().skip(start) {
+            let layer_path = layer.root().join(path);
+            let ty = *layer_path.get_type().await?;
+            if ty != FileSystemEntryType::NotFound {
+                return Ok(Some((layer_path, ty)));
+            }
+            if hides_lower_layers(*layer, path).await? {
+                return Ok(None);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The whiteout next to `top_layer_path` that hides `path` in lower layers.
+fn whiteout_path(top_layer_path: FileSystemPathVc, path: &str) -> FileSystemPathVc {
+    let name = path.rsplit('/').next().unwrap_or_default();
+    top_layer_path
+        .parent()
+        .join(&format!("{}{}", WHITEOUT_PREFIX, name))
+}
+
+/// Whether the entry at `path` in layers below `layer` is hidden by `layer`,
+/// because `layer` contains a whiteout for it or one of its parent
+/// directories, an opaque parent directory or a parent that is not a
+/// directory.
+async fn hides_lower_layers(layer: FileSystemVc, path: &str) -> Result<bool> {
+    let root = layer.root();
+    let mut parent = String::new();
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        let parent_path = root.join(&parent);
+        let ty = *parent_path.get_type().await?;
+        if ty == FileSystemEntryType::NotFound {
+            // The layer doesn't contain anything further down
+            return Ok(false);
+        }
+        if ty != FileSystemEntryType::Directory
+            || *parent_path.join(OPAQUE_MARKER).get_type().await? != FileSystemEntryType::NotFound
+            || *parent_path
+                .join(&format!("{}{}", WHITEOUT_PREFIX, segment))
+                .get_type()
+                .await?
+                != FileSystemEntryType::NotFound
+        {
+            return Ok(true);
+        }
+        parent = join_path(&parent, segment).unwrap_or_default();
+    }
+    Ok(false)
+}
+
+#[turbo_tasks::value_impl]
+impl FileSystem for OverlayFileSystem {
+    #[turbo_tasks::function]
+    async fn read(&self, path: FileSystemPathVc) -> Result<FileContentVc> {
+        // Layers are checked in order, so a change in a layer above the one
+        // serving the content invalidates the read.
+        Ok(match self.find_layer(&path.await?.path, 0).await? {
+            Some((layer_path, FileSystemEntryType::File | FileSystemEntryType::Symlink)) => {
+                layer_path.read()
+            }
+            // A directory shadows files in lower layers
+            _ => FileContent::NotFound.cell(),
+        })
+    }
+
+    #[turbo_tasks::function]
+    async fn read_link(&self, path: FileSystemPathVc) -> Result<LinkContentVc> {
+        Ok(match self.find_layer(&path.await?.path, 0).await? {
+            Some((layer_path, FileSystemEntryType::Symlink)) => layer_path.read_link(),
+            _ => LinkContent::NotFound.cell(),
+        })
+    }
+
+    #[turbo_tasks::function]
+    async fn read_dir(&self, path: FileSystemPathVc) -> Result<DirectoryContentVc> {
+        let fs_path = path.await?;
+        let mut found = false;
+        let mut merged = HashMap::new();
+        // Names hidden by whiteouts of the layers above
+        let mut hidden = HashSet::new();
+        // Layers are merged top down, so entries of upper layers win
+        for layer in self.layers.iter() {
+            let layer_path = layer.root().join(&fs_path.path);
+            let dir_content = layer_path.read_dir().await?;
+            let entries = match &*dir_content {
+                DirectoryContent::Entries(e) => e,
+                DirectoryContent::NotFound => {
+                    if *layer_path.get_type().await? != FileSystemEntryType::NotFound {
+                        // A file shadows directories in lower layers
+                        break;
+                    }
+                    if hides_lower_layers(*layer, &fs_path.path).await? {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            found = true;
+
+            let mut opaque = false;
+            let mut whiteouts = Vec::new();
+            for (name, entry) in entries {
+                use DirectoryEntry::*;
+
+                if name == OPAQUE_MARKER {
+                    opaque = true;
+                    continue;
+                }
+                if let Some(name) = name.strip_prefix(WHITEOUT_PREFIX) {
+                    whiteouts.push(name.to_string());
+                    continue;
+                }
+                if hidden.contains(name) || merged.contains_key(name) {
+                    continue;
+                }
+
+                let entry_path = || {
+                    FileSystemPathVc::new_normalized(
+                        fs_path.fs,
+                        join_path(&fs_path.path, name).unwrap_or_default(),
+                    )
+                };
+                let entry = match *entry {
+                    File(_) => File(entry_path()),
+                    Directory(_) => Directory(entry_path()),
+                    Symlink(_) => Symlink(entry_path()),
+                    Other(_) => Other(entry_path()),
+                    Error => Error,
+                };
+
+                merged.insert(name.clone(), entry);
+            }
+            if opaque || hides_lower_layers(*layer, &fs_path.path).await? {
+                break;
+            }
+            hidden.extend(whiteouts);
+        }
+
+        Ok(if found {
+            DirectoryContentVc::new(merged)
+        } else {
+            DirectoryContentVc::not_found()
+        })
+    }
+
+    #[turbo_tasks::function]
+    async fn write(&self, path: FileSystemPathVc, content: FileContentVc) -> Result<CompletionVc> {
+        let path = &path.await?.path;
+        let top_layer_path = self.top_layer_path(path).await?;
+        let whiteout = whiteout_path(top_layer_path, path);
+        if let FileContent::NotFound = &*content.await? {
+            top_layer_path.write(content).await?;
+            if self.find_layer(path, 1).await?.is_some()
+                && !hides_lower_layers(self.layers[0], path).await?
+            {
+                // Lower layers still contain the file
+                return Ok(whiteout.write(FileContent::Content(File::from("")).cell()));
+            }
+            Ok(CompletionVc::new())
+        } else {
+            if *whiteout.get_type().await? != FileSystemEntryType::NotFound {
+                whiteout.write(FileContent::NotFound.cell()).await?;
+            }
+            Ok(top_layer_path.write(content))
+        }
+    }
+
+    #[turbo_tasks::function]
+    async fn write_link(
+        &self,
+        path: FileSystemPathVc,
+        target: LinkContentVc,
+    ) -> Result<CompletionVc> {
+        let path = &path.await?.path;
+        let top_layer_path = self.top_layer_path(path).await?;
+        let whiteout = whiteout_path(top_layer_path, path);
+        if let LinkContent::NotFound = &*target.await? {
+            top_layer_path.write_link(target).await?;
+            if self.find_layer(path, 1).await?.is_some()
+                && !hides_lower_layers(self.layers[0], path).await?
+            {
+                // Lower layers still contain the link
+                return Ok(whiteout.write(FileContent::Content(File::from("")).cell()));
+            }
+            Ok(CompletionVc::new())
+        } else {
+            if *whiteout.get_type().await? != FileSystemEntryType::NotFound {
+                whiteout.write(FileContent::NotFound.cell()).await?;
+            }
+            Ok(top_layer_path.write_link(target))
+        }
+    }
+
+    #[turbo_tasks::function]
+    async fn metadata(&self, path: FileSystemPathVc) -> Result<FileMetaVc> {
+        let path = &path.await?.path;
+        match self.find_layer(path, 0).await? {
+            Some((layer_path, _)) => Ok(layer_path.metadata()),
+            None => bail!("path {} not found, can't read metadata", path),
+        }
+    }
+}
+#[turbo_tasks::value_impl]
+impl ValueToString for OverlayFileSystem {
+    #[turbo_tasks::function]
+    async fn to_string(&self) -> Result<StringVc> {
+        let mut names = Vec::with_capacity(self.layers.len());
+        for layer in self.layers.iter() {
+            names.push(layer.to_string().await?.clone_value());
+        }
+        Ok(StringVc::cell(names.join("-over-")))
+    }
+}
@@ -14,6 +14,7 @@ use bytes::{Buf, Bytes};
 use futures::Stream;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tokio::io::{AsyncRead, ReadBuf};
+use turbo_tasks::heap_size::HeapSize;
 use turbo_tasks_hash::{DeterministicHash, DeterministicHasher};
 use RopeElem::{Local, Shared};
 
@@ -334,6 +335,17 @@ impl DeterministicHash for RopeElem {
     }
 }
 
+impl HeapSize for RopeElem {
+    /// Local bytes are counted fully, even when the Bytes buffer is shared with
+    /// other Bytes.
+    fn heap_size(&self) -> usize {
+        match self {
+            Local(bytes) => bytes.len(),
+            Shared(inner) => inner.heap_size(),
+        }
+    }
+}
+
 /// Implements the Read/AsyncRead/Stream/Iterator trait over a Rope.
 pub struct RopeReader {
     /// The Rope's tree is kept as a cloned stack, allowing us to accomplish
@@ -492,3 +504,31 @@ impl From<RopeElem> for StackElem {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use turbo_tasks::heap_size::HeapSize;
+
+    use super::{Rope, RopeBuilder};
+
+    #[test]
+    fn heap_size_counts_bytes() {
+        let rope = Rope::from("hello world".to_string());
+        assert!(rope.heap_size() >= 11);
+    }
+
+    #[test]
+    fn heap_size_shares_ropes() {
+        let shared = Rope::from(vec![0u8; 1000]);
+        let alone = shared.heap_size();
+
+        let mut builder = RopeBuilder::from("prefix");
+        builder += &shared;
+        let rope = builder.build();
+
+        // Both ropes hold the shared bytes, so they split their size
+        assert!(shared.heap_size() < alone);
+        assert!(rope.heap_size() >= 6);
+        assert!(shared.heap_size() + rope.heap_size() >= alone + 6);
+    }
+}
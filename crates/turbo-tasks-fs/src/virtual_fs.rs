@@ -0,0 +1,467 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, bail, Result};
+use turbo_tasks::{primitives::StringVc, CompletionVc, ValueToString, ValueToStringVc};
+
+use crate::{
+    invalidator_map::InvalidatorMap,
+    util::{join_path, normalize_path},
+    DirectoryContentVc, DirectoryEntry, File, FileContent, FileContentVc, FileMeta, FileMetaVc,
+    FileSystem, FileSystemPathVc, FileSystemVc, LinkContent, LinkContentVc, LinkType,
+};
+
+/// Maximum number of symlinks followed while resolving a path, like `ELOOP`
+/// on Linux.
+const MAX_SYMLINKS: usize = 40;
+
+#[derive(Clone)]
+enum VirtualEntry {
+    File(File),
+    Directory,
+    Symlink { target: String, link_type: LinkType },
+}
+
+/// Entries by normalized path. The root directory is implicit.
+type Entries = BTreeMap<String, VirtualEntry>;
+
+fn parent_path(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn is_inside_or_equal(path: &str, dir: &str) -> bool {
+    dir.is_empty()
+        || path
+            .strip_prefix(dir)
+            .map_or(false, |rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Resolves all symlinks in `path`, including the last segment when
+/// `follow_last` is set. The paths of traversed symlinks are added to
+/// `links`. Returns `None` when a symlink points outside of the file system
+/// or there are too many symlinks.
+fn resolve(
+    entries: &Entries,
+    path: &str,
+    follow_last: bool,
+    links: &mut Vec<String>,
+) -> Option<String> {
+    let mut path = path.to_string();
+    for _ in 0..MAX_SYMLINKS {
+        let segments = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        let mut resolved = String::new();
+        let mut redirect = None;
+        for (i, segment) in segments.iter().enumerate() {
+            let current = join_path(&resolved, segment)?;
+            let is_last = i + 1 == segments.len();
+            if let Some(VirtualEntry::Symlink { target, link_type }) = entries.get(&current) {
+                if !is_last || follow_last {
+                    links.push(current);
+                    let base = if link_type.contains(LinkType::ABSOLUTE) {
+                        ""
+                    } else {
+                        &resolved
+                    };
+                    let target = join_path(base, target)?;
+                    redirect = Some(join_path(&target, &segments[i + 1..].join("/"))?);
+                    break;
+                }
+            }
+            resolved = current;
+        }
+        match redirect {
+            Some(target) => path = target,
+            None => return Some(resolved),
+        }
+    }
+    None
+}
+
+/// A [FileSystem] that keeps all files in memory.
+///
+/// The content can be changed at any time with [VirtualFileSystem::write_file]
+/// and friends, e. g. with unsaved buffers of an editor, or by writing to it
+/// like to any other [FileSystem]. Reads depending on a changed path are
+/// invalidated. Useful for tests, editor integrations and generated sources.
+#[turbo_tasks::value(cell = "new", eq = "manual", serialization = "none")]
+pub struct VirtualFileSystem {
+    pub name: String,
+    #[turbo_tasks(debug_ignore, trace_ignore)]
+    entries: Arc<Mutex<Entries>>,
+    #[turbo_tasks(debug_ignore, trace_ignore)]
+    invalidator_map: Arc<InvalidatorMap>,
+    #[turbo_tasks(debug_ignore, trace_ignore)]
+    dir_invalidator_map: Arc<InvalidatorMap>,
+}
+
+impl VirtualFileSystem {
+    /// registers the paths as an invalidator for the current task,
+    /// has to be called within a turbo-tasks function
+    fn register_invalidator(&self, path: &str, file: bool, links: Vec<String>) {
+        for link in links {
+            self.invalidator_map
+                .insert(link, turbo_tasks::get_invalidator());
+        }
+        let invalidator = turbo_tasks::get_invalidator();
+        if file {
+            self.invalidator_map.insert(path.to_string(), invalidator);
+        } else {
+            self.dir_invalidator_map
+                .insert(path.to_string(), invalidator);
+        }
+    }
+
+    /// Invalidates all reads of `path`, the paths inside of it and the
+    /// directory containing it.
+    fn invalidate_path(&self, path: &str) {
+        for (_, invalidators) in self
+            .invalidator_map
+            .lock()
+            .unwrap()
+            .drain_filter(|key, _| is_inside_or_equal(key, path))
+        {
+            invalidators.into_iter().for_each(|i| i.invalidate());
+        }
+        let parent = parent_path(path);
+        for (_, invalidators) in self
+            .dir_invalidator_map
+            .lock()
+            .unwrap()
+            .drain_filter(|key, _| {
+                (!path.is_empty() && key == parent) || is_inside_or_equal(key, path)
+            })
+        {
+            invalidators.into_iter().for_each(|i| i.invalidate());
+        }
+    }
+
+    /// Inserts an entry at `path`, creating missing parent directories.
+    /// Returns the topmost path that has been changed.
+    fn insert_entry(entries: &mut Entries, path: &str, entry: VirtualEntry) -> Result<String> {
+        if path.is_empty() {
+            bail!("the root of a virtual file system can't be replaced");
+        }
+        let mut changed = None;
+        let mut parent = String::new();
+        for segment in parent_path(path).split('/').filter(|s| !s.is_empty()) {
+            parent = join_path(&parent, segment).unwrap_or_default();
+            match entries.get(&parent) {
+                Some(VirtualEntry::Directory) => {}
+                Some(_) => bail!("{} is not a directory", parent),
+                None => {
+                    entries.insert(parent.clone(), VirtualEntry::Directory);
+                    changed.get_or_insert_with(|| parent.clone());
+                }
+            }
+        }
+        if let Some(VirtualEntry::Directory) = entries.get(path) {
+            if !matches!(entry, VirtualEntry::Directory) {
+                bail!("{} is a directory", path);
+            }
+        }
+        entries.insert(path.to_string(), entry);
+        Ok(changed.unwrap_or_else(|| path.to_string()))
+    }
+
+    fn set_entry(&self, path: &str, entry: VirtualEntry) -> Result<()> {
+        let path = normalize_path(path).ok_or_else(|| anyhow!("invalid path {}", path))?;
+        let changed = {
+            let mut entries = self.entries.lock().unwrap();
+            let mut links = Vec::new();
+            let resolved = resolve(&entries, parent_path(&path), true, &mut links)
+                .ok_or_else(|| anyhow!("unable to resolve parent directory of {}", path))?;
+            let path = join_path(&resolved, path.rsplit('/').next().unwrap_or_default())
+                .unwrap_or_default();
+            Self::insert_entry(&mut entries, &path, entry)?
+        };
+        self.invalidate_path(&changed);
+        Ok(())
+    }
+
+    /// Creates or replaces the file at `path`. Missing parent directories are
+    /// created.
+    pub fn write_file(&self, path: &str, file: impl Into<File>) -> Result<()> {
+        self.set_entry(path, VirtualEntry::File(file.into()))
+    }
+
+    /// Creates the directory at `path` and all missing parent directories.
+    pub fn create_dir(&self, path: &str) -> Result<()> {
+        self.set_entry(path, VirtualEntry::Directory)
+    }
+
+    /// Creates or replaces the symlink at `path`. Relative targets are
+    /// resolved from the directory containing the symlink, absolute targets
+    /// from the root of the file system.
+    pub fn symlink(&self, path: &str, target: &str, link_type: LinkType) -> Result<()> {
+        self.set_entry(
+            path,
+            VirtualEntry::Symlink {
+                target: target.to_string(),
+                link_type,
+            },
+        )
+    }
+
+    /// Removes the entry at `path`, including all contents of directories.
+    /// Removing a path that doesn't exist is not an error.
+    pub fn remove(&self, path: &str) -> Result<()> {
+        let path = normalize_path(path).ok_or_else(|| anyhow!("invalid path {}", path))?;
+        if path.is_empty() {
+            bail!("the root of a virtual file system can't be removed");
+        }
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if entries.remove(&path).is_none() {
+                return Ok(());
+            }
+            entries.retain(|key, _| !is_inside_or_equal(key, &path));
+        }
+        self.invalidate_path(&path);
+        Ok(())
+    }
+
+    /// Resolves `path` and returns the entry it points to together with the
+    /// symlinks on the way
+    fn lookup(
+        entries: &Entries,
+        path: &str,
+        follow_last: bool,
+    ) -> (String, Option<VirtualEntry>, Vec<String>) {
+        let mut links = Vec::new();
+        match resolve(entries, path, follow_last, &mut links) {
+            Some(resolved) => {
+                let entry = if resolved.is_empty() {
+                    Some(VirtualEntry::Directory)
+                } else {
+                    entries.get(&resolved).cloned()
+                };
+                (resolved, entry, links)
+            }
+            None => (path.to_string(), None, links),
+        }
+    }
+
+    /// Like [VirtualFileSystem::lookup], but also registers an invalidator
+    /// for the current task. It's registered while holding the lock, so a
+    /// concurrent write either happens before the lookup or invalidates it.
+    /// `f` is called with the entries as well, to read more of them.
+    fn lookup_and_register<T>(
+        &self,
+        path: &str,
+        follow_last: bool,
+        file: bool,
+        f: impl FnOnce(&Entries, &str, Option<VirtualEntry>) -> T,
+    ) -> T {
+        let entries = self.entries.lock().unwrap();
+        let (resolved, entry, links) = Self::lookup(&entries, path, follow_last);
+        self.register_invalidator(&resolved, file, links);
+        f(&entries, &resolved, entry)
+    }
+}
+
+impl VirtualFileSystemVc {
+    /// Creates an empty virtual file system. Every call creates a new file
+    /// system.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self::cell(VirtualFileSystem {
+            name: name.into(),
+            entries: Default::default(),
+            invalidator_map: Arc::new(InvalidatorMap::new()),
+            dir_invalidator_map: Arc::new(InvalidatorMap::new()),
+        })
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl FileSystem for VirtualFileSystem {
+    #[turbo_tasks::function]
+    async fn read(&self, fs_path: FileSystemPathVc) -> Result<FileContentVc> {
+        let (path, entry) =
+            self.lookup_and_register(&fs_path.await?.path, true, true, |_, path, entry| {
+                (path.to_string(), entry)
+            });
+        Ok(match entry {
+            Some(VirtualEntry::File(file)) => FileContent::Content(file).cell(),
+            Some(VirtualEntry::Directory) => bail!("reading file {}: is a directory", path),
+            Some(VirtualEntry::Symlink { .. }) | None => FileContent::NotFound.cell(),
+        })
+    }
+
+    #[turbo_tasks::function]
+    async fn read_link(&self, fs_path: FileSystemPathVc) -> Result<LinkContentVc> {
+        let entry =
+            self.lookup_and_register(&fs_path.await?.path, false, true, |_, _, entry| entry);
+        Ok(match entry {
+            Some(VirtualEntry::Symlink { target, link_type }) => {
+                LinkContent::Link { target, link_type }.cell()
+            }
+            _ => LinkContent::NotFound.cell(),
+        })
+    }
+
+    #[turbo_tasks::function]
+    async fn read_dir(&self, fs_path: FileSystemPathVc) -> Result<DirectoryContentVc> {
+        // Entries are listed by the requested path, not the resolved one
+        let fs_path_value = fs_path.await?;
+        let children =
+            self.lookup_and_register(&fs_path_value.path, true, false, |entries, path, entry| {
+                if !matches!(entry, Some(VirtualEntry::Directory)) {
+                    return None;
+                }
+                let prefix = if path.is_empty() {
+                    String::new()
+                } else {
+                    format!("{path}/")
+                };
+                Some(
+                    entries
+                        .range(prefix.clone()..)
+                        .take_while(|(key, _)| key.starts_with(&prefix))
+                        .filter_map(|(key, entry)| {
+                            let name = &key[prefix.len()..];
+                            if name.contains('/') {
+                                None
+                            } else {
+                                Some((name.to_string(), entry.clone()))
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            });
+        let children = match children {
+            Some(children) => children,
+            None => return Ok(DirectoryContentVc::not_found()),
+        };
+        let entries = children
+            .into_iter()
+            .map(|(name, entry)| {
+                let entry_path = FileSystemPathVc::new_normalized(
+                    fs_path_value.fs,
+                    join_path(&fs_path_value.path, &name).unwrap_or_default(),
+                );
+                let entry = match entry {
+                    VirtualEntry::File(_) => DirectoryEntry::File(entry_path),
+                    VirtualEntry::Directory => DirectoryEntry::Directory(entry_path),
+                    VirtualEntry::Symlink { .. } => DirectoryEntry::Symlink(entry_path),
+                };
+                (name, entry)
+            })
+            .collect::<HashMap<_, _>>();
+
+        Ok(DirectoryContentVc::new(entries))
+    }
+
+    #[turbo_tasks::function]
+    async fn write(
+        &self,
+        fs_path: FileSystemPathVc,
+        content: FileContentVc,
+    ) -> Result<CompletionVc> {
+        let path = &fs_path.await?.path;
+        match &*content.await? {
+            FileContent::Content(file) => self.write_file(path, file.clone())?,
+            FileContent::NotFound => {
+                let (resolved, entry, _) = Self::lookup(&self.entries.lock().unwrap(), path, false);
+                if let Some(VirtualEntry::File(_)) = entry {
+                    self.remove(&resolved)?;
+                }
+            }
+        }
+        Ok(CompletionVc::new())
+    }
+
+    #[turbo_tasks::function]
+    async fn write_link(
+        &self,
+        fs_path: FileSystemPathVc,
+        target: LinkContentVc,
+    ) -> Result<CompletionVc> {
+        let path = &fs_path.await?.path;
+        match &*target.await? {
+            LinkContent::Link { target, link_type } => self.symlink(path, target, *link_type)?,
+            LinkContent::Invalid => bail!("invalid symlink target: {}", path),
+            LinkContent::NotFound => {
+                let (resolved, entry, _) = Self::lookup(&self.entries.lock().unwrap(), path, false);
+                if let Some(VirtualEntry::Symlink { .. }) = entry {
+                    self.remove(&resolved)?;
+                }
+            }
+        }
+        Ok(CompletionVc::new())
+    }
+
+    #[turbo_tasks::function]
+    async fn metadata(&self, fs_path: FileSystemPathVc) -> Result<FileMetaVc> {
+        let (path, entry) =
+            self.lookup_and_register(&fs_path.await?.path, true, true, |_, path, entry| {
+                (path.to_string(), entry)
+            });
+        Ok(match entry {
+            Some(VirtualEntry::File(file)) => file.meta().clone().cell(),
+            Some(_) => FileMeta::default().cell(),
+            None => bail!("path {} not found, can't read metadata", path),
+        })
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ValueToString for VirtualFileSystem {
+    #[turbo_tasks::function]
+    fn to_string(&self) -> StringVc {
+        StringVc::cell(self.name.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::{resolve, Entries, VirtualEntry, MAX_SYMLINKS};
+    use crate::LinkType;
+
+    fn entries() -> Entries {
+        let symlink = |target: &str, link_type| VirtualEntry::Symlink {
+            target: target.to_string(),
+            link_type,
+        };
+        Entries::from([
+            ("dir".to_string(), VirtualEntry::Directory),
+            ("dir/file.js".to_string(), VirtualEntry::File("".into())),
+            ("link".to_string(), symlink("dir", LinkType::DIRECTORY)),
+            ("dir/up".to_string(), symlink("../link", LinkType::UNSET)),
+            (
+                "abs".to_string(),
+                symlink("dir/file.js", LinkType::ABSOLUTE),
+            ),
+            ("loop".to_string(), symlink("loop", LinkType::UNSET)),
+            ("outside".to_string(), symlink("../x", LinkType::UNSET)),
+        ])
+    }
+
+    #[rstest]
+    #[case::no_link("dir/file.js", true, Some("dir/file.js"), 0)]
+    #[case::missing("dir/missing.js", true, Some("dir/missing.js"), 0)]
+    #[case::dir_link("link/file.js", true, Some("dir/file.js"), 1)]
+    #[case::chained("dir/up/file.js", true, Some("dir/file.js"), 2)]
+    #[case::absolute("abs", true, Some("dir/file.js"), 1)]
+    #[case::last_not_followed("link", false, Some("link"), 0)]
+    #[case::last_followed("link", true, Some("dir"), 1)]
+    #[case::cycle("loop", true, None, MAX_SYMLINKS)]
+    #[case::outside("outside", true, None, 1)]
+    fn resolve_symlinks(
+        #[case] path: &str,
+        #[case] follow_last: bool,
+        #[case] expected: Option<&str>,
+        #[case] link_count: usize,
+    ) {
+        let mut links = Vec::new();
+        let resolved = resolve(&entries(), path, follow_last, &mut links);
+        assert_eq!(resolved.as_deref(), expected);
+        assert_eq!(links.len(), link_count);
+    }
+}
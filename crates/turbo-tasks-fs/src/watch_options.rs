@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::{glob::Glob, util::sys_to_unix};
+
+/// Configures how [DiskFileSystem::start_watching_with_options] watches for
+/// changes.
+///
+/// [DiskFileSystem::start_watching_with_options]: crate::DiskFileSystem::start_watching_with_options
+#[derive(Clone, Debug)]
+pub struct WatchOptions {
+    /// Time to wait for further events of the same path before reporting a
+    /// change
+    pub debounce: Duration,
+    /// Polls the file system in the given interval instead of using native
+    /// events, for file systems that don't support them, e. g. network
+    /// shares or some container mounts. Events are debounced by the interval
+    /// in this mode.
+    pub poll_interval: Option<Duration>,
+    /// Ignores changes to paths matched by the `.gitignore` files in the root
+    /// and in the directories below it, and to the `.git` directory. Ignored
+    /// directories are not watched at all.
+    ///
+    /// Caveat: Reads of ignored paths are never invalidated, e. g. when
+    /// `node_modules` is ignored and packages are installed.
+    pub gitignore: bool,
+    /// Ignores changes to paths relative to the root that are matched by any
+    /// of the globs, including all paths inside of matched directories. Matched
+    /// directories are not watched at all.
+    pub ignore: Vec<Glob>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(1),
+            poll_interval: None,
+            gitignore: false,
+            ignore: Vec::new(),
+        }
+    }
+}
+
+impl WatchOptions {
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    pub fn with_polling(mut self, interval: Duration) -> Self {
+        self.poll_interval = Some(interval);
+        self
+    }
+
+    pub fn with_gitignore(mut self) -> Self {
+        self.gitignore = true;
+        self
+    }
+
+    pub fn with_ignore(mut self, glob: &str) -> Result<Self> {
+        self.ignore.push(Glob::parse(glob)?);
+        Ok(self)
+    }
+}
+
+/// Decides which paths are watched and which events of the watcher are
+/// ignored
+pub(crate) struct WatchFilter {
+    root: PathBuf,
+    gitignore: bool,
+    /// The rules of the `.gitignore` files that have been read, by the
+    /// directory they are in
+    gitignores: HashMap<PathBuf, Gitignore>,
+    ignore: Vec<Glob>,
+}
+
+impl WatchFilter {
+    pub fn new(root: &str, options: &WatchOptions) -> Self {
+        Self {
+            root: PathBuf::from(root),
+            gitignore: options.gitignore,
+            gitignores: HashMap::new(),
+            ignore: options.ignore.clone(),
+        }
+    }
+
+    /// When nothing is ignored, the root can be watched recursively instead
+    /// of watching every directory on its own.
+    pub fn ignores_nothing(&self) -> bool {
+        !self.gitignore && self.ignore.is_empty()
+    }
+
+    /// Whether the path is a `.gitignore` file that is read by the filter
+    pub fn is_gitignore_file(&self, path: &Path) -> bool {
+        self.gitignore
+            && path.starts_with(&self.root)
+            && path.file_name() == Some(OsStr::new(".gitignore"))
+    }
+
+    /// (Re)reads the `.gitignore` file in the directory
+    pub fn load_gitignore(&mut self, dir: &Path) -> Result<()> {
+        if !self.gitignore {
+            return Ok(());
+        }
+        let mut builder = GitignoreBuilder::new(dir);
+        if dir == self.root {
+            builder.add_line(None, "/.git/")?;
+        }
+        let gitignore_path = dir.join(".gitignore");
+        if gitignore_path.exists() {
+            if let Some(err) = builder.add(&gitignore_path) {
+                return Err(err).with_context(|| {
+                    format!("reading ignore rules from {}", gitignore_path.display())
+                });
+            }
+        }
+        let gitignore = builder.build()?;
+        if gitignore.is_empty() {
+            self.gitignores.remove(dir);
+        } else {
+            self.gitignores.insert(dir.to_path_buf(), gitignore);
+        }
+        Ok(())
+    }
+
+    /// Calls `watch` for the directory and all directories inside of it that
+    /// are not ignored, reading their `.gitignore` files on the way. Ignored
+    /// directories, e. g. `node_modules`, are not traversed at all.
+    pub fn scan(&mut self, dir: &Path, watch: &mut impl FnMut(&Path) -> Result<()>) -> Result<()> {
+        self.load_gitignore(dir)?;
+        watch(dir)?;
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            // Removed in the meantime, the watcher reports that
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("reading directory {}", dir.display()))
+            }
+        };
+        for entry in entries {
+            let entry = entry?;
+            // Symlinks are not followed
+            if entry.file_type()?.is_dir() {
+                let path = entry.path();
+                if !self.is_ignored(&path) {
+                    self.scan(&path, watch)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) if relative.as_os_str().is_empty() => return false,
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+        if !self.gitignores.is_empty() {
+            // Removed paths can't be checked for being a directory, but
+            // directory rules still match the paths inside of them
+            let is_dir = path.is_dir();
+            // Rules of nested `.gitignore` files take precedence
+            for dir in path.ancestors().skip(1) {
+                if let Some(gitignore) = self.gitignores.get(dir) {
+                    let matched = gitignore.matched_path_or_any_parents(path, is_dir);
+                    if matched.is_ignore() {
+                        return true;
+                    }
+                    if matched.is_whitelist() {
+                        break;
+                    }
+                }
+                if dir == self.root {
+                    break;
+                }
+            }
+        }
+        if !self.ignore.is_empty() {
+            let relative = relative.to_string_lossy();
+            let relative = sys_to_unix(&relative);
+            let mut current = relative.as_ref();
+            loop {
+                if self.ignore.iter().any(|glob| glob.execute(current)) {
+                    return true;
+                }
+                match current.rsplit_once('/') {
+                    Some((parent, _)) => current = parent,
+                    None => break,
+                }
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use anyhow::Result;
+
+    use super::{WatchFilter, WatchOptions};
+
+    fn create_tree(root: &Path) -> Result<()> {
+        for dir in [".git", "node_modules/pkg", "src/nested", "src/logs", "dist"] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        fs::write(root.join(".gitignore"), "node_modules/\n*.log\n")?;
+        fs::write(root.join("src/.gitignore"), "logs/\n!important.log\n")?;
+        Ok(())
+    }
+
+    fn scan(filter: &mut WatchFilter, root: &Path) -> Result<Vec<PathBuf>> {
+        let mut watched = Vec::new();
+        filter.scan(root, &mut |dir| {
+            watched.push(dir.strip_prefix(root)?.to_path_buf());
+            Ok(())
+        })?;
+        watched.sort();
+        Ok(watched)
+    }
+
+    #[test]
+    fn scan_skips_ignored_dirs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        create_tree(root)?;
+        let options = WatchOptions::default()
+            .with_gitignore()
+            .with_ignore("dist")?;
+        let mut filter = WatchFilter::new(root.to_str().unwrap(), &options);
+        assert!(!filter.ignores_nothing());
+
+        let watched = scan(&mut filter, root)?;
+        assert_eq!(
+            watched,
+            ["", "src", "src/nested"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn nested_gitignore() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        create_tree(root)?;
+        let mut filter = WatchFilter::new(
+            root.to_str().unwrap(),
+            &WatchOptions::default().with_gitignore(),
+        );
+        scan(&mut filter, root)?;
+
+        assert!(filter.is_ignored(&root.join(".git/index")));
+        assert!(filter.is_ignored(&root.join("node_modules/pkg/index.js")));
+        assert!(filter.is_ignored(&root.join("debug.log")));
+        assert!(filter.is_ignored(&root.join("src/logs/today.txt")));
+        assert!(filter.is_ignored(&root.join("src/nested/debug.log")));
+        assert!(!filter.is_ignored(&root.join("src/important.log")));
+        assert!(!filter.is_ignored(&root.join("src/nested/index.js")));
+        // The nested rules don't apply outside of their directory
+        assert!(!filter.is_ignored(&root.join("logs/today.txt")));
+        Ok(())
+    }
+
+    #[test]
+    fn reloads_gitignore() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        create_tree(root)?;
+        let mut filter = WatchFilter::new(
+            root.to_str().unwrap(),
+            &WatchOptions::default().with_gitignore(),
+        );
+        scan(&mut filter, root)?;
+
+        let gitignore = root.join("src/.gitignore");
+        assert!(filter.is_gitignore_file(&gitignore));
+        fs::remove_file(&gitignore)?;
+        filter.load_gitignore(&root.join("src"))?;
+        assert!(!filter.is_ignored(&root.join("src/logs/today.txt")));
+        assert!(filter.is_ignored(&root.join("src/important.log")));
+        Ok(())
+    }
+
+    #[test]
+    fn default_ignores_nothing() {
+        let filter = WatchFilter::new("/root", &WatchOptions::default());
+        assert!(filter.ignores_nothing());
+        assert!(!filter.is_gitignore_file(Path::new("/root/.gitignore")));
+        assert!(!filter.is_ignored(Path::new("/root/node_modules")));
+    }
+}
@@ -0,0 +1,235 @@
+use anyhow::Result;
+use turbo_tasks::TurboTasks;
+use turbo_tasks_fs::{
+    overlay::OverlayFileSystemVc,
+    register,
+    virtual_fs::{VirtualFileSystem, VirtualFileSystemVc},
+    DirectoryContent, File, FileContent, FileContentVc, FileSystemPathVc, FileSystemVc,
+    LinkContent, LinkType,
+};
+use turbo_tasks_memory::MemoryBackend;
+
+async fn content(file: FileContentVc) -> Result<Option<String>> {
+    Ok(match &*file.strongly_consistent().await? {
+        FileContent::Content(file) => Some(file.content().to_str()?.into_owned()),
+        FileContent::NotFound => None,
+    })
+}
+
+async fn names(dir: FileSystemPathVc) -> Result<Option<Vec<String>>> {
+    Ok(match &*dir.read_dir().strongly_consistent().await? {
+        DirectoryContent::Entries(entries) => {
+            let mut names = entries.keys().cloned().collect::<Vec<_>>();
+            names.sort();
+            Some(names)
+        }
+        DirectoryContent::NotFound => None,
+    })
+}
+
+/// Creates an overlay of two virtual file systems, the upper one first
+async fn layers() -> Result<(VirtualFileSystemVc, VirtualFileSystemVc, FileSystemVc)> {
+    let upper = VirtualFileSystemVc::new("upper");
+    let lower = VirtualFileSystemVc::new("lower");
+    let overlay = OverlayFileSystemVc::new(vec![upper.into(), lower.into()]);
+    Ok((upper, lower, overlay.into()))
+}
+
+fn write(vfs: &VirtualFileSystem, files: &[(&str, &str)]) -> Result<()> {
+    for (path, content) in files {
+        vfs.write_file(path, *content)?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn merges_layers() {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(async {
+        let (upper, lower, fs) = layers().await?;
+        write(&*upper.await?, &[("dir/a.txt", "upper a")])?;
+        write(
+            &*lower.await?,
+            &[("dir/a.txt", "lower a"), ("dir/b.txt", "lower b")],
+        )?;
+
+        let dir = fs.root().join("dir");
+        assert_eq!(
+            content(dir.join("a.txt").read()).await?.as_deref(),
+            Some("upper a")
+        );
+        assert_eq!(
+            content(dir.join("b.txt").read()).await?.as_deref(),
+            Some("lower b")
+        );
+        assert_eq!(
+            names(dir).await?,
+            Some(vec!["a.txt".to_string(), "b.txt".to_string()])
+        );
+        assert_eq!(names(fs.root().join("missing")).await?, None);
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn whiteouts_hide_lower_entries() {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(async {
+        let (upper, lower, fs) = layers().await?;
+        write(
+            &*upper.await?,
+            &[("dir/.wh.a.txt", ""), (".wh.removed", "")],
+        )?;
+        write(
+            &*lower.await?,
+            &[
+                ("dir/a.txt", "lower a"),
+                ("dir/b.txt", "lower b"),
+                ("removed/c.txt", "lower c"),
+            ],
+        )?;
+
+        let dir = fs.root().join("dir");
+        assert_eq!(content(dir.join("a.txt").read()).await?, None);
+        assert_eq!(content(dir.join(".wh.a.txt").read()).await?, None);
+        assert_eq!(names(dir).await?, Some(vec!["b.txt".to_string()]));
+        assert_eq!(names(fs.root()).await?, Some(vec!["dir".to_string()]));
+        // Whiteouts of directories hide everything inside of them
+        assert_eq!(content(fs.root().join("removed/c.txt").read()).await?, None);
+        assert_eq!(names(fs.root().join("removed")).await?, None);
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn opaque_directories_hide_lower_contents() {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(async {
+        let (upper, lower, fs) = layers().await?;
+        write(
+            &*upper.await?,
+            &[("dir/.wh..wh..opq", ""), ("dir/a.txt", "upper a")],
+        )?;
+        write(
+            &*lower.await?,
+            &[("dir/b.txt", "lower b"), ("dir/nested/c.txt", "lower c")],
+        )?;
+
+        let dir = fs.root().join("dir");
+        assert_eq!(names(dir).await?, Some(vec!["a.txt".to_string()]));
+        assert_eq!(content(dir.join("b.txt").read()).await?, None);
+        assert_eq!(content(dir.join("nested/c.txt").read()).await?, None);
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn upper_entries_shadow_other_types() {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(async {
+        let (upper, lower, fs) = layers().await?;
+        write(
+            &*upper.await?,
+            &[("dir/file.txt", "upper"), ("file", "upper file")],
+        )?;
+        write(
+            &*lower.await?,
+            &[("dir", "lower file"), ("file/nested.txt", "lower nested")],
+        )?;
+
+        // A directory shadows a file
+        assert_eq!(content(fs.root().join("dir").read()).await?, None);
+        assert_eq!(
+            names(fs.root().join("dir")).await?,
+            Some(vec!["file.txt".to_string()])
+        );
+        // A file shadows a directory
+        assert_eq!(
+            content(fs.root().join("file").read()).await?.as_deref(),
+            Some("upper file")
+        );
+        assert_eq!(names(fs.root().join("file")).await?, None);
+        assert_eq!(
+            content(fs.root().join("file/nested.txt").read()).await?,
+            None
+        );
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn removing_writes_whiteouts() {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(async {
+        let (upper, lower, fs) = layers().await?;
+        write(&*upper.await?, &[("a.txt", "upper a")])?;
+        write(&*lower.await?, &[("a.txt", "lower a")])?;
+
+        let file = fs.root().join("a.txt");
+        file.write(FileContent::NotFound.cell()).await?;
+        assert_eq!(content(file.read()).await?, None);
+        assert_eq!(
+            content(upper.as_file_system().root().join(".wh.a.txt").read())
+                .await?
+                .as_deref(),
+            Some("")
+        );
+
+        file.write(FileContent::Content(File::from("new")).cell())
+            .await?;
+        assert_eq!(content(file.read()).await?.as_deref(), Some("new"));
+        assert_eq!(
+            content(upper.as_file_system().root().join(".wh.a.txt").read()).await?,
+            None
+        );
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn writing_links_removes_whiteouts() {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(async {
+        let (upper, lower, fs) = layers().await?;
+        write(&*lower.await?, &[("link", "lower link")])?;
+        let whiteout = upper.as_file_system().root().join(".wh.link");
+
+        let link = fs.root().join("link");
+        link.write_link(LinkContent::NotFound.cell()).await?;
+        assert_eq!(content(whiteout.read()).await?.as_deref(), Some(""));
+        assert_eq!(content(link.read()).await?, None);
+
+        link.write_link(
+            LinkContent::Link {
+                target: "a.txt".to_string(),
+                link_type: LinkType::UNSET,
+            }
+            .cell(),
+        )
+        .await?;
+        assert_eq!(content(whiteout.read()).await?, None);
+        assert!(matches!(
+            &*link.read_link().strongly_consistent().await?,
+            LinkContent::Link { target, .. } if target == "a.txt"
+        ));
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
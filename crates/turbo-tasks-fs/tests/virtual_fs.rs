@@ -0,0 +1,104 @@
+use anyhow::Result;
+use turbo_tasks::TurboTasks;
+use turbo_tasks_fs::{
+    register, virtual_fs::VirtualFileSystemVc, DirectoryContent, FileContent, FileContentVc,
+    FileSystemVc, LinkType,
+};
+use turbo_tasks_memory::MemoryBackend;
+
+async fn content(file: FileContentVc) -> Result<Option<String>> {
+    Ok(match &*file.strongly_consistent().await? {
+        FileContent::Content(file) => Some(file.content().to_str()?.into_owned()),
+        FileContent::NotFound => None,
+    })
+}
+
+#[tokio::test]
+async fn invalidates_read_on_write() {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(async {
+        let vfs = VirtualFileSystemVc::new("test");
+        let fs: FileSystemVc = vfs.into();
+        let file = fs.root().join("dir/file.txt");
+        assert_eq!(content(file.read()).await?, None);
+
+        vfs.await?.write_file("dir/file.txt", "first")?;
+        assert_eq!(content(file.read()).await?.as_deref(), Some("first"));
+
+        vfs.await?.write_file("dir/file.txt", "second")?;
+        assert_eq!(content(file.read()).await?.as_deref(), Some("second"));
+
+        vfs.await?.remove("dir")?;
+        assert_eq!(content(file.read()).await?, None);
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn invalidates_read_dir() {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(async {
+        let vfs = VirtualFileSystemVc::new("test");
+        let fs: FileSystemVc = vfs.into();
+        let dir = fs.root().join("dir");
+        let names = || async move {
+            Ok::<_, anyhow::Error>(match &*dir.read_dir().strongly_consistent().await? {
+                DirectoryContent::Entries(entries) => {
+                    let mut names = entries.keys().cloned().collect::<Vec<_>>();
+                    names.sort();
+                    Some(names)
+                }
+                DirectoryContent::NotFound => None,
+            })
+        };
+        assert_eq!(names().await?, None);
+
+        vfs.await?.write_file("dir/a.txt", "a")?;
+        assert_eq!(names().await?, Some(vec!["a.txt".to_string()]));
+
+        vfs.await?.write_file("dir/nested/b.txt", "b")?;
+        assert_eq!(
+            names().await?,
+            Some(vec!["a.txt".to_string(), "nested".to_string()])
+        );
+
+        vfs.await?.remove("dir/a.txt")?;
+        assert_eq!(names().await?, Some(vec!["nested".to_string()]));
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn invalidates_reads_through_symlinks() {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(async {
+        let vfs = VirtualFileSystemVc::new("test");
+        let fs: FileSystemVc = vfs.into();
+        vfs.await?.write_file("a/file.txt", "a")?;
+        vfs.await?.write_file("b/file.txt", "b")?;
+        vfs.await?.symlink("link", "a", LinkType::DIRECTORY)?;
+        let file = fs.root().join("link/file.txt");
+        assert_eq!(content(file.read()).await?.as_deref(), Some("a"));
+
+        // Changing the target content invalidates the read
+        vfs.await?.write_file("a/file.txt", "changed")?;
+        assert_eq!(content(file.read()).await?.as_deref(), Some("changed"));
+
+        // Retargeting the link invalidates the read as well
+        vfs.await?.symlink("link", "b", LinkType::DIRECTORY)?;
+        assert_eq!(content(file.read()).await?.as_deref(), Some("b"));
+
+        vfs.await?.remove("link")?;
+        assert_eq!(content(file.read()).await?, None);
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use anyhow::Result;
+use bincode::Options;
+use turbo_tasks::{backend::PersistentTaskType, persisted_graph::TaskData, RawVc};
+
+use crate::state::{TaskState, TaskStateChange};
+
+/// Serializes a task type in a way that the serialization of a task type
+/// without inputs is a prefix of the serialization of the same task type with
+/// inputs.
+pub fn task_type_to_bytes(ty: &PersistentTaskType) -> Result<Vec<u8>, bincode::Error> {
+    let mut result = Vec::new();
+    let opt = bincode::DefaultOptions::new();
+    let inputs = match ty {
+        PersistentTaskType::Native(f, i) => {
+            result.push(0);
+            opt.serialize_into(&mut result, f)?;
+            i
+        }
+        PersistentTaskType::ResolveNative(f, i) => {
+            result.push(1);
+            opt.serialize_into(&mut result, f)?;
+            i
+        }
+        PersistentTaskType::ResolveTrait(t, n, i) => {
+            result.push(2);
+            opt.serialize_into(&mut result, t)?;
+            opt.serialize_into(&mut result, n)?;
+            i
+        }
+    };
+    for input in inputs {
+        opt.serialize_into(&mut result, input)?;
+    }
+    Ok(result)
+}
+
+/// Collects writes to a [KvDatabase], which are applied atomically.
+pub trait KvBatch {
+    fn write(&mut self) -> Result<()>;
+
+    /// Discards all writes. The batch must not be used afterwards.
+    fn cancel(&mut self);
+}
+
+/// Tables that store a set of task ids
+#[derive(Clone, Copy, Debug)]
+pub enum TaskSet {
+    ExternallyActive,
+    PotentialActiveExternal,
+    PotentialDirtyActive,
+    PendingActiveUpdate,
+}
+
+/// The key-value tables a [KvPersistedGraph](crate::KvPersistedGraph) is
+/// stored in. Tasks are identified by ids of the database, which are never 0.
+///
+/// Reads don't see the writes of batches that haven't been written yet.
+pub trait KvDatabase: Sized + Send + Sync {
+    type Batch: KvBatch;
+
+    /// Opens the database at `path`. The database starts empty when it has
+    /// been written by a different `version`, or when the last session hasn't
+    /// been closed cleanly. In that case tasks might be persisted partially,
+    /// e. g. without their dependencies, so the database can't be trusted.
+    fn open(path: &Path, version: &str) -> Result<Self>;
+
+    fn batch(&self) -> Self::Batch;
+
+    /// Marks the database as closed cleanly. It must not be written
+    /// afterwards.
+    fn close(&self) -> Result<()>;
+
+    fn get_last_task_id(&self) -> Result<usize>;
+    fn increment_last_task_id(&self, batch: &mut Self::Batch) -> Result<()>;
+
+    fn get_task_type(&self, task: usize) -> Result<Option<PersistentTaskType>>;
+    fn write_task_type(
+        &self,
+        batch: &mut Self::Batch,
+        task: usize,
+        ty: &PersistentTaskType,
+    ) -> Result<()>;
+
+    /// Looks up a task by its serialized task type, see [task_type_to_bytes].
+    fn get_cache(&self, key: &[u8]) -> Result<Option<usize>>;
+    /// Returns up to `limit` tasks whose serialized task type starts with
+    /// `prefix`. The boolean is true when all tasks have been returned.
+    fn get_cache_prefix(&self, prefix: &[u8], limit: usize) -> Result<(Vec<usize>, bool)>;
+    fn write_cache(&self, batch: &mut Self::Batch, key: &[u8], task: usize) -> Result<()>;
+
+    fn get_state(&self, task: usize) -> Result<Option<TaskState>>;
+    fn merge_state(
+        &self,
+        batch: &mut Self::Batch,
+        task: usize,
+        change: TaskStateChange,
+    ) -> Result<()>;
+
+    fn get_data(&self, task: usize) -> Result<Option<TaskData>>;
+    /// Fails when the data can't be serialized.
+    fn write_data(&self, batch: &mut Self::Batch, task: usize, data: &TaskData) -> Result<()>;
+
+    fn get_children(&self, task: usize) -> Result<Option<Vec<usize>>>;
+    fn write_children(
+        &self,
+        batch: &mut Self::Batch,
+        task: usize,
+        children: &[usize],
+    ) -> Result<()>;
+
+    fn get_dependencies(&self, task: usize) -> Result<Option<Vec<RawVc>>>;
+    fn write_dependencies(
+        &self,
+        batch: &mut Self::Batch,
+        task: usize,
+        dependencies: &[RawVc],
+    ) -> Result<()>;
+
+    fn get_dependents(&self, vc: &RawVc) -> Result<Vec<usize>>;
+    fn insert_dependent(&self, batch: &mut Self::Batch, vc: &RawVc, task: usize) -> Result<()>;
+    fn remove_dependent(&self, batch: &mut Self::Batch, vc: &RawVc, task: usize) -> Result<()>;
+
+    fn get_set(&self, set: TaskSet) -> Result<Vec<usize>>;
+    fn insert_into_set(&self, batch: &mut Self::Batch, set: TaskSet, task: usize) -> Result<()>;
+    fn remove_from_set(&self, batch: &mut Self::Batch, set: TaskSet, task: usize) -> Result<()>;
+
+    /// Stores when the task has been used the last time, in seconds since the
+    /// unix epoch.
+    fn write_last_used(&self, batch: &mut Self::Batch, task: usize, time: u64) -> Result<()>;
+}
@@ -0,0 +1,11 @@
+//! A [PersistedGraph](turbo_tasks::persisted_graph::PersistedGraph) that is
+//! stored in the key-value tables of a [KvDatabase]. The databases are
+//! implemented by `turbo-tasks-rocksdb` and `turbo-tasks-logdb`.
+
+mod database;
+mod persisted_graph;
+mod state;
+
+pub use database::{task_type_to_bytes, KvBatch, KvDatabase, TaskSet};
+pub use persisted_graph::{KvPersistedGraph, Stats};
+pub use state::{InternalTaskState, TaskState, TaskStateChange};
@@ -0,0 +1,774 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+use bincode::Options;
+use flurry::HashMap;
+use parking_lot::Mutex;
+use turbo_tasks::{
+    backend::PersistentTaskType,
+    persisted_graph::{
+        ActivateResult, DeactivateResult, PersistResult, PersistTaskState, PersistedGraph,
+        PersistedGraphApi, ReadTaskState, TaskCell, TaskData,
+    },
+    util::{NoMoveVec, SharedError},
+    with_task_id_mapping, FunctionId, IdMapping, RawVc, TaskId,
+};
+
+use crate::{
+    database::{task_type_to_bytes, KvBatch, KvDatabase, TaskSet},
+    state::{InternalTaskState, TaskState, TaskStateChange},
+};
+
+#[derive(Default, Debug)]
+pub struct Stats {
+    lookups: AtomicUsize,
+    reads: AtomicUsize,
+    activates: AtomicUsize,
+    deactivates: AtomicUsize,
+    persists: AtomicUsize,
+    cleans: AtomicUsize,
+    dependent_dirty: AtomicUsize,
+    dirties: AtomicUsize,
+    flaggings: AtomicUsize,
+}
+
+const AC_UNKNOWN: u8 = 0;
+const AC_ACTIVE: u8 = 1;
+const AC_INACTIVE: u8 = 2;
+
+/// A [PersistedGraph] stored in the tables of a [KvDatabase].
+pub struct KvPersistedGraph<D: KvDatabase> {
+    database: D,
+    task_id_forward_mapping: HashMap<TaskId, usize>,
+    task_id_backward_mapping: HashMap<usize, TaskId>,
+    last_task_id: AtomicUsize,
+    #[cfg(feature = "unsafe_once_map")]
+    cache_once: turbo_tasks::util::OnceConcurrentlyMap<[u8], Result<usize, SharedError>>,
+    #[cfg(not(feature = "unsafe_once_map"))]
+    cache_once: turbo_tasks::util::SafeOnceConcurrentlyMap<Vec<u8>, Result<usize, SharedError>>,
+    stats: Stats,
+    /// AC_UNKNOWN | AC_ACTIVE | AC_INACTIVE
+    active_cache: NoMoveVec<AtomicU8>,
+    /// Tasks that have been read or persisted in this session. Their
+    /// `last_used` time is updated when stopping.
+    used_tasks: flurry::HashSet<usize>,
+    /// Externally active tasks that have been marked again in this session
+    renewed_externally_active: Mutex<HashSet<usize>>,
+}
+
+impl<D: KvDatabase> KvPersistedGraph<D> {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_version(path, "")
+    }
+
+    /// Opens the database at `path`, see [KvDatabase::open].
+    pub fn with_version<P: AsRef<Path>>(path: P, version: &str) -> Result<Self> {
+        let db = D::open(path.as_ref(), version)?;
+        let last_id = db.get_last_task_id()?;
+        Ok(Self {
+            database: db,
+            task_id_forward_mapping: HashMap::new(),
+            task_id_backward_mapping: HashMap::new(),
+            last_task_id: AtomicUsize::new(last_id),
+            #[cfg(feature = "unsafe_once_map")]
+            cache_once: turbo_tasks::util::OnceConcurrentlyMap::new(),
+            #[cfg(not(feature = "unsafe_once_map"))]
+            cache_once: turbo_tasks::util::SafeOnceConcurrentlyMap::new(),
+            stats: Stats::default(),
+            active_cache: NoMoveVec::new_init_default(),
+            used_tasks: flurry::HashSet::new(),
+            renewed_externally_active: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Counts the operations of this session, can be printed with `{:#?}`.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn get_task_type(&self, ty: &PersistentTaskType) -> Result<Option<usize>> {
+        self.database.get_cache(&task_type_to_bytes(ty)?)
+    }
+
+    fn get_or_create_task_type(&self, ty: &PersistentTaskType) -> Result<usize> {
+        let db = &self.database;
+        let ty_bytes = task_type_to_bytes(ty)?;
+        if let Some(db_task) = db.get_cache(&ty_bytes)? {
+            return Ok(db_task);
+        }
+        Ok(self.cache_once.action(&ty_bytes, || {
+            if let Some(db_task) = db.get_cache(&ty_bytes)? {
+                return Ok(db_task);
+            }
+            // 0 is used for tasks that are not in the graph
+            let db_task = self.last_task_id.fetch_add(1, Ordering::Relaxed) + 1;
+            let b = &mut db.batch();
+            db.increment_last_task_id(b)?;
+            db.write_task_type(b, db_task, ty)?;
+            b.write()?;
+            // Need to write it in two steps due to unordered writes
+            // Once it's in "cache" it can be discovered by lookups
+            let b = &mut db.batch();
+            db.write_cache(b, &ty_bytes, db_task)?;
+            b.write()?;
+            Ok(db_task)
+        })?)
+    }
+
+    fn lookup_task_type(&self, id: usize) -> Result<PersistentTaskType> {
+        self.database
+            .get_task_type(id)?
+            .ok_or_else(|| anyhow!("Invalid task id {}", id))
+    }
+
+    fn get_active(&self, db_task: usize) -> Result<bool> {
+        let ac = self.active_cache.get_init_default(db_task);
+        let ac_value = ac.load(Ordering::Acquire);
+        if ac_value != AC_UNKNOWN {
+            return Ok(ac_value == AC_ACTIVE);
+        }
+        if let Some(TaskState { active, .. }) = self.database.get_state(db_task)? {
+            self.set_active_cache(db_task, active);
+            return Ok(active);
+        }
+        Ok(false)
+    }
+
+    fn set_active_cache(&self, db_task: usize, active: bool) {
+        self.active_cache.get_init_default(db_task).store(
+            if active { AC_ACTIVE } else { AC_INACTIVE },
+            Ordering::Release,
+        );
+    }
+}
+
+impl<D: KvDatabase> PersistedGraph for KvPersistedGraph<D> {
+    fn read(
+        &self,
+        task: TaskId,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<(TaskData, ReadTaskState)>> {
+        let mapping = PgApiReadOnlyMapping::new(self, api);
+        let db_task = mapping.forward(task);
+        if db_task == 0 {
+            return Ok(None);
+        }
+        self.stats.reads.fetch_add(1, Ordering::Relaxed);
+        with_task_id_mapping(&mapping, || {
+            let db = &self.database;
+            if let Some(data) = db.get_data(db_task)? {
+                if let Some(TaskState {
+                    internal: Some(InternalTaskState { clean }),
+                    active_parents,
+                    ..
+                }) = db.get_state(db_task)?
+                {
+                    self.used_tasks.pin().insert(db_task);
+                    return Ok(Some((
+                        data,
+                        ReadTaskState {
+                            clean,
+                            keeps_external_active: active_parents > 0,
+                        },
+                    )));
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    fn is_persisted(&self, task: TaskId, api: &dyn PersistedGraphApi) -> Result<bool> {
+        let db_task = PgApiReadOnlyMapping::new(self, api).forward(task);
+        if db_task == 0 {
+            return Ok(false);
+        }
+        Ok(matches!(
+            self.database.get_state(db_task)?,
+            Some(TaskState {
+                internal: Some(_),
+                ..
+            })
+        ))
+    }
+
+    fn lookup_one(
+        &self,
+        task_type: &PersistentTaskType,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<TaskId>> {
+        self.stats.lookups.fetch_add(1, Ordering::Relaxed);
+        let mapping = PgApiReadOnlyMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let db = &self.database;
+            if let Some(db_task) = db.get_cache(&task_type_to_bytes(task_type)?)? {
+                if let Some(TaskState {
+                    internal: Some(_), ..
+                }) = db.get_state(db_task)?
+                {
+                    return Ok(Some(mapping.backward(db_task)));
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    fn lookup(
+        &self,
+        partial_task_type: &PersistentTaskType,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<bool> {
+        self.stats.lookups.fetch_add(1, Ordering::Relaxed);
+        let mapping = PgApiReadOnlyMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let (db_tasks, complete) = self
+                .database
+                .get_cache_prefix(&task_type_to_bytes(partial_task_type)?, 1000)?;
+            for db_task in db_tasks {
+                mapping.backward(db_task);
+            }
+            Ok(complete)
+        })
+    }
+
+    fn persist(
+        &self,
+        task: TaskId,
+        mut data: TaskData,
+        state: PersistTaskState,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<PersistResult>> {
+        self.stats.persists.fetch_add(1, Ordering::Relaxed);
+        let mapping = PgApiMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let db_task = mapping.forward(task);
+            let db = &self.database;
+            let mut tasks_to_deactivate = Vec::new();
+            let mut tasks_to_activate = Vec::new();
+            let b = &mut db.batch();
+            if self.get_active(db_task)? {
+                // Task is and will stay active
+                if let Some(old_children) = db.get_children(db_task)? {
+                    // There are existing children that are active
+                    // We need to compute a diff and update the active parents counts
+                    let mut removed_children = old_children.into_iter().collect::<HashSet<_>>();
+                    for child in data.children.iter() {
+                        let db_child = mapping.forward(*child);
+                        if !removed_children.remove(&db_child) {
+                            db.merge_state(
+                                b,
+                                db_child,
+                                TaskStateChange::IncrementActiveParents(1),
+                            )?;
+                            tasks_to_activate.push(*child);
+                            db.insert_into_set(b, TaskSet::PendingActiveUpdate, db_child)?;
+                        }
+                    }
+                    for db_child in removed_children {
+                        db.merge_state(b, db_child, TaskStateChange::DecrementActiveParents(1))?;
+                        tasks_to_deactivate.push(mapping.backward(db_child));
+                        db.insert_into_set(b, TaskSet::PendingActiveUpdate, db_child)?;
+                    }
+                } else {
+                    // Task had no children before
+                    // No need for expensive diffing
+                    for child in data.children.iter() {
+                        let db_child = mapping.forward(*child);
+                        db.merge_state(b, db_child, TaskStateChange::IncrementActiveParents(1))?;
+                        tasks_to_activate.push(*child);
+                        db.insert_into_set(b, TaskSet::PendingActiveUpdate, db_child)?;
+                    }
+                }
+            } else if state.externally_active {
+                // Task was not active before, but will activate with this operation
+                for child in data.children.iter() {
+                    let db_child = mapping.forward(*child);
+                    db.merge_state(b, db_child, TaskStateChange::IncrementActiveParents(1))?;
+                    tasks_to_activate.push(*child);
+                    db.insert_into_set(b, TaskSet::PendingActiveUpdate, db_child)?;
+                }
+            }
+            db.merge_state(
+                b,
+                db_task,
+                TaskStateChange::Persist(state.externally_active),
+            )?;
+            for cell in data.cells.iter_mut() {
+                if let TaskCell::Content(ref c) = cell {
+                    // TODO we can avoid double serialization
+                    // by having a custom Serialize impl on TaskCell
+                    if bincode::DefaultOptions::new().serialize(c).is_err() {
+                        *cell = TaskCell::NeedComputation;
+                    }
+                }
+            }
+            if db.write_data(b, db_task, &data).is_err() {
+                b.cancel();
+                return Ok(None);
+            }
+            let children = data
+                .children
+                .iter()
+                .map(|c| mapping.forward(*c))
+                .collect::<Vec<_>>();
+            db.write_children(b, db_task, &children)?;
+            if let Some(old_dependencies) = db.get_dependencies(db_task)? {
+                // There are existing dependencies, we need to compute a diff
+                let mut removed_dependencies = old_dependencies.into_iter().collect::<HashSet<_>>();
+                for dep in data.dependencies.iter() {
+                    if !removed_dependencies.remove(dep) {
+                        db.insert_dependent(b, dep, db_task)?;
+                    }
+                }
+                for dep in removed_dependencies {
+                    db.remove_dependent(b, &dep, db_task)?;
+                }
+            } else {
+                for dep in data.dependencies.iter() {
+                    db.insert_dependent(b, dep, db_task)?;
+                }
+            }
+            db.write_dependencies(b, db_task, &data.dependencies)?;
+            db.remove_from_set(b, TaskSet::PendingActiveUpdate, db_task)?;
+            b.write()?;
+            if state.externally_active {
+                self.set_active_cache(db_task, true);
+            }
+            self.used_tasks.pin().insert(db_task);
+            Ok(Some(PersistResult {
+                tasks_to_activate,
+                tasks_to_deactivate,
+            }))
+        })
+    }
+
+    fn activate_when_needed(
+        &self,
+        task: TaskId,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<ActivateResult>> {
+        self.stats.activates.fetch_add(1, Ordering::Relaxed);
+        let mapping = PgApiMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let db_task = mapping.forward(task);
+            let db = &self.database;
+            let b = &mut db.batch();
+            db.remove_from_set(b, TaskSet::PendingActiveUpdate, db_task)?;
+            if let Some(TaskState {
+                internal,
+                active,
+                active_parents,
+                externally_active,
+            }) = db.get_state(db_task)?
+            {
+                if !active && (active_parents > 0 || externally_active) {
+                    let children = db.get_children(db_task)?.unwrap_or_default();
+                    let mut more_tasks_to_activate = Vec::new();
+                    for db_child in children {
+                        db.merge_state(b, db_child, TaskStateChange::IncrementActiveParents(1))?;
+                        more_tasks_to_activate.push(mapping.backward(db_child));
+                        db.insert_into_set(b, TaskSet::PendingActiveUpdate, db_child)?;
+                    }
+                    db.merge_state(b, db_task, TaskStateChange::Activate)?;
+                    if let Some(InternalTaskState { clean: false }) = internal {
+                        db.insert_into_set(b, TaskSet::PotentialDirtyActive, db_task)?;
+                    }
+                    if internal.is_none() {
+                        db.insert_into_set(b, TaskSet::PotentialActiveExternal, db_task)?;
+                    }
+                    b.write()?;
+                    self.set_active_cache(db_task, true);
+                    return Ok(Some(ActivateResult {
+                        keeps_external_active: active_parents > 0,
+                        external: internal.is_none(),
+                        dirty: internal.map(|i| !i.clean).unwrap_or_default(),
+                        more_tasks_to_activate,
+                    }));
+                }
+            }
+            b.write()?;
+            Ok(None)
+        })
+    }
+
+    fn deactivate_when_needed(
+        &self,
+        task: TaskId,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<DeactivateResult>> {
+        self.stats.deactivates.fetch_add(1, Ordering::Relaxed);
+        let mapping = PgApiMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let db_task = mapping.forward(task);
+            let db = &self.database;
+            let b = &mut db.batch();
+            db.remove_from_set(b, TaskSet::PendingActiveUpdate, db_task)?;
+            if let Some(TaskState {
+                active,
+                active_parents,
+                externally_active,
+                ..
+            }) = db.get_state(db_task)?
+            {
+                if active && active_parents == 0 && !externally_active {
+                    let children = db.get_children(db_task)?.unwrap_or_default();
+                    let mut more_tasks_to_deactivate = Vec::new();
+                    for db_child in children {
+                        db.merge_state(b, db_child, TaskStateChange::DecrementActiveParents(1))?;
+                        more_tasks_to_deactivate.push(mapping.backward(db_child));
+                        db.insert_into_set(b, TaskSet::PendingActiveUpdate, db_child)?;
+                    }
+                    db.merge_state(b, db_task, TaskStateChange::Deactivate)?;
+                    db.remove_from_set(b, TaskSet::PotentialDirtyActive, db_task)?;
+                    b.write()?;
+                    self.set_active_cache(db_task, false);
+                    return Ok(Some(DeactivateResult {
+                        more_tasks_to_deactivate,
+                    }));
+                }
+            }
+            b.write()?;
+            Ok(None)
+        })
+    }
+
+    fn set_externally_active(&self, task: TaskId, api: &dyn PersistedGraphApi) -> Result<bool> {
+        self.stats.flaggings.fetch_add(1, Ordering::Relaxed);
+        let mapping = PgApiMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let db_task = mapping.forward(task);
+            let db = &self.database;
+            let b = &mut db.batch();
+            db.merge_state(b, db_task, TaskStateChange::SetExternallyActive)?;
+            db.insert_into_set(b, TaskSet::ExternallyActive, db_task)?;
+            b.write()?;
+            self.renewed_externally_active.lock().insert(db_task);
+            if let Some(TaskState {
+                active,
+                active_parents,
+                ..
+            }) = db.get_state(db_task)?
+            {
+                self.set_active_cache(db_task, active);
+                return Ok(!active && active_parents == 0);
+            }
+            Ok(false)
+        })
+    }
+
+    fn unset_externally_active(&self, task: TaskId, api: &dyn PersistedGraphApi) -> Result<bool> {
+        self.stats.flaggings.fetch_add(1, Ordering::Relaxed);
+        let mapping = PgApiMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let db_task = mapping.forward(task);
+            let db = &self.database;
+            let b = &mut db.batch();
+            db.merge_state(b, db_task, TaskStateChange::UnsetExternallyActive)?;
+            db.remove_from_set(b, TaskSet::ExternallyActive, db_task)?;
+            b.write()?;
+            self.renewed_externally_active.lock().remove(&db_task);
+            if let Some(TaskState {
+                active,
+                active_parents,
+                ..
+            }) = db.get_state(db_task)?
+            {
+                self.set_active_cache(db_task, active);
+                return Ok(active && active_parents == 0);
+            }
+            Ok(false)
+        })
+    }
+
+    fn remove_outdated_externally_active(
+        &self,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Vec<TaskId>> {
+        let mapping = PgApiReadOnlyMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let db = &self.database;
+            let renewed = self.renewed_externally_active.lock();
+            let mut result = Vec::new();
+            let b = &mut db.batch();
+            for db_task in db.get_set(TaskSet::ExternallyActive)? {
+                if renewed.contains(&db_task) {
+                    continue;
+                }
+                db.merge_state(b, db_task, TaskStateChange::UnsetExternallyActive)?;
+                db.remove_from_set(b, TaskSet::ExternallyActive, db_task)?;
+                if let Some(TaskState {
+                    active: true,
+                    active_parents: 0,
+                    ..
+                }) = db.get_state(db_task)?
+                {
+                    result.push(mapping.backward(db_task));
+                }
+            }
+            b.write()?;
+            Ok(result)
+        })
+    }
+
+    fn make_dirty(&self, task: TaskId, api: &dyn PersistedGraphApi) -> Result<bool> {
+        self.stats.dirties.fetch_add(1, Ordering::Relaxed);
+        let mapping = PgApiMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let db_task = mapping.forward(task);
+            let db = &self.database;
+            let b = &mut db.batch();
+            db.merge_state(b, db_task, TaskStateChange::MakeDirty)?;
+            db.insert_into_set(b, TaskSet::PotentialDirtyActive, db_task)?;
+            b.write()?;
+            self.get_active(db_task)
+        })
+    }
+
+    fn make_dependent_dirty(&self, vc: RawVc, api: &dyn PersistedGraphApi) -> Result<Vec<TaskId>> {
+        let mapping = PgApiReadOnlyMapping::new(self, api);
+        let db_task = mapping.forward(vc.get_task_id());
+        if db_task == 0 {
+            return Ok(Vec::new());
+        }
+        self.stats.dependent_dirty.fetch_add(1, Ordering::Relaxed);
+        with_task_id_mapping(&mapping, || {
+            let db = &self.database;
+            let b = &mut db.batch();
+            let tasks = db.get_dependents(&vc)?;
+            for db_task in tasks.iter() {
+                db.merge_state(b, *db_task, TaskStateChange::MakeDirty)?;
+                db.insert_into_set(b, TaskSet::PotentialDirtyActive, *db_task)?;
+            }
+            b.write()?;
+            let mut result = Vec::new();
+            for db_task in tasks {
+                if self.get_active(db_task)? {
+                    result.push(mapping.backward(db_task));
+                }
+            }
+            Ok(result)
+        })
+    }
+
+    fn get_function_tasks(
+        &self,
+        function: FunctionId,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Vec<TaskId>> {
+        let mapping = PgApiReadOnlyMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let prefix = task_type_to_bytes(&PersistentTaskType::Native(function, Vec::new()))?;
+            let (db_tasks, _) = self.database.get_cache_prefix(&prefix, usize::MAX)?;
+            Ok(db_tasks
+                .into_iter()
+                .map(|db_task| mapping.backward(db_task))
+                .collect())
+        })
+    }
+
+    fn make_clean(&self, task: TaskId, api: &dyn PersistedGraphApi) -> Result<()> {
+        let mapping = PgApiReadOnlyMapping::new(self, api);
+        let db_task = mapping.forward(task);
+        if db_task == 0 {
+            return Ok(());
+        }
+        self.stats.cleans.fetch_add(1, Ordering::Relaxed);
+        with_task_id_mapping(mapping, || {
+            let db = &self.database;
+            let b = &mut db.batch();
+            db.merge_state(b, db_task, TaskStateChange::MakeClean)?;
+            db.remove_from_set(b, TaskSet::PotentialDirtyActive, db_task)?;
+            b.write()
+        })
+    }
+
+    fn get_active_external_tasks(&self, api: &dyn PersistedGraphApi) -> Result<Vec<TaskId>> {
+        // For startup
+        let mapping = PgApiReadOnlyMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let mut result = Vec::new();
+            let db = &self.database;
+            let b = &mut db.batch();
+            for db_task in db.get_set(TaskSet::PotentialActiveExternal)? {
+                if let Some(TaskState {
+                    internal, active, ..
+                }) = db.get_state(db_task)?
+                {
+                    self.set_active_cache(db_task, active);
+                    if internal.is_none() && active {
+                        result.push(mapping.backward(db_task));
+                        continue;
+                    }
+                }
+                db.remove_from_set(b, TaskSet::PotentialActiveExternal, db_task)?;
+            }
+            b.write()?;
+            Ok(result)
+        })
+    }
+
+    fn get_dirty_active_tasks(&self, api: &dyn PersistedGraphApi) -> Result<Vec<TaskId>> {
+        // For startup
+        let mapping = PgApiReadOnlyMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let mut result = Vec::new();
+            let db = &self.database;
+            let b = &mut db.batch();
+            for db_task in db.get_set(TaskSet::PotentialDirtyActive)? {
+                if let Some(TaskState {
+                    internal, active, ..
+                }) = db.get_state(db_task)?
+                {
+                    self.set_active_cache(db_task, active);
+                    if matches!(internal, Some(InternalTaskState { clean: false })) && active {
+                        result.push(mapping.backward(db_task));
+                        continue;
+                    }
+                }
+                db.remove_from_set(b, TaskSet::PotentialDirtyActive, db_task)?;
+            }
+            b.write()?;
+            Ok(result)
+        })
+    }
+
+    fn get_pending_active_update(
+        &self,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<(Vec<TaskId>, Vec<TaskId>)> {
+        // For startup
+        let mapping = PgApiReadOnlyMapping::new(self, api);
+        with_task_id_mapping(&mapping, || {
+            let mut tasks_to_activate = Vec::new();
+            let mut tasks_to_deactivate = Vec::new();
+            let db = &self.database;
+            let b = &mut db.batch();
+            for db_task in db.get_set(TaskSet::PendingActiveUpdate)? {
+                if let Some(TaskState {
+                    active_parents,
+                    externally_active,
+                    active,
+                    ..
+                }) = db.get_state(db_task)?
+                {
+                    self.set_active_cache(db_task, active);
+                    if !active && (active_parents > 0 || externally_active) {
+                        tasks_to_activate.push(mapping.backward(db_task));
+                        continue;
+                    }
+                    if active && active_parents == 0 && !externally_active {
+                        tasks_to_deactivate.push(mapping.backward(db_task));
+                        continue;
+                    }
+                }
+                db.remove_from_set(b, TaskSet::PendingActiveUpdate, db_task)?;
+            }
+            b.write()?;
+            Ok((tasks_to_activate, tasks_to_deactivate))
+        })
+    }
+
+    fn stop(&self, _api: &dyn PersistedGraphApi) -> Result<()> {
+        let db = &self.database;
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        let b = &mut db.batch();
+        for db_task in self.used_tasks.pin().iter() {
+            db.write_last_used(b, *db_task, now)?;
+        }
+        b.write()?;
+        // All writes are done at this point, so the next session can trust the
+        // database
+        db.close()
+    }
+}
+
+struct PgApiMapping<'a, D: KvDatabase> {
+    this: &'a KvPersistedGraph<D>,
+    task_id_forward_mapping: flurry::HashMapRef<'a, TaskId, usize>,
+    task_id_backward_mapping: flurry::HashMapRef<'a, usize, TaskId>,
+    api: &'a dyn PersistedGraphApi,
+}
+
+impl<'a, D: KvDatabase> PgApiMapping<'a, D> {
+    fn new(this: &'a KvPersistedGraph<D>, api: &'a dyn PersistedGraphApi) -> Self {
+        Self {
+            this,
+            api,
+            task_id_backward_mapping: this.task_id_backward_mapping.pin(),
+            task_id_forward_mapping: this.task_id_forward_mapping.pin(),
+        }
+    }
+}
+
+impl<'a, D: KvDatabase> IdMapping<TaskId> for PgApiMapping<'a, D> {
+    fn forward(&self, id: TaskId) -> usize {
+        let m = &self.task_id_forward_mapping;
+        if let Some(r) = m.get(&id) {
+            return *r;
+        }
+        let ty = self.api.lookup_task_type(id);
+        let new_id = self.this.get_or_create_task_type(ty).unwrap();
+        let _ = self.task_id_backward_mapping.try_insert(new_id, id);
+        let _ = m.try_insert(id, new_id);
+        new_id
+    }
+
+    fn backward(&self, id: usize) -> TaskId {
+        let m = &self.task_id_backward_mapping;
+        if let Some(r) = m.get(&id) {
+            return *r;
+        }
+        let ty = self.this.lookup_task_type(id).unwrap();
+        let new_id = self.api.get_or_create_task_type(ty);
+        let _ = self.task_id_forward_mapping.try_insert(new_id, id);
+        let _ = m.try_insert(id, new_id);
+        new_id
+    }
+}
+
+struct PgApiReadOnlyMapping<'a, D: KvDatabase> {
+    this: &'a KvPersistedGraph<D>,
+    task_id_forward_mapping: flurry::HashMapRef<'a, TaskId, usize>,
+    task_id_backward_mapping: flurry::HashMapRef<'a, usize, TaskId>,
+    api: &'a dyn PersistedGraphApi,
+}
+
+impl<'a, D: KvDatabase> PgApiReadOnlyMapping<'a, D> {
+    fn new(this: &'a KvPersistedGraph<D>, api: &'a dyn PersistedGraphApi) -> Self {
+        Self {
+            this,
+            api,
+            task_id_backward_mapping: this.task_id_backward_mapping.pin(),
+            task_id_forward_mapping: this.task_id_forward_mapping.pin(),
+        }
+    }
+}
+
+impl<'a, D: KvDatabase> IdMapping<TaskId> for PgApiReadOnlyMapping<'a, D> {
+    fn forward(&self, id: TaskId) -> usize {
+        let m = &self.task_id_forward_mapping;
+        if let Some(r) = m.get(&id) {
+            return *r;
+        }
+        let ty = self.api.lookup_task_type(id);
+        self.this.get_task_type(ty).unwrap().unwrap_or_default()
+    }
+
+    fn backward(&self, id: usize) -> TaskId {
+        let m = &self.task_id_backward_mapping;
+        if let Some(r) = m.get(&id) {
+            return *r;
+        }
+        let ty = self.this.lookup_task_type(id).unwrap();
+        let new_id = self.api.get_or_create_task_type(ty);
+        let _ = self.task_id_forward_mapping.try_insert(new_id, id);
+        let _ = m.try_insert(id, new_id);
+        new_id
+    }
+}
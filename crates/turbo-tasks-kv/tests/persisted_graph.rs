@@ -0,0 +1,258 @@
+use std::{path::Path, sync::Once};
+
+use anyhow::bail;
+use parking_lot::Mutex;
+use turbo_tasks::{
+    backend::PersistentTaskType,
+    persisted_graph::{PersistTaskState, PersistedGraph, PersistedGraphApi, TaskData},
+    registry, FunctionId, NativeFunction, RawVc, TaskId, TaskInput,
+};
+use turbo_tasks_kv::{KvDatabase, KvPersistedGraph};
+
+/// Assigns in-memory task ids to task types, like the memory backend does.
+#[derive(Default)]
+struct Api {
+    task_types: Mutex<Vec<&'static PersistentTaskType>>,
+}
+
+impl PersistedGraphApi for Api {
+    fn get_or_create_task_type(&self, ty: PersistentTaskType) -> TaskId {
+        let mut task_types = self.task_types.lock();
+        if let Some(index) = task_types.iter().position(|t| **t == ty) {
+            return TaskId::from(index + 1);
+        }
+        task_types.push(Box::leak(Box::new(ty)));
+        TaskId::from(task_types.len())
+    }
+
+    fn lookup_task_type(&self, id: TaskId) -> &PersistentTaskType {
+        self.task_types.lock()[*id - 1]
+    }
+}
+
+fn function() -> FunctionId {
+    static REGISTER: Once = Once::new();
+    const NAME: &str = "turbo-tasks-kv::tests::persisted_graph::function";
+    REGISTER.call_once(|| {
+        let function = NativeFunction::new("function".to_string(), |_| bail!("not executed"));
+        registry::register_function(NAME, Box::leak(Box::new(function)));
+    });
+    registry::get_function_id_by_global_name(NAME).unwrap()
+}
+
+fn task(api: &Api, name: &str) -> TaskId {
+    api.get_or_create_task_type(PersistentTaskType::Native(
+        function(),
+        vec![TaskInput::String(name.to_string())],
+    ))
+}
+
+fn data(task: TaskId, children: Vec<TaskId>, dependencies: Vec<RawVc>) -> TaskData {
+    TaskData {
+        children,
+        dependencies,
+        cells: Vec::new(),
+        output: RawVc::TaskOutput(task),
+    }
+}
+
+fn persist<D: KvDatabase>(
+    graph: &KvPersistedGraph<D>,
+    api: &Api,
+    data: TaskData,
+    externally_active: bool,
+) {
+    let task = data.output.get_task_id();
+    graph
+        .persist(task, data, PersistTaskState { externally_active }, api)
+        .unwrap()
+        .unwrap();
+}
+
+fn persists_tasks<D: KvDatabase>(path: &Path) {
+    let graph = KvPersistedGraph::<D>::new(path).unwrap();
+    let api = Api::default();
+    let (a, b, c) = (task(&api, "a"), task(&api, "b"), task(&api, "c"));
+    persist(
+        &graph,
+        &api,
+        data(a, vec![b], vec![RawVc::TaskOutput(c)]),
+        false,
+    );
+
+    let (data, state) = graph.read(a, &api).unwrap().unwrap();
+    assert_eq!(data.children, vec![b]);
+    assert_eq!(data.dependencies, vec![RawVc::TaskOutput(c)]);
+    assert_eq!(data.output, RawVc::TaskOutput(a));
+    assert!(state.clean);
+    assert!(graph.is_persisted(a, &api).unwrap());
+    assert!(!graph.is_persisted(b, &api).unwrap());
+    assert!(graph.read(b, &api).unwrap().is_none());
+    assert_eq!(
+        graph.lookup_one(api.lookup_task_type(a), &api).unwrap(),
+        Some(a)
+    );
+    assert_eq!(
+        graph.lookup_one(api.lookup_task_type(b), &api).unwrap(),
+        None
+    );
+}
+
+fn reopens_closed_database<D: KvDatabase>(path: &Path) {
+    let graph = KvPersistedGraph::<D>::new(path).unwrap();
+    let api = Api::default();
+    let (a, b) = (task(&api, "a"), task(&api, "b"));
+    persist(&graph, &api, data(a, vec![b], Vec::new()), false);
+    graph.stop(&api).unwrap();
+    drop(graph);
+
+    let graph = KvPersistedGraph::<D>::new(path).unwrap();
+    // Task ids of the new session are assigned in a different order
+    let api = Api::default();
+    let (b, a) = (task(&api, "b"), task(&api, "a"));
+    assert_eq!(
+        graph.lookup_one(api.lookup_task_type(a), &api).unwrap(),
+        Some(a)
+    );
+    let (data, _) = graph.read(a, &api).unwrap().unwrap();
+    assert_eq!(data.children, vec![b]);
+    assert_eq!(data.output, RawVc::TaskOutput(a));
+    assert_eq!(graph.get_function_tasks(function(), &api).unwrap().len(), 2);
+}
+
+fn discards_unclosed_database<D: KvDatabase>(path: &Path) {
+    let graph = KvPersistedGraph::<D>::new(path).unwrap();
+    let api = Api::default();
+    let a = task(&api, "a");
+    persist(&graph, &api, data(a, Vec::new(), Vec::new()), false);
+    drop(graph);
+
+    let graph = KvPersistedGraph::<D>::new(path).unwrap();
+    let api = Api::default();
+    let a = task(&api, "a");
+    assert_eq!(
+        graph.lookup_one(api.lookup_task_type(a), &api).unwrap(),
+        None
+    );
+    assert!(graph.read(a, &api).unwrap().is_none());
+}
+
+fn discards_other_version<D: KvDatabase>(path: &Path) {
+    let graph = KvPersistedGraph::<D>::with_version(path, "1").unwrap();
+    let api = Api::default();
+    let a = task(&api, "a");
+    persist(&graph, &api, data(a, Vec::new(), Vec::new()), false);
+    graph.stop(&api).unwrap();
+    drop(graph);
+
+    let graph = KvPersistedGraph::<D>::with_version(path, "2").unwrap();
+    let api = Api::default();
+    let a = task(&api, "a");
+    assert_eq!(
+        graph.lookup_one(api.lookup_task_type(a), &api).unwrap(),
+        None
+    );
+}
+
+fn makes_dependents_dirty<D: KvDatabase>(path: &Path) {
+    let graph = KvPersistedGraph::<D>::new(path).unwrap();
+    let api = Api::default();
+    let (a, b, c) = (task(&api, "a"), task(&api, "b"), task(&api, "c"));
+    persist(
+        &graph,
+        &api,
+        data(a, Vec::new(), vec![RawVc::TaskOutput(c)]),
+        true,
+    );
+    persist(
+        &graph,
+        &api,
+        data(b, Vec::new(), vec![RawVc::TaskOutput(c)]),
+        false,
+    );
+    persist(&graph, &api, data(c, Vec::new(), Vec::new()), false);
+
+    // Only active tasks need to be scheduled
+    assert_eq!(
+        graph
+            .make_dependent_dirty(RawVc::TaskOutput(c), &api)
+            .unwrap(),
+        vec![a]
+    );
+    assert!(!graph.read(a, &api).unwrap().unwrap().1.clean);
+    assert!(!graph.read(b, &api).unwrap().unwrap().1.clean);
+    assert_eq!(graph.get_dirty_active_tasks(&api).unwrap(), vec![a]);
+
+    graph.make_clean(a, &api).unwrap();
+    assert!(graph.read(a, &api).unwrap().unwrap().1.clean);
+    assert!(graph.get_dirty_active_tasks(&api).unwrap().is_empty());
+}
+
+fn activates_children<D: KvDatabase>(path: &Path) {
+    let graph = KvPersistedGraph::<D>::new(path).unwrap();
+    let api = Api::default();
+    let (a, b) = (task(&api, "a"), task(&api, "b"));
+    let result = graph
+        .persist(
+            a,
+            data(a, vec![b], Vec::new()),
+            PersistTaskState {
+                externally_active: true,
+            },
+            &api,
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.tasks_to_activate, vec![b]);
+    assert_eq!(
+        graph.get_pending_active_update(&api).unwrap(),
+        (vec![b], Vec::new())
+    );
+
+    let result = graph.activate_when_needed(b, &api).unwrap().unwrap();
+    assert!(result.keeps_external_active);
+    // b hasn't been persisted, so it lives in memory only
+    assert!(result.external);
+    assert_eq!(graph.get_active_external_tasks(&api).unwrap(), vec![b]);
+    assert!(graph.activate_when_needed(b, &api).unwrap().is_none());
+
+    assert!(graph.unset_externally_active(a, &api).unwrap());
+    let result = graph.deactivate_when_needed(a, &api).unwrap().unwrap();
+    assert_eq!(result.more_tasks_to_deactivate, vec![b]);
+    assert!(graph.deactivate_when_needed(b, &api).unwrap().is_some());
+    assert!(graph.get_active_external_tasks(&api).unwrap().is_empty());
+}
+
+macro_rules! persisted_graph_tests {
+    ($name:ident, $database:ty, [$($test:ident),*]) => {
+        mod $name {
+            $(
+                #[test]
+                fn $test() {
+                    let dir = tempfile::tempdir().unwrap();
+                    super::$test::<$database>(&dir.path().join(".db"));
+                }
+            )*
+        }
+    };
+}
+
+macro_rules! all_persisted_graph_tests {
+    ($name:ident, $database:ty) => {
+        persisted_graph_tests!(
+            $name,
+            $database,
+            [
+                persists_tasks,
+                reopens_closed_database,
+                discards_unclosed_database,
+                discards_other_version,
+                makes_dependents_dirty,
+                activates_children
+            ]
+        );
+    };
+}
+
+all_persisted_graph_tests!(logdb, turbo_tasks_logdb::LogDb);
+all_persisted_graph_tests!(rocksdb, turbo_tasks_rocksdb::RocksDb);
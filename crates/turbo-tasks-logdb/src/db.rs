@@ -0,0 +1,226 @@
+use std::{marker::PhantomData, path::Path, sync::Arc};
+
+use anyhow::Result;
+use bincode::Options;
+use serde::{de::DeserializeOwned, Serialize};
+use turbo_tasks::{backend::PersistentTaskType, persisted_graph::TaskData, RawVc};
+use turbo_tasks_kv::{TaskSet, TaskState, TaskStateChange};
+
+use crate::store::{Batch, Op, Store};
+
+fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    Ok(bincode::DefaultOptions::new().serialize(value)?)
+}
+
+fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    Ok(bincode::DefaultOptions::new().deserialize(bytes)?)
+}
+
+/// Maps a serialized key to a value
+pub struct Table<K, V> {
+    store: Arc<Store>,
+    id: u8,
+    phantom: PhantomData<fn(K) -> V>,
+}
+
+impl<K: Serialize, V: Serialize + DeserializeOwned + 'static> Table<K, V> {
+    fn new(store: Arc<Store>, id: u8) -> Self {
+        Self {
+            store,
+            id,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Result<Option<V>> {
+        self.store
+            .get(self.id, &serialize(key)?)
+            .map(|bytes| deserialize(&bytes))
+            .transpose()
+    }
+
+    pub fn write(&self, batch: &mut Batch, key: &K, value: &V) -> Result<()> {
+        batch.push(Op::Put {
+            table: self.id,
+            key: serialize(key)?,
+            value: serialize(value)?,
+        });
+        Ok(())
+    }
+
+    /// Computes the new value from the current value when the batch is
+    /// written.
+    pub fn update(
+        &self,
+        batch: &mut Batch,
+        key: &K,
+        update: impl FnOnce(Option<V>) -> V + Send + 'static,
+    ) -> Result<()> {
+        batch.push(Op::Merge {
+            table: self.id,
+            key: serialize(key)?,
+            merge: Box::new(move |old| {
+                let old = old.map(deserialize).transpose()?;
+                Ok(Some(serialize(&update(old))?))
+            }),
+        });
+        Ok(())
+    }
+}
+
+impl Table<usize, TaskState> {
+    pub fn merge(&self, batch: &mut Batch, key: &usize, change: TaskStateChange) -> Result<()> {
+        self.update(batch, key, move |state| state.unwrap_or_default() + change)
+    }
+}
+
+/// Maps raw bytes to a value. Entries can be looked up by prefix.
+pub struct RawTable<V> {
+    store: Arc<Store>,
+    id: u8,
+    phantom: PhantomData<fn() -> V>,
+}
+
+impl<V: Serialize + DeserializeOwned> RawTable<V> {
+    fn new(store: Arc<Store>, id: u8) -> Self {
+        Self {
+            store,
+            id,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<V>> {
+        self.store
+            .get(self.id, key)
+            .map(|bytes| deserialize(&bytes))
+            .transpose()
+    }
+
+    /// Returns up to `limit` values whose key starts with `prefix`. The
+    /// boolean is true when all values have been returned.
+    pub fn get_prefix(&self, prefix: &[u8], limit: usize) -> Result<(Vec<V>, bool)> {
+        let (entries, complete) = self.store.get_prefix(self.id, prefix, limit);
+        let values = entries
+            .into_iter()
+            .map(|(_, value)| deserialize(&value))
+            .collect::<Result<_>>()?;
+        Ok((values, complete))
+    }
+
+    pub fn write(&self, batch: &mut Batch, key: &[u8], value: &V) -> Result<()> {
+        batch.push(Op::Put {
+            table: self.id,
+            key: key.to_vec(),
+            value: serialize(value)?,
+        });
+        Ok(())
+    }
+}
+
+/// Maps a key to a set of values. Every value is stored as separate entry with
+/// the serialized key as prefix.
+pub struct SetTable<K, V> {
+    store: Arc<Store>,
+    id: u8,
+    phantom: PhantomData<fn(K) -> V>,
+}
+
+impl<K: Serialize, V: Serialize + DeserializeOwned> SetTable<K, V> {
+    fn new(store: Arc<Store>, id: u8) -> Self {
+        Self {
+            store,
+            id,
+            phantom: PhantomData,
+        }
+    }
+
+    fn entry_key(key: &K, value: &V) -> Result<Vec<u8>> {
+        let mut bytes = serialize(key)?;
+        bincode::DefaultOptions::new().serialize_into(&mut bytes, value)?;
+        Ok(bytes)
+    }
+
+    pub fn get_all(&self, key: &K) -> Result<Vec<V>> {
+        let prefix = serialize(key)?;
+        let (entries, _) = self.store.get_prefix(self.id, &prefix, usize::MAX);
+        entries
+            .into_iter()
+            .map(|(entry_key, _)| deserialize(&entry_key[prefix.len()..]))
+            .collect()
+    }
+
+    pub fn insert(&self, batch: &mut Batch, key: &K, value: &V) -> Result<()> {
+        batch.push(Op::Put {
+            table: self.id,
+            key: Self::entry_key(key, value)?,
+            value: Vec::new(),
+        });
+        Ok(())
+    }
+
+    pub fn remove(&self, batch: &mut Batch, key: &K, value: &V) -> Result<()> {
+        batch.push(Op::Delete {
+            table: self.id,
+            key: Self::entry_key(key, value)?,
+        });
+        Ok(())
+    }
+}
+
+pub struct Database {
+    store: Arc<Store>,
+    pub last_task_id: Table<(), usize>,
+    pub task_type: Table<usize, PersistentTaskType>,
+    pub cache: RawTable<usize>,
+    pub state: Table<usize, TaskState>,
+    pub data: Table<usize, TaskData>,
+    pub children: Table<usize, Vec<usize>>,
+    pub dependencies: Table<usize, Vec<RawVc>>,
+    pub dependents: SetTable<RawVc, usize>,
+    pub externally_active_tasks: SetTable<(), usize>,
+    pub potential_active_external_tasks: SetTable<(), usize>,
+    pub potential_dirty_active_tasks: SetTable<(), usize>,
+    pub pending_active_update: SetTable<(), usize>,
+    /// Seconds since the unix epoch
+    pub last_used: Table<usize, u64>,
+}
+
+impl Database {
+    pub fn open(path: &Path, version: &str) -> Result<Self> {
+        let store = Arc::new(Store::open(path, version, 13)?);
+        Ok(Self {
+            last_task_id: Table::new(store.clone(), 0),
+            task_type: Table::new(store.clone(), 1),
+            cache: RawTable::new(store.clone(), 2),
+            state: Table::new(store.clone(), 3),
+            data: Table::new(store.clone(), 4),
+            children: Table::new(store.clone(), 5),
+            dependencies: Table::new(store.clone(), 6),
+            dependents: SetTable::new(store.clone(), 7),
+            externally_active_tasks: SetTable::new(store.clone(), 8),
+            potential_active_external_tasks: SetTable::new(store.clone(), 9),
+            potential_dirty_active_tasks: SetTable::new(store.clone(), 10),
+            pending_active_update: SetTable::new(store.clone(), 11),
+            last_used: Table::new(store.clone(), 12),
+            store,
+        })
+    }
+
+    pub fn set(&self, set: TaskSet) -> &SetTable<(), usize> {
+        match set {
+            TaskSet::ExternallyActive => &self.externally_active_tasks,
+            TaskSet::PotentialActiveExternal => &self.potential_active_external_tasks,
+            TaskSet::PotentialDirtyActive => &self.potential_dirty_active_tasks,
+            TaskSet::PendingActiveUpdate => &self.pending_active_update,
+        }
+    }
+
+    pub fn batch(&self) -> Batch {
+        Batch::new(self.store.clone())
+    }
+
+    pub fn close(&self) -> Result<()> {
+        self.store.close()
+    }
+}
@@ -0,0 +1,14 @@
+//! A [PersistedGraph](turbo_tasks::persisted_graph::PersistedGraph) that is
+//! stored in a single append-only log file. It doesn't need any native
+//! dependencies, in contrast to `turbo-tasks-rocksdb`. Both share the graph
+//! implementation of `turbo-tasks-kv`.
+//!
+//! All data is kept in memory while the graph is in use. Every write is
+//! appended to the log, which is compacted when it grows too large and when
+//! the graph is stopped.
+
+mod db;
+mod persisted_graph;
+mod store;
+
+pub use persisted_graph::{LogDb, LogDbPersistedGraph};
@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use anyhow::Result;
+use turbo_tasks::{backend::PersistentTaskType, persisted_graph::TaskData, RawVc};
+use turbo_tasks_kv::{KvBatch, KvDatabase, KvPersistedGraph, TaskSet, TaskState, TaskStateChange};
+
+use crate::{db::Database, store::Batch};
+
+pub type LogDbPersistedGraph = KvPersistedGraph<LogDb>;
+
+/// A [KvDatabase] stored in a single append-only log file
+pub struct LogDb {
+    database: Database,
+}
+
+impl KvBatch for Batch {
+    fn write(&mut self) -> Result<()> {
+        Batch::write(self)
+    }
+
+    fn cancel(&mut self) {
+        Batch::cancel(self)
+    }
+}
+
+impl KvDatabase for LogDb {
+    type Batch = Batch;
+
+    fn open(path: &Path, version: &str) -> Result<Self> {
+        Ok(Self {
+            database: Database::open(path, version)?,
+        })
+    }
+
+    fn batch(&self) -> Batch {
+        self.database.batch()
+    }
+
+    fn close(&self) -> Result<()> {
+        self.database.close()
+    }
+
+    fn get_last_task_id(&self) -> Result<usize> {
+        Ok(self.database.last_task_id.get(&())?.unwrap_or_default())
+    }
+
+    fn increment_last_task_id(&self, batch: &mut Batch) -> Result<()> {
+        self.database
+            .last_task_id
+            .update(batch, &(), |last| last.unwrap_or_default() + 1)
+    }
+
+    fn get_task_type(&self, task: usize) -> Result<Option<PersistentTaskType>> {
+        self.database.task_type.get(&task)
+    }
+
+    fn write_task_type(
+        &self,
+        batch: &mut Batch,
+        task: usize,
+        ty: &PersistentTaskType,
+    ) -> Result<()> {
+        self.database.task_type.write(batch, &task, ty)
+    }
+
+    fn get_cache(&self, key: &[u8]) -> Result<Option<usize>> {
+        self.database.cache.get(key)
+    }
+
+    fn get_cache_prefix(&self, prefix: &[u8], limit: usize) -> Result<(Vec<usize>, bool)> {
+        self.database.cache.get_prefix(prefix, limit)
+    }
+
+    fn write_cache(&self, batch: &mut Batch, key: &[u8], task: usize) -> Result<()> {
+        self.database.cache.write(batch, key, &task)
+    }
+
+    fn get_state(&self, task: usize) -> Result<Option<TaskState>> {
+        self.database.state.get(&task)
+    }
+
+    fn merge_state(&self, batch: &mut Batch, task: usize, change: TaskStateChange) -> Result<()> {
+        self.database.state.merge(batch, &task, change)
+    }
+
+    fn get_data(&self, task: usize) -> Result<Option<TaskData>> {
+        self.database.data.get(&task)
+    }
+
+    fn write_data(&self, batch: &mut Batch, task: usize, data: &TaskData) -> Result<()> {
+        self.database.data.write(batch, &task, data)
+    }
+
+    fn get_children(&self, task: usize) -> Result<Option<Vec<usize>>> {
+        self.database.children.get(&task)
+    }
+
+    fn write_children(&self, batch: &mut Batch, task: usize, children: &[usize]) -> Result<()> {
+        self.database
+            .children
+            .write(batch, &task, &children.to_vec())
+    }
+
+    fn get_dependencies(&self, task: usize) -> Result<Option<Vec<RawVc>>> {
+        self.database.dependencies.get(&task)
+    }
+
+    fn write_dependencies(
+        &self,
+        batch: &mut Batch,
+        task: usize,
+        dependencies: &[RawVc],
+    ) -> Result<()> {
+        self.database
+            .dependencies
+            .write(batch, &task, &dependencies.to_vec())
+    }
+
+    fn get_dependents(&self, vc: &RawVc) -> Result<Vec<usize>> {
+        self.database.dependents.get_all(vc)
+    }
+
+    fn insert_dependent(&self, batch: &mut Batch, vc: &RawVc, task: usize) -> Result<()> {
+        self.database.dependents.insert(batch, vc, &task)
+    }
+
+    fn remove_dependent(&self, batch: &mut Batch, vc: &RawVc, task: usize) -> Result<()> {
+        self.database.dependents.remove(batch, vc, &task)
+    }
+
+    fn get_set(&self, set: TaskSet) -> Result<Vec<usize>> {
+        self.database.set(set).get_all(&())
+    }
+
+    fn insert_into_set(&self, batch: &mut Batch, set: TaskSet, task: usize) -> Result<()> {
+        self.database.set(set).insert(batch, &(), &task)
+    }
+
+    fn remove_from_set(&self, batch: &mut Batch, set: TaskSet, task: usize) -> Result<()> {
+        self.database.set(set).remove(batch, &(), &task)
+    }
+
+    fn write_last_used(&self, batch: &mut Batch, task: usize, time: u64) -> Result<()> {
+        self.database.last_used.write(batch, &task, &time)
+    }
+}
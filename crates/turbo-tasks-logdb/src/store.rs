@@ -0,0 +1,546 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, ErrorKind, Write},
+    mem::take,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use parking_lot::{Mutex, RwLock};
+
+/// Identifies the file format
+const MAGIC: &[u8] = b"TTLOGDB1";
+
+const RECORD_PUT: u8 = 0;
+const RECORD_DELETE: u8 = 1;
+/// Written as last record when the store has been closed cleanly
+const RECORD_CLOSE: u8 = 2;
+
+/// The default for [Store::min_compaction_size]
+const MIN_COMPACTION_SIZE: u64 = 64 * 1024 * 1024;
+
+pub type MergeFn = Box<dyn FnOnce(Option<&[u8]>) -> Result<Option<Vec<u8>>> + Send>;
+
+pub enum Op {
+    Put {
+        table: u8,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        table: u8,
+        key: Vec<u8>,
+    },
+    /// Computes the new value from the current value when the batch is
+    /// written. Returning `None` deletes the entry.
+    Merge {
+        table: u8,
+        key: Vec<u8>,
+        merge: MergeFn,
+    },
+}
+
+struct Tables {
+    tables: Vec<BTreeMap<Vec<u8>, Vec<u8>>>,
+    /// Size of all keys and values
+    live_size: u64,
+}
+
+impl Tables {
+    fn new(count: usize) -> Self {
+        Self {
+            tables: (0..count).map(|_| BTreeMap::new()).collect(),
+            live_size: 0,
+        }
+    }
+
+    fn get(&self, table: u8, key: &[u8]) -> Option<&Vec<u8>> {
+        self.tables[table as usize].get(key)
+    }
+
+    fn put(&mut self, table: u8, key: Vec<u8>, value: Vec<u8>) {
+        let key_len = key.len() as u64;
+        let value_len = value.len() as u64;
+        match self.tables[table as usize].insert(key, value) {
+            Some(old) => self.live_size = self.live_size + value_len - old.len() as u64,
+            None => self.live_size += key_len + value_len,
+        }
+    }
+
+    fn delete(&mut self, table: u8, key: &[u8]) {
+        if let Some(old) = self.tables[table as usize].remove(key) {
+            self.live_size -= (key.len() + old.len()) as u64;
+        }
+    }
+}
+
+struct Log {
+    writer: BufWriter<File>,
+    /// Size of the log file
+    size: u64,
+    /// Records that have been appended while the log is compacted. They are
+    /// appended to the compacted log as well.
+    compaction: Option<Vec<u8>>,
+}
+
+impl Log {
+    fn append(&mut self, records: &[u8]) -> Result<()> {
+        self.writer.write_all(records)?;
+        self.size += records.len() as u64;
+        if let Some(compaction) = &mut self.compaction {
+            compaction.extend_from_slice(records);
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+        Ok(())
+    }
+}
+
+fn encode_record(buf: &mut Vec<u8>, kind: u8, table: u8, key: &[u8], value: Option<&[u8]>) {
+    buf.extend_from_slice(&[kind, table]);
+    buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    buf.extend_from_slice(key);
+    if let Some(value) = value {
+        buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        buf.extend_from_slice(value);
+    }
+}
+
+/// A key-value store with a fixed number of tables. All entries are kept in
+/// memory and every change is appended to a log file, which is replayed when
+/// the store is opened again.
+///
+/// The store is only restored when it has been closed cleanly. After a crash
+/// the log might miss writes that were buffered, and the entries written by
+/// the last session might not be consistent with each other.
+pub struct Store {
+    path: PathBuf,
+    version: String,
+    tables: RwLock<Tables>,
+    log: Mutex<Log>,
+    /// The log is compacted when it's larger than this and twice as large as
+    /// the live data
+    min_compaction_size: u64,
+}
+
+impl Store {
+    /// Opens the store at `path`. It starts empty when the file has been
+    /// written by a different `version` or hasn't been closed cleanly.
+    pub fn open(path: &Path, version: &str, table_count: usize) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tables = Tables::new(table_count);
+        let restored = match fs::read(path) {
+            Ok(content) => replay(&content, version, &mut tables),
+            Err(err) if err.kind() == ErrorKind::NotFound => false,
+            Err(err) => {
+                return Err(err).with_context(|| format!("reading {}", path.display()));
+            }
+        };
+        let log = if restored {
+            // Appending to the closed log is fine, as the close record is
+            // only valid at the end of the file
+            let file = OpenOptions::new().append(true).open(path)?;
+            let size = file.metadata()?.len();
+            Log {
+                writer: BufWriter::new(file),
+                size,
+                compaction: None,
+            }
+        } else {
+            tables = Tables::new(table_count);
+            let temp_path = path.with_extension("tmp");
+            let log = create_log(&temp_path, version, &tables.tables, false)?;
+            fs::rename(&temp_path, path)?;
+            log
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            version: version.to_string(),
+            tables: RwLock::new(tables),
+            log: Mutex::new(log),
+            min_compaction_size: MIN_COMPACTION_SIZE,
+        })
+    }
+
+    pub fn get(&self, table: u8, key: &[u8]) -> Option<Vec<u8>> {
+        self.tables.read().get(table, key).cloned()
+    }
+
+    /// Returns up to `limit` entries whose key starts with `prefix`. The
+    /// boolean is true when all entries have been returned.
+    pub fn get_prefix(
+        &self,
+        table: u8,
+        prefix: &[u8],
+        limit: usize,
+    ) -> (Vec<(Vec<u8>, Vec<u8>)>, bool) {
+        let tables = self.tables.read();
+        let mut result = Vec::new();
+        for (key, value) in tables.tables[table as usize].range(prefix.to_vec()..) {
+            if !key.starts_with(prefix) {
+                return (result, true);
+            }
+            if result.len() == limit {
+                return (result, false);
+            }
+            result.push((key.clone(), value.clone()));
+        }
+        (result, true)
+    }
+
+    /// Applies all operations atomically and appends them to the log. When
+    /// an operation or the log write fails, none of them is applied. When
+    /// the log can't be compacted afterwards, the operations have been applied
+    /// nevertheless.
+    pub fn write(&self, ops: Vec<Op>) -> Result<()> {
+        let mut tables = self.tables.write();
+        // Merges see the values written earlier in the same batch
+        let mut pending: HashMap<(u8, Vec<u8>), Option<Vec<u8>>> = HashMap::new();
+        let mut resolved = Vec::with_capacity(ops.len());
+        for op in ops {
+            match op {
+                Op::Put { table, key, value } => {
+                    pending.insert((table, key.clone()), Some(value.clone()));
+                    resolved.push((table, key, Some(value)));
+                }
+                Op::Delete { table, key } => {
+                    pending.insert((table, key.clone()), None);
+                    resolved.push((table, key, None));
+                }
+                Op::Merge { table, key, merge } => {
+                    let value = match pending.get(&(table, key.clone())) {
+                        Some(value) => merge(value.as_deref())?,
+                        None => merge(tables.get(table, &key).map(|v| &v[..]))?,
+                    };
+                    pending.insert((table, key.clone()), value.clone());
+                    resolved.push((table, key, value));
+                }
+            }
+        }
+        let mut records = Vec::new();
+        for (table, key, value) in resolved.iter() {
+            match value {
+                Some(value) => encode_record(&mut records, RECORD_PUT, *table, key, Some(value)),
+                None => encode_record(&mut records, RECORD_DELETE, *table, key, None),
+            }
+        }
+        let mut log = self.log.lock();
+        log.append(&records)?;
+        for (table, key, value) in resolved {
+            match value {
+                Some(value) => tables.put(table, key, value),
+                None => tables.delete(table, &key),
+            }
+        }
+        let compact = log.compaction.is_none()
+            && log.size > self.min_compaction_size
+            && log.size > tables.live_size * 2;
+        if !compact {
+            return Ok(());
+        }
+        log.compaction = Some(Vec::new());
+        drop(log);
+        // The snapshot is written without holding any lock, so other writes
+        // can continue in the meantime
+        let snapshot = tables.tables.clone();
+        drop(tables);
+        self.compact(&snapshot)
+    }
+
+    /// Replaces the log with a log that contains the `snapshot` and all
+    /// records that have been appended since the snapshot has been taken.
+    fn compact(&self, snapshot: &[BTreeMap<Vec<u8>, Vec<u8>>]) -> Result<()> {
+        let temp_path = self.path.with_extension("compact");
+        let new_log = create_log(&temp_path, &self.version, snapshot, false);
+        let mut log = self.log.lock();
+        let appended = match log.compaction.take() {
+            Some(appended) => appended,
+            None => {
+                // The store has been closed in the meantime
+                let _ = fs::remove_file(&temp_path);
+                return Ok(());
+            }
+        };
+        let mut new_log = new_log?;
+        new_log.append(&appended)?;
+        new_log.sync()?;
+        fs::rename(&temp_path, &self.path)?;
+        *log = new_log;
+        Ok(())
+    }
+
+    /// Compacts the log and marks it as closed cleanly, so it's restored when
+    /// opened the next time. The store must not be written afterwards.
+    pub fn close(&self) -> Result<()> {
+        let tables = self.tables.read();
+        let mut log = self.log.lock();
+        log.compaction = None;
+        log.writer.flush()?;
+        let temp_path = self.path.with_extension("tmp");
+        *log = create_log(&temp_path, &self.version, &tables.tables, true)?;
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Writes all entries into a new log file at `path`.
+fn create_log(
+    path: &Path,
+    version: &str,
+    tables: &[BTreeMap<Vec<u8>, Vec<u8>>],
+    close: bool,
+) -> Result<Log> {
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut log = Log {
+        writer: BufWriter::new(file),
+        size: 0,
+        compaction: None,
+    };
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&(version.len() as u32).to_le_bytes());
+    header.extend_from_slice(version.as_bytes());
+    log.append(&header)?;
+    let mut records = Vec::new();
+    for (table, entries) in tables.iter().enumerate() {
+        for (key, value) in entries.iter() {
+            encode_record(&mut records, RECORD_PUT, table as u8, key, Some(value));
+            log.append(&records)?;
+            records.clear();
+        }
+    }
+    if close {
+        log.append(&[RECORD_CLOSE])?;
+    }
+    log.sync()?;
+    Ok(log)
+}
+
+/// Restores all entries from the log. Returns false when the log can't be
+/// used.
+fn replay(content: &[u8], version: &str, tables: &mut Tables) -> bool {
+    let mut reader = Reader { content };
+    if reader.bytes(MAGIC.len()) != Some(MAGIC) || reader.block() != Some(version.as_bytes()) {
+        return false;
+    }
+    let mut closed = false;
+    while let Some(kind) = reader.u8() {
+        closed = false;
+        match kind {
+            RECORD_PUT => match (reader.u8(), reader.block(), reader.block()) {
+                (Some(table), Some(key), Some(value)) if (table as usize) < tables.tables.len() => {
+                    tables.put(table, key.to_vec(), value.to_vec());
+                }
+                _ => return false,
+            },
+            RECORD_DELETE => match (reader.u8(), reader.block()) {
+                (Some(table), Some(key)) if (table as usize) < tables.tables.len() => {
+                    tables.delete(table, key);
+                }
+                _ => return false,
+            },
+            RECORD_CLOSE => closed = true,
+            _ => return false,
+        }
+    }
+    closed
+}
+
+struct Reader<'a> {
+    content: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.content.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.content.split_at(len);
+        self.content = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    /// Reads a length-prefixed block of bytes
+    fn block(&mut self) -> Option<&'a [u8]> {
+        let len = u32::from_le_bytes(self.bytes(4)?.try_into().ok()?);
+        self.bytes(len as usize)
+    }
+}
+
+/// Collects operations to write them atomically
+pub struct Batch {
+    store: Arc<Store>,
+    ops: Vec<Op>,
+}
+
+impl Batch {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self {
+            store,
+            ops: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, op: Op) {
+        self.ops.push(op);
+    }
+
+    pub fn write(&mut self) -> Result<()> {
+        self.store.write(take(&mut self.ops))
+    }
+
+    pub fn cancel(&mut self) {
+        self.ops.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use anyhow::anyhow;
+
+    use super::{Op, Store};
+
+    fn open(path: &Path) -> Store {
+        Store::open(path, "1", 2).unwrap()
+    }
+
+    fn put(table: u8, key: &[u8], value: &[u8]) -> Op {
+        Op::Put {
+            table,
+            key: key.to_vec(),
+            value: value.to_vec(),
+        }
+    }
+
+    #[test]
+    fn replays_closed_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.log");
+        let store = open(&path);
+        store
+            .write(vec![
+                put(0, b"a", b"1"),
+                put(0, b"b", b"2"),
+                put(1, b"a", b"3"),
+            ])
+            .unwrap();
+        store
+            .write(vec![
+                Op::Delete {
+                    table: 0,
+                    key: b"b".to_vec(),
+                },
+                Op::Merge {
+                    table: 1,
+                    key: b"a".to_vec(),
+                    merge: Box::new(|old| Ok(Some([old.unwrap(), b"4"].concat()))),
+                },
+            ])
+            .unwrap();
+        store.close().unwrap();
+        drop(store);
+
+        let store = open(&path);
+        assert_eq!(store.get(0, b"a"), Some(b"1".to_vec()));
+        assert_eq!(store.get(0, b"b"), None);
+        assert_eq!(store.get(1, b"a"), Some(b"34".to_vec()));
+    }
+
+    #[test]
+    fn ignores_unclosed_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.log");
+        let store = open(&path);
+        store.write(vec![put(0, b"a", b"1")]).unwrap();
+        drop(store);
+
+        let store = open(&path);
+        assert_eq!(store.get(0, b"a"), None);
+    }
+
+    #[test]
+    fn ignores_truncated_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.log");
+        let store = open(&path);
+        store.write(vec![put(0, b"a", b"1")]).unwrap();
+        store.close().unwrap();
+        drop(store);
+
+        // Cuts the last record and the close record
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() - 3]).unwrap();
+        let store = open(&path);
+        assert_eq!(store.get(0, b"a"), None);
+    }
+
+    #[test]
+    fn ignores_other_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.log");
+        let store = open(&path);
+        store.write(vec![put(0, b"a", b"1")]).unwrap();
+        store.close().unwrap();
+        drop(store);
+
+        let store = Store::open(&path, "2", 2).unwrap();
+        assert_eq!(store.get(0, b"a"), None);
+    }
+
+    #[test]
+    fn failing_batch_is_not_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.log");
+        let store = open(&path);
+        let result = store.write(vec![
+            put(0, b"a", b"1"),
+            Op::Merge {
+                table: 0,
+                key: b"b".to_vec(),
+                merge: Box::new(|_| Err(anyhow!("merge failed"))),
+            },
+        ]);
+        assert!(result.is_err());
+        assert_eq!(store.get(0, b"a"), None);
+
+        store.close().unwrap();
+        drop(store);
+        let store = open(&path);
+        assert_eq!(store.get(0, b"a"), None);
+    }
+
+    #[test]
+    fn compacts_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.log");
+        let mut store = open(&path);
+        store.min_compaction_size = 1024;
+        let value = [0; 100];
+        for _ in 0..100 {
+            store.write(vec![put(0, b"a", &value)]).unwrap();
+        }
+        let size = fs::metadata(&path).unwrap().len();
+        assert!(size < 1024, "log has not been compacted, size {size}");
+
+        // Writes after the compaction are appended to the compacted log
+        store.write(vec![put(0, b"b", b"2")]).unwrap();
+        store.close().unwrap();
+        drop(store);
+        let store = open(&path);
+        assert_eq!(store.get(0, b"a"), Some(value.to_vec()));
+        assert_eq!(store.get(0, b"b"), Some(b"2".to_vec()));
+    }
+}
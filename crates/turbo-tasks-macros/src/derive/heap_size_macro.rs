@@ -0,0 +1,61 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Field, FieldsNamed, FieldsUnnamed};
+use turbo_tasks_macros_shared::{generate_destructuring, match_expansion};
+
+fn ignore_field(_field: &Field) -> bool {
+    false
+}
+
+/// This macro generates the implementation of the `HeapSize` trait for a given
+/// type.
+///
+/// Fields that don't implement `HeapSize` are counted as not using any heap
+/// memory.
+pub fn derive_heap_size(input: TokenStream) -> TokenStream {
+    let derive_input = parse_macro_input!(input as DeriveInput);
+
+    let ident = &derive_input.ident;
+    let sum_sizes = match_expansion(&derive_input, &size_named, &size_unnamed, &size_unit);
+
+    quote! {
+        impl turbo_tasks::heap_size::HeapSize for #ident {
+            fn heap_size(&self) -> usize {
+                use turbo_tasks::heap_size::HeapSize;
+                #sum_sizes
+            }
+        }
+    }
+    .into()
+}
+
+/// Sums a struct or enum variant with named fields (e.g. `struct Foo {
+/// bar: u32 }`, `Foo::Bar { baz: u32 }`).
+fn size_named(_ident: &Ident, fields: &FieldsNamed) -> (TokenStream2, TokenStream2) {
+    let (captures, fields_idents) = generate_destructuring(fields.named.iter(), &ignore_field);
+    (
+        captures,
+        quote! {
+            0 #(+ #fields_idents.heap_size())*
+        },
+    )
+}
+
+/// Sums a struct or enum variant with unnamed fields (e.g. `struct
+/// Foo(u32)`, `Foo::Bar(u32)`).
+fn size_unnamed(_ident: &Ident, fields: &FieldsUnnamed) -> (TokenStream2, TokenStream2) {
+    let (captures, fields_idents) = generate_destructuring(fields.unnamed.iter(), &ignore_field);
+    (
+        captures,
+        quote! {
+            0 #(+ #fields_idents.heap_size())*
+        },
+    )
+}
+
+/// A unit struct or enum variant (e.g. `struct Foo;`, `Foo::Bar`) doesn't use
+/// heap memory.
+fn size_unit(_ident: &Ident) -> (TokenStream2, TokenStream2) {
+    (quote! {}, quote! { 0 })
+}
@@ -1,9 +1,11 @@
 mod deterministic_hash_macro;
+mod heap_size_macro;
 mod trace_raw_vcs_macro;
 mod value_debug_format_macro;
 mod value_debug_macro;
 
 pub use deterministic_hash_macro::derive_deterministic_hash;
+pub use heap_size_macro::derive_heap_size;
 use syn::{spanned::Spanned, Attribute, Meta, MetaList, NestedMeta};
 pub use trace_raw_vcs_macro::derive_trace_raw_vcs;
 pub use value_debug_format_macro::derive_value_debug_format;
@@ -35,6 +35,11 @@ pub fn derive_deterministic_hash(input: TokenStream) -> TokenStream {
     derive::derive_deterministic_hash(input)
 }
 
+#[proc_macro_derive(HeapSize, attributes(turbo_tasks))]
+pub fn derive_heap_size_attr(input: TokenStream) -> TokenStream {
+    derive::derive_heap_size(input)
+}
+
 /// Creates a ValueVc struct for a `struct` or `enum` that represent
 /// that type placed into a cell in a Task.
 ///
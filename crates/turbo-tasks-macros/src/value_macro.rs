@@ -347,11 +347,11 @@ pub fn value(args: TokenStream, input: TokenStream) -> TokenStream {
     let derive = match serialization_mode {
         SerializationMode::None | SerializationMode::Custom | SerializationMode::CustomForInput => {
             quote! {
-                #[derive(turbo_tasks::trace::TraceRawVcs)]
+                #[derive(turbo_tasks::trace::TraceRawVcs, turbo_tasks::heap_size::HeapSize)]
             }
         }
         SerializationMode::Auto | SerializationMode::AutoForInput => quote! {
-            #[derive(turbo_tasks::trace::TraceRawVcs, serde::Serialize, serde::Deserialize)]
+            #[derive(turbo_tasks::trace::TraceRawVcs, turbo_tasks::heap_size::HeapSize, serde::Serialize, serde::Deserialize)]
         },
     };
     let debug_derive = if inner_type.is_some() {
@@ -569,6 +569,7 @@ pub fn value(args: TokenStream, input: TokenStream) -> TokenStream {
         ) {
             #value_type_init_ident.get_or_init(|| {
                 let mut value = #new_value_type;
+                value.register_heap_size::<#ident>();
                 f(&mut value);
                 value
             }).register(global_name);
@@ -597,6 +598,12 @@ pub fn value(args: TokenStream, input: TokenStream) -> TokenStream {
             node: turbo_tasks::RawVc,
         }
 
+        impl turbo_tasks::heap_size::HeapSize for #ref_ident {
+            fn heap_size(&self) -> usize {
+                0
+            }
+        }
+
         #read_ref
 
         impl #ref_ident {
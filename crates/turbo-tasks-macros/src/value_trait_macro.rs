@@ -201,6 +201,12 @@ pub fn value_trait(args: TokenStream, input: TokenStream) -> TokenStream {
             node: turbo_tasks::RawVc,
         }
 
+        impl turbo_tasks::heap_size::HeapSize for #ref_ident {
+            fn heap_size(&self) -> usize {
+                0
+            }
+        }
+
         impl #ref_ident {
             /// see [turbo_tasks::RawVc::resolve]
             pub async fn resolve(self) -> turbo_tasks::Result<Self> {
@@ -1,10 +1,13 @@
-use std::{collections::HashSet, fmt::Debug};
+use std::{collections::HashSet, fmt::Debug, mem::take};
 
-use turbo_tasks::{backend::CellContent, TaskId, TurboTasksBackendApi};
+use turbo_tasks::{backend::CellContent, registry, SharedReference, TaskId, TurboTasksBackendApi};
 
 #[derive(Default, Debug)]
 pub struct Cell {
     content: CellContent,
+    /// The content has been dropped by the garbage collection and needs to be
+    /// recomputed.
+    unloaded: bool,
     updates: u32,
     pub(crate) dependent_tasks: HashSet<TaskId>,
 }
@@ -25,8 +28,44 @@ impl Cell {
         self.dependent_tasks.insert(reader);
     }
 
+    /// Estimates the memory used by the content. Returns `None` when there is
+    /// no content.
+    pub fn estimate_content_size(&self) -> Option<usize> {
+        match &self.content.0 {
+            Some(SharedReference(Some(ty), value)) => Some(
+                registry::get_value_type(*ty)
+                    .estimate_size(&**value)
+                    .unwrap_or_default(),
+            ),
+            Some(SharedReference(None, _)) => Some(0),
+            None => None,
+        }
+    }
+
+    pub fn has_dependent_tasks(&self) -> bool {
+        !self.dependent_tasks.is_empty()
+    }
+
+    pub fn is_unloaded(&self) -> bool {
+        self.unloaded
+    }
+
+    /// Drops the content to free memory. Only content that no task depends on
+    /// is dropped, as it's compared with the recomputed content to decide
+    /// whether dependent tasks need to be notified. Returns `true` when there
+    /// was content to drop.
+    pub fn unload(&mut self) -> bool {
+        if self.has_dependent_tasks() || self.content.0.is_none() {
+            return false;
+        }
+        take(&mut self.content);
+        self.unloaded = true;
+        true
+    }
+
     pub fn assign(&mut self, content: CellContent, turbo_tasks: &dyn TurboTasksBackendApi) {
         self.content = content;
+        self.unloaded = false;
         self.updates += 1;
         // notify
         if !self.dependent_tasks.is_empty() {
@@ -0,0 +1,167 @@
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::Instant,
+};
+
+use parking_lot::Mutex;
+use turbo_malloc::TurboMalloc;
+use turbo_tasks::{TaskId, TurboTasksBackendApi};
+
+use crate::{
+    memory_backend::Job,
+    stats::GcStats,
+    task::{Task, UnloadResult},
+    MemoryBackend,
+};
+
+/// The garbage collection frees memory until the usage is below this percentage
+/// of the memory limit. Collecting a bit more than needed avoids running it
+/// again right away.
+const TARGET_MEMORY_PERCENTAGE: usize = 90;
+
+/// Number of tasks taken from the queue at once. The memory usage is checked
+/// after every batch.
+const BATCH_SIZE: usize = 1000;
+
+/// Unloads tasks when the memory usage exceeds the memory limit. Tasks are
+/// unloaded least recently used first, and only when they are not part of an
+/// active [TaskScope](crate::scope::TaskScope). Cells that no task depends on
+/// are dropped, and tasks that nothing depends on are evicted from the task
+/// cache, see [Task::unload]. Unloaded tasks are recomputed when they are read
+/// again.
+///
+/// The memory usage is measured by [TurboMalloc], so garbage collection only
+/// happens when it's the global allocator.
+pub struct GcQueue {
+    memory_limit: usize,
+    /// Incremented on every collection. Tasks remember the generation in which
+    /// they were used last.
+    generation: AtomicU32,
+    /// Tasks that hold execution results, with the generation in which they
+    /// were enqueued. Entries are roughly ordered by generation, the oldest
+    /// come first.
+    queue: Mutex<VecDeque<(TaskId, u32)>>,
+    /// Set while a collection is scheduled or running.
+    scheduled: AtomicBool,
+    stats: Mutex<GcStats>,
+}
+
+impl GcQueue {
+    pub fn new(memory_limit: usize) -> Self {
+        Self {
+            memory_limit,
+            generation: AtomicU32::new(0),
+            queue: Mutex::new(VecDeque::new()),
+            scheduled: AtomicBool::new(false),
+            stats: Mutex::new(GcStats::default()),
+        }
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats.lock().clone()
+    }
+
+    /// Called when a task has finished execution. Enqueues the task and
+    /// schedules a collection when the memory limit is exceeded.
+    pub fn task_executed(
+        &self,
+        task: &Task,
+        backend: &MemoryBackend,
+        turbo_tasks: &dyn TurboTasksBackendApi,
+    ) {
+        let generation = self.generation();
+        task.gc_touch(generation);
+        if task.gc_enqueue() {
+            self.queue.lock().push_back((task.id(), generation));
+        }
+        if TurboMalloc::memory_usage() > self.memory_limit
+            && !self.scheduled.swap(true, Ordering::AcqRel)
+        {
+            turbo_tasks.schedule_backend_background_job(
+                backend.create_backend_job(Job::GarbageCollection),
+            );
+        }
+    }
+
+    /// Unloads the least recently used tasks until the memory usage is below
+    /// the target.
+    pub fn run_gc(&self, backend: &MemoryBackend) {
+        let start = Instant::now();
+        let target = self.memory_limit / 100 * TARGET_MEMORY_PERCENTAGE;
+        // Tasks used from now on have a newer generation than all entries in
+        // the queue, so they are not unloaded in this collection.
+        let generation = self.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        let mut evicted_tasks = 0;
+        let mut unloaded_cells = 0;
+
+        while TurboMalloc::memory_usage() > target {
+            let batch = {
+                let mut queue = self.queue.lock();
+                let mut batch = Vec::with_capacity(BATCH_SIZE);
+                while batch.len() < BATCH_SIZE {
+                    match queue.front() {
+                        Some(&(_, entry_generation)) if entry_generation < generation => {
+                            batch.extend(queue.pop_front());
+                        }
+                        _ => break,
+                    }
+                }
+                batch
+            };
+            if batch.is_empty() {
+                break;
+            }
+            let mut requeue = Vec::new();
+            for (id, entry_generation) in batch {
+                backend.with_task(id, |task| {
+                    let last_used = task.gc_last_used();
+                    if last_used > entry_generation {
+                        // The task has been used since it was enqueued
+                        requeue.push((id, last_used));
+                        return;
+                    }
+                    match task.unload(backend) {
+                        UnloadResult::Evicted { cells } => {
+                            evicted_tasks += 1;
+                            unloaded_cells += cells;
+                            task.gc_dequeue();
+                        }
+                        UnloadResult::Unloaded { cells } => {
+                            unloaded_cells += cells;
+                            // The task can be evicted in a later collection,
+                            // once nothing depends on it anymore
+                            requeue.push((id, generation));
+                        }
+                        UnloadResult::Active => {
+                            // Try again in a later collection
+                            requeue.push((id, generation));
+                        }
+                        UnloadResult::NotUnloadable => {
+                            // A task that is not done is enqueued again when
+                            // the execution finishes
+                            task.gc_dequeue();
+                        }
+                    }
+                });
+            }
+            if !requeue.is_empty() {
+                self.queue.lock().extend(requeue);
+            }
+        }
+
+        {
+            let mut stats = self.stats.lock();
+            stats.collections += 1;
+            stats.evicted_tasks += evicted_tasks;
+            stats.unloaded_cells += unloaded_cells;
+            stats.total_duration += start.elapsed();
+            stats.memory_usage = TurboMalloc::memory_usage();
+        }
+        self.scheduled.store(false, Ordering::Release);
+    }
+}
@@ -14,7 +14,7 @@ use rustc_hash::FxHasher;
 use tokio::task::futures::TaskLocalFuture;
 use turbo_tasks::{
     backend::{
-        Backend, BackendJobId, CellContent, PersistentTaskType, TaskExecutionSpec,
+        trace_cache_hit, Backend, BackendJobId, CellContent, PersistentTaskType, TaskExecutionSpec,
         TransientTaskType,
     },
     event::EventListener,
@@ -23,8 +23,10 @@ use turbo_tasks::{
 };
 
 use crate::{
+    gc::GcQueue,
     output::Output,
     scope::{TaskScope, TaskScopeId},
+    stats::GcStats,
     task::{
         run_add_to_scope_queue, run_remove_from_scope_queue, Task, TaskDependency,
         DEPENDENCIES_TO_TRACK,
@@ -39,6 +41,7 @@ pub struct MemoryBackend {
     backend_jobs: NoMoveVec<Job>,
     backend_job_id_factory: IdFactory<BackendJobId>,
     task_cache: DashMap<PersistentTaskType, TaskId, BuildHasherDefault<FxHasher>>,
+    pub(crate) gc_queue: Option<GcQueue>,
 }
 
 impl Default for MemoryBackend {
@@ -49,6 +52,18 @@ impl Default for MemoryBackend {
 
 impl MemoryBackend {
     pub fn new() -> Self {
+        Self::new_with_gc_queue(None)
+    }
+
+    /// Creates a backend that unloads the least recently used inactive tasks
+    /// when more than `memory_limit` bytes are allocated. Unloaded tasks are
+    /// recomputed on demand. This requires [turbo_malloc::TurboMalloc] to be
+    /// the global allocator.
+    pub fn with_memory_limit(memory_limit: usize) -> Self {
+        Self::new_with_gc_queue(Some(GcQueue::new(memory_limit)))
+    }
+
+    fn new_with_gc_queue(gc_queue: Option<GcQueue>) -> Self {
         let memory_task_scopes = NoMoveVec::new();
         let scope_id_factory = IdFactory::new();
         let initial_scope: TaskScopeId = scope_id_factory.get();
@@ -63,6 +78,7 @@ impl MemoryBackend {
             backend_jobs: NoMoveVec::new(),
             backend_job_id_factory: IdFactory::new(),
             task_cache: DashMap::default(),
+            gc_queue,
         }
     }
 
@@ -104,6 +120,18 @@ impl MemoryBackend {
         }
     }
 
+    /// Removes a task from the task cache, so calling the function again
+    /// creates a new task.
+    pub(crate) fn remove_from_task_cache(&self, task_type: &PersistentTaskType, id: TaskId) {
+        self.task_cache
+            .remove_if(task_type, |_, cached| *cached == id);
+    }
+
+    /// Returns statistics of the garbage collection, when it's enabled.
+    pub fn gc_stats(&self) -> Option<GcStats> {
+        self.gc_queue.as_ref().map(|gc_queue| gc_queue.stats())
+    }
+
     pub fn with_task<T>(&self, id: TaskId, func: impl FnOnce(&Task) -> T) -> T {
         func(self.memory_tasks.get(*id).unwrap())
     }
@@ -202,6 +230,10 @@ impl Backend for MemoryBackend {
         self.with_task(task, |task| task.get_description())
     }
 
+    fn get_task_name(&self, task: TaskId) -> Cow<'static, str> {
+        self.with_task(task, |task| task.get_name())
+    }
+
     type ExecutionScopeFuture<T: Future<Output = Result<()>> + Send + 'static> =
         TaskLocalFuture<RefCell<HashSet<TaskDependency>>, T>;
     fn execution_scope<T: Future<Output = Result<()>> + Send + 'static>(
@@ -246,10 +278,22 @@ impl Backend for MemoryBackend {
         turbo_tasks: &dyn TurboTasksBackendApi,
     ) -> bool {
         self.with_task(task, |task| {
-            task.execution_completed(duration, self, turbo_tasks)
+            let reschedule = task.execution_completed(duration, self, turbo_tasks);
+            if let Some(gc_queue) = &self.gc_queue {
+                gc_queue.task_executed(task, self, turbo_tasks);
+            }
+            reschedule
         })
     }
 
+    fn is_task_execution_stale(
+        &self,
+        task: TaskId,
+        _turbo_tasks: &dyn TurboTasksBackendApi,
+    ) -> bool {
+        self.with_task(task, |task| task.is_execution_stale())
+    }
+
     fn try_read_task_output(
         &self,
         task: TaskId,
@@ -298,17 +342,15 @@ impl Backend for MemoryBackend {
         task: TaskId,
         index: CellId,
         reader: TaskId,
-        _turbo_tasks: &dyn TurboTasksBackendApi,
+        turbo_tasks: &dyn TurboTasksBackendApi,
     ) -> Result<Result<CellContent, EventListener>> {
         if task == reader {
-            Ok(Ok(self.with_task(task, |task| {
-                task.with_cell_mut(index, |cell| cell.read_content_untracked())
-            })))
+            Ok(Ok(self.with_task(task, |task| task.read_own_cell(index))))
         } else {
             Task::add_dependency_to_current(TaskDependency::TaskCell(task, index));
-            Ok(Ok(self.with_task(task, |task| {
-                task.with_cell_mut(index, |cell| cell.read_content(reader))
-            })))
+            Ok(self.with_task(task, |task| {
+                task.read_cell(index, Some(reader), self, turbo_tasks)
+            }))
         }
     }
 
@@ -316,11 +358,9 @@ impl Backend for MemoryBackend {
         &self,
         task: TaskId,
         index: CellId,
-        _turbo_tasks: &dyn TurboTasksBackendApi,
+        turbo_tasks: &dyn TurboTasksBackendApi,
     ) -> Result<Result<CellContent, EventListener>> {
-        Ok(Ok(self.with_task(task, |task| {
-            task.with_cell(index, |cell| cell.read_content_untracked())
-        })))
+        Ok(self.with_task(task, |task| task.read_cell(index, None, self, turbo_tasks)))
     }
 
     fn track_read_task_cell(
@@ -381,9 +421,7 @@ impl Backend for MemoryBackend {
         content: CellContent,
         turbo_tasks: &dyn TurboTasksBackendApi,
     ) {
-        self.with_task(task, |task| {
-            task.with_cell_mut(index, |cell| cell.assign(content, turbo_tasks))
-        })
+        self.with_task(task, |task| task.assign_cell(index, content, turbo_tasks))
     }
 
     /// SAFETY: Must only called once with the same id
@@ -414,6 +452,7 @@ impl Backend for MemoryBackend {
     ) -> TaskId {
         let result = if let Some(task) = self.task_cache.get(&task_type).map(|task| *task) {
             // fast pass without creating a new task
+            trace_cache_hit(&task_type, task);
             self.connect_task_child(parent_task, task, turbo_tasks);
 
             // TODO maybe force (background) scheduling to avoid inactive tasks hanging in
@@ -451,6 +490,7 @@ impl Backend for MemoryBackend {
                         self.memory_tasks.remove(*id);
                         turbo_tasks.reuse_task_id(id);
                     }
+                    trace_cache_hit(entry.key(), *entry.get());
                     *entry.get()
                 }
             };
@@ -495,6 +535,9 @@ pub(crate) enum Job {
     /// Remove tasks from a scope. Scheduled by `run_remove_from_scope_queue` to
     /// split off work.
     RemoveFromScopeQueue(VecDeque<TaskId>, TaskScopeId),
+    /// Unloads tasks until the memory usage is below the limit. Scheduled by
+    /// the [GcQueue] when the limit is exceeded.
+    GarbageCollection,
 }
 
 impl Job {
@@ -527,6 +570,11 @@ impl Job {
             Job::RemoveFromScopeQueue(queue, id) => {
                 run_remove_from_scope_queue(queue, id, backend, turbo_tasks);
             }
+            Job::GarbageCollection => {
+                if let Some(gc_queue) = &backend.gc_queue {
+                    gc_queue.run_gc(backend);
+                }
+            }
         }
     }
 }
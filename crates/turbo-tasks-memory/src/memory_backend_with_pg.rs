@@ -17,16 +17,16 @@ use concurrent_queue::ConcurrentQueue;
 use dashmap::{mapref::entry::Entry, DashMap, DashSet};
 use turbo_tasks::{
     backend::{
-        Backend, BackendJobId, CellContent, PersistentTaskType, TaskExecutionSpec,
+        trace_cache_hit, Backend, BackendJobId, CellContent, PersistentTaskType, TaskExecutionSpec,
         TransientTaskType,
     },
     event::{Event, EventListener},
     persisted_graph::{
-        ActivateResult, DeactivateResult, PersistResult, PersistTaskState, PersistedGraph,
-        PersistedGraphApi, ReadTaskState, TaskCell, TaskData,
+        ActivateResult, DeactivateResult, ExternalStateCheck, PersistResult, PersistTaskState,
+        PersistedGraph, PersistedGraphApi, ReadTaskState, TaskCell, TaskData,
     },
     util::{IdFactory, NoMoveVec, SharedError},
-    CellId, RawVc, TaskId, TraitTypeId, TurboTasksBackendApi,
+    CellId, FunctionId, RawVc, SharedReference, TaskId, TraitTypeId, TurboTasksBackendApi,
 };
 
 type RootTaskFn =
@@ -147,6 +147,8 @@ pub struct MemoryBackendWithPersistedGraph<P: PersistedGraph> {
     persist_capacity: AtomicUsize,
     persist_job: BackendJobId,
     partial_lookups: DashMap<PersistentTaskType, bool>,
+    /// Checks persisted tasks reading external state on startup
+    external_state_check: Option<Box<dyn ExternalStateCheck>>,
     #[cfg(feature = "unsafe_once_map")]
     partial_lookup: turbo_tasks::util::OnceConcurrentlyMap<PersistentTaskType, bool>,
     #[cfg(not(feature = "unsafe_once_map"))]
@@ -158,6 +160,18 @@ pub struct MemoryBackendWithPersistedGraph<P: PersistedGraph> {
 
 impl<P: PersistedGraph> MemoryBackendWithPersistedGraph<P> {
     pub fn new(pg: P) -> Self {
+        Self::new_inner(pg, None)
+    }
+
+    /// Creates a backend that checks persisted tasks reading external state,
+    /// e. g. from disk, on startup. Outdated tasks are made dirty and are
+    /// recomputed once they are active. Dependent tasks are only invalidated
+    /// when their cells change.
+    pub fn with_external_state_check(pg: P, check: impl ExternalStateCheck + 'static) -> Self {
+        Self::new_inner(pg, Some(Box::new(check)))
+    }
+
+    fn new_inner(pg: P, external_state_check: Option<Box<dyn ExternalStateCheck>>) -> Self {
         let background_job_id_factory = IdFactory::new();
         let persist_job = background_job_id_factory.get();
         Self {
@@ -174,6 +188,7 @@ impl<P: PersistedGraph> MemoryBackendWithPersistedGraph<P> {
             persist_capacity: AtomicUsize::new(num_cpus::get()),
             persist_job,
             partial_lookups: DashMap::new(),
+            external_state_check,
             #[cfg(feature = "unsafe_once_map")]
             partial_lookup: turbo_tasks::util::OnceConcurrentlyMap::new(),
             #[cfg(not(feature = "unsafe_once_map"))]
@@ -903,6 +918,9 @@ impl<P: PersistedGraph> MemoryBackendWithPersistedGraph<P> {
 
 impl<P: PersistedGraph> Backend for MemoryBackendWithPersistedGraph<P> {
     fn startup(&self, turbo_tasks: &dyn TurboTasksBackendApi) {
+        if let Some(check) = &self.external_state_check {
+            self.check_external_state(&**check, turbo_tasks);
+        }
         let (tasks_to_activate, tasks_to_deactivate) =
             self.pg_get_pending_active_update(turbo_tasks);
         let tasks = self.pg_get_active_external_tasks(turbo_tasks);
@@ -1010,6 +1028,15 @@ impl<P: PersistedGraph> Backend for MemoryBackendWithPersistedGraph<P> {
         format!("{:?}", task_info.task_type)
     }
 
+    fn get_task_name(&self, task: TaskId) -> Cow<'static, str> {
+        let task_info = self.tasks.get(*task).unwrap();
+        match &task_info.task_type {
+            TaskType::Persistent(ty) => ty.get_name(),
+            TaskType::Root(_) => Cow::Borrowed("root"),
+            TaskType::Once(_) => Cow::Borrowed("once"),
+        }
+    }
+
     type ExecutionScopeFuture<T: Future<Output = Result<()>> + Send + 'static> = T;
     fn execution_scope<T: Future<Output = Result<()>> + Send + 'static>(
         &self,
@@ -1508,11 +1535,13 @@ impl<P: PersistedGraph> Backend for MemoryBackendWithPersistedGraph<P> {
         turbo_tasks: &dyn TurboTasksBackendApi,
     ) -> TaskId {
         if let Some(task) = self.cache.get(&task_type) {
+            trace_cache_hit(&task_type, *task);
             self.connect(parent_task, *task, turbo_tasks);
             return *task;
         }
         if let Some(task) = self.lookup(&task_type, turbo_tasks) {
             // a return value from lookup was already added to the cache by the id mapping
+            trace_cache_hit(&task_type, task);
             self.connect(parent_task, task, turbo_tasks);
             return task;
         }
@@ -1535,6 +1564,7 @@ impl<P: PersistedGraph> Backend for MemoryBackendWithPersistedGraph<P> {
         }
         match self.cache.entry(task_type) {
             Entry::Occupied(e) => {
+                trace_cache_hit(e.key(), *e.get());
                 let existing_task = *e.into_ref();
                 // SAFETY: We are still the only owner of this task and id
                 unsafe {
@@ -1808,6 +1838,76 @@ impl<P: PersistedGraph> MemoryBackendWithPersistedGraph<P> {
             .unwrap()
     }
 
+    fn pg_get_function_tasks(
+        &self,
+        function: FunctionId,
+        turbo_tasks: &dyn TurboTasksBackendApi,
+    ) -> Vec<TaskId> {
+        self.pg
+            .get_function_tasks(
+                function,
+                &MemoryBackendPersistedGraphApi {
+                    backend: self,
+                    turbo_tasks,
+                },
+            )
+            .unwrap()
+    }
+
+    /// Reads the content of a persisted cell, following task outputs.
+    fn pg_read_cell(
+        &self,
+        mut vc: RawVc,
+        turbo_tasks: &dyn TurboTasksBackendApi,
+    ) -> Option<SharedReference> {
+        loop {
+            match vc {
+                RawVc::TaskOutput(task) => vc = self.pg_read(task, turbo_tasks)?.0.output,
+                RawVc::TaskCell(task, index) => {
+                    let (data, _) = self.pg_read(task, turbo_tasks)?;
+                    return data
+                        .cells
+                        .into_iter()
+                        .find(|(cell, _)| *cell == index)
+                        .and_then(|(_, cell)| match cell {
+                            TaskCell::Content(CellContent(content)) => content,
+                            TaskCell::NeedComputation => None,
+                        });
+                }
+            }
+        }
+    }
+
+    /// Makes persisted tasks dirty that read external state which changed
+    /// since they have been persisted. Tasks that can't be checked are made
+    /// dirty too.
+    fn check_external_state(
+        &self,
+        check: &dyn ExternalStateCheck,
+        turbo_tasks: &dyn TurboTasksBackendApi,
+    ) {
+        for function in check.functions() {
+            for task in self.pg_get_function_tasks(function, turbo_tasks) {
+                let inputs = match &self.tasks.get(*task).unwrap().task_type {
+                    TaskType::Persistent(PersistentTaskType::Native(_, inputs)) => inputs,
+                    _ => continue,
+                };
+                let outdated = match self.pg_read(task, turbo_tasks) {
+                    Some((data, state)) if state.clean => check
+                        .is_outdated(function, inputs, data.output, &|vc| {
+                            self.pg_read_cell(vc, turbo_tasks)
+                        })
+                        .unwrap_or(true),
+                    // Dirty tasks are recomputed anyway
+                    _ => false,
+                };
+                if outdated {
+                    self.pg_make_dirty(task, turbo_tasks);
+                }
+            }
+        }
+    }
+
     fn pg_make_clean(&self, task: TaskId, turbo_tasks: &dyn TurboTasksBackendApi) {
         self.pg
             .make_clean(
@@ -6,6 +6,7 @@ use std::{
     time::Duration,
 };
 
+use serde_json::{json, Value};
 use turbo_tasks::{registry, FunctionId, TaskId, TraitTypeId};
 
 use crate::{
@@ -60,9 +61,16 @@ pub enum ReferenceType {
 pub struct TaskStats {
     pub count: usize,
     pub active_count: usize,
+    pub unloaded_count: usize,
     pub executions: usize,
     pub roots: usize,
     pub scopes: usize,
+    /// Number of cells with content
+    pub cells: usize,
+    /// Estimated memory used by the content of all cells, in bytes
+    pub memory: usize,
+    /// Time spent executing the tasks. It only includes the time the task
+    /// has been polled, so it approximates the CPU time.
     pub total_duration: Duration,
     pub total_current_duration: Duration,
     pub total_update_duration: Duration,
@@ -75,9 +83,12 @@ impl Default for TaskStats {
         Self {
             count: 0,
             active_count: 0,
+            unloaded_count: 0,
             executions: 0,
             roots: 0,
             scopes: 0,
+            cells: 0,
+            memory: 0,
             total_duration: Duration::ZERO,
             total_current_duration: Duration::ZERO,
             total_update_duration: Duration::ZERO,
@@ -87,6 +98,22 @@ impl Default for TaskStats {
     }
 }
 
+/// Statistics of the garbage collection of a [MemoryBackend] created with
+/// [MemoryBackend::with_memory_limit].
+#[derive(Default, Clone, Debug)]
+pub struct GcStats {
+    /// Number of collections that have run.
+    pub collections: usize,
+    /// Number of tasks that have been evicted in total.
+    pub evicted_tasks: usize,
+    /// Number of cells that have been unloaded in total.
+    pub unloaded_cells: usize,
+    /// Time spent in collections.
+    pub total_duration: Duration,
+    /// Memory usage after the last collection.
+    pub memory_usage: usize,
+}
+
 pub struct Stats {
     tasks: HashMap<TaskType, TaskStats>,
 }
@@ -123,15 +150,21 @@ impl Stats {
             total_duration,
             last_duration,
             executions,
+            cells,
+            memory,
             root_scoped,
             child_scopes,
             active,
+            unloaded,
         } = info;
         let stats = self.tasks.entry(ty).or_default();
         stats.count += 1;
         if active {
             stats.active_count += 1
         }
+        if unloaded {
+            stats.unloaded_count += 1
+        }
         stats.total_duration += total_duration;
         stats.total_current_duration += last_duration;
         if executions > 1 {
@@ -143,6 +176,8 @@ impl Stats {
             stats.roots += 1;
         }
         stats.scopes += child_scopes;
+        stats.cells += cells;
+        stats.memory += memory;
 
         let StatsReferences { tasks, .. } = task.get_stats_references();
         let set: HashSet<_> = tasks.into_iter().collect();
@@ -211,6 +246,36 @@ impl Stats {
         }
     }
 
+    /// Lists the stats of all task types as JSON, sorted by the estimated
+    /// memory of their cells. Durations are in microseconds and memory in
+    /// bytes.
+    pub fn to_json(&self) -> Value {
+        let mut tasks: Vec<_> = self.tasks.iter().collect();
+        tasks.sort_by(|(_, a), (_, b)| {
+            b.memory
+                .cmp(&a.memory)
+                .then(b.total_duration.cmp(&a.total_duration))
+        });
+        Value::Array(
+            tasks
+                .into_iter()
+                .map(|(ty, stats)| {
+                    json!({
+                        "name": ty.to_string(),
+                        "count": stats.count,
+                        "active_count": stats.active_count,
+                        "unloaded_count": stats.unloaded_count,
+                        "executions": stats.executions,
+                        "cells": stats.cells,
+                        "memory": stats.memory,
+                        "total_duration": stats.total_duration.as_micros() as u64,
+                        "max_duration": stats.max_duration.as_micros() as u64,
+                    })
+                })
+                .collect(),
+        )
+    }
+
     pub fn treeify(&self, tree_ref_type: ReferenceType) -> GroupTree {
         let mut incoming_references_count = self
             .tasks
@@ -8,6 +8,7 @@ use std::{
     hash::Hash,
     mem::{replace, take},
     pin::Pin,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering as AtomicOrdering},
     time::Duration,
 };
 
@@ -15,7 +16,7 @@ use anyhow::Result;
 use parking_lot::{Mutex, RwLock, RwLockWriteGuard};
 use tokio::task_local;
 use turbo_tasks::{
-    backend::PersistentTaskType,
+    backend::{CellContent, PersistentTaskType},
     event::{Event, EventListener},
     get_invalidator, registry, CellId, FunctionId, Invalidator, RawVc, TaskId, TaskInput,
     TraitTypeId, TurboTasksBackendApi, ValueTypeId,
@@ -117,6 +118,14 @@ pub struct Task {
     /// It will only be accessed from the task execution, which happens
     /// non-concurrently.
     execution_data: Mutex<TaskExecutionData>,
+    /// The garbage collection generation in which the task was used last.
+    gc_last_used: AtomicU32,
+    /// Whether the task is in the queue of the garbage collection.
+    gc_queued: AtomicBool,
+    /// Whether the task has been invalidated while it's in progress. This
+    /// mirrors the `InProgressDirty` state, so it can be checked on every
+    /// poll of the execution without locking the state.
+    execution_stale: AtomicBool,
 }
 
 /// Task data that is only modified during task execution.
@@ -160,8 +169,19 @@ struct TaskState {
     output: Output,
     // TODO use AutoMap here
     cells: HashMap<ValueTypeId, Vec<Cell>>,
+    /// Cell updates of the running execution. They are applied when the
+    /// execution completes, so dependent tasks are only notified once.
+    cell_updates: HashMap<CellId, CellContent>,
     event: Event,
 
+    /// Cells have been unloaded by the garbage collection. The task is
+    /// recomputed once one of them is read again.
+    unloaded: bool,
+    /// Nothing depended on the task anymore, so the garbage collection has
+    /// evicted it from the task cache and it doesn't track its dependencies
+    /// anymore. The task is recomputed once it's read again.
+    evicted: bool,
+
     // Stats:
     executions: u32,
     total_duration: Duration,
@@ -177,7 +197,10 @@ impl TaskState {
             collectibles: Default::default(),
             output: Default::default(),
             cells: Default::default(),
+            cell_updates: Default::default(),
             event: Event::new(move || format!("TaskState({id})::event")),
+            unloaded: false,
+            evicted: false,
             executions: Default::default(),
             total_duration: Default::default(),
             last_duration: Default::default(),
@@ -194,7 +217,10 @@ impl TaskState {
             collectibles: Default::default(),
             output: Default::default(),
             cells: Default::default(),
+            cell_updates: Default::default(),
             event: Event::new(move || format!("TaskState({id})::event")),
+            unloaded: false,
+            evicted: false,
             executions: Default::default(),
             total_duration: Default::default(),
             last_duration: Default::default(),
@@ -278,6 +304,8 @@ enum TaskStateType {
     /// Invalid execution is happening
     ///
     /// on finish this will move to Dirty or Scheduled depending on active flag
+    ///
+    /// the execution is cancelled at its next await point
     InProgressDirty,
 }
 
@@ -308,6 +336,9 @@ impl Task {
             ty: TaskType::Native(native_fn, bound_fn),
             state: RwLock::new(TaskState::new(id)),
             execution_data: Default::default(),
+            gc_last_used: Default::default(),
+            gc_queued: Default::default(),
+            execution_stale: Default::default(),
         }
     }
 
@@ -322,6 +353,9 @@ impl Task {
             ty: TaskType::ResolveNative(native_fn),
             state: RwLock::new(TaskState::new(id)),
             execution_data: Default::default(),
+            gc_last_used: Default::default(),
+            gc_queued: Default::default(),
+            execution_stale: Default::default(),
         }
     }
 
@@ -337,6 +371,9 @@ impl Task {
             ty: TaskType::ResolveTrait(trait_type, trait_fn_name),
             state: RwLock::new(TaskState::new(id)),
             execution_data: Default::default(),
+            gc_last_used: Default::default(),
+            gc_queued: Default::default(),
+            execution_stale: Default::default(),
         }
     }
 
@@ -351,6 +388,9 @@ impl Task {
             ty: TaskType::Root(Box::new(functor)),
             state: RwLock::new(TaskState::new_scheduled_in_scope(id, scope)),
             execution_data: Default::default(),
+            gc_last_used: Default::default(),
+            gc_queued: Default::default(),
+            execution_stale: Default::default(),
         }
     }
 
@@ -365,6 +405,9 @@ impl Task {
             ty: TaskType::Once(Mutex::new(Some(Box::pin(functor)))),
             state: RwLock::new(TaskState::new_scheduled_in_scope(id, scope)),
             execution_data: Default::default(),
+            gc_last_used: Default::default(),
+            gc_queued: Default::default(),
+            execution_stale: Default::default(),
         }
     }
 
@@ -393,6 +436,26 @@ impl Task {
         }
     }
 
+    /// The global name of the function executed by the task.
+    pub(crate) fn get_name(&self) -> Cow<'static, str> {
+        match &self.ty {
+            TaskType::Root(..) => Cow::Borrowed("root"),
+            TaskType::Once(..) => Cow::Borrowed("once"),
+            TaskType::Native(native_fn, _) => {
+                Cow::Borrowed(registry::get_function_global_name(*native_fn))
+            }
+            TaskType::ResolveNative(native_fn) => Cow::Owned(format!(
+                "[resolve] {}",
+                registry::get_function_global_name(*native_fn)
+            )),
+            TaskType::ResolveTrait(trait_type, fn_name) => Cow::Owned(format!(
+                "[resolve trait] {}::{}",
+                registry::get_trait(*trait_type).name,
+                fn_name
+            )),
+        }
+    }
+
     pub(crate) fn remove_dependency(dep: TaskDependency, reader: TaskId, backend: &MemoryBackend) {
         match dep {
             TaskDependency::TaskOutput(task) => {
@@ -471,6 +534,7 @@ impl Task {
             }
             Scheduled => {
                 state.state_type = InProgress;
+                self.execution_stale.store(false, AtomicOrdering::Release);
                 state.executions += 1;
                 // TODO we need to reconsider the approach of doing scope changes in background
                 // since they affect collectibles and need to be computed eagerly to allow
@@ -578,9 +642,22 @@ impl Task {
             let mut state = self.state.write();
             state.total_duration += duration;
             state.last_duration = duration;
+            let state = &mut *state;
+            let cell_updates = take(&mut state.cell_updates);
+            self.execution_stale.store(false, AtomicOrdering::Release);
             match state.state_type {
                 InProgress => {
+                    for (index, content) in cell_updates {
+                        let list = state.cells.entry(index.type_id).or_default();
+                        let i = index.index as usize;
+                        if list.len() <= i {
+                            list.resize_with(i + 1, Default::default);
+                        }
+                        list[i].assign(content, turbo_tasks);
+                    }
                     state.state_type = Done;
+                    state.unloaded = false;
+                    state.evicted = false;
                     for scope in state.scopes.iter() {
                         backend.with_scope(scope, |scope| {
                             scope.decrement_unfinished_tasks(backend);
@@ -661,10 +738,200 @@ impl Task {
             }
             InProgress => {
                 state.state_type = InProgressDirty;
+                self.execution_stale.store(true, AtomicOrdering::Release);
+            }
+        }
+    }
+
+    /// Unloads the task to free memory. Only tasks that are done, not active
+    /// and can be recomputed from their inputs are unloaded.
+    ///
+    /// Cells that no task depends on are dropped, and the task is recomputed
+    /// once one of them is read again. When nothing depends on the task at
+    /// all, it's evicted: it stops tracking its dependencies and is removed
+    /// from the task cache, so calling the function again creates a new task.
+    /// The evicted task is kept, as its id might still be stored in other
+    /// cells, and is recomputed when it's read again.
+    pub(crate) fn unload(&self, backend: &MemoryBackend) -> UnloadResult {
+        if matches!(self.ty, TaskType::Root(_) | TaskType::Once(_)) {
+            return UnloadResult::NotUnloadable;
+        }
+        let mut state = self.state.write();
+        if state.state_type != Done || state.evicted {
+            return UnloadResult::NotUnloadable;
+        }
+        if state
+            .scopes
+            .iter()
+            .any(|scope| backend.with_scope(scope, |scope| scope.state.lock().is_active()))
+        {
+            return UnloadResult::Active;
+        }
+        let mut cells = 0;
+        let mut has_dependent_tasks = !state.output.dependent_tasks.is_empty();
+        for list in state.cells.values_mut() {
+            for cell in list.iter_mut() {
+                if cell.unload() {
+                    cells += 1;
+                }
+                has_dependent_tasks |= cell.has_dependent_tasks();
+            }
+        }
+        if cells > 0 {
+            state.unloaded = true;
+        }
+        if has_dependent_tasks {
+            return UnloadResult::Unloaded { cells };
+        }
+        state.evicted = true;
+        // Dependencies are taken while the state is locked, so they can't be
+        // mixed up with the dependencies of the next execution.
+        let dependencies = take(&mut self.execution_data.lock().dependencies);
+        drop(state);
+        for dep in dependencies.into_iter() {
+            Task::remove_dependency(dep, self.id, backend);
+        }
+        if let Some(task_type) = self.persistent_task_type() {
+            backend.remove_from_task_cache(&task_type, self.id);
+        }
+        UnloadResult::Evicted { cells }
+    }
+
+    /// Reads a cell of the task. When the cell has been unloaded, the task is
+    /// recomputed and the returned listener is notified once it's done.
+    pub(crate) fn read_cell(
+        &self,
+        index: CellId,
+        reader: Option<TaskId>,
+        backend: &MemoryBackend,
+        turbo_tasks: &dyn TurboTasksBackendApi,
+    ) -> Result<CellContent, EventListener> {
+        self.gc_touch_current(backend);
+        {
+            // Most reads don't change the cell, so they only need a read lock
+            let state = self.state.read();
+            if let Some(content) = state.cell_updates.get(&index) {
+                if reader.is_none() {
+                    return Ok(content.clone());
+                }
+            } else {
+                let cell = state
+                    .cells
+                    .get(&index.type_id)
+                    .and_then(|list| list.get(index.index as usize));
+                match cell {
+                    Some(cell) if cell.is_unloaded() => {}
+                    Some(cell) => {
+                        if reader.map_or(true, |reader| cell.dependent_tasks.contains(&reader)) {
+                            return Ok(cell.read_content_untracked());
+                        }
+                    }
+                    None => {
+                        if reader.is_none() {
+                            return Ok(CellContent::default());
+                        }
+                    }
+                }
+            }
+        }
+        let mut state = self.state.write();
+        let state_ref = &mut *state;
+        let list = state_ref.cells.entry(index.type_id).or_default();
+        let i = index.index as usize;
+        if list.len() <= i {
+            list.resize_with(i + 1, Default::default);
+        }
+        let cell = &mut list[i];
+        if let Some(content) = state_ref.cell_updates.get(&index) {
+            // The reader depends on the cell, so it's notified when the update
+            // is applied.
+            if let Some(reader) = reader {
+                cell.track_read(reader);
+            }
+            return Ok(content.clone());
+        }
+        if cell.is_unloaded() {
+            return Err(self.recompute_unloaded(state, backend, turbo_tasks));
+        }
+        Ok(match reader {
+            Some(reader) => cell.read_content(reader),
+            None => cell.read_content_untracked(),
+        })
+    }
+
+    /// Schedules an unloaded task, when it isn't already, and returns a
+    /// listener for its completion. Unlike invalidated tasks, it isn't added
+    /// to the dirty lists of its scopes, as only the reader needs it.
+    fn recompute_unloaded(
+        &self,
+        mut state: RwLockWriteGuard<TaskState>,
+        backend: &MemoryBackend,
+        turbo_tasks: &dyn TurboTasksBackendApi,
+    ) -> EventListener {
+        let listener = state.event.listen();
+        match state.state_type {
+            Done => {
+                for scope in state.scopes.iter() {
+                    backend.with_scope(scope, |scope| {
+                        scope.increment_unfinished_tasks(backend);
+                    });
+                }
+                state.state_type = Scheduled;
+                drop(state);
+                turbo_tasks.schedule(self.id);
+            }
+            Dirty => {
+                state.state_type = Scheduled;
+                drop(state);
+                turbo_tasks.schedule(self.id);
             }
+            Scheduled | InProgress | InProgressDirty => {}
+        }
+        listener
+    }
+
+    /// Returns the key of the task in the task cache.
+    fn persistent_task_type(&self) -> Option<PersistentTaskType> {
+        Some(match &self.ty {
+            TaskType::Native(fn_id, _) => PersistentTaskType::Native(*fn_id, self.inputs.clone()),
+            TaskType::ResolveNative(fn_id) => {
+                PersistentTaskType::ResolveNative(*fn_id, self.inputs.clone())
+            }
+            TaskType::ResolveTrait(trait_type, fn_name) => {
+                PersistentTaskType::ResolveTrait(*trait_type, fn_name.clone(), self.inputs.clone())
+            }
+            TaskType::Root(_) | TaskType::Once(_) => return None,
+        })
+    }
+
+    pub(crate) fn id(&self) -> TaskId {
+        self.id
+    }
+
+    pub(crate) fn gc_last_used(&self) -> u32 {
+        self.gc_last_used.load(AtomicOrdering::Relaxed)
+    }
+
+    pub(crate) fn gc_touch(&self, generation: u32) {
+        self.gc_last_used.store(generation, AtomicOrdering::Relaxed);
+    }
+
+    fn gc_touch_current(&self, backend: &MemoryBackend) {
+        if let Some(gc_queue) = &backend.gc_queue {
+            self.gc_touch(gc_queue.generation());
         }
     }
 
+    /// Marks the task as enqueued for garbage collection. Returns `false` when
+    /// it already is.
+    pub(crate) fn gc_enqueue(&self) -> bool {
+        !self.gc_queued.swap(true, AtomicOrdering::AcqRel)
+    }
+
+    pub(crate) fn gc_dequeue(&self) {
+        self.gc_queued.store(false, AtomicOrdering::Release);
+    }
+
     pub(crate) fn schedule_when_dirty(&self, turbo_tasks: &dyn TurboTasksBackendApi) {
         let mut state = self.state.write();
         if state.state_type == TaskStateType::Dirty {
@@ -1176,6 +1443,48 @@ impl Task {
         func(&mut list[i])
     }
 
+    /// Assigns new content to a cell. Updates made during an execution are
+    /// buffered until it completes, updates from outside of an execution,
+    /// e. g. when restoring a recording, are applied immediately.
+    pub(crate) fn assign_cell(
+        &self,
+        index: CellId,
+        content: CellContent,
+        turbo_tasks: &dyn TurboTasksBackendApi,
+    ) {
+        let mut state = self.state.write();
+        if matches!(state.state_type, InProgress | InProgressDirty) {
+            state.cell_updates.insert(index, content);
+            return;
+        }
+        let list = state.cells.entry(index.type_id).or_default();
+        let i = index.index as usize;
+        if list.len() <= i {
+            list.resize_with(i + 1, Default::default);
+        }
+        list[i].assign(content, turbo_tasks)
+    }
+
+    /// Returns true when the task has been invalidated while it's in progress.
+    pub(crate) fn is_execution_stale(&self) -> bool {
+        self.execution_stale.load(AtomicOrdering::Acquire)
+    }
+
+    /// Reads a cell of the task from its own execution, including the
+    /// buffered updates of that execution.
+    pub(crate) fn read_own_cell(&self, index: CellId) -> CellContent {
+        let state = self.state.read();
+        if let Some(content) = state.cell_updates.get(&index) {
+            return content.clone();
+        }
+        state
+            .cells
+            .get(&index.type_id)
+            .and_then(|list| list.get(index.index as usize))
+            .map(|cell| cell.read_content_untracked())
+            .unwrap_or_default()
+    }
+
     /// Access to a cell.
     pub(crate) fn with_cell<T>(&self, index: CellId, func: impl FnOnce(&Cell) -> T) -> T {
         let state = self.state.read();
@@ -1209,11 +1518,20 @@ impl Task {
 
     pub fn get_stats_info(&self, backend: &MemoryBackend) -> TaskStatsInfo {
         let state = self.state.read();
+        let cell_sizes: Vec<_> = state
+            .cells
+            .values()
+            .flatten()
+            .filter_map(|cell| cell.estimate_content_size())
+            .collect();
         TaskStatsInfo {
             total_duration: state.total_duration,
             last_duration: state.last_duration,
             executions: state.executions,
+            cells: cell_sizes.len(),
+            memory: cell_sizes.into_iter().sum(),
             root_scoped: matches!(state.scopes, TaskScopes::Root(_)),
+            unloaded: state.unloaded,
             child_scopes: match state.scopes {
                 TaskScopes::Root(_) => 1,
                 TaskScopes::Inner(ref list, _) => list.len(),
@@ -1384,6 +1702,7 @@ impl Task {
         backend: &MemoryBackend,
         turbo_tasks: &dyn TurboTasksBackendApi,
     ) -> Result<Result<T, EventListener>> {
+        self.gc_touch_current(backend);
         let mut state = self.state.write();
         if strongly_consistent {
             state = self.ensure_root_scoped(state, backend, turbo_tasks);
@@ -1407,6 +1726,7 @@ impl Task {
             }
         }
         match state.state_type {
+            Done if state.evicted => Ok(Err(self.recompute_unloaded(state, backend, turbo_tasks))),
             Done => {
                 let result = func(&mut state.output)?;
                 drop(state);
@@ -1414,6 +1734,9 @@ impl Task {
                 Ok(Ok(result))
             }
             Dirty | Scheduled | InProgress | InProgressDirty => {
+                if state.evicted {
+                    return Ok(Err(self.recompute_unloaded(state, backend, turbo_tasks)));
+                }
                 let listener = state.event.listen();
                 drop(state);
                 Ok(Err(listener))
@@ -1583,7 +1906,24 @@ pub struct TaskStatsInfo {
     pub total_duration: Duration,
     pub last_duration: Duration,
     pub executions: u32,
+    /// Number of cells with content
+    pub cells: usize,
+    /// Estimated memory used by the content of all cells
+    pub memory: usize,
     pub root_scoped: bool,
+    pub unloaded: bool,
     pub child_scopes: usize,
     pub active: bool,
 }
+
+/// The outcome of [Task::unload].
+pub(crate) enum UnloadResult {
+    /// The task has been evicted, nothing depended on it.
+    Evicted { cells: usize },
+    /// Cells that no task depends on have been unloaded.
+    Unloaded { cells: usize },
+    /// The task is part of an active scope.
+    Active,
+    /// The task is not done or can't be recomputed.
+    NotUnloadable,
+}
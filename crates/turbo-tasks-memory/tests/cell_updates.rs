@@ -0,0 +1,149 @@
+#![feature(min_specialization)]
+
+use std::sync::Mutex;
+
+use anyhow::Result;
+use tokio::sync::Notify;
+use turbo_tasks::{get_invalidator, Invalidator};
+use turbo_tasks_testing::{register, run};
+
+register!();
+
+#[tokio::test]
+async fn reads_updates_during_execution() {
+    run! {
+        assert_eq!(*double_in_child(21).strongly_consistent().await?, 42);
+    }
+}
+
+#[tokio::test]
+async fn recomputes_stale_execution() {
+    run! {
+        let input = InputVc::new(1);
+        assert_eq!(*observe(input).strongly_consistent().await?, 1);
+
+        let state = input.await?;
+        state.set(2, true);
+        let (result, ()) = tokio::join!(observe(input).strongly_consistent(), async {
+            // Invalidates the execution while it's waiting at the gate
+            state.started.notified().await;
+            state.set(3, false);
+            state.resume.notify_one();
+        });
+        assert_eq!(*result?, 3);
+        assert_eq!(*doubled(input).await?.value, 6);
+
+        let state = state.state.lock().unwrap();
+        assert_eq!(state.executions, 3);
+        // The stale execution has been dropped at the gate
+        assert_eq!(state.completed, vec![1, 3]);
+        // Its cell update has been discarded
+        assert_eq!(state.observed, vec![1, 3]);
+    }
+}
+
+#[turbo_tasks::value(transparent)]
+struct Number(u32);
+
+#[turbo_tasks::function]
+async fn double_in_child(value: u32) -> Result<NumberVc> {
+    let number = NumberVc::cell(value);
+    // The cell is read by this execution and by another task before the
+    // execution has completed.
+    assert_eq!(*number.await?, value);
+    Ok(NumberVc::cell(*double(number).await?))
+}
+
+#[turbo_tasks::function]
+async fn double(number: NumberVc) -> Result<NumberVc> {
+    Ok(NumberVc::cell(*number.await? * 2))
+}
+
+#[turbo_tasks::value]
+struct Doubled {
+    number: NumberVc,
+    value: u32,
+}
+
+#[turbo_tasks::function]
+async fn doubled(input: InputVc) -> Result<DoubledVc> {
+    let state = input.await?;
+    let (value, gated) = state.get();
+    let number = NumberVc::cell(value);
+    if gated {
+        state.started.notify_one();
+        state.resume.notified().await;
+    }
+    let value = *number.await?;
+    state.state.lock().unwrap().completed.push(value);
+    Ok(Doubled {
+        number,
+        value: value * 2,
+    }
+    .cell())
+}
+
+#[turbo_tasks::function]
+async fn observe(input: InputVc) -> Result<NumberVc> {
+    let number = *doubled(input).await?.number.await?;
+    input.await?.state.lock().unwrap().observed.push(number);
+    Ok(NumberVc::cell(number))
+}
+
+#[turbo_tasks::value(serialization = "none", cell = "new", eq = "manual")]
+struct Input {
+    #[turbo_tasks(debug_ignore, trace_ignore)]
+    state: Mutex<InputState>,
+    /// Notified when a gated execution has reached the gate.
+    #[turbo_tasks(debug_ignore, trace_ignore)]
+    started: Notify,
+    /// Notified to let a gated execution continue.
+    #[turbo_tasks(debug_ignore, trace_ignore)]
+    resume: Notify,
+}
+
+#[derive(Default)]
+struct InputState {
+    value: u32,
+    gated: bool,
+    invalidator: Option<Invalidator>,
+    /// Number of executions that have read the input.
+    executions: u32,
+    /// Values of the executions that got past the gate.
+    completed: Vec<u32>,
+    /// Values of the number cell observed by the dependent task.
+    observed: Vec<u32>,
+}
+
+impl Input {
+    /// Returns the value and whether the execution has to wait at the gate.
+    /// The current task is invalidated when the value changes.
+    fn get(&self) -> (u32, bool) {
+        let mut state = self.state.lock().unwrap();
+        state.invalidator = Some(get_invalidator());
+        state.executions += 1;
+        (state.value, state.gated)
+    }
+
+    fn set(&self, value: u32, gated: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.value = value;
+        state.gated = gated;
+        if let Some(invalidator) = state.invalidator.take() {
+            invalidator.invalidate();
+        }
+    }
+}
+
+impl InputVc {
+    fn new(value: u32) -> Self {
+        InputVc::cell(Input {
+            state: Mutex::new(InputState {
+                value,
+                ..Default::default()
+            }),
+            started: Notify::new(),
+            resume: Notify::new(),
+        })
+    }
+}
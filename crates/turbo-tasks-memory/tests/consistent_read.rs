@@ -0,0 +1,150 @@
+#![feature(min_specialization)]
+
+use std::{sync::Mutex, time::Duration};
+
+use anyhow::Result;
+use turbo_tasks::{get_invalidator, Invalidator, StronglyConsistentReader};
+use turbo_tasks_testing::{register, run};
+
+register!();
+
+const SHORT: Duration = Duration::from_millis(50);
+const LONG: Duration = Duration::from_millis(500);
+
+#[tokio::test]
+async fn fresh_read() {
+    run! {
+        let reader = StronglyConsistentReader::<Number>::new(Some(SHORT));
+        let slow = SlowVc::new(1);
+        let result = reader.read(slow.get_value()).await?;
+        assert_eq!(result.value.0, 1);
+        assert!(!result.stale);
+    }
+}
+
+#[tokio::test]
+async fn stale_read_after_timeout() {
+    run! {
+        let reader = StronglyConsistentReader::<Number>::new(Some(SHORT));
+        let slow = SlowVc::new(1);
+        assert_eq!(reader.read(slow.get_value()).await?.value.0, 1);
+
+        slow.await?.set(2, LONG);
+        let result = reader.read(slow.get_value()).await?;
+        assert_eq!(result.value.0, 1);
+        assert!(result.stale);
+
+        tokio::time::sleep(LONG).await;
+        let result = reader.read(slow.get_value()).await?;
+        assert_eq!(result.value.0, 2);
+        assert!(!result.stale);
+    }
+}
+
+#[tokio::test]
+async fn waits_without_earlier_value() {
+    run! {
+        let reader = StronglyConsistentReader::<Number>::new(Some(SHORT));
+        let slow = SlowVc::new(1);
+        slow.await?.set(2, LONG);
+        let result = reader.read(slow.get_value()).await?;
+        assert_eq!(result.value.0, 2);
+        assert!(!result.stale);
+    }
+}
+
+#[tokio::test]
+async fn waits_without_timeout() {
+    run! {
+        let reader = StronglyConsistentReader::<Number>::new(None);
+        let slow = SlowVc::new(1);
+        assert_eq!(reader.read(slow.get_value()).await?.value.0, 1);
+
+        slow.await?.set(2, LONG);
+        let result = reader.read(slow.get_value()).await?;
+        assert_eq!(result.value.0, 2);
+        assert!(!result.stale);
+    }
+}
+
+#[tokio::test]
+async fn evicts_least_recently_used() {
+    run! {
+        let reader = StronglyConsistentReader::<Number>::with_max_entries(Some(SHORT), 2);
+        let a = SlowVc::new(1);
+        let b = SlowVc::new(10);
+        let c = SlowVc::new(100);
+        reader.read(a.get_value()).await?;
+        reader.read(b.get_value()).await?;
+
+        // Reading `a` stale marks it as recently used, so reading `c` evicts `b`
+        a.await?.set(2, LONG);
+        assert!(reader.read(a.get_value()).await?.stale);
+        reader.read(c.get_value()).await?;
+
+        b.await?.set(20, LONG);
+        let result = reader.read(b.get_value()).await?;
+        assert_eq!(result.value.0, 20);
+        assert!(!result.stale);
+
+        // Reading `b` again evicts `a`, but keeps `c`
+        c.await?.set(200, LONG);
+        let result = reader.read(c.get_value()).await?;
+        assert_eq!(result.value.0, 100);
+        assert!(result.stale);
+    }
+}
+
+#[turbo_tasks::value(transparent)]
+struct Number(u32);
+
+#[turbo_tasks::value(serialization = "none", cell = "new", eq = "manual")]
+struct Slow {
+    #[turbo_tasks(debug_ignore, trace_ignore)]
+    state: Mutex<SlowState>,
+}
+
+struct SlowState {
+    value: u32,
+    delay: Duration,
+    invalidator: Option<Invalidator>,
+}
+
+impl Slow {
+    /// Changes the value, which takes `delay` to compute.
+    fn set(&self, value: u32, delay: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.value = value;
+        state.delay = delay;
+        if let Some(invalidator) = state.invalidator.take() {
+            invalidator.invalidate();
+        }
+    }
+}
+
+impl SlowVc {
+    fn new(value: u32) -> Self {
+        SlowVc::cell(Slow {
+            state: Mutex::new(SlowState {
+                value,
+                delay: Duration::ZERO,
+                invalidator: None,
+            }),
+        })
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl SlowVc {
+    #[turbo_tasks::function]
+    pub async fn get_value(self) -> Result<NumberVc> {
+        let this = self.await?;
+        let (value, delay) = {
+            let mut state = this.state.lock().unwrap();
+            state.invalidator = Some(get_invalidator());
+            (state.value, state.delay)
+        };
+        tokio::time::sleep(delay).await;
+        Ok(NumberVc::cell(value))
+    }
+}
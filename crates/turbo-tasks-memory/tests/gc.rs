@@ -0,0 +1,103 @@
+#![feature(min_specialization)]
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use turbo_tasks::{NothingVc, RawVc, TurboTasks};
+use turbo_tasks_memory::MemoryBackend;
+use turbo_tasks_testing::register;
+
+register!();
+
+#[global_allocator]
+static ALLOC: turbo_malloc::TurboMalloc = turbo_malloc::TurboMalloc;
+
+/// A limit that is always exceeded, so every finished task triggers a
+/// collection.
+const MEMORY_LIMIT: usize = 1;
+
+#[tokio::test]
+async fn evicts_unused_tasks() {
+    *REGISTER;
+    let tt = TurboTasks::new(MemoryBackend::with_memory_limit(MEMORY_LIMIT));
+    let vc = tt
+        .run_once(async { Ok(RawVc::from(compute_a(1))) })
+        .await
+        .unwrap();
+    let value = vc.into_read_untracked::<Number>(&*tt).await.unwrap();
+    assert_eq!(value.0, 2);
+    assert_eq!(COMPUTE_A_EXECUTIONS.load(Ordering::SeqCst), 1);
+
+    for _ in 0..100 {
+        if tt.backend().gc_stats().unwrap().evicted_tasks > 0 {
+            break;
+        }
+        tt.run_once(async { Ok(()) }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(tt.backend().gc_stats().unwrap().evicted_tasks > 0);
+
+    // The evicted task is recomputed when it's read again
+    let value = vc.into_read_untracked::<Number>(&*tt).await.unwrap();
+    assert_eq!(value.0, 2);
+    assert_eq!(COMPUTE_A_EXECUTIONS.load(Ordering::SeqCst), 2);
+
+    // Calling the function again creates a new task
+    let new_vc = tt
+        .run_once(async { Ok(RawVc::from(compute_a(1))) })
+        .await
+        .unwrap();
+    assert_ne!(vc, new_vc);
+}
+
+#[tokio::test]
+async fn keeps_active_tasks() {
+    *REGISTER;
+    let tt = TurboTasks::new(MemoryBackend::with_memory_limit(MEMORY_LIMIT));
+    let root = tt.spawn_root_task(|| {
+        Box::pin(async {
+            compute_b(1).await?;
+            Ok(NothingVc::new().into())
+        })
+    });
+    tt.wait_task_completion(root, true).await.unwrap();
+    assert_eq!(COMPUTE_B_EXECUTIONS.load(Ordering::SeqCst), 1);
+
+    for _ in 0..100 {
+        if tt.backend().gc_stats().unwrap().collections > 0 {
+            break;
+        }
+        tt.run_once(async { Ok(()) }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(tt.backend().gc_stats().unwrap().collections > 0);
+
+    // The task is part of the active root scope, so it's neither unloaded nor
+    // recomputed
+    let value = tt
+        .run_once(async { Ok(compute_b(1).await?.0) })
+        .await
+        .unwrap();
+    assert_eq!(value, 2);
+    assert_eq!(COMPUTE_B_EXECUTIONS.load(Ordering::SeqCst), 1);
+}
+
+static COMPUTE_A_EXECUTIONS: AtomicUsize = AtomicUsize::new(0);
+static COMPUTE_B_EXECUTIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[turbo_tasks::value(transparent)]
+struct Number(u32);
+
+#[turbo_tasks::function]
+fn compute_a(value: u32) -> NumberVc {
+    COMPUTE_A_EXECUTIONS.fetch_add(1, Ordering::SeqCst);
+    NumberVc::cell(value * 2)
+}
+
+#[turbo_tasks::function]
+fn compute_b(value: u32) -> NumberVc {
+    COMPUTE_B_EXECUTIONS.fetch_add(1, Ordering::SeqCst);
+    NumberVc::cell(value * 2)
+}
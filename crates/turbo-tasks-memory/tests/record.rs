@@ -0,0 +1,133 @@
+#![feature(min_specialization)]
+
+use std::{
+    collections::HashSet,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use anyhow::Result;
+use turbo_tasks::{get_invalidator, NothingVc, TurboTasks};
+use turbo_tasks_memory::MemoryBackend;
+use turbo_tasks_testing::register;
+
+register!();
+
+#[tokio::test]
+async fn replays_without_mismatches() {
+    *REGISTER;
+    let path = recording_path("replays_without_mismatches");
+    record(&path, || async {
+        double(21).await?;
+        // The order of sets differs between processes
+        names(100).await?;
+        Ok(())
+    })
+    .await;
+
+    let tt = TurboTasks::new(MemoryBackend::new());
+    let report = tt.replay(&path).await.unwrap();
+    assert_eq!(report.tasks, 2);
+    assert_eq!(report.transient_tasks, 1);
+    assert_eq!(report.executions, 2);
+    assert!(report.mismatches.is_empty(), "{report}");
+}
+
+#[tokio::test]
+async fn detects_non_determinism() {
+    *REGISTER;
+    let path = recording_path("detects_non_determinism");
+    record(&path, || async {
+        counter().await?;
+        Ok(())
+    })
+    .await;
+
+    let tt = TurboTasks::new(MemoryBackend::new());
+    let report = tt.replay(&path).await.unwrap();
+    assert_eq!(report.mismatches.len(), 1, "{report}");
+    assert!(report.mismatches[0].message.starts_with("cell"));
+}
+
+#[tokio::test]
+async fn restores_external_state() {
+    *REGISTER;
+    let path = recording_path("restores_external_state");
+    EXTERNAL.store(1, Ordering::SeqCst);
+    record(&path, || async {
+        assert_eq!(*plus_one(read_external()).await?, 2);
+        Ok(())
+    })
+    .await;
+
+    // The replay sees the recorded external state
+    EXTERNAL.store(10, Ordering::SeqCst);
+    let tt = TurboTasks::new(MemoryBackend::new());
+    let report = tt.replay(&path).await.unwrap();
+    assert_eq!(report.external_executions, 1);
+    assert!(report.mismatches.is_empty(), "{report}");
+}
+
+fn recording_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "turbo-tasks-record-{}-{}.log",
+        std::process::id(),
+        name
+    ))
+}
+
+/// Records the executions of a root task calling `func`
+async fn record<F: Future<Output = Result<()>> + Send + 'static>(
+    path: &Path,
+    func: impl Fn() -> F + Send + Sync + 'static,
+) {
+    let tt = TurboTasks::with_recording(MemoryBackend::new(), path).unwrap();
+    let root = tt.spawn_root_task(move || {
+        let future = func();
+        Box::pin(async move {
+            future.await?;
+            Ok(NothingVc::new().into())
+        })
+    });
+    tt.wait_task_completion(root, true).await.unwrap();
+    tt.stop_and_wait().await;
+    tt.recording_result().unwrap();
+}
+
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+static EXTERNAL: AtomicU32 = AtomicU32::new(0);
+
+#[turbo_tasks::value(transparent)]
+struct Number(u32);
+
+#[turbo_tasks::value(transparent)]
+struct Names(HashSet<String>);
+
+#[turbo_tasks::function]
+fn double(value: u32) -> NumberVc {
+    NumberVc::cell(value * 2)
+}
+
+#[turbo_tasks::function]
+fn names(count: u32) -> NamesVc {
+    NamesVc::cell((0..count).map(|i| format!("name{i}")).collect())
+}
+
+#[turbo_tasks::function]
+fn counter() -> NumberVc {
+    NumberVc::cell(COUNTER.fetch_add(1, Ordering::SeqCst))
+}
+
+#[turbo_tasks::function]
+fn read_external() -> NumberVc {
+    // Tasks that read external state take an invalidator to get notified
+    // about changes
+    let _invalidator = get_invalidator();
+    NumberVc::cell(EXTERNAL.load(Ordering::SeqCst))
+}
+
+#[turbo_tasks::function]
+async fn plus_one(value: NumberVc) -> Result<NumberVc> {
+    Ok(NumberVc::cell(*value.await? + 1))
+}
@@ -0,0 +1,338 @@
+//! Inspection and maintenance of the database written by
+//! [RocksDbPersistedGraph](crate::RocksDbPersistedGraph).
+//!
+//! Task names are read from the database directly. Reading task data and
+//! showing cell contents requires the value types to be registered in the
+//! [registry], so tools using this module should call the `register()`
+//! function of all crates that might have written to the database.
+
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use bincode::Options;
+use serde::Deserialize;
+use turbo_tasks::{
+    debug::ValueDebugVc, macro_helpers::find_cell_by_type, persisted_graph::TaskData, registry,
+    RawVc, SharedReference,
+};
+
+use crate::{
+    db::{Database, InternalTaskState, TaskState},
+    persisted_graph::{SESSION_MARKER, VERSION_FILE},
+    table::CFStats,
+};
+
+/// Mirrors a serialized [PersistentTaskType], but keeps the global names
+/// instead of looking them up in the registry. The inputs are ignored.
+///
+/// [PersistentTaskType]: turbo_tasks::backend::PersistentTaskType
+#[derive(Deserialize)]
+enum SerializedTaskType {
+    Native(String),
+    ResolveNative(String),
+    ResolveTrait(String, String),
+}
+
+fn function_name(global_name: &str) -> String {
+    match registry::get_function_id_by_global_name(global_name) {
+        Some(id) => registry::get_function(id).name.clone(),
+        None => global_name.to_string(),
+    }
+}
+
+fn trait_name(global_name: &str) -> String {
+    match registry::get_trait_type_id_by_global_name(global_name) {
+        Some(id) => registry::get_trait(id).name.clone(),
+        None => global_name.to_string(),
+    }
+}
+
+fn task_name(task_type: &[u8]) -> Result<String> {
+    let ty: SerializedTaskType = bincode::DefaultOptions::new()
+        .allow_trailing_bytes()
+        .deserialize(task_type)?;
+    Ok(match ty {
+        SerializedTaskType::Native(f) => function_name(&f),
+        SerializedTaskType::ResolveNative(f) => format!("resolve {}", function_name(&f)),
+        SerializedTaskType::ResolveTrait(t, n) => {
+            format!("resolve trait {}::{}", trait_name(&t), n)
+        }
+    })
+}
+
+fn now() -> Result<u64> {
+    Ok(SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs())
+}
+
+pub struct TaskInfo {
+    /// The id of the task in the database
+    pub id: usize,
+    pub name: String,
+    /// Tasks that are not persisted are only referenced by other tasks
+    pub persisted: bool,
+    pub dirty: bool,
+    pub active: bool,
+    pub active_parents: u32,
+    pub externally_active: bool,
+    /// Seconds since the unix epoch. `None` when the task hasn't been read or
+    /// persisted since the database tracks it.
+    pub last_used: Option<u64>,
+}
+
+impl Display for TaskInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.id, self.name)?;
+        if !self.persisted {
+            write!(f, " [not persisted]")?;
+        }
+        if self.dirty {
+            write!(f, " [dirty]")?;
+        }
+        if self.active {
+            write!(f, " [active, {} active parents]", self.active_parents)?;
+        }
+        if self.externally_active {
+            write!(f, " [externally active]")?;
+        }
+        if let Some(last_used) = self.last_used {
+            let days = now().unwrap_or_default().saturating_sub(last_used) / (24 * 60 * 60);
+            write!(f, " (used {days} days ago)")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct CacheInspector {
+    database: Database,
+    path: PathBuf,
+}
+
+impl CacheInspector {
+    /// Opens the database at `path`. Fails when it's used by another process.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let database = Database::open(path)
+            .with_context(|| format!("unable to open the cache at {}", path.display()))?;
+        Ok(Self {
+            database,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// The version the database has been written with
+    pub fn version(&self) -> Option<String> {
+        fs::read_to_string(self.path.join(VERSION_FILE)).ok()
+    }
+
+    /// Returns false when the last session hasn't been stopped cleanly. The
+    /// database will be cleared when the next session starts.
+    pub fn is_consistent(&self) -> bool {
+        !self.path.join(SESSION_MARKER).exists()
+    }
+
+    pub fn stats(&self) -> Result<Vec<CFStats>> {
+        self.database.get_stats()
+    }
+
+    /// Lists all tasks that are known to the database
+    pub fn tasks(&self) -> Result<Vec<TaskInfo>> {
+        let db = &self.database;
+        let mut names = Vec::new();
+        db.task_type.for_each_raw(|key, value| {
+            let id: usize = bincode::DefaultOptions::new().deserialize(key)?;
+            names.push((id, task_name(value)?));
+            Ok(())
+        })?;
+        names
+            .into_iter()
+            .map(|(id, name)| {
+                let TaskState {
+                    internal,
+                    active,
+                    active_parents,
+                    externally_active,
+                } = db.state.get(&id)?.unwrap_or_default();
+                Ok(TaskInfo {
+                    id,
+                    name,
+                    persisted: internal.is_some(),
+                    dirty: matches!(internal, Some(InternalTaskState { clean: false })),
+                    active,
+                    active_parents,
+                    externally_active,
+                    last_used: db.last_used.get(&id)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Reads the persisted data of a task. Task ids in the data refer to ids
+    /// in the database.
+    pub fn task_data(&self, id: usize) -> Result<Option<TaskData>> {
+        self.database.data.get(&id).context(
+            "task data can't be deserialized, the crates that wrote it might not be registered",
+        )
+    }
+
+    /// Compacts all column families, which removes deleted and overwritten
+    /// entries from disk.
+    pub fn compact(&self) -> Result<()> {
+        self.database.compact()
+    }
+
+    /// Removes the data of all inactive tasks that haven't been used for
+    /// `max_age`. They will be recomputed when they are needed again. The
+    /// task types are kept, as other tasks might still refer to the task ids.
+    /// Returns the number of removed tasks.
+    pub fn prune(&self, max_age: Duration) -> Result<usize> {
+        let db = &self.database;
+        let cutoff = now()?.saturating_sub(max_age.as_secs());
+        let mut pruned = HashSet::new();
+        for (id, state) in db.state.get_all()? {
+            let TaskState {
+                internal,
+                active,
+                active_parents,
+                externally_active,
+            } = state;
+            if internal.is_none() || active || active_parents > 0 || externally_active {
+                continue;
+            }
+            if matches!(db.last_used.get(&id)?, Some(last_used) if last_used >= cutoff) {
+                continue;
+            }
+            pruned.insert(id);
+        }
+        let b = &mut db.batch();
+        for id in pruned.iter() {
+            // The task is no longer invalidated by its dependencies
+            let dependencies = db.dependencies.get(id).context(
+                "dependencies can't be deserialized, the crates that wrote them might not be \
+                 registered",
+            )?;
+            for dependency in dependencies.unwrap_or_default() {
+                db.dependents.remove(b, &dependency, id)?;
+            }
+            db.state.delete(b, id)?;
+            db.data.delete(b, id)?;
+            db.children.delete(b, id)?;
+            db.dependencies.delete(b, id)?;
+            db.last_used.delete(b, id)?;
+            db.potential_dirty_active_tasks.remove(b, &(), id)?;
+            db.pending_active_update.remove(b, &(), id)?;
+        }
+        // Parents would still refer to the pruned tasks as their children
+        for (parent, children) in db.children.get_all()? {
+            if pruned.contains(&parent) || !children.iter().any(|child| pruned.contains(child)) {
+                continue;
+            }
+            let children: Vec<usize> = children
+                .into_iter()
+                .filter(|child| !pruned.contains(child))
+                .collect();
+            db.children.write(b, &parent, &children)?;
+        }
+        b.write()?;
+        Ok(pruned.len())
+    }
+}
+
+/// Formats the content of a cell with
+/// [ValueDebug](turbo_tasks::debug::ValueDebug). Needs to be called within a
+/// turbo-tasks context, e. g. in
+/// [TurboTasks::run_once](turbo_tasks::TurboTasks::run_once). Falls back to
+/// the type name when the value can't be formatted, e. g. when it references
+/// other tasks, which are not loaded while inspecting the database.
+pub async fn debug_cell(content: SharedReference) -> String {
+    let ty = match content.0 {
+        Some(ty) => ty,
+        None => return content.to_string(),
+    };
+    let cell = find_cell_by_type(ty);
+    cell.update_shared_reference(content.clone());
+    let raw: RawVc = cell.into();
+    match ValueDebugVc::from(raw).dbg().await {
+        Ok(string) => string.as_str().to_string(),
+        Err(_) => content.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use turbo_tasks::{RawVc, TaskId};
+
+    use super::{now, CacheInspector};
+    use crate::db::{InternalTaskState, TaskState};
+
+    fn persisted() -> TaskState {
+        TaskState {
+            internal: Some(InternalTaskState { clean: true }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn prune_removes_unused_tasks() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let inspector = CacheInspector::open(dir.path())?;
+        let db = &inspector.database;
+        let now = now()?;
+        // Task 1 is the parent of 2 and 3. Task 2 depends on the output of 3
+        // and hasn't been used for a long time.
+        let output = RawVc::TaskOutput(TaskId::from(3));
+        let b = &mut db.batch();
+        for id in [1, 2, 3] {
+            db.state.write(b, &id, &persisted())?;
+        }
+        db.children.write(b, &1, &vec![2, 3])?;
+        db.dependencies.write(b, &2, &vec![output])?;
+        db.dependents.insert(b, &output, &2)?;
+        db.last_used.write(b, &1, &now)?;
+        db.last_used.write(b, &2, &0)?;
+        db.last_used.write(b, &3, &now)?;
+        b.write()?;
+
+        assert_eq!(inspector.prune(Duration::from_secs(24 * 60 * 60))?, 1);
+        assert!(db.state.get(&1)?.is_some());
+        assert!(db.state.get(&2)?.is_none());
+        assert!(db.state.get(&3)?.is_some());
+        assert_eq!(db.children.get(&1)?, Some(vec![3]));
+        assert!(db.dependencies.get(&2)?.is_none());
+        assert!(db.dependents.get_all(&output)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn prune_keeps_active_tasks() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let inspector = CacheInspector::open(dir.path())?;
+        let db = &inspector.database;
+        let b = &mut db.batch();
+        db.state.write(
+            b,
+            &1,
+            &TaskState {
+                active: true,
+                ..persisted()
+            },
+        )?;
+        db.state.write(b, &2, &TaskState::default())?;
+        b.write()?;
+
+        // Task 2 isn't persisted, so there is nothing to prune
+        assert_eq!(inspector.prune(Duration::ZERO)?, 0);
+        assert!(db.state.get(&1)?.is_some());
+        Ok(())
+    }
+}
@@ -2,10 +2,11 @@
 #![deny(unsafe_op_in_unsafe_fn)]
 
 mod db;
+pub mod inspect;
 mod persisted_graph;
 mod table;
 
-pub use persisted_graph::RocksDbPersistedGraph;
+pub use persisted_graph::{RocksDb, RocksDbPersistedGraph};
 
 #[doc(hidden)]
 pub mod private {
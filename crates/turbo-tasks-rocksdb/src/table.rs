@@ -433,6 +433,32 @@ macro_rules! table_base_internal {
                 iter.status()?;
                 Ok(stats)
             }
+
+            /// Calls `func` with the serialized key and value of every entry.
+            /// Useful when values can't be deserialized, e. g. because they
+            /// reference types that are not registered.
+            #[allow(dead_code)]
+            pub fn for_each_raw(&self, mut func: impl FnMut(&[u8], &[u8]) -> Result<()>) -> Result<()> {
+                let cf = self.db.cf_handle(stringify!($name)).unwrap();
+                let mut iter = self.db.raw_iterator_cf_opt(cf, $crate::table::get_default_read_options());
+                iter.seek_to_first();
+                while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+                    func(key, value)?;
+                    iter.next();
+                }
+                iter.status()?;
+                Ok(())
+            }
+
+            /// Compacts the whole column family, which removes deleted and
+            /// overwritten entries from disk.
+            pub fn compact(&self) {
+                let cf = self.db.cf_handle(stringify!($name)).unwrap();
+                self.db.compact_range_cf::<&[u8], &[u8]>(cf, None, None);
+                if let Some(cf) = self.db.cf_handle(concat!(stringify!($name), "_inverse")) {
+                    self.db.compact_range_cf::<&[u8], &[u8]>(cf, None, None);
+                }
+            }
         }
     };
 }
@@ -1279,6 +1305,23 @@ macro_rules! database {
                     )*];
                     Ok(results)
                 }
+
+                /// Writes all memtables to disk. The WAL is disabled, so
+                /// writes are lost on a crash until they are flushed.
+                pub fn flush(&self) -> Result<()> {
+                    let mut opt = rocksdb::FlushOptions::new();
+                    opt.set_wait(true);
+                    self.db.flush_opt(&opt)?;
+                    Ok(())
+                }
+
+                pub fn compact(&self) -> Result<()> {
+                    self.flush()?;
+                    $(
+                        self.$table.compact();
+                    )*
+                    Ok(())
+                }
             }
 
             impl std::fmt::Debug for Database {
@@ -1451,6 +1494,30 @@ pub struct CFStats {
     pub max_value_pair: (Vec<u8>, Vec<u8>),
 }
 
+impl CFStats {
+    /// Sums up the stats of multiple column families
+    pub fn total(name: &str, stats: &[CFStats]) -> CFStats {
+        let mut total = CFStats {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        for cf in stats {
+            total.entries += cf.entries;
+            total.total_key_size += cf.total_key_size;
+            total.total_value_size += cf.total_value_size;
+            if total.max_key_size < cf.max_key_size {
+                total.max_key_pair = cf.max_key_pair.clone();
+                total.max_key_size = cf.max_key_size;
+            }
+            if total.max_value_size < cf.max_value_size {
+                total.max_value_pair = cf.max_value_pair.clone();
+                total.max_value_size = cf.max_value_size;
+            }
+        }
+        total
+    }
+}
+
 impl Display for CFStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         fn start(v: &Vec<u8>) -> &[u8] {
@@ -202,6 +202,10 @@ impl TurboTasksApi for VcStorage {
         let cell = map.entry((task, index)).or_default();
         *cell = content;
     }
+
+    fn external_state_read(&self, _task: TaskId) {
+        // ignore
+    }
 }
 
 impl VcStorage {
@@ -92,6 +92,25 @@ impl PersistentTaskType {
         }
     }
 
+    /// The global name of the function called by the task. It's used to name
+    /// tracing spans.
+    pub fn get_name(&self) -> Cow<'static, str> {
+        match self {
+            PersistentTaskType::Native(native_fn, _) => {
+                Cow::Borrowed(registry::get_function_global_name(*native_fn))
+            }
+            PersistentTaskType::ResolveNative(native_fn, _) => Cow::Owned(format!(
+                "[resolve] {}",
+                registry::get_function_global_name(*native_fn)
+            )),
+            PersistentTaskType::ResolveTrait(trait_type, fn_name, _) => Cow::Owned(format!(
+                "[resolve trait] {}::{}",
+                registry::get_trait(*trait_type).name,
+                fn_name
+            )),
+        }
+    }
+
     pub fn partial(&self, len: usize) -> Self {
         match self {
             PersistentTaskType::Native(f, v) => PersistentTaskType::Native(*f, v[..len].to_vec()),
@@ -105,6 +124,17 @@ impl PersistentTaskType {
     }
 }
 
+/// Records a call that has been answered by an existing task. It's an instant
+/// event in the trace, as no function is executed for it.
+pub fn trace_cache_hit(task_type: &PersistentTaskType, task: TaskId) {
+    tracing::trace!(
+        name = %task_type.get_name(),
+        task_id = *task,
+        cache_hit = true,
+        "cache hit"
+    );
+}
+
 pub struct TaskExecutionSpec {
     pub future: Pin<Box<dyn Future<Output = Result<RawVc>> + Send>>,
 }
@@ -165,6 +195,12 @@ pub trait Backend: Sync + Send {
 
     fn get_task_description(&self, task: TaskId) -> String;
 
+    /// Returns a short name for the task, e. g. the global name of the function
+    /// it executes. It's used to name tracing spans.
+    fn get_task_name(&self, task: TaskId) -> Cow<'static, str> {
+        Cow::Owned(self.get_task_description(task))
+    }
+
     type ExecutionScopeFuture<T: Future<Output = Result<()>> + Send + 'static>: Future<Output = Result<()>>
         + Send
         + 'static;
@@ -195,6 +231,21 @@ pub trait Backend: Sync + Send {
         turbo_tasks: &dyn TurboTasksBackendApi,
     ) -> bool;
 
+    /// Returns true when the running execution of the task is stale, because
+    /// the task has been invalidated after the execution started. A stale
+    /// execution is cancelled at its next await point by dropping its future.
+    /// `task_execution_result` is not called for a cancelled execution, but
+    /// `task_execution_completed` is.
+    ///
+    /// This is checked on every poll of the execution, so it should be cheap.
+    fn is_task_execution_stale(
+        &self,
+        _task: TaskId,
+        _turbo_tasks: &dyn TurboTasksBackendApi,
+    ) -> bool {
+        false
+    }
+
     fn run_backend_job<'a>(
         &'a self,
         id: BackendJobId,
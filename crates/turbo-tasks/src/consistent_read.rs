@@ -0,0 +1,86 @@
+use std::{any::Any, sync::Mutex, time::Duration};
+
+use anyhow::Result;
+use indexmap::IndexMap;
+
+use crate::{RawVc, ReadRef};
+
+/// The result of a [StronglyConsistentReader] read
+pub struct MaybeStale<T> {
+    pub value: T,
+    /// The value is from an earlier read, as the strongly consistent read
+    /// didn't complete in time. It might not reflect the latest changes.
+    pub stale: bool,
+}
+
+/// Reads [RawVc]s strongly consistent with a deadline. When the deadline
+/// passes, the value of the last completed read of the same [RawVc] is
+/// returned and marked as stale, while the computation continues in the
+/// background. When there is no such value, the read keeps waiting.
+///
+/// The reader needs to be kept between reads to remember the completed
+/// values, e. g. for the lifetime of a server. Reads need to happen within a
+/// turbo-tasks context.
+pub struct StronglyConsistentReader<T> {
+    /// `None` disables the deadline, so reads are never stale.
+    timeout: Option<Duration>,
+    /// Remembers the last completed read per [RawVc] in least recently used
+    /// order. Limited to `max_entries`, as some [RawVc]s are never read again.
+    completed: Mutex<IndexMap<RawVc, ReadRef<T>>>,
+    max_entries: usize,
+}
+
+impl<T: Any + Send + Sync> StronglyConsistentReader<T> {
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self::with_max_entries(timeout, 1000)
+    }
+
+    pub fn with_max_entries(timeout: Option<Duration>, max_entries: usize) -> Self {
+        Self {
+            timeout,
+            completed: Mutex::new(IndexMap::new()),
+            max_entries,
+        }
+    }
+
+    /// Reads `vc` strongly consistent.
+    pub async fn read(&self, vc: impl Into<RawVc>) -> Result<MaybeStale<ReadRef<T>>> {
+        let vc = vc.into();
+        let mut read = Box::pin(vc.into_strongly_consistent_read::<T>());
+        if let Some(timeout) = self.timeout {
+            match tokio::time::timeout(timeout, &mut read).await {
+                Ok(value) => return Ok(self.complete(vc, value?)),
+                Err(_) => {
+                    if let Some(value) = self.get(vc) {
+                        return Ok(MaybeStale { value, stale: true });
+                    }
+                }
+            }
+        }
+        let value = read.await?;
+        Ok(self.complete(vc, value))
+    }
+
+    /// Returns the last completed value of `vc` and marks it as recently used.
+    fn get(&self, vc: RawVc) -> Option<ReadRef<T>> {
+        let mut completed = self.completed.lock().unwrap();
+        let value = completed.shift_remove(&vc)?;
+        completed.insert(vc, value.clone());
+        Some(value)
+    }
+
+    /// Remembers the completed value of `vc`, evicting the least recently used
+    /// value when the reader is full.
+    fn complete(&self, vc: RawVc, value: ReadRef<T>) -> MaybeStale<ReadRef<T>> {
+        let mut completed = self.completed.lock().unwrap();
+        completed.shift_remove(&vc);
+        if completed.len() >= self.max_entries {
+            completed.shift_remove_index(0);
+        }
+        completed.insert(vc, value.clone());
+        MaybeStale {
+            value,
+            stale: false,
+        }
+    }
+}
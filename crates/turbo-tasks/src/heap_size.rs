@@ -0,0 +1,203 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    mem::{size_of, size_of_val},
+    sync::Arc,
+};
+
+use indexmap::{IndexMap, IndexSet};
+pub use turbo_tasks_macros::HeapSize;
+
+/// Estimates the memory a value holds on the heap, excluding the size of the
+/// value itself. Automatically derived when using `turbo_tasks::value`, which
+/// allows to estimate the memory used by cells.
+///
+/// The derived implementation uses [autoref specialization] to fall back to
+/// `0` for fields that don't implement it, so the estimate is a lower bound.
+///
+/// [autoref specialization]: https://github.com/dtolnay/case-studies/blob/master/autoref-specialization/README.md
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+// Use autoref specialization to fall back to no heap memory if `T` does not
+// implement it directly, hence the `for &T` clause.
+impl<T: ?Sized> HeapSize for &T {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+macro_rules! no_heap_impls {
+    ( $( $ty:ty ),* ) => {
+        $(
+            impl HeapSize for $ty {
+                fn heap_size(&self) -> usize {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+no_heap_impls!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    crate::RawVc,
+    crate::TaskId
+);
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, |value| value.heap_size())
+    }
+}
+
+impl<T: HeapSize> HeapSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        size_of::<T>() + (**self).heap_size()
+    }
+}
+
+impl HeapSize for str {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl<T: HeapSize> HeapSize for [T] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(|v| v.heap_size()).sum()
+    }
+}
+
+/// The memory is shared with other references, so every reference only counts
+/// its share of it. That way the estimates of all references add up to the
+/// memory that is actually used.
+impl<T: HeapSize + ?Sized> HeapSize for Arc<T> {
+    fn heap_size(&self) -> usize {
+        (size_of_val(&**self) + (**self).heap_size()) / Arc::strong_count(self)
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(|v| v.heap_size()).sum::<usize>()
+    }
+}
+
+// Sets and maps only estimate the space of their entries, not the overhead of
+// their internal structure.
+macro_rules! set_impls {
+    ( $( $ty:ident ),* ) => {
+        $(
+            impl<T: HeapSize, S> HeapSize for $ty<T, S> {
+                fn heap_size(&self) -> usize {
+                    self.capacity() * size_of::<T>()
+                        + self.iter().map(|v| v.heap_size()).sum::<usize>()
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! map_impls {
+    ( $( $ty:ident ),* ) => {
+        $(
+            impl<K: HeapSize, V: HeapSize, S> HeapSize for $ty<K, V, S> {
+                fn heap_size(&self) -> usize {
+                    self.capacity() * size_of::<(K, V)>()
+                        + self
+                            .iter()
+                            .map(|(k, v)| k.heap_size() + v.heap_size())
+                            .sum::<usize>()
+                }
+            }
+        )*
+    };
+}
+
+set_impls!(HashSet, IndexSet);
+map_impls!(HashMap, IndexMap);
+
+impl<T: HeapSize> HeapSize for BTreeSet<T> {
+    fn heap_size(&self) -> usize {
+        self.len() * size_of::<T>() + self.iter().map(|v| v.heap_size()).sum::<usize>()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for BTreeMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.len() * size_of::<(K, V)>()
+            + self
+                .iter()
+                .map(|(k, v)| k.heap_size() + v.heap_size())
+                .sum::<usize>()
+    }
+}
+
+macro_rules! tuple_impls {
+    ( $( $name:ident )+ ) => {
+        impl<$($name: HeapSize),+> HeapSize for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn heap_size(&self) -> usize {
+                let ($($name,)+) = self;
+                0 $(+ $name.heap_size())+
+            }
+        }
+    };
+}
+
+tuple_impls! { A }
+tuple_impls! { A B }
+tuple_impls! { A B C }
+tuple_impls! { A B C D }
+tuple_impls! { A B C D E }
+tuple_impls! { A B C D E F }
+
+#[cfg(test)]
+mod tests {
+    use std::{mem::size_of, sync::Arc};
+
+    use super::HeapSize;
+
+    #[test]
+    fn arc_counts_its_share() {
+        let value = Arc::new(vec![0u64; 8]);
+        let size = size_of::<Vec<u64>>() + value.capacity() * size_of::<u64>();
+        assert_eq!(value.heap_size(), size);
+
+        let other = value.clone();
+        assert_eq!(value.heap_size(), size / 2);
+        assert_eq!(value.heap_size() + other.heap_size(), size);
+    }
+
+    #[test]
+    fn arc_of_unsized() {
+        let value: Arc<str> = Arc::from("hello world");
+        assert_eq!(value.heap_size(), 11);
+
+        let value: Arc<[String]> = Arc::from(vec![String::with_capacity(16)]);
+        assert_eq!(value.heap_size(), size_of::<String>() + 16);
+    }
+}
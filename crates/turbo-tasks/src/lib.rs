@@ -36,9 +36,11 @@
 pub mod backend;
 mod collectibles;
 mod completion;
+mod consistent_read;
 pub mod debug;
 mod display;
 pub mod event;
+pub mod heap_size;
 mod id;
 mod id_factory;
 mod join_iter_ext;
@@ -50,8 +52,10 @@ mod nothing;
 mod once_map;
 pub mod persisted_graph;
 pub mod primitives;
+mod priority;
 mod raw_vc;
 mod read_ref;
+pub mod record;
 pub mod registry;
 mod task_input;
 mod timed_future;
@@ -63,6 +67,7 @@ mod value_type;
 pub use anyhow::{Error, Result};
 pub use collectibles::CollectiblesSource;
 pub use completion::{Completion, CompletionVc, CompletionsVc};
+pub use consistent_read::{MaybeStale, StronglyConsistentReader};
 pub use display::{ValueToString, ValueToStringVc};
 pub use id::{
     with_task_id_mapping, without_task_id_mapping, FunctionId, IdMapping, TaskId, TraitTypeId,
@@ -70,12 +75,13 @@ pub use id::{
 };
 pub use join_iter_ext::{JoinIterExt, TryJoinIterExt};
 pub use manager::{
-    dynamic_call, emit, get_invalidator, run_once, spawn_blocking, spawn_thread, trait_call,
-    turbo_tasks, Invalidator, TaskIdProvider, TurboTasks, TurboTasksApi, TurboTasksBackendApi,
-    TurboTasksCallApi,
+    dynamic_call, emit, get_invalidator, run_once, run_once_with_priority, spawn_blocking,
+    spawn_thread, trait_call, turbo_tasks, Invalidator, TaskIdProvider, TurboTasks, TurboTasksApi,
+    TurboTasksBackendApi, TurboTasksCallApi,
 };
 pub use native_function::{NativeFunction, NativeFunctionVc};
 pub use nothing::{Nothing, NothingVc};
+pub use priority::TaskPriority;
 pub use raw_vc::{CellId, CollectiblesFuture, RawVc, ReadRawVcFuture, ResolveTypeError};
 pub use read_ref::ReadRef;
 pub use task_input::{FromTaskInput, SharedReference, SharedValue, TaskInput};
@@ -2,14 +2,16 @@ use std::{
     borrow::Cow,
     cell::RefCell,
     collections::{HashMap, HashSet},
-    future::Future,
+    future::{poll_fn, Future},
     hash::Hash,
     panic::AssertUnwindSafe,
+    path::Path,
     pin::Pin,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Mutex, Weak,
     },
+    task::Poll,
     thread,
     time::{Duration, Instant},
 };
@@ -19,13 +21,19 @@ use futures::FutureExt;
 use nohash_hasher::BuildNoHashHasher;
 use serde::{de::Visitor, Deserialize, Serialize};
 use tokio::{runtime::Handle, select, task_local};
+use tracing::Instrument;
 
 use crate::{
     backend::{Backend, CellContent, PersistentTaskType, TransientTaskType},
     event::{Event, EventListener},
     id::{BackendJobId, FunctionId, TraitTypeId},
     id_factory::IdFactory,
+    priority::{ExecutionPriority, Prioritized, PriorityScheduler, TaskPriority},
     raw_vc::{CellId, RawVc},
+    record::{
+        format_value, read_recording, values_equal, RecordedEvent, Recorder, ReplayMapping,
+        ReplayMismatch, ReplayReport,
+    },
     task_input::{SharedReference, TaskInput},
     timed_future::{self, TimedFuture},
     trace::TraceRawVcs,
@@ -108,6 +116,9 @@ pub trait TurboTasksApi: TurboTasksCallApi + Sync + Send {
 
     fn read_current_task_cell(&self, index: CellId) -> Result<CellContent>;
     fn update_current_task_cell(&self, index: CellId, content: CellContent);
+
+    /// Called when `task` takes an [Invalidator], as it reads external state
+    fn external_state_read(&self, task: TaskId);
 }
 
 pub trait TaskIdProvider {
@@ -180,6 +191,8 @@ pub struct TurboTasks<B: Backend + 'static> {
     event: Event,
     event_foreground: Event,
     event_background: Event,
+    recorder: Option<Recorder>,
+    scheduler: PriorityScheduler,
 }
 
 // TODO implement our own thread pool and make these thread locals instead
@@ -191,6 +204,10 @@ task_local! {
 
     static CURRENT_TASK_ID: TaskId;
 
+    /// The priority of the current task execution, or of the tasks scheduled
+    /// within [run_once_with_priority]
+    static CURRENT_PRIORITY: Arc<ExecutionPriority>;
+
     /// Affected [Task]s, that are tracked during task execution
     /// These tasks will be invalidated when the execution finishes
     /// or before reading a cell value
@@ -203,7 +220,19 @@ impl<B: Backend> TurboTasks<B> {
     // that should be safe as long tasks can't outlife turbo task
     // so we probably want to make sure that all tasks are joined
     // when trying to drop turbo tasks
-    pub fn new(mut backend: B) -> Arc<Self> {
+    pub fn new(backend: B) -> Arc<Self> {
+        Self::new_internal(backend, None)
+    }
+
+    /// Creates a new instance that records all task executions to the file
+    /// at `path`. The recording is complete once [TurboTasks::stop_and_wait]
+    /// has been called. It can be replayed with [TurboTasks::replay].
+    pub fn with_recording(backend: B, path: impl AsRef<Path>) -> Result<Arc<Self>> {
+        let recorder = Recorder::new(path.as_ref())?;
+        Ok(Self::new_internal(backend, Some(recorder)))
+    }
+
+    fn new_internal(mut backend: B, recorder: Option<Recorder>) -> Arc<Self> {
         let task_id_factory = IdFactory::new();
         backend.initialize(&task_id_factory);
         let this = Arc::new_cyclic(|this| Self {
@@ -220,6 +249,10 @@ impl<B: Backend> TurboTasks<B> {
             event: Event::new(|| "TurboTasks::event".to_string()),
             event_foreground: Event::new(|| "TurboTasks::event_foreground".to_string()),
             event_background: Event::new(|| "TurboTasks::event_background".to_string()),
+            recorder,
+            scheduler: PriorityScheduler::new(
+                std::thread::available_parallelism().map_or(1, |n| n.get()),
+            ),
         });
         this.backend.startup(&*this);
         this
@@ -240,6 +273,7 @@ impl<B: Backend> TurboTasks<B> {
         let id = self
             .backend
             .create_transient_task(TransientTaskType::Root(Box::new(functor)), self);
+        self.transient_task_created(id);
         self.schedule(id);
         id
     }
@@ -254,10 +288,17 @@ impl<B: Backend> TurboTasks<B> {
         let id = self
             .backend
             .create_transient_task(TransientTaskType::Once(Box::pin(future)), self);
+        self.transient_task_created(id);
         self.schedule(id);
         id
     }
 
+    fn transient_task_created(&self, task: TaskId) {
+        if let Some(recorder) = &self.recorder {
+            recorder.transient_task_created(task, self.backend.get_task_description(task));
+        }
+    }
+
     pub async fn run_once<T: TraceRawVcs + Send + 'static>(
         &self,
         future: impl Future<Output = Result<T>> + Send + 'static,
@@ -276,14 +317,30 @@ impl<B: Backend> TurboTasks<B> {
         Ok(rx.await?)
     }
 
+    fn get_or_create_persistent_task(&self, task_type: PersistentTaskType) -> TaskId {
+        let parent_task = current_task("turbo_function calls");
+        match &self.recorder {
+            Some(recorder) => {
+                let task = self.backend.get_or_create_persistent_task(
+                    task_type.clone(),
+                    parent_task,
+                    self,
+                );
+                recorder.task_created(task, &task_type, || self.backend.get_task_description(task));
+                task
+            }
+            None => self
+                .backend
+                .get_or_create_persistent_task(task_type, parent_task, self),
+        }
+    }
+
     /// Call a native function with arguments.
     /// All inputs must be resolved.
     pub(crate) fn native_call(&self, func: FunctionId, inputs: Vec<TaskInput>) -> RawVc {
-        RawVc::TaskOutput(self.backend.get_or_create_persistent_task(
-            PersistentTaskType::Native(func, inputs),
-            current_task("turbo_function calls"),
-            self,
-        ))
+        RawVc::TaskOutput(
+            self.get_or_create_persistent_task(PersistentTaskType::Native(func, inputs)),
+        )
     }
 
     /// Calls a native function with arguments. Resolves arguments when needed
@@ -292,11 +349,9 @@ impl<B: Backend> TurboTasks<B> {
         if inputs.iter().all(|i| i.is_resolved() && !i.is_nothing()) {
             self.native_call(func, inputs)
         } else {
-            RawVc::TaskOutput(self.backend.get_or_create_persistent_task(
-                PersistentTaskType::ResolveNative(func, inputs),
-                current_task("turbo_function calls"),
-                self,
-            ))
+            RawVc::TaskOutput(
+                self.get_or_create_persistent_task(PersistentTaskType::ResolveNative(func, inputs)),
+            )
         }
     }
 
@@ -308,16 +363,24 @@ impl<B: Backend> TurboTasks<B> {
         trait_fn_name: Cow<'static, str>,
         inputs: Vec<TaskInput>,
     ) -> RawVc {
-        RawVc::TaskOutput(self.backend.get_or_create_persistent_task(
-            PersistentTaskType::ResolveTrait(trait_type, trait_fn_name, inputs),
-            current_task("turbo_function calls"),
-            self,
-        ))
+        RawVc::TaskOutput(
+            self.get_or_create_persistent_task(PersistentTaskType::ResolveTrait(
+                trait_type,
+                trait_fn_name,
+                inputs,
+            )),
+        )
     }
 
     pub(crate) fn schedule(&self, task_id: TaskId) {
         self.begin_primary_job();
         self.scheduled_tasks.fetch_add(1, Ordering::AcqRel);
+        // Tasks inherit the priority of the task that scheduled them
+        let priority = CURRENT_PRIORITY
+            .try_with(|priority| priority.get())
+            .unwrap_or_default();
+        let scheduled = self.scheduler.start(task_id, priority);
+        let execution_priority = scheduled.priority().clone();
 
         #[cfg(feature = "tokio_tracing")]
         let description = self.backend.get_task_description(task_id);
@@ -329,15 +392,34 @@ impl<B: Backend> TurboTasks<B> {
                     break;
                 }
                 if let Some(execution) = this.backend.try_start_task_execution(task_id, &*this) {
+                    let span = tracing::info_span!(
+                        "turbo_tasks::function",
+                        name = %this.backend.get_task_name(task_id),
+                        task_id = *task_id,
+                        cache_hit = false,
+                        duration_us = tracing::field::Empty,
+                    );
+                    let mut future = execution.future;
+                    // The execution is dropped when it becomes stale, as its
+                    // result won't be used anyway
+                    let cancellable = poll_fn(|cx| {
+                        if this.backend.is_task_execution_stale(task_id, &*this) {
+                            return Poll::Ready(None);
+                        }
+                        future.as_mut().poll(cx).map(Some)
+                    });
                     // Setup thread locals
+                    let prioritized = Prioritized::new(&this.scheduler, &scheduled, cancellable);
                     let (result, duration) = CELL_COUNTERS
                         .scope(Default::default(), async {
                             let (result, duration) =
-                                TimedFuture::new(AssertUnwindSafe(execution.future).catch_unwind())
+                                TimedFuture::new(AssertUnwindSafe(prioritized).catch_unwind())
+                                    .instrument(span.clone())
                                     .await;
                             (result, duration)
                         })
                         .await;
+                    span.record("duration_us", duration.as_micros() as u64);
                     if cfg!(feature = "log_function_stats") && duration.as_millis() > 1000 {
                         println!(
                             "{} took {}",
@@ -345,14 +427,23 @@ impl<B: Backend> TurboTasks<B> {
                             FormatDuration(duration)
                         )
                     }
-                    let result = result.map_err(|any| match any.downcast::<String>() {
-                        Ok(owned) => Some(Cow::Owned(*owned)),
-                        Err(any) => match any.downcast::<&'static str>() {
-                            Ok(str) => Some(Cow::Borrowed(*str)),
-                            Err(_) => None,
-                        },
-                    });
-                    this.backend.task_execution_result(task_id, result, &*this);
+                    let result = match result {
+                        Ok(Some(result)) => Some(Ok(result)),
+                        Ok(None) => None,
+                        Err(any) => Some(Err(match any.downcast::<String>() {
+                            Ok(owned) => Some(Cow::Owned(*owned)),
+                            Err(any) => match any.downcast::<&'static str>() {
+                                Ok(str) => Some(Cow::Borrowed(*str)),
+                                Err(_) => None,
+                            },
+                        })),
+                    };
+                    if let Some(recorder) = &this.recorder {
+                        recorder.execution_completed(task_id, &result);
+                    }
+                    if let Some(result) = result {
+                        this.backend.task_execution_result(task_id, result, &*this);
+                    }
                     this.notify_scheduled_tasks_internal();
                     let reexecute = this
                         .backend
@@ -364,6 +455,7 @@ impl<B: Backend> TurboTasks<B> {
                     break;
                 }
             }
+            this.scheduler.finish(&scheduled);
             this.finish_primary_job();
             anyhow::Ok(())
         };
@@ -372,9 +464,12 @@ impl<B: Backend> TurboTasks<B> {
             self.pin(),
             CURRENT_TASK_ID.scope(
                 task_id,
-                TASKS_TO_NOTIFY.scope(
-                    Default::default(),
-                    self.backend.execution_scope(task_id, future),
+                CURRENT_PRIORITY.scope(
+                    execution_priority,
+                    TASKS_TO_NOTIFY.scope(
+                        Default::default(),
+                        self.backend.execution_scope(task_id, future),
+                    ),
                 ),
             ),
         );
@@ -520,6 +615,193 @@ impl<B: Backend> TurboTasks<B> {
             }
         }
         self.backend.stop(self);
+        if let Some(recorder) = &self.recorder {
+            // Errors are reported by `recording_result`
+            let _ = recorder.flush();
+        }
+    }
+
+    /// Returns whether the recording of an instance created with
+    /// [TurboTasks::with_recording] has been written completely. It should be
+    /// checked after [TurboTasks::stop_and_wait].
+    pub fn recording_result(&self) -> Result<()> {
+        match &self.recorder {
+            Some(recorder) => recorder.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Raises the priority of `task` to the priority of the current task,
+    /// as the current task is waiting for it
+    fn raise_priority(&self, task: TaskId) {
+        if let Ok(priority) = CURRENT_PRIORITY.try_with(|priority| priority.get()) {
+            self.scheduler.raise(task, priority);
+        }
+    }
+
+    /// Waits until no tasks are scheduled or executing
+    async fn wait_idle(&self) {
+        loop {
+            let listener = self.event.listen();
+            if self.currently_scheduled_tasks.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            listener.await;
+        }
+    }
+
+    /// Replays a recording written by an instance created with
+    /// [TurboTasks::with_recording]. All recorded tasks are created again,
+    /// the recorded invalidations are repeated and the results of all
+    /// executions are compared with the recorded ones. The same functions and
+    /// value types need to be registered as when recording.
+    pub async fn replay(&self, path: impl AsRef<Path>) -> Result<ReplayReport> {
+        let events = read_recording(path.as_ref())?;
+        let this = self.pin();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        // Runs as process, so it doesn't count as running task while waiting
+        // for other tasks to settle
+        let task_id = self.run_once_process(Box::pin(async move {
+            let report = this.replay_events(events).await?;
+            tx.send(report)
+                .map_err(|_| anyhow!("unable to send result"))?;
+            Ok(())
+        }));
+        // INVALIDATION: A Once task will never invalidate, therefore we don't need to
+        // track a dependency
+        let raw_result = read_task_output_untracked(self, task_id, false).await?;
+        raw_result.into_read_untracked::<Nothing>(self).await?;
+        Ok(rx.await?)
+    }
+
+    async fn replay_events(&self, events: Vec<RecordedEvent>) -> Result<ReplayReport> {
+        let mut report = ReplayReport::default();
+        let mut mapping = ReplayMapping::default();
+        let mut descriptions = HashMap::new();
+        for event in events {
+            match event {
+                RecordedEvent::Task {
+                    id,
+                    description,
+                    task_type,
+                } => {
+                    let task_type = match task_type {
+                        Some(task_type) => mapping.deserialize::<PersistentTaskType>(&task_type)?,
+                        None => None,
+                    };
+                    let task_type = match task_type {
+                        Some(task_type) => task_type,
+                        None => {
+                            report.skipped_tasks += 1;
+                            continue;
+                        }
+                    };
+                    let task = self.backend.get_or_create_persistent_task(
+                        task_type,
+                        current_task("replaying"),
+                        self,
+                    );
+                    mapping.insert(id, task);
+                    descriptions.insert(id, description);
+                    report.tasks += 1;
+                }
+                RecordedEvent::TransientTask { .. } => {
+                    report.transient_tasks += 1;
+                }
+                RecordedEvent::Execution {
+                    task: id,
+                    cells,
+                    output,
+                    external,
+                } => {
+                    let task = match mapping.get(id) {
+                        Some(task) => task,
+                        None => continue,
+                    };
+                    report.executions += 1;
+                    let mut mismatch = |message: String| {
+                        report.mismatches.push(ReplayMismatch {
+                            task: descriptions[&id].clone(),
+                            message,
+                        })
+                    };
+                    // INVALIDATION: Replaying doesn't need to be invalidated
+                    let replayed_output = read_task_output_untracked(self, task, false)
+                        .await
+                        .map(|output| mapping.serialize(&output));
+                    if external {
+                        // The external state might have changed since the
+                        // recording, so the recorded cells are restored. That
+                        // invalidates all tasks that have read them.
+                        for (index, recorded) in cells {
+                            let recorded = match recorded {
+                                Some(recorded) => recorded,
+                                None => continue,
+                            };
+                            match mapping.deserialize::<CellContent>(&recorded) {
+                                Ok(Some(content)) => {
+                                    self.backend.update_task_cell(task, index, content, self)
+                                }
+                                Ok(None) => {}
+                                Err(err) => {
+                                    mismatch(format!("cell {index} can't be restored: {err:#}"))
+                                }
+                            }
+                        }
+                        self.notify_scheduled_tasks();
+                        self.wait_idle().await;
+                        report.external_executions += 1;
+                        continue;
+                    }
+                    match (output, replayed_output) {
+                        (Ok(recorded), Ok(Some(replayed)))
+                            if !values_equal(&recorded, &replayed) =>
+                        {
+                            mismatch(format!(
+                                "output {} instead of {}",
+                                format_value(&replayed),
+                                format_value(&recorded)
+                            ))
+                        }
+                        (Ok(_), Err(err)) => mismatch(format!("failed with {err:#}")),
+                        (Err(err), Ok(_)) => {
+                            mismatch(format!("succeeded, but the recording failed with {err}"))
+                        }
+                        _ => {}
+                    }
+                    for (index, recorded) in cells {
+                        let recorded = match recorded {
+                            Some(recorded) => recorded,
+                            None => continue,
+                        };
+                        // INVALIDATION: Replaying doesn't need to be invalidated
+                        match read_task_cell_untracked(self, task, index).await {
+                            Ok(content) => match mapping.serialize(&content) {
+                                Some(replayed) if !values_equal(&replayed, &recorded) => {
+                                    mismatch(format!(
+                                        "cell {} is {} instead of {}",
+                                        index,
+                                        format_value(&replayed),
+                                        format_value(&recorded)
+                                    ))
+                                }
+                                _ => {}
+                            },
+                            Err(err) => mismatch(format!("cell {index} can't be read: {err:#}")),
+                        }
+                    }
+                }
+                RecordedEvent::Invalidation { task: id } => {
+                    if let Some(task) = mapping.get(id) {
+                        self.backend.invalidate_task(task, self);
+                        // Executions caused by the invalidation need to be
+                        // completed before comparing them
+                        self.wait_idle().await;
+                    }
+                }
+            }
+        }
+        Ok(report)
     }
 
     pub(crate) fn schedule_background_job<
@@ -627,6 +909,9 @@ impl<B: Backend> TurboTasksCallApi for TurboTasks<B> {
 
 impl<B: Backend> TurboTasksApi for TurboTasks<B> {
     fn invalidate(&self, task: TaskId) {
+        if let Some(recorder) = &self.recorder {
+            recorder.invalidated(task);
+        }
         self.backend.invalidate_task(task, self);
     }
 
@@ -645,6 +930,7 @@ impl<B: Backend> TurboTasksApi for TurboTasks<B> {
         task: TaskId,
         strongly_consistent: bool,
     ) -> Result<Result<RawVc, EventListener>> {
+        self.raise_priority(task);
         self.backend.try_read_task_output(
             task,
             current_task("reading Vcs"),
@@ -658,6 +944,7 @@ impl<B: Backend> TurboTasksApi for TurboTasks<B> {
         task: TaskId,
         strongly_consistent: bool,
     ) -> Result<Result<RawVc, EventListener>> {
+        self.raise_priority(task);
         self.backend
             .try_read_task_output_untracked(task, strongly_consistent, self)
     }
@@ -667,6 +954,7 @@ impl<B: Backend> TurboTasksApi for TurboTasks<B> {
         task: TaskId,
         index: CellId,
     ) -> Result<Result<CellContent, EventListener>> {
+        self.raise_priority(task);
         self.backend
             .try_read_task_cell(task, index, current_task("reading Vcs"), self)
     }
@@ -676,6 +964,7 @@ impl<B: Backend> TurboTasksApi for TurboTasks<B> {
         task: TaskId,
         index: CellId,
     ) -> Result<Result<CellContent, EventListener>> {
+        self.raise_priority(task);
         self.backend.try_read_task_cell_untracked(task, index, self)
     }
 
@@ -736,12 +1025,17 @@ impl<B: Backend> TurboTasksApi for TurboTasks<B> {
     }
 
     fn update_current_task_cell(&self, index: CellId, content: CellContent) {
-        self.backend.update_task_cell(
-            current_task("cellting turbo_tasks values"),
-            index,
-            content,
-            self,
-        );
+        let task = current_task("cellting turbo_tasks values");
+        if let Some(recorder) = &self.recorder {
+            recorder.cell_updated(task, index, &content);
+        }
+        self.backend.update_task_cell(task, index, content, self);
+    }
+
+    fn external_state_read(&self, task: TaskId) {
+        if let Some(recorder) = &self.recorder {
+            recorder.external_state_read(task);
+        }
     }
 }
 
@@ -928,6 +1222,22 @@ pub async fn run_once<T: Send + 'static>(
     Ok(rx.await?)
 }
 
+/// Like [run_once], but the once task and all tasks it schedules are executed
+/// with the given priority. Tasks with a higher priority are polled first
+/// when there are more tasks to poll than threads.
+pub async fn run_once_with_priority<T: Send + 'static>(
+    tt: Arc<dyn TurboTasksApi>,
+    priority: TaskPriority,
+    future: impl Future<Output = Result<T>> + Send + 'static,
+) -> Result<T> {
+    CURRENT_PRIORITY
+        .scope(
+            Arc::new(ExecutionPriority::new(priority)),
+            run_once(tt, future),
+        )
+        .await
+}
+
 /// see [TurboTasks] `dynamic_call`
 pub fn dynamic_call(func: FunctionId, inputs: Vec<TaskInput>) -> RawVc {
     with_turbo_tasks(|tt| tt.dynamic_call(func, inputs))
@@ -973,8 +1283,10 @@ pub fn current_task_for_testing() -> TaskId {
 /// based on external events.
 pub fn get_invalidator() -> Invalidator {
     let handle = Handle::current();
+    let task = current_task("turbo_tasks::get_invalidator()");
+    with_turbo_tasks(|tt| tt.external_state_read(task));
     Invalidator {
-        task: current_task("turbo_tasks::get_invalidator()"),
+        task,
         turbo_tasks: weak_turbo_tasks(),
         handle,
     }
@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     backend::{CellContent, PersistentTaskType},
-    CellId, RawVc, TaskId,
+    CellId, FunctionId, RawVc, SharedReference, TaskId, TaskInput,
 };
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -173,6 +173,16 @@ pub trait PersistedGraph: Sync + Send {
     /// return a list of active tasks that should be scheduled
     fn make_dependent_dirty(&self, vc: RawVc, api: &dyn PersistedGraphApi) -> Result<Vec<TaskId>>;
 
+    /// get all stored tasks of a native function.
+    /// This is usually called at the beginning for functions that read
+    /// external state (e. g. the file system), which might have changed
+    /// between sessions.
+    fn get_function_tasks(
+        &self,
+        function: FunctionId,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Vec<TaskId>>;
+
     /// Get all tasks that are active, but not persisted.
     /// This is usually called at beginning to create and schedule
     /// tasks that are missing in the persisted graph
@@ -196,6 +206,24 @@ pub trait PersistedGraph: Sync + Send {
     }
 }
 
+/// Checks persisted tasks that read external state (e. g. the file system) on
+/// startup, as the state might have changed while no session was running.
+pub trait ExternalStateCheck: Send + Sync {
+    /// The native functions whose persisted tasks are checked
+    fn functions(&self) -> Vec<FunctionId>;
+
+    /// Returns true when the persisted result of a task of `function` with
+    /// `inputs` is outdated. `read_cell` reads persisted cells, e. g. of the
+    /// inputs or the `output` of the task.
+    fn is_outdated(
+        &self,
+        function: FunctionId,
+        inputs: &[TaskInput],
+        output: RawVc,
+        read_cell: &dyn Fn(RawVc) -> Option<SharedReference>,
+    ) -> Result<bool>;
+}
+
 pub trait PersistedGraphApi {
     fn get_or_create_task_type(&self, ty: PersistentTaskType) -> TaskId;
 
@@ -313,6 +341,14 @@ impl PersistedGraph for () {
         Ok(Vec::new())
     }
 
+    fn get_function_tasks(
+        &self,
+        _function: FunctionId,
+        _api: &dyn PersistedGraphApi,
+    ) -> Result<Vec<TaskId>> {
+        Ok(Vec::new())
+    }
+
     fn get_active_external_tasks(&self, _api: &dyn PersistedGraphApi) -> Result<Vec<TaskId>> {
         Ok(Vec::new())
     }
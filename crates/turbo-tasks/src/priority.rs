@@ -0,0 +1,404 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use crate::TaskId;
+
+/// The priority of a task execution. Tasks inherit the priority of the task
+/// that scheduled them, and the priority of a task is raised when a task with
+/// a higher priority reads it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TaskPriority {
+    /// Work nobody is waiting for, e. g. eager compilation
+    Low,
+    #[default]
+    Normal,
+    /// Work somebody is waiting for, e. g. an HTTP request
+    High,
+}
+
+const PRIORITY_COUNT: usize = 3;
+
+impl TaskPriority {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => TaskPriority::Low,
+            1 => TaskPriority::Normal,
+            _ => TaskPriority::High,
+        }
+    }
+}
+
+/// The priority of an execution, which can be raised while it's running
+pub(crate) struct ExecutionPriority(AtomicU8);
+
+impl ExecutionPriority {
+    pub fn new(priority: TaskPriority) -> Self {
+        Self(AtomicU8::new(priority as u8))
+    }
+
+    pub fn get(&self) -> TaskPriority {
+        TaskPriority::from_u8(self.0.load(Ordering::Acquire))
+    }
+
+    fn set(&self, priority: TaskPriority) {
+        self.0.store(priority as u8, Ordering::Release)
+    }
+}
+
+/// Identifies an execution of a task. A task can be scheduled again while its
+/// previous execution is still finishing, so executions are not identified by
+/// their [TaskId].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct ExecutionId(u64);
+
+/// An execution registered with the [PriorityScheduler]
+pub(crate) struct Execution {
+    id: ExecutionId,
+    task: TaskId,
+    priority: Arc<ExecutionPriority>,
+}
+
+impl Execution {
+    pub fn priority(&self) -> &Arc<ExecutionPriority> {
+        &self.priority
+    }
+}
+
+enum Waiting {
+    Queued(Waker),
+    /// A permit has been handed over, but the execution hasn't been polled yet
+    Granted,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    /// Number of executions that are currently polled
+    polling: usize,
+    /// The latest execution of each task, which is raised when the task is
+    /// read
+    tasks: HashMap<TaskId, (ExecutionId, Arc<ExecutionPriority>)>,
+    /// Executions waiting for a permit by priority
+    queues: [VecDeque<ExecutionId>; PRIORITY_COUNT],
+    waiting: HashMap<ExecutionId, Waiting>,
+}
+
+/// Limits the number of task executions that are polled at the same time, and
+/// hands out permits to executions with higher priority first. Permits are
+/// only held while polling, so executions waiting for other tasks never block
+/// them.
+pub(crate) struct PriorityScheduler {
+    limit: usize,
+    next_id: AtomicU64,
+    state: Mutex<SchedulerState>,
+    /// Number of executions per priority. Allows to skip raising priorities
+    /// without locking when there is nothing to raise.
+    counts: [AtomicUsize; PRIORITY_COUNT],
+}
+
+impl PriorityScheduler {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            next_id: AtomicU64::new(0),
+            state: Default::default(),
+            counts: Default::default(),
+        }
+    }
+
+    /// Registers a new execution of `task`. When the task is still executing,
+    /// the new execution keeps the priority of the previous one if it's
+    /// higher, as readers raise the priority of a task only once.
+    pub fn start(&self, task: TaskId, priority: TaskPriority) -> Execution {
+        let id = ExecutionId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut state = self.state.lock().unwrap();
+        let priority = match state.tasks.get(&task) {
+            Some((_, previous)) => priority.max(previous.get()),
+            None => priority,
+        };
+        let execution_priority = Arc::new(ExecutionPriority::new(priority));
+        state.tasks.insert(task, (id, execution_priority.clone()));
+        self.counts[priority as usize].fetch_add(1, Ordering::AcqRel);
+        Execution {
+            id,
+            task,
+            priority: execution_priority,
+        }
+    }
+
+    /// Unregisters the `execution`
+    pub fn finish(&self, execution: &Execution) {
+        let mut state = self.state.lock().unwrap();
+        self.counts[execution.priority.get() as usize].fetch_sub(1, Ordering::AcqRel);
+        if let Entry::Occupied(e) = state.tasks.entry(execution.task) {
+            if e.get().0 == execution.id {
+                e.remove();
+            }
+        }
+        match state.waiting.remove(&execution.id) {
+            Some(Waiting::Granted) => self.release_locked(&mut state),
+            Some(Waiting::Queued(_)) => {
+                for queue in state.queues.iter_mut() {
+                    queue.retain(|id| *id != execution.id);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Raises the priority of the latest execution of `task` to `priority`,
+    /// if it's executing with a lower priority.
+    pub fn raise(&self, task: TaskId, priority: TaskPriority) {
+        if self.counts[..priority as usize]
+            .iter()
+            .all(|count| count.load(Ordering::Acquire) == 0)
+        {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if let Some((id, execution_priority)) = state.tasks.get(&task).cloned() {
+            self.raise_locked(&mut state, id, &execution_priority, priority);
+        }
+    }
+
+    fn raise_locked(
+        &self,
+        state: &mut SchedulerState,
+        id: ExecutionId,
+        execution_priority: &ExecutionPriority,
+        priority: TaskPriority,
+    ) {
+        let old_priority = execution_priority.get();
+        if old_priority >= priority {
+            return;
+        }
+        execution_priority.set(priority);
+        self.counts[old_priority as usize].fetch_sub(1, Ordering::AcqRel);
+        self.counts[priority as usize].fetch_add(1, Ordering::AcqRel);
+        if let Some(Waiting::Queued(_)) = state.waiting.get(&id) {
+            state.queues[old_priority as usize].retain(|queued| *queued != id);
+            state.queues[priority as usize].push_back(id);
+        }
+    }
+
+    fn poll_acquire(&self, execution: &Execution, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.waiting.get_mut(&execution.id) {
+            Some(Waiting::Granted) => {
+                state.waiting.remove(&execution.id);
+                return Poll::Ready(());
+            }
+            Some(Waiting::Queued(waker)) => {
+                if !waker.will_wake(cx.waker()) {
+                    *waker = cx.waker().clone();
+                }
+                return Poll::Pending;
+            }
+            None => {}
+        }
+        let priority = execution.priority.get();
+        if state.polling < self.limit
+            && state.queues[priority as usize..]
+                .iter()
+                .all(|queue| queue.is_empty())
+        {
+            state.polling += 1;
+            return Poll::Ready(());
+        }
+        state.queues[priority as usize].push_back(execution.id);
+        state
+            .waiting
+            .insert(execution.id, Waiting::Queued(cx.waker().clone()));
+        Poll::Pending
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.release_locked(&mut state);
+    }
+
+    /// Hands the permit over to the waiting execution with the highest
+    /// priority
+    fn release_locked(&self, state: &mut SchedulerState) {
+        let next = state
+            .queues
+            .iter_mut()
+            .rev()
+            .find_map(|queue| queue.pop_front());
+        match next {
+            Some(id) => {
+                if let Some(Waiting::Queued(waker)) = state.waiting.insert(id, Waiting::Granted) {
+                    waker.wake();
+                }
+            }
+            None => state.polling -= 1,
+        }
+    }
+}
+
+/// Polls the execution of a task only while it holds a permit of the
+/// [PriorityScheduler]
+pub(crate) struct Prioritized<'a, F> {
+    scheduler: &'a PriorityScheduler,
+    execution: &'a Execution,
+    future: F,
+}
+
+impl<'a, F> Prioritized<'a, F> {
+    pub fn new(scheduler: &'a PriorityScheduler, execution: &'a Execution, future: F) -> Self {
+        Self {
+            scheduler,
+            execution,
+            future,
+        }
+    }
+}
+
+/// Releases the permit even when polling panics
+struct Permit<'a>(&'a PriorityScheduler);
+
+impl<'a> Drop for Permit<'a> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+impl<'a, F: Future + Unpin> Future for Prioritized<'a, F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.scheduler.poll_acquire(this.execution, cx).is_pending() {
+            return Poll::Pending;
+        }
+        let _permit = Permit(this.scheduler);
+        Pin::new(&mut this.future).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+    };
+
+    use futures::task::{waker, ArcWake};
+
+    use super::{PriorityScheduler, TaskPriority};
+    use crate::TaskId;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl ArcWake for CountingWaker {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn grants_permits_by_priority() {
+        let scheduler = PriorityScheduler::new(1);
+        let woken = Arc::new(CountingWaker::default());
+        let waker = waker(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let running = scheduler.start(TaskId::from(1), TaskPriority::Low);
+        let normal = scheduler.start(TaskId::from(2), TaskPriority::Normal);
+        let high = scheduler.start(TaskId::from(3), TaskPriority::High);
+        assert_eq!(scheduler.poll_acquire(&running, &mut cx), Poll::Ready(()));
+        assert_eq!(scheduler.poll_acquire(&normal, &mut cx), Poll::Pending);
+        assert_eq!(scheduler.poll_acquire(&high, &mut cx), Poll::Pending);
+
+        scheduler.release();
+        assert_eq!(woken.0.load(Ordering::SeqCst), 1);
+        assert_eq!(scheduler.poll_acquire(&normal, &mut cx), Poll::Pending);
+        assert_eq!(scheduler.poll_acquire(&high, &mut cx), Poll::Ready(()));
+
+        scheduler.release();
+        assert_eq!(scheduler.poll_acquire(&normal, &mut cx), Poll::Ready(()));
+    }
+
+    #[test]
+    fn raises_queued_executions() {
+        let scheduler = PriorityScheduler::new(1);
+        let waker = waker(Arc::new(CountingWaker::default()));
+        let mut cx = Context::from_waker(&waker);
+
+        let running = scheduler.start(TaskId::from(1), TaskPriority::Normal);
+        let normal = scheduler.start(TaskId::from(2), TaskPriority::Normal);
+        let low = scheduler.start(TaskId::from(3), TaskPriority::Low);
+        assert_eq!(scheduler.poll_acquire(&running, &mut cx), Poll::Ready(()));
+        assert_eq!(scheduler.poll_acquire(&normal, &mut cx), Poll::Pending);
+        assert_eq!(scheduler.poll_acquire(&low, &mut cx), Poll::Pending);
+
+        scheduler.raise(TaskId::from(3), TaskPriority::High);
+        assert_eq!(low.priority().get(), TaskPriority::High);
+        scheduler.release();
+        assert_eq!(scheduler.poll_acquire(&low, &mut cx), Poll::Ready(()));
+    }
+
+    #[test]
+    fn finishing_a_previous_execution_keeps_the_next_one() {
+        let scheduler = PriorityScheduler::new(1);
+        let woken = Arc::new(CountingWaker::default());
+        let waker = waker(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+        let task = TaskId::from(1);
+
+        let running = scheduler.start(TaskId::from(2), TaskPriority::Normal);
+        assert_eq!(scheduler.poll_acquire(&running, &mut cx), Poll::Ready(()));
+
+        // The task is scheduled again while the previous execution is still
+        // waiting for a permit
+        let previous = scheduler.start(task, TaskPriority::Normal);
+        assert_eq!(scheduler.poll_acquire(&previous, &mut cx), Poll::Pending);
+        let next = scheduler.start(task, TaskPriority::Low);
+        assert_eq!(next.priority().get(), TaskPriority::Normal);
+        assert_eq!(scheduler.poll_acquire(&next, &mut cx), Poll::Pending);
+        scheduler.finish(&previous);
+
+        // Raising the task raises the next execution
+        scheduler.raise(task, TaskPriority::High);
+        assert_eq!(next.priority().get(), TaskPriority::High);
+
+        // The next execution is still queued and gets the permit
+        scheduler.release();
+        assert_eq!(woken.0.load(Ordering::SeqCst), 1);
+        assert_eq!(scheduler.poll_acquire(&next, &mut cx), Poll::Ready(()));
+        scheduler.release();
+        scheduler.finish(&next);
+        scheduler.finish(&running);
+
+        // All permits have been returned
+        let executions = [
+            scheduler.start(TaskId::from(3), TaskPriority::Normal),
+            scheduler.start(TaskId::from(4), TaskPriority::Normal),
+        ];
+        assert_eq!(
+            scheduler.poll_acquire(&executions[0], &mut cx),
+            Poll::Ready(())
+        );
+        assert_eq!(
+            scheduler.poll_acquire(&executions[1], &mut cx),
+            Poll::Pending
+        );
+        let counts = scheduler
+            .counts
+            .iter()
+            .map(|count| count.load(Ordering::SeqCst))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![0, 2, 0]);
+    }
+}
@@ -0,0 +1,407 @@
+//! Recording of task executions, to reproduce a session later with
+//! [TurboTasks::replay](crate::TurboTasks::replay).
+//!
+//! A recording contains one JSON encoded event per line. Task ids are replaced
+//! by ids that are only valid within the recording. Values that are not
+//! serializable can't be recorded, so tasks with such inputs are not replayed
+//! and cells with such values are not compared. Transient tasks, e. g. root
+//! tasks, are recorded, but can't be created again while replaying.
+//!
+//! Tasks that read external state, e. g. files, are recognized by taking an
+//! [Invalidator](crate::Invalidator). Their cells are restored from the
+//! recording while replaying, so the replay sees the same external state as
+//! the recorded session.
+
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    backend::{CellContent, PersistentTaskType},
+    with_task_id_mapping, CellId, IdMapping, RawVc, TaskId,
+};
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum RecordedEvent {
+    /// A persistent task has been created. The task type is missing when the
+    /// inputs are not serializable.
+    Task {
+        id: usize,
+        description: String,
+        task_type: Option<Value>,
+    },
+    /// A transient task has been created, e. g. a root task
+    TransientTask { id: usize, description: String },
+    /// An execution of a task has completed. Contains all cells that have
+    /// been updated by the execution. `external` is set when the execution
+    /// has read external state.
+    Execution {
+        task: usize,
+        cells: Vec<(CellId, Option<Value>)>,
+        output: Result<Value, String>,
+        external: bool,
+    },
+    /// A task has been invalidated externally, e. g. by a file watcher
+    Invalidation { task: usize },
+}
+
+#[derive(Default)]
+struct RecordedTasks {
+    ids: HashMap<TaskId, usize>,
+    /// Tasks that have a [RecordedEvent::Task] event
+    created: HashSet<TaskId>,
+}
+
+struct RecordingWriter {
+    writer: BufWriter<File>,
+    /// The first error that occurred while writing. No further events are
+    /// written after that, as the recording is incomplete anyway.
+    error: Option<String>,
+}
+
+/// Writes all events of a [TurboTasks](crate::TurboTasks) instance to a file
+pub(crate) struct Recorder {
+    tasks: Mutex<RecordedTasks>,
+    /// Cells updated by the executions that are in progress
+    cells: Mutex<HashMap<TaskId, Vec<(CellId, Option<Value>)>>>,
+    /// Executions in progress that have read external state
+    external: Mutex<HashSet<TaskId>>,
+    writer: Mutex<RecordingWriter>,
+}
+
+/// Replaces task ids with ids of the recording while serializing
+struct RecordingMapping<'a>(&'a Recorder);
+
+impl<'a> IdMapping<TaskId> for RecordingMapping<'a> {
+    fn forward(&self, task: TaskId) -> usize {
+        let mut tasks = self.0.tasks.lock().unwrap();
+        let next_id = tasks.ids.len();
+        *tasks.ids.entry(task).or_insert(next_id)
+    }
+
+    fn backward(&self, _id: usize) -> TaskId {
+        unreachable!("nothing is deserialized while recording")
+    }
+}
+
+impl Recorder {
+    pub fn new(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("unable to create recording {}", path.display()))?;
+        Ok(Self {
+            tasks: Default::default(),
+            cells: Default::default(),
+            external: Default::default(),
+            writer: Mutex::new(RecordingWriter {
+                writer: BufWriter::new(file),
+                error: None,
+            }),
+        })
+    }
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Option<Value> {
+        with_task_id_mapping(RecordingMapping(self), || serde_json::to_value(value).ok())
+    }
+
+    fn id(&self, task: TaskId) -> Option<usize> {
+        let tasks = self.tasks.lock().unwrap();
+        if tasks.created.contains(&task) {
+            tasks.ids.get(&task).copied()
+        } else {
+            None
+        }
+    }
+
+    fn write(&self, event: RecordedEvent) {
+        let mut writer = self.writer.lock().unwrap();
+        let RecordingWriter { writer, error } = &mut *writer;
+        if error.is_some() {
+            return;
+        }
+        let result = serde_json::to_writer(&mut *writer, &event)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"));
+        if let Err(err) = result {
+            *error = Some(err.to_string());
+        }
+    }
+
+    /// Records the creation of a task. Calls for tasks that already exist are
+    /// ignored.
+    pub fn task_created(
+        &self,
+        task: TaskId,
+        task_type: &PersistentTaskType,
+        description: impl FnOnce() -> String,
+    ) {
+        if self.tasks.lock().unwrap().created.contains(&task) {
+            return;
+        }
+        let task_type = self.serialize(task_type);
+        let id = RecordingMapping(self).forward(task);
+        if !self.tasks.lock().unwrap().created.insert(task) {
+            return;
+        }
+        self.write(RecordedEvent::Task {
+            id,
+            description: description(),
+            task_type,
+        });
+    }
+
+    /// Records the creation of a transient task, which can't be created again
+    /// while replaying.
+    pub fn transient_task_created(&self, task: TaskId, description: String) {
+        let id = RecordingMapping(self).forward(task);
+        self.tasks.lock().unwrap().created.insert(task);
+        self.write(RecordedEvent::TransientTask { id, description });
+    }
+
+    /// Marks the execution of `task` as reading external state
+    pub fn external_state_read(&self, task: TaskId) {
+        if self.id(task).is_some() {
+            self.external.lock().unwrap().insert(task);
+        }
+    }
+
+    pub fn cell_updated(&self, task: TaskId, index: CellId, content: &CellContent) {
+        if self.id(task).is_none() {
+            return;
+        }
+        let content = self.serialize(content);
+        self.cells
+            .lock()
+            .unwrap()
+            .entry(task)
+            .or_default()
+            .push((index, content));
+    }
+
+    /// Records the result of an execution. `None` means that the execution
+    /// has been cancelled.
+    pub fn execution_completed(
+        &self,
+        task: TaskId,
+        result: &Option<Result<Result<RawVc>, Option<Cow<'static, str>>>>,
+    ) {
+        let cells = self.cells.lock().unwrap().remove(&task);
+        let external = self.external.lock().unwrap().remove(&task);
+        let (id, result) = match (self.id(task), result) {
+            (Some(id), Some(result)) => (id, result),
+            _ => return,
+        };
+        let output = match result {
+            Ok(Ok(output)) => self
+                .serialize(output)
+                .ok_or_else(|| "unserializable output".to_string()),
+            Ok(Err(err)) => Err(format!("{err:#}")),
+            Err(Some(message)) => Err(format!("panic: {message}")),
+            Err(None) => Err("panic".to_string()),
+        };
+        self.write(RecordedEvent::Execution {
+            task: id,
+            cells: cells.unwrap_or_default(),
+            output,
+            external,
+        });
+    }
+
+    pub fn invalidated(&self, task: TaskId) {
+        if let Some(id) = self.id(task) {
+            self.write(RecordedEvent::Invalidation { task: id });
+        }
+    }
+
+    /// Flushes all events to the file. Fails when any event couldn't be
+    /// written.
+    pub fn flush(&self) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        let RecordingWriter { writer, error } = &mut *writer;
+        if error.is_none() {
+            if let Err(err) = writer.flush() {
+                *error = Some(err.to_string());
+            }
+        }
+        match error {
+            Some(err) => Err(anyhow!("unable to write to the recording: {err}")),
+            None => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn read_recording(path: &Path) -> Result<Vec<RecordedEvent>> {
+    let file =
+        File::open(path).with_context(|| format!("unable to open recording {}", path.display()))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(&line?)
+                .with_context(|| format!("invalid event in line {} of the recording", i + 1))
+        })
+        .collect()
+}
+
+/// Maps the ids of a recording to the tasks created while replaying it
+#[derive(Default)]
+pub(crate) struct ReplayMapping {
+    tasks: HashMap<usize, TaskId>,
+    ids: HashMap<TaskId, usize>,
+    /// Set when deserializing an id that has no task
+    missing: Cell<bool>,
+}
+
+impl IdMapping<TaskId> for ReplayMapping {
+    fn forward(&self, task: TaskId) -> usize {
+        // Tasks that are not part of the recording will never match
+        self.ids.get(&task).copied().unwrap_or(usize::MAX)
+    }
+
+    fn backward(&self, id: usize) -> TaskId {
+        match self.tasks.get(&id) {
+            Some(task) => *task,
+            None => {
+                self.missing.set(true);
+                TaskId::from(usize::MAX)
+            }
+        }
+    }
+}
+
+impl ReplayMapping {
+    pub fn insert(&mut self, id: usize, task: TaskId) {
+        self.tasks.insert(id, task);
+        self.ids.insert(task, id);
+    }
+
+    pub fn get(&self, id: usize) -> Option<TaskId> {
+        self.tasks.get(&id).copied()
+    }
+
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Option<Value> {
+        with_task_id_mapping(self, || serde_json::to_value(value).ok())
+    }
+
+    /// Returns `None` when the value references a task that doesn't exist
+    /// while replaying.
+    pub fn deserialize<T: DeserializeOwned>(&self, value: &Value) -> Result<Option<T>> {
+        self.missing.set(false);
+        // Values need to be deserialized from a string, as some of them borrow
+        // strings from the input
+        let value = with_task_id_mapping(self, || serde_json::from_str(&value.to_string()))?;
+        Ok(if self.missing.get() {
+            None
+        } else {
+            Some(value)
+        })
+    }
+}
+
+/// Compares a recorded value with a replayed one. Sets and maps can be
+/// serialized as arrays in their iteration order, which differs between
+/// processes, so the order of arrays is ignored.
+pub(crate) fn values_equal(a: &Value, b: &Value) -> bool {
+    a == b || canonicalize(a) == canonicalize(b)
+}
+
+/// Sorts all nested arrays by their serialized items. Keys of objects are
+/// sorted too, so equal items are serialized the same way.
+fn canonicalize(value: &Value) -> Value {
+    match value {
+        Value::Array(items) => {
+            let mut items: Vec<_> = items.iter().map(canonicalize).collect();
+            items.sort_by_cached_key(|item| item.to_string());
+            Value::Array(items)
+        }
+        Value::Object(entries) => {
+            let mut entries: Vec<_> = entries
+                .iter()
+                .map(|(key, value)| (key.clone(), canonicalize(value)))
+                .collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(entries.into_iter().collect())
+        }
+        _ => value.clone(),
+    }
+}
+
+/// Shortens a value for reporting
+pub(crate) fn format_value(value: &Value) -> String {
+    const MAX_LENGTH: usize = 200;
+    let mut string = value.to_string();
+    if string.len() > MAX_LENGTH {
+        let mut end = MAX_LENGTH;
+        while !string.is_char_boundary(end) {
+            end -= 1;
+        }
+        string.truncate(end);
+        string.push_str("...");
+    }
+    string
+}
+
+/// A difference between a recording and its replay
+pub struct ReplayMismatch {
+    /// Description of the task in the recording
+    pub task: String,
+    pub message: String,
+}
+
+impl Display for ReplayMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.task, self.message)
+    }
+}
+
+#[derive(Default)]
+pub struct ReplayReport {
+    /// Number of tasks that have been created again
+    pub tasks: usize,
+    /// Number of tasks that can't be created again, as their inputs are not
+    /// serializable
+    pub skipped_tasks: usize,
+    /// Number of transient tasks, e. g. root tasks, which can't be created
+    /// again
+    pub transient_tasks: usize,
+    /// Number of compared task executions
+    pub executions: usize,
+    /// Number of executions that have read external state. Their cells are
+    /// restored from the recording instead of being compared.
+    pub external_executions: usize,
+    /// Executions that yielded different results than in the recording. As
+    /// the inputs are the same, this points to non-deterministic functions,
+    /// or to reads that are not tracked, e. g. of files that have changed
+    /// since the recording.
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+impl Display for ReplayReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "replayed {} tasks ({} skipped, {} transient) and {} executions ({} with external \
+             state), {} mismatches",
+            self.tasks,
+            self.skipped_tasks,
+            self.transient_tasks,
+            self.executions,
+            self.external_executions,
+            self.mismatches.len()
+        )?;
+        for mismatch in self.mismatches.iter() {
+            writeln!(f, "- {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use std::{fmt::Debug, hash::Hash, ops::Deref};
 
 use dashmap::{mapref::entry::Entry, DashMap};
 use once_cell::sync::Lazy;
+use turbo_tasks_hash::Xxh3Hash64Hasher;
 
 use crate::{
     id::{FunctionId, TraitTypeId, ValueTypeId},
@@ -92,6 +93,31 @@ pub fn get_function_global_name(id: FunctionId) -> &'static str {
     &FUNCTIONS.get(*id).unwrap().1
 }
 
+/// A hash of the global names of all registered functions and value types.
+/// Data persisted by a process with different functions or value types can't
+/// be reused.
+pub fn get_registry_hash() -> u64 {
+    fn sorted_names<K>(map: &DashMap<String, K>) -> Vec<String> {
+        let mut names = map
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+    let mut hasher = Xxh3Hash64Hasher::new();
+    for names in [
+        sorted_names(&FUNCTIONS_BY_NAME),
+        sorted_names(&VALUE_TYPES_BY_NAME),
+    ] {
+        hasher.write_value(names.len());
+        for name in names.iter() {
+            hasher.write_ref(name);
+        }
+    }
+    hasher.finish()
+}
+
 pub fn register_value_type(global_name: &str, ty: &'static ValueType) {
     register_thing(
         global_name,
@@ -3,12 +3,14 @@ use std::{
     collections::{HashMap, HashSet},
     fmt::{self, Debug, Display, Formatter},
     hash::Hash,
+    mem::size_of,
     sync::Arc,
 };
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    heap_size::HeapSize,
     id::{FunctionId, TraitTypeId},
     magic_any::{AnyDeserializeSeed, MagicAny, MagicAnyDeserializeSeed},
     registry::{register_trait_type, register_value_type},
@@ -58,6 +60,7 @@ pub trait TypedForInput: Typed {}
 
 type MagicSerializationFn = fn(&dyn MagicAny) -> &dyn erased_serde::Serialize;
 type AnySerializationFn = fn(&(dyn Any + Sync + Send)) -> &dyn erased_serde::Serialize;
+type EstimateSizeFn = fn(&(dyn Any + Sync + Send)) -> usize;
 
 // TODO this type need some refactoring when multiple languages are added to
 // turbo-task In this case a trait_method might be of a different function type.
@@ -79,6 +82,9 @@ pub struct ValueType {
     /// Functors for serialization
     magic_serialization: Option<(MagicSerializationFn, MagicAnyDeserializeSeed)>,
     any_serialization: Option<(AnySerializationFn, AnyDeserializeSeed)>,
+
+    /// Functor for memory estimation
+    estimate_size: Option<EstimateSizeFn>,
 }
 
 impl Hash for ValueType {
@@ -129,6 +135,16 @@ pub fn any_as_serialize<T: Any + Serialize + Send + Sync + 'static>(
     );
 }
 
+fn any_estimate_size<T: Any + HeapSize>(this: &(dyn Any + Send + Sync)) -> usize {
+    match this.downcast_ref::<T>() {
+        Some(value) => size_of::<T>() + value.heap_size(),
+        None => panic!(
+            "any_estimate_size::<{}> called with invalid type",
+            type_name::<T>()
+        ),
+    }
+}
+
 impl ValueType {
     /// This is internally used by `#[turbo_tasks::value]`
     pub fn new<T>() -> Self {
@@ -138,6 +154,7 @@ impl ValueType {
             trait_methods: HashMap::new(),
             magic_serialization: None,
             any_serialization: None,
+            estimate_size: None,
         }
     }
 
@@ -154,6 +171,7 @@ impl ValueType {
                 MagicAnyDeserializeSeed::new::<T>(),
             )),
             any_serialization: Some((any_as_serialize::<T>, AnyDeserializeSeed::new::<T>())),
+            estimate_size: None,
         }
     }
 
@@ -167,9 +185,21 @@ impl ValueType {
             trait_methods: HashMap::new(),
             magic_serialization: None,
             any_serialization: Some((any_as_serialize::<T>, AnyDeserializeSeed::new::<T>())),
+            estimate_size: None,
         }
     }
 
+    /// This is internally used by `#[turbo_tasks::value]`
+    pub fn register_heap_size<T: Any + HeapSize>(&mut self) {
+        self.estimate_size = Some(any_estimate_size::<T>);
+    }
+
+    /// Estimates the memory used by a value of this type, including its heap
+    /// memory. Returns `None` when the type doesn't support estimation.
+    pub fn estimate_size(&self, value: &(dyn Any + Send + Sync)) -> Option<usize> {
+        self.estimate_size.map(|estimate| estimate(value))
+    }
+
     pub fn magic_as_serializable<'a>(
         &self,
         arc: &'a Arc<dyn MagicAny>,
@@ -412,6 +412,20 @@ impl ConsoleUi {
             seen: Arc::new(Mutex::new(SeenIssues::new())),
         }
     }
+
+    /// Logs a message that isn't reported as issue by a source, e. g. about
+    /// the state of the server. It's formatted like an issue of the `category`
+    /// and respects the log level.
+    pub fn log(&self, severity: IssueSeverity, category: &str, message: &str) {
+        if severity <= self.options.log_level {
+            println!(
+                "{} - [{}] {}",
+                severity.style(severity_to_style(severity)),
+                category,
+                message
+            );
+        }
+    }
 }
 
 #[turbo_tasks::value(transparent)]
@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser};
+use turbopack_cli_utils::issue::IssueSeverityCliOption;
+
+use crate::build::BuildTarget;
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about, long_about = None)]
+pub enum Arguments {
+    /// Builds a production bundle of the given entries and writes it to disk.
+    Build(BuildArguments),
+}
+
+#[derive(Debug, Args)]
+pub struct BuildArguments {
+    /// The entries of the bundle, relative to `dir`.
+    #[clap(value_parser, required = true)]
+    pub entries: Vec<String>,
+
+    /// The directory of the project.
+    /// If no directory is provided, the current directory will be used.
+    #[clap(long, value_parser)]
+    pub dir: Option<PathBuf>,
+
+    /// The root directory of the project. Nothing outside of this directory can
+    /// be accessed. e. g. the monorepo root.
+    /// If no directory is provided, `dir` will be used.
+    #[clap(long, value_parser)]
+    pub root: Option<PathBuf>,
+
+    /// The directory the bundle is written to, relative to `dir`.
+    #[clap(short, long, value_parser, default_value = "dist")]
+    pub out_dir: PathBuf,
+
+    /// The runtime the bundle is built for.
+    #[clap(long, value_enum, default_value_t = BuildTarget::Browser)]
+    pub target: BuildTarget,
+
+    /// The browserslist query that determines the target browsers. Edge
+    /// functions are built for the latest V8 instead.
+    #[clap(
+        long,
+        default_value = "last 1 Chrome versions, last 1 Firefox versions, last 1 Safari versions, \
+                         last 1 Edge versions"
+    )]
+    pub browserslist: String,
+
+    /// Don't minify the emitted chunks.
+    #[clap(long)]
+    pub no_minify: bool,
+
+    #[clap(short, long)]
+    /// Filter by issue severity.
+    pub log_level: Option<IssueSeverityCliOption>,
+
+    #[clap(long)]
+    /// Show all log messages without limit.
+    pub show_all: bool,
+
+    #[clap(long)]
+    /// Expand the log details.
+    pub log_detail: bool,
+}
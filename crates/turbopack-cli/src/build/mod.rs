@@ -0,0 +1,334 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    env::current_dir,
+    path::{PathBuf, MAIN_SEPARATOR},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use indexmap::IndexMap;
+use owo_colors::OwoColorize;
+use turbo_tasks::{
+    primitives::StringsVc, util::FormatDuration, NothingVc, RawVc, TransientValue, TurboTasks,
+    Value,
+};
+use turbo_tasks_fs::{DiskFileSystemVc, File, FileSystemPathVc, FileSystemVc};
+use turbo_tasks_memory::MemoryBackend;
+use turbopack::{
+    ecmascript::EcmascriptModuleAssetVc, emit_asset, emit_with_completion,
+    resolve_options_context::ResolveOptionsContext, transition::TransitionsByNameVc,
+    ModuleAssetContextVc,
+};
+use turbopack_cli_utils::issue::{ConsoleUi, ConsoleUiVc, LogOptions};
+use turbopack_core::{
+    asset::AssetVc,
+    chunk::{production::ProductionChunkingContextVc, ChunkGroupVc, ChunkableAssetVc},
+    context::AssetContextVc,
+    environment::{
+        BrowserEnvironment, EdgeFunctionEnvironment, EnvironmentIntention, EnvironmentVc,
+        ExecutionEnvironment, EDGE_RESOLVE_CONDITIONS,
+    },
+    issue::IssueSeverity,
+    reference::all_assets,
+    source_asset::SourceAssetVc,
+    virtual_asset::VirtualAssetVc,
+};
+
+#[cfg(feature = "cli")]
+use crate::arguments::BuildArguments;
+
+/// The runtime a production bundle is built for.
+#[turbo_tasks::value(serialization = "auto_for_input")]
+#[derive(Debug, Copy, Clone, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum BuildTarget {
+    /// A browser with DOM access.
+    Browser,
+    /// An edge function that only provides Web APIs. Node.js builtins need
+    /// to be polyfilled by packages.
+    Edge,
+}
+
+/// Options of a production build.
+#[derive(Debug)]
+pub struct BuildOptions {
+    /// The entries of the bundle, relative to `dir`.
+    pub entries: Vec<String>,
+    /// The directory of the project.
+    pub dir: Option<PathBuf>,
+    /// The root directory of the project. Defaults to `dir`.
+    pub root: Option<PathBuf>,
+    /// The directory the bundle is written to, relative to `dir`.
+    pub out_dir: PathBuf,
+    /// The runtime the bundle is built for.
+    pub target: BuildTarget,
+    /// The browserslist query that determines the target browsers.
+    pub browserslist_query: String,
+    /// Whether the emitted chunks should be minified.
+    pub minify: bool,
+    pub log_level: IssueSeverity,
+    pub show_all: bool,
+    pub log_detail: bool,
+}
+
+#[cfg(feature = "cli")]
+impl From<BuildArguments> for BuildOptions {
+    fn from(args: BuildArguments) -> Self {
+        BuildOptions {
+            entries: args.entries,
+            dir: args.dir,
+            root: args.root,
+            out_dir: args.out_dir,
+            target: args.target,
+            browserslist_query: args.browserslist,
+            minify: !args.no_minify,
+            log_level: args
+                .log_level
+                .map_or_else(|| IssueSeverity::Warning, |l| l.0),
+            show_all: args.show_all,
+            log_detail: args.log_detail,
+        }
+    }
+}
+
+/// The name of the manifest that maps every entry to the chunk files that need
+/// to be loaded for it and lists all emitted `assets`, e. g. async chunks,
+/// source maps and static assets. It is written into the output directory.
+const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// Builds the entries for production and writes all chunks, their referenced
+/// assets and a manifest to the output directory.
+pub async fn build(options: &BuildOptions) -> Result<()> {
+    let start = Instant::now();
+    crate::register();
+
+    let dir = options
+        .dir
+        .as_ref()
+        .map(|dir| dir.canonicalize())
+        .unwrap_or_else(current_dir)
+        .context("project directory can't be found")?;
+    let root = if let Some(root) = options.root.as_ref() {
+        root.canonicalize()
+            .context("root directory can't be found")?
+    } else {
+        dir.clone()
+    };
+    let root_dir = root
+        .to_str()
+        .context("root directory contains invalid characters")?
+        .to_string();
+    let project_relative = dir
+        .strip_prefix(&root)
+        .map_err(|_| {
+            anyhow!(
+                "project directory {} is not inside of the root directory {}",
+                dir.display(),
+                root.display()
+            )
+        })?
+        .to_str()
+        .context("project directory contains invalid characters")?
+        .replace(MAIN_SEPARATOR, "/");
+    let output_dir = dir
+        .join(&options.out_dir)
+        .to_str()
+        .context("output directory contains invalid characters")?
+        .to_string();
+
+    let console_ui = ConsoleUi::new(LogOptions {
+        current_dir: current_dir().unwrap(),
+        show_all: options.show_all,
+        log_detail: options.log_detail,
+        log_level: options.log_level,
+    });
+
+    let tt = TurboTasks::new(MemoryBackend::new());
+    let entries = options.entries.clone();
+    let target = options.target;
+    let browserslist_query = options.browserslist_query.clone();
+    let minify = options.minify;
+    let task = tt.spawn_once_task(async move {
+        let console_ui = console_ui.cell();
+        let output = build_operation(
+            root_dir,
+            project_relative,
+            output_dir,
+            entries,
+            Value::new(target),
+            browserslist_query,
+            minify,
+            console_ui,
+        );
+        handle_issues(output, console_ui).await?;
+        output.await?;
+        Ok(NothingVc::new().into())
+    });
+    tt.wait_task_completion(task, true).await?;
+
+    let (elapsed, count) = tt.get_or_wait_update_info(Duration::from_millis(100)).await;
+    println!(
+        "{event_type} - build done in {total} ({elapsed} task execution, {count} tasks)",
+        event_type = "event".purple(),
+        total = FormatDuration(start.elapsed()),
+        elapsed = FormatDuration(elapsed),
+    );
+
+    Ok(())
+}
+
+async fn handle_issues<T: Into<RawVc>>(source: T, console_ui: ConsoleUiVc) -> Result<()> {
+    let state = console_ui
+        .group_and_display_issues(TransientValue::new(source.into()))
+        .await?;
+
+    if state.has_fatal {
+        Err(anyhow!("Fatal issue(s) occurred"))
+    } else {
+        Ok(())
+    }
+}
+
+#[turbo_tasks::function]
+async fn disk_fs(name: &str, path: &str, console_ui: ConsoleUiVc) -> Result<FileSystemVc> {
+    let disk_fs = DiskFileSystemVc::new(name.to_string(), path.to_string());
+    handle_issues(disk_fs, console_ui).await?;
+    disk_fs.await?.invalidate();
+    Ok(disk_fs.into())
+}
+
+#[turbo_tasks::function]
+fn build_environment(target: Value<BuildTarget>, browserslist_query: &str) -> EnvironmentVc {
+    match target.into_value() {
+        BuildTarget::Browser => EnvironmentVc::new(
+            Value::new(ExecutionEnvironment::Browser(
+                BrowserEnvironment {
+                    dom: true,
+                    web_worker: false,
+                    service_worker: false,
+                    browserslist_query: browserslist_query.to_owned(),
+                }
+                .into(),
+            )),
+            Value::new(EnvironmentIntention::Client),
+        ),
+        BuildTarget::Edge => EnvironmentVc::new(
+            Value::new(ExecutionEnvironment::EdgeFunction(
+                EdgeFunctionEnvironment::default().into(),
+            )),
+            Value::new(EnvironmentIntention::Api),
+        ),
+    }
+}
+
+#[turbo_tasks::function]
+fn build_asset_context(target: Value<BuildTarget>, environment: EnvironmentVc) -> AssetContextVc {
+    let mut custom_conditions = vec!["production".to_string()];
+    if target.into_value() == BuildTarget::Edge {
+        custom_conditions.extend(
+            EDGE_RESOLVE_CONDITIONS
+                .iter()
+                .map(|condition| condition.to_string()),
+        );
+    }
+    ModuleAssetContextVc::new(
+        TransitionsByNameVc::cell(HashMap::new()),
+        environment,
+        Default::default(),
+        ResolveOptionsContext {
+            enable_typescript: true,
+            enable_react: true,
+            enable_node_modules: true,
+            custom_conditions,
+            browser: true,
+            module: true,
+            ..Default::default()
+        }
+        .cell(),
+    )
+    .into()
+}
+
+#[turbo_tasks::function]
+async fn build_operation(
+    root_dir: String,
+    project_relative: String,
+    output_dir: String,
+    entries: Vec<String>,
+    target: Value<BuildTarget>,
+    browserslist_query: String,
+    minify: bool,
+    console_ui: ConsoleUiVc,
+) -> Result<StringsVc> {
+    let fs = disk_fs("project", &root_dir, console_ui);
+    let output_fs = disk_fs("output", &output_dir, console_ui);
+
+    let project_path = fs.root().join(&project_relative);
+    let output_root = output_fs.root();
+
+    let context = build_asset_context(target, build_environment(target, &browserslist_query));
+    let chunking_context = ProductionChunkingContextVc::builder(
+        project_path,
+        output_root,
+        output_root.join("chunks"),
+        output_root.join("assets"),
+    )
+    .minify(minify)
+    .build();
+
+    let mut entry_files = IndexMap::new();
+    let mut emitted = BTreeSet::new();
+    for entry in entries {
+        let source = SourceAssetVc::new(project_path.join(&entry)).into();
+        let module = context.process(source);
+        let chunk = if let Some(ecmascript) = EcmascriptModuleAssetVc::resolve_from(module).await? {
+            ecmascript.as_evaluated_chunk(chunking_context, None)
+        } else if let Some(chunkable) = ChunkableAssetVc::resolve_from(module).await? {
+            chunkable.as_chunk(chunking_context)
+        } else {
+            return Err(anyhow!(
+                "Entry {entry} is not chunkable, so it can't be used as a build entry"
+            ));
+        };
+
+        let chunks = ChunkGroupVc::from_chunk(chunk).chunks().await?;
+        let mut files = Vec::new();
+        for chunk in chunks.iter() {
+            let chunk: AssetVc = (*chunk).into();
+            // Emits the chunk and every asset it references, e. g. async chunks,
+            // source maps and static assets.
+            emit_with_completion(chunk, output_root).await?;
+            files.push(output_file(chunk.path(), output_root).await?);
+            // Only assets inside of the output directory are emitted, the
+            // others are source files.
+            for asset in all_assets(chunk).await?.iter() {
+                if let Some(file) = output_root.await?.get_path_to(&*asset.path().await?) {
+                    emitted.insert(file.to_string());
+                }
+            }
+        }
+        entry_files.insert(entry, files);
+    }
+
+    let manifest = serde_json::json!({
+        "entries": entry_files,
+        "assets": emitted,
+    });
+    let manifest = VirtualAssetVc::new(
+        output_root.join(MANIFEST_FILENAME),
+        File::from(serde_json::to_string_pretty(&manifest)?).into(),
+    );
+    emit_asset(manifest.into()).await?;
+
+    Ok(StringsVc::cell(emitted.into_iter().collect()))
+}
+
+/// Returns the path of an emitted file relative to the output directory.
+async fn output_file(path: FileSystemPathVc, output_root: FileSystemPathVc) -> Result<String> {
+    let path = path.await?;
+    output_root
+        .await?
+        .get_path_to(&path)
+        .map(|p| p.to_string())
+        .ok_or_else(|| anyhow!("chunk {} is not inside of the output directory", path.path))
+}
@@ -0,0 +1,11 @@
+#![feature(min_specialization)]
+
+#[cfg(feature = "cli")]
+pub mod arguments;
+pub mod build;
+
+pub fn register() {
+    turbopack::register();
+    turbopack_cli_utils::register();
+    include!(concat!(env!("OUT_DIR"), "/register.rs"));
+}
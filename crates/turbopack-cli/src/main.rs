@@ -0,0 +1,23 @@
+#![feature(min_specialization)]
+
+use anyhow::Result;
+#[cfg(feature = "cli")]
+use clap::Parser;
+
+#[global_allocator]
+static ALLOC: turbo_malloc::TurboMalloc = turbo_malloc::TurboMalloc;
+
+#[cfg(not(feature = "cli"))]
+fn main() -> Result<()> {
+    unimplemented!("Cannot run binary without CLI feature enabled");
+}
+
+#[tokio::main]
+#[cfg(feature = "cli")]
+async fn main() -> Result<()> {
+    use turbopack_cli::arguments::Arguments;
+
+    match Arguments::parse() {
+        Arguments::Build(args) => turbopack_cli::build::build(&args.into()).await,
+    }
+}
@@ -0,0 +1,76 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde_json::Value;
+use turbopack_cli::build::{build, BuildOptions, BuildTarget};
+use turbopack_core::issue::IssueSeverity;
+
+fn options(dir: &Path, root: Option<PathBuf>) -> BuildOptions {
+    BuildOptions {
+        entries: vec!["index.js".to_string()],
+        dir: Some(dir.to_path_buf()),
+        root,
+        out_dir: PathBuf::from("dist"),
+        target: BuildTarget::Browser,
+        browserslist_query: "last 1 Chrome versions".to_string(),
+        minify: false,
+        log_level: IssueSeverity::Warning,
+        show_all: false,
+        log_detail: false,
+    }
+}
+
+fn strings(value: &Value) -> Vec<&str> {
+    value
+        .as_array()
+        .expect("expected an array")
+        .iter()
+        .map(|v| v.as_str().expect("expected a string"))
+        .collect()
+}
+
+#[tokio::test]
+async fn manifest_lists_all_emitted_assets() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    fs::write(
+        dir.path().join("index.js"),
+        "import(\"./lazy.js\").then((m) => console.log(m.default));\n",
+    )?;
+    fs::write(dir.path().join("lazy.js"), "export default 42;\n")?;
+
+    build(&options(dir.path(), None)).await?;
+
+    let out_dir = dir.path().join("dist");
+    let manifest: Value =
+        serde_json::from_str(&fs::read_to_string(out_dir.join("manifest.json"))?)?;
+    let entry_files = strings(&manifest["entries"]["index.js"]);
+    let assets = strings(&manifest["assets"]);
+    assert!(!entry_files.is_empty());
+    for file in &entry_files {
+        assert!(assets.contains(file), "{file} is missing in the assets");
+    }
+    // The async chunk of `lazy.js` is not loaded by the entry itself.
+    assert!(assets.len() > entry_files.len());
+    for file in &assets {
+        assert!(out_dir.join(file).is_file(), "{file} has not been emitted");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn fails_for_project_outside_of_root() -> Result<()> {
+    let root = tempfile::tempdir()?;
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("index.js"), "console.log(42);\n")?;
+
+    let error = build(&options(dir.path(), Some(root.path().to_path_buf())))
+        .await
+        .expect_err("the project is not inside of the root");
+    assert!(error
+        .to_string()
+        .contains("is not inside of the root directory"));
+    Ok(())
+}
@@ -193,4 +193,15 @@ impl ChunkingContext for DevChunkingContext {
         context.layer = (!layer.is_empty()).then(|| layer.to_string());
         Ok(DevChunkingContextVc::new(Value::new(context)).into())
     }
+
+    #[turbo_tasks::function]
+    async fn worker_context(self_vc: DevChunkingContextVc) -> Result<ChunkingContextVc> {
+        let mut context = self_vc.await?.clone_value();
+        context.layer = Some(match context.layer {
+            Some(layer) => format!("{layer}_worker"),
+            None => "worker".to_string(),
+        });
+        context.enable_hot_module_replacement = false;
+        Ok(DevChunkingContextVc::new(Value::new(context)).into())
+    }
 }
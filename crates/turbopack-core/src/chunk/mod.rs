@@ -1,9 +1,10 @@
 pub mod dev;
 pub mod optimize;
+pub mod production;
 
 use std::{collections::VecDeque, fmt::Debug};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use indexmap::IndexSet;
 use serde::{Deserialize, Serialize};
 use turbo_tasks::{
@@ -12,12 +13,12 @@ use turbo_tasks::{
     trace::TraceRawVcs,
     ValueToString, ValueToStringVc,
 };
-use turbo_tasks_fs::FileSystemPathVc;
-use turbo_tasks_hash::{encode_hex, DeterministicHash, Xxh3Hash64Hasher};
+use turbo_tasks_fs::{File, FileContent, FileSystemPathVc};
+use turbo_tasks_hash::{encode_hex, hash_xxh3_hash64, DeterministicHash, Xxh3Hash64Hasher};
 
 use self::optimize::optimize;
 use crate::{
-    asset::{Asset, AssetVc, AssetsVc},
+    asset::{Asset, AssetContent, AssetContentVc, AssetVc, AssetsVc},
     reference::{AssetReference, AssetReferenceVc, AssetReferencesVc},
     resolve::{ResolveResult, ResolveResultVc},
 };
@@ -61,11 +62,90 @@ pub trait ChunkingContext {
         BoolVc::cell(false)
     }
 
+    fn is_minification_enabled(&self) -> BoolVc {
+        BoolVc::cell(false)
+    }
+
+    /// When enabled, chunks are named by the hash of their content, which is
+    /// generated at [content_hash_placeholder_path] next to the path returned
+    /// by [ChunkingContext::chunk_path].
+    fn is_content_hashing_enabled(&self) -> BoolVc {
+        BoolVc::cell(false)
+    }
+
+    /// When enabled, evaluated chunks embed the runtime without the parts
+    /// that are only needed for hot module replacement.
+    fn is_production_runtime_enabled(&self) -> BoolVc {
+        BoolVc::cell(false)
+    }
+
     fn layer(&self) -> StringVc {
         StringVc::cell("".to_string())
     }
 
     fn with_layer(&self, layer: &str) -> ChunkingContextVc;
+
+    /// Returns the context of the isolated chunk group of a web worker. Its
+    /// chunks contain their own runtime, which doesn't support hot module
+    /// replacement, so they are placed in a separate layer.
+    fn worker_context(&self) -> ChunkingContextVc;
+}
+
+/// The stem of the path that chunks which are named by the hash of their
+/// content are generated at. It's as long as the hash, so replacing it with the
+/// hash keeps the positions of source maps intact.
+const CONTENT_HASH_PLACEHOLDER: &str = "__content_hash__";
+
+/// Returns the path next to `path` that a chunk which is named by the hash of
+/// its content is generated at.
+#[turbo_tasks::function]
+pub fn content_hash_placeholder_path(path: FileSystemPathVc, extension: &str) -> FileSystemPathVc {
+    path.parent()
+        .join(&format!("{CONTENT_HASH_PLACEHOLDER}{extension}"))
+}
+
+#[turbo_tasks::function]
+async fn content_hash(content: AssetContentVc) -> Result<StringVc> {
+    let hash = match &*content.await? {
+        AssetContent::File(file) => match &*file.await? {
+            FileContent::Content(file) => hash_xxh3_hash64(file.content()),
+            FileContent::NotFound => bail!("chunk content not found"),
+        },
+        AssetContent::Redirect { .. } => bail!("chunk content can't be a redirect"),
+    };
+    Ok(StringVc::cell(encode_hex(hash)))
+}
+
+/// Returns the path of a chunk next to `path`, named by the hash of its
+/// `content`, which has been generated at [content_hash_placeholder_path].
+#[turbo_tasks::function]
+pub async fn content_hashed_chunk_path(
+    path: FileSystemPathVc,
+    content: AssetContentVc,
+    extension: &str,
+) -> Result<FileSystemPathVc> {
+    let hash = &*content_hash(content).await?;
+    Ok(path.parent().join(&format!("{hash}{extension}")))
+}
+
+/// Replaces the placeholder in the `content` of a chunk, which has been
+/// generated at [content_hash_placeholder_path], with the hash of the content.
+/// The chunk is only generated once, as the hash covers everything but the
+/// chunk's own name.
+#[turbo_tasks::function]
+pub async fn with_content_hash(content: AssetContentVc) -> Result<AssetContentVc> {
+    let hash = &*content_hash(content).await?;
+    let code = match &*content.await? {
+        AssetContent::File(file) => match &*file.await? {
+            FileContent::Content(file) => file
+                .content()
+                .to_str()?
+                .replace(CONTENT_HASH_PLACEHOLDER, hash),
+            FileContent::NotFound => bail!("chunk content not found"),
+        },
+        AssetContent::Redirect { .. } => bail!("chunk content can't be a redirect"),
+    };
+    Ok(File::from(code).into())
 }
 
 /// An [Asset] that can be converted into a [Chunk].
@@ -175,6 +255,11 @@ pub enum ChunkingType {
     /// A async loader is placed into the referencing chunk and loads the
     /// separate chunk group in which the asset is placed.
     SeparateAsync,
+    /// A loader is placed into the referencing chunk and references an
+    /// isolated chunk group with its own runtime in which the asset is the
+    /// entry, e. g. for a web worker. Nothing is shared with the referencing
+    /// chunk group.
+    Isolated,
 }
 
 impl Default for ChunkingType {
@@ -306,6 +391,10 @@ pub trait FromChunkableAsset: ChunkItem + Sized + Debug {
         context: ChunkingContextVc,
         asset: ChunkableAssetVc,
     ) -> Result<Option<(Self, ChunkableAssetVc)>>;
+    async fn from_isolated_asset(
+        context: ChunkingContextVc,
+        asset: ChunkableAssetVc,
+    ) -> Result<Option<Self>>;
 }
 
 pub async fn chunk_content_split<I: FromChunkableAsset>(
@@ -465,6 +554,17 @@ async fn chunk_content_internal<I: FromChunkableAsset>(
                                 continue 'outer;
                             }
                         }
+                        ChunkingType::Isolated => {
+                            // The loader references the isolated chunk group itself
+                            if let Some(loader_item) =
+                                I::from_isolated_asset(context, chunkable_asset).await?
+                            {
+                                inner_chunk_items.push(loader_item);
+                            } else {
+                                external_asset_references.push(reference);
+                                continue 'outer;
+                            }
+                        }
                     }
                 }
 
@@ -0,0 +1,195 @@
+use anyhow::Result;
+use turbo_tasks::{
+    primitives::{BoolVc, StringVc},
+    Value, ValueToString,
+};
+use turbo_tasks_fs::FileSystemPathVc;
+use turbo_tasks_hash::{encode_hex, hash_xxh3_hash64};
+
+use super::{ChunkingContext, ChunkingContextVc};
+use crate::asset::AssetVc;
+
+pub struct ProductionChunkingContextBuilder {
+    context: ProductionChunkingContext,
+}
+
+impl ProductionChunkingContextBuilder {
+    pub fn layer(mut self, layer: &str) -> Self {
+        self.context.layer = (!layer.is_empty()).then(|| layer.to_string());
+        self
+    }
+
+    pub fn css_chunk_root_path(mut self, path: FileSystemPathVc) -> Self {
+        self.context.css_chunk_root_path = Some(path);
+        self
+    }
+
+    pub fn minify(mut self, minify: bool) -> Self {
+        self.context.minify = minify;
+        self
+    }
+
+    pub fn build(self) -> ChunkingContextVc {
+        ProductionChunkingContextVc::new(Value::new(self.context)).into()
+    }
+}
+
+/// A chunking context for production builds.
+/// Chunks and static assets are named by the hash of their content, so the
+/// resulting filenames can be cached forever and are stable between builds.
+/// It never enables hot module replacement and minifies the chunk contents by
+/// default.
+#[turbo_tasks::value(serialization = "auto_for_input")]
+#[derive(Debug, Clone, Hash, PartialOrd, Ord)]
+pub struct ProductionChunkingContext {
+    /// This path get striped off of path before creating a name out of it
+    context_path: FileSystemPathVc,
+    /// This path is used to compute the url to request chunks or assets from
+    output_root_path: FileSystemPathVc,
+    /// Chunks are placed at this path
+    chunk_root_path: FileSystemPathVc,
+    /// Css Chunks are placed at this path
+    css_chunk_root_path: Option<FileSystemPathVc>,
+    /// Static assets are placed at this path
+    asset_root_path: FileSystemPathVc,
+    /// Layer name within this context
+    layer: Option<String>,
+    /// Minify the content of chunks
+    minify: bool,
+}
+
+impl ProductionChunkingContextVc {
+    pub fn builder(
+        context_path: FileSystemPathVc,
+        output_root_path: FileSystemPathVc,
+        chunk_root_path: FileSystemPathVc,
+        asset_root_path: FileSystemPathVc,
+    ) -> ProductionChunkingContextBuilder {
+        ProductionChunkingContextBuilder {
+            context: ProductionChunkingContext {
+                context_path,
+                output_root_path,
+                chunk_root_path,
+                css_chunk_root_path: None,
+                asset_root_path,
+                layer: None,
+                minify: true,
+            },
+        }
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ProductionChunkingContextVc {
+    #[turbo_tasks::function]
+    fn new(this: Value<ProductionChunkingContext>) -> Self {
+        this.into_value().cell()
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ChunkingContext for ProductionChunkingContext {
+    #[turbo_tasks::function]
+    fn output_root(&self) -> FileSystemPathVc {
+        self.output_root_path
+    }
+
+    #[turbo_tasks::function]
+    async fn chunk_path(
+        &self,
+        path_vc: FileSystemPathVc,
+        extension: &str,
+    ) -> Result<FileSystemPathVc> {
+        // For clippy -- This explicit deref is necessary
+        let path = &*path_vc.await?;
+
+        // Chunks that are named by the hash of their content only take the
+        // directory from this path (see `is_content_hashing_enabled`).
+        // Chunks encode everything that makes them unique into their path, so
+        // hashing it gives a unique name to the others.
+        let name = if let Some(inner) = self.context_path.await?.get_path_to(path) {
+            inner.to_string()
+        } else {
+            path_vc.to_string().await?.to_string()
+        };
+        let hash = encode_hex(hash_xxh3_hash64(name.as_bytes()));
+        let name = format!("{hash}{extension}");
+
+        let mut root_path = self.chunk_root_path;
+        #[allow(clippy::single_match, reason = "future extensions")]
+        match extension {
+            ".css" => {
+                if let Some(path) = self.css_chunk_root_path {
+                    root_path = path;
+                }
+            }
+            _ => {}
+        }
+        let root_path = if let Some(layer) = self.layer.as_deref() {
+            root_path.join(layer)
+        } else {
+            root_path
+        };
+        Ok(root_path.join(&name))
+    }
+
+    #[turbo_tasks::function]
+    async fn can_be_in_same_chunk(&self, asset_a: AssetVc, asset_b: AssetVc) -> Result<BoolVc> {
+        let parent_dir = asset_a.path().parent().await?;
+
+        let path = asset_b.path().await?;
+        if let Some(rel_path) = parent_dir.get_path_to(&path) {
+            if !rel_path.starts_with("node_modules/") && !rel_path.contains("/node_modules/") {
+                return Ok(BoolVc::cell(true));
+            }
+        }
+
+        Ok(BoolVc::cell(false))
+    }
+
+    #[turbo_tasks::function]
+    fn asset_path(&self, content_hash: &str, extension: &str) -> FileSystemPathVc {
+        self.asset_root_path
+            .join(&format!("{content_hash}.{extension}"))
+    }
+
+    #[turbo_tasks::function]
+    fn is_minification_enabled(&self) -> BoolVc {
+        BoolVc::cell(self.minify)
+    }
+
+    #[turbo_tasks::function]
+    fn is_content_hashing_enabled(&self) -> BoolVc {
+        BoolVc::cell(true)
+    }
+
+    #[turbo_tasks::function]
+    fn is_production_runtime_enabled(&self) -> BoolVc {
+        BoolVc::cell(true)
+    }
+
+    #[turbo_tasks::function]
+    fn layer(&self) -> StringVc {
+        StringVc::cell(self.layer.clone().unwrap_or_default())
+    }
+
+    #[turbo_tasks::function]
+    async fn with_layer(
+        self_vc: ProductionChunkingContextVc,
+        layer: &str,
+    ) -> Result<ChunkingContextVc> {
+        let mut context = self_vc.await?.clone_value();
+        context.layer = (!layer.is_empty()).then(|| layer.to_string());
+        Ok(ProductionChunkingContextVc::new(Value::new(context)).into())
+    }
+
+    #[turbo_tasks::function]
+    async fn worker_context(self_vc: ProductionChunkingContextVc) -> Result<ChunkingContextVc> {
+        let mut context = self_vc.await?.clone_value();
+        context.layer = Some(match context.layer {
+            Some(layer) => format!("{layer}_worker"),
+            None => "worker".to_string(),
+        });
+        Ok(ProductionChunkingContextVc::new(Value::new(context)).into())
+    }
+}
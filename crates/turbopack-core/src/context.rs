@@ -21,6 +21,13 @@ pub trait AssetContext {
         resolve_options: ResolveOptionsVc,
     ) -> ResolveResultVc;
     fn process(&self, asset: AssetVc) -> AssetVc;
+    /// Upgrades an asset that is referenced by url (e. g. `new URL("./file",
+    /// import.meta.url)`). Such assets are emitted as they are instead of
+    /// being processed as modules. Contexts that don't support this return the
+    /// asset unchanged, which leaves the url expression as it is.
+    fn process_url(&self, asset: AssetVc) -> AssetVc {
+        asset
+    }
     fn process_resolve_result(&self, result: ResolveResultVc) -> ResolveResultVc;
     fn with_transition(&self, transition: &str) -> AssetContextVc;
 }
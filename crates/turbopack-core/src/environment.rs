@@ -3,7 +3,7 @@ use std::{
     str::FromStr,
 };
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use swc_core::ecma::preset_env::{Version, Versions};
 use turbo_tasks::{
     primitives::{BoolVc, OptionStringVc, StringVc, StringsVc},
@@ -14,6 +14,7 @@ use turbo_tasks_env::ProcessEnvVc;
 use crate::target::CompileTargetVc;
 
 static DEFAULT_NODEJS_VERSION: &str = "16.0.0";
+static DEFAULT_EDGE_BROWSERSLIST_QUERY: &str = "last 1 Chrome versions";
 
 #[turbo_tasks::value]
 pub struct Environment {
@@ -62,7 +63,7 @@ pub enum EnvironmentIntention {
 pub enum ExecutionEnvironment {
     NodeJsBuildTime(NodeJsEnvironmentVc),
     NodeJsLambda(NodeJsEnvironmentVc),
-    EdgeFunction(NodeJsEnvironmentVc),
+    EdgeFunction(EdgeFunctionEnvironmentVc),
     Browser(BrowserEnvironmentVc),
     // TODO allow custom trait here
     Custom(u8),
@@ -76,8 +77,9 @@ impl EnvironmentVc {
         Ok(match this.execution {
             ExecutionEnvironment::NodeJsBuildTime(node_env, ..)
             | ExecutionEnvironment::NodeJsLambda(node_env) => node_env.await?.compile_target,
-            ExecutionEnvironment::Browser(_) => CompileTargetVc::unknown(),
-            ExecutionEnvironment::EdgeFunction(_) => todo!(),
+            ExecutionEnvironment::Browser(_) | ExecutionEnvironment::EdgeFunction(_) => {
+                CompileTargetVc::unknown()
+            }
             ExecutionEnvironment::Custom(_) => todo!(),
         })
     }
@@ -94,7 +96,12 @@ impl EnvironmentVc {
                     &browserslist::Opts::new(),
                 )?)?)
             }
-            ExecutionEnvironment::EdgeFunction(_) => todo!(),
+            ExecutionEnvironment::EdgeFunction(edge_env) => {
+                RuntimeVersionsVc::cell(Versions::parse_versions(browserslist::resolve(
+                    edge_env.await?.browserslist_query.split(','),
+                    &browserslist::Opts::new(),
+                )?)?)
+            }
             ExecutionEnvironment::Custom(_) => todo!(),
         })
     }
@@ -112,6 +119,29 @@ impl EnvironmentVc {
         })
     }
 
+    #[turbo_tasks::function]
+    pub async fn is_browser(self) -> Result<BoolVc> {
+        let this = self.await?;
+        Ok(match this.execution {
+            ExecutionEnvironment::Browser(_) => BoolVc::cell(true),
+            ExecutionEnvironment::NodeJsBuildTime(..)
+            | ExecutionEnvironment::NodeJsLambda(_)
+            | ExecutionEnvironment::EdgeFunction(_) => BoolVc::cell(false),
+            ExecutionEnvironment::Custom(_) => {
+                bail!("custom execution environments are not supported")
+            }
+        })
+    }
+
+    #[turbo_tasks::function]
+    pub async fn is_edge_function(self) -> Result<BoolVc> {
+        let this = self.await?;
+        Ok(BoolVc::cell(matches!(
+            this.execution,
+            ExecutionEnvironment::EdgeFunction(_)
+        )))
+    }
+
     #[turbo_tasks::function]
     pub async fn resolve_extensions(self) -> Result<StringsVc> {
         let env = self.await?;
@@ -123,9 +153,10 @@ impl EnvironmentVc {
                     ".json".to_string(),
                 ])
             }
-            ExecutionEnvironment::EdgeFunction(_) | ExecutionEnvironment::Browser(_) => {
-                StringsVc::empty()
+            ExecutionEnvironment::EdgeFunction(_) => {
+                StringsVc::cell(vec![".js".to_string(), ".json".to_string()])
             }
+            ExecutionEnvironment::Browser(_) => StringsVc::empty(),
             ExecutionEnvironment::Custom(_) => todo!(),
         })
     }
@@ -137,7 +168,7 @@ impl EnvironmentVc {
             ExecutionEnvironment::NodeJsBuildTime(..) | ExecutionEnvironment::NodeJsLambda(_) => {
                 BoolVc::cell(true)
             }
-            ExecutionEnvironment::EdgeFunction(_) | ExecutionEnvironment::Browser(_) => {
+            ExecutionEnvironment::Browser(_) | ExecutionEnvironment::EdgeFunction(_) => {
                 BoolVc::cell(false)
             }
             ExecutionEnvironment::Custom(_) => todo!(),
@@ -151,9 +182,13 @@ impl EnvironmentVc {
             ExecutionEnvironment::NodeJsBuildTime(..) | ExecutionEnvironment::NodeJsLambda(_) => {
                 StringsVc::cell(vec!["node".to_string()])
             }
-            ExecutionEnvironment::EdgeFunction(_) | ExecutionEnvironment::Browser(_) => {
-                StringsVc::empty()
-            }
+            ExecutionEnvironment::EdgeFunction(_) => StringsVc::cell(
+                EDGE_RESOLVE_CONDITIONS
+                    .iter()
+                    .map(|condition| condition.to_string())
+                    .collect(),
+            ),
+            ExecutionEnvironment::Browser(_) => StringsVc::empty(),
             ExecutionEnvironment::Custom(_) => todo!(),
         })
     }
@@ -245,6 +280,27 @@ pub struct BrowserEnvironment {
     pub browserslist_query: String,
 }
 
+/// The conditions of package `exports` that edge functions resolve, in order of
+/// preference.
+pub const EDGE_RESOLVE_CONDITIONS: [&str; 3] = ["edge-light", "worker", "browser"];
+
+/// A runtime that only provides Web APIs, e. g. the Vercel Edge Runtime or
+/// Cloudflare Workers. Node.js builtins are not available and have to be
+/// polyfilled by packages.
+#[turbo_tasks::value(shared)]
+pub struct EdgeFunctionEnvironment {
+    /// The browserslist query of the V8 versions the code runs on.
+    pub browserslist_query: String,
+}
+
+impl Default for EdgeFunctionEnvironment {
+    fn default() -> Self {
+        EdgeFunctionEnvironment {
+            browserslist_query: DEFAULT_EDGE_BROWSERSLIST_QUERY.to_owned(),
+        }
+    }
+}
+
 #[turbo_tasks::value(transparent)]
 pub struct RuntimeVersions(#[turbo_tasks(trace_ignore)] pub Versions);
 
@@ -2,7 +2,7 @@ use anyhow::Result;
 use turbo_tasks::{primitives::StringVc, ValueToString};
 use turbo_tasks_fs::FileSystemPathVc;
 
-use super::{Issue, IssueVc};
+use super::{Issue, IssueSeverity, IssueSeverityVc, IssueVc};
 use crate::resolve::{options::ResolveOptionsVc, parse::RequestVc};
 
 #[turbo_tasks::value(shared)]
@@ -72,3 +72,53 @@ Type of request: {request_type}",
     // TODO add sub_issue for a description of resolve_options
     // TODO add source link
 }
+
+/// Several archives of a package have been found, e. g. different versions of
+/// it in the cache of Yarn PnP. Only the first one is used, as the version
+/// isn't looked up in the lockfile.
+#[turbo_tasks::value(shared)]
+pub struct AmbiguousPackageIssue {
+    pub context: FileSystemPathVc,
+    pub package_name: String,
+    pub archives: Vec<String>,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for AmbiguousPackageIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(format!(
+            "Multiple archives of the package {}",
+            self.package_name
+        ))
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("resolve".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.context
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "The package {} is resolved from {}, which might not be the version the project \
+             depends on",
+            self.package_name, self.archives[0]
+        ))
+    }
+
+    #[turbo_tasks::function]
+    fn detail(&self) -> StringVc {
+        StringVc::cell(format!("Found archives:\n{}", self.archives.join("\n")))
+    }
+}
@@ -41,3 +41,43 @@ impl Issue for UnsupportedModuleIssue {
         }))
     }
 }
+
+/// A Node.js builtin that was imported in an environment without Node.js APIs,
+/// e. g. an edge function.
+#[turbo_tasks::value(shared)]
+pub struct UnsupportedNodeBuiltinIssue {
+    pub context: FileSystemPathVc,
+    pub module: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for UnsupportedNodeBuiltinIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("resolve".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("Unsupported Node.js builtin".into())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.context
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "The Node.js builtin {} is not available in edge functions. Install a package that \
+             polyfills it instead.",
+            self.module
+        ))
+    }
+}
@@ -318,3 +318,217 @@ impl ExportsField {
         self.0.lookup(request)
     }
 }
+
+/// Content of an "imports" field in a package.json. It maps `#`-prefixed
+/// requests within the package to files of the package or to other packages.
+///
+/// See <https://nodejs.org/api/packages.html#imports>
+#[derive(PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportsField(AliasMap<ExportsValue>);
+
+impl TryFrom<&Value> for ImportsField {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        let object = if let Value::Object(object) = value {
+            object
+        } else {
+            bail!("\"imports\" field must be an object");
+        };
+        let mut map = AliasMap::new();
+        for (key, value) in object.iter() {
+            if !key.starts_with('#') || key == "#" || key.starts_with("#/") {
+                bail!(
+                    "invalid key \"{}\" in an imports field. Keys must start with \"#\" followed \
+                     by a name.",
+                    key
+                );
+            }
+
+            let mut value: ExportsValue = value.try_into()?;
+
+            let pattern = if is_folder_shorthand(key) {
+                expand_folder_shorthand(key, &mut value)?
+            } else {
+                AliasPattern::parse(key)
+            };
+
+            map.insert(pattern, value);
+        }
+        Ok(Self(map))
+    }
+}
+
+impl ImportsField {
+    /// Looks up a request string in the "imports" field. Returns an iterator of
+    /// matching requests. Usually only the first one is relevant, except
+    /// when conditions don't match or only partially match.
+    pub fn lookup<'a>(&'a self, request: &'a str) -> AliasMapLookupIterator<'a, ExportsValue> {
+        self.0.lookup(request)
+    }
+}
+
+/// A valid target of an "imports" field.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImportsTarget<'a> {
+    /// An exact file of the package, relative to the package root. No
+    /// extensions or directory indices are applied to it.
+    Path(&'a str),
+    /// A request to another package.
+    Package(&'a str),
+}
+
+impl<'a> ImportsTarget<'a> {
+    /// Validates a target of an "imports" field. Returns [None] for targets
+    /// that escape the package, contain `.`, `..` or `node_modules` segments,
+    /// or are absolute paths or urls.
+    ///
+    /// See PACKAGE_TARGET_RESOLVE in
+    /// <https://nodejs.org/api/esm.html#resolution-algorithm-specification>
+    pub fn parse(target: &'a str) -> Option<Self> {
+        if let Some(path) = target.strip_prefix("./") {
+            let valid = path.split(['/', '\\']).all(|segment| {
+                !segment.is_empty()
+                    && segment != "."
+                    && segment != ".."
+                    && !segment.eq_ignore_ascii_case("node_modules")
+            });
+            return valid.then_some(ImportsTarget::Path(path));
+        }
+        if target.is_empty()
+            || target.starts_with('.')
+            || target.starts_with('/')
+            || target.starts_with('#')
+            || has_url_scheme(target)
+        {
+            return None;
+        }
+        Some(ImportsTarget::Package(target))
+    }
+}
+
+/// Returns true if the string starts with a url scheme like `file:`.
+fn has_url_scheme(target: &str) -> bool {
+    match target.split_once(':') {
+        Some((scheme, _)) => {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use serde_json::json;
+
+    use super::{ExportsField, ExportsValue, ImportsField, ImportsTarget};
+
+    fn lookup(field: &ImportsField, request: &str) -> Vec<ExportsValue> {
+        field
+            .lookup(request)
+            .map(|m| m.try_into_self().map(Cow::into_owned))
+            .collect::<anyhow::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn imports_field() {
+        let field = ImportsField::try_from(&json!({
+            "#dep": {
+                "node": "dep-node-native",
+                "default": "./dep-polyfill.js"
+            },
+            "#internal/*": "./src/internal/*.js",
+            "#folder/": "./src/folder/"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            lookup(&field, "#dep"),
+            vec![ExportsValue::Conditional(vec![
+                (
+                    "node".to_string(),
+                    ExportsValue::Result("dep-node-native".to_string())
+                ),
+                (
+                    "default".to_string(),
+                    ExportsValue::Result("./dep-polyfill.js".to_string())
+                ),
+            ])]
+        );
+        assert_eq!(
+            lookup(&field, "#internal/a/b"),
+            vec![ExportsValue::Result("./src/internal/a/b.js".to_string())]
+        );
+        assert_eq!(
+            lookup(&field, "#folder/file.js"),
+            vec![ExportsValue::Result("./src/folder/file.js".to_string())]
+        );
+        assert!(lookup(&field, "#other").is_empty());
+        assert!(lookup(&field, "dep").is_empty());
+    }
+
+    #[test]
+    fn imports_field_invalid() {
+        assert!(ImportsField::try_from(&json!("./index.js")).is_err());
+        assert!(ImportsField::try_from(&json!(["#dep"])).is_err());
+        for key in ["dep", "./dep", "#", "#/dep"] {
+            assert!(
+                ImportsField::try_from(&json!({ key: "./dep.js" })).is_err(),
+                "{key} should be an invalid key"
+            );
+        }
+        assert!(ImportsField::try_from(&json!({ "#dep": 42 })).is_err());
+    }
+
+    #[test]
+    fn exports_field_invalid() {
+        assert!(ExportsField::try_from(&json!({ ".": { "./nested": "./index.js" } })).is_err());
+        assert!(ExportsField::try_from(&json!({ "./index": 42 })).is_err());
+        assert!(ExportsField::try_from(&json!({ "./folder/": "./other-folder" })).is_err());
+        assert!(ExportsField::try_from(&json!({ "./folder/": "./other/*/" })).is_err());
+    }
+
+    #[test]
+    fn imports_target() {
+        assert_eq!(
+            ImportsTarget::parse("./src/index.js"),
+            Some(ImportsTarget::Path("src/index.js"))
+        );
+        assert_eq!(
+            ImportsTarget::parse("dep"),
+            Some(ImportsTarget::Package("dep"))
+        );
+        assert_eq!(
+            ImportsTarget::parse("@scope/dep/file.js"),
+            Some(ImportsTarget::Package("@scope/dep/file.js"))
+        );
+        for target in [
+            "",
+            ".",
+            "./",
+            "../outside.js",
+            "./../outside.js",
+            "./src/../../outside.js",
+            "./src/./index.js",
+            "./src//index.js",
+            "./node_modules/dep/index.js",
+            "./src\\..\\..\\outside.js",
+            "/absolute.js",
+            "#dep",
+            "file:///absolute.js",
+            "https://example.com/dep.js",
+        ] {
+            assert_eq!(
+                ImportsTarget::parse(target),
+                None,
+                "{target} should be invalid"
+            );
+        }
+    }
+}
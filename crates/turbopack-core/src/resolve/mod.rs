@@ -15,12 +15,14 @@ use turbo_tasks::{
     TryJoinIterExt, Value, ValueToString, ValueToStringVc,
 };
 use turbo_tasks_fs::{
+    archive::ArchiveFileSystemVc,
     util::{normalize_path, normalize_request},
-    FileJsonContent, FileJsonContentVc, FileSystemEntryType, FileSystemPathVc, RealPathResult,
+    DirectoryContent, DirectoryEntry, FileJsonContent, FileJsonContentVc, FileSystemEntryType,
+    FileSystemPathVc, RealPathResult,
 };
 
 use self::{
-    exports::{ExportsField, ExportsValue},
+    exports::{ExportsField, ExportsValue, ImportsField, ImportsTarget},
     options::{
         resolve_modules_options, ImportMapResult, ResolveInPackage, ResolveIntoPackage,
         ResolveModules, ResolveModulesOptionsVc, ResolveOptionsVc,
@@ -32,7 +34,9 @@ use crate::{
     asset::{AssetVc, AssetsVc},
     issue::{
         package_json::{PackageJsonIssue, PackageJsonIssueVc},
-        resolve::{ResolvingIssue, ResolvingIssueVc},
+        resolve::{
+            AmbiguousPackageIssue, AmbiguousPackageIssueVc, ResolvingIssue, ResolvingIssueVc,
+        },
     },
     reference::{AssetReference, AssetReferenceVc},
     resolve::{
@@ -384,6 +388,41 @@ async fn exports_field(
     }
 }
 
+#[turbo_tasks::value(shared)]
+enum ImportsFieldResult {
+    Some(#[turbo_tasks(debug_ignore, trace_ignore)] ImportsField),
+    None,
+}
+
+#[turbo_tasks::function]
+async fn imports_field(
+    package_json_path: FileSystemPathVc,
+    package_json: FileJsonContentVc,
+    field: &str,
+) -> Result<ImportsFieldResultVc> {
+    if let FileJsonContent::Content(package_json) = &*package_json.await? {
+        let field_value = &package_json[field];
+        if let serde_json::Value::Null = field_value {
+            return Ok(ImportsFieldResult::None.into());
+        }
+        let imports_field: Result<ImportsField> = field_value.try_into();
+        match imports_field {
+            Ok(imports_field) => Ok(ImportsFieldResult::Some(imports_field).into()),
+            Err(err) => {
+                let issue: PackageJsonIssueVc = PackageJsonIssue {
+                    path: package_json_path,
+                    error_message: err.to_string(),
+                }
+                .into();
+                issue.as_issue().emit();
+                Ok(ImportsFieldResult::None.into())
+            }
+        }
+    } else {
+        Ok(ImportsFieldResult::None.into())
+    }
+}
+
 #[turbo_tasks::value(shared)]
 pub enum FindContextFileResult {
     Found(FileSystemPathVc, Vec<AssetReferenceVc>),
@@ -469,6 +508,40 @@ async fn find_package(
                     packages.push(package_dir.resolve().await?);
                 }
             }
+            ResolveModules::Archives(dir) => {
+                if let DirectoryContent::Entries(entries) = &*dir.read_dir().await? {
+                    let mut archives = entries
+                        .iter()
+                        .filter_map(|(name, entry)| match entry {
+                            DirectoryEntry::File(path) => {
+                                package_archive_subdirectory(name, &package_name)
+                                    .map(|subdirectory| (name, *path, subdirectory))
+                            }
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    // Directory entries are not ordered
+                    archives.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+                    if archives.len() > 1 {
+                        let issue: AmbiguousPackageIssueVc = AmbiguousPackageIssue {
+                            context: *dir,
+                            package_name: package_name.clone(),
+                            archives: archives.iter().map(|(name, ..)| name.to_string()).collect(),
+                        }
+                        .into();
+                        issue.as_issue().emit();
+                    }
+                    if let Some((_, archive, subdirectory)) = archives.into_iter().next() {
+                        packages.push(
+                            ArchiveFileSystemVc::new(archive, &subdirectory)
+                                .as_file_system()
+                                .root()
+                                .resolve()
+                                .await?,
+                        );
+                    }
+                }
+            }
             ResolveModules::Registry(_, _) => todo!(),
         }
     }
@@ -478,6 +551,29 @@ async fn find_package(
     }))
 }
 
+/// Returns the directory of the package inside of the archive, when the file
+/// name is an archive of the package. See [ResolveModules::Archives].
+fn package_archive_subdirectory(file_name: &str, package_name: &str) -> Option<String> {
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    let ident = package_name.replace('/', "-");
+    match extension {
+        // Yarn keeps the `@` of scoped names
+        "zip" => {
+            stem.strip_prefix(ident.as_str())?.strip_prefix("-npm-")?;
+            Some(format!("node_modules/{}", package_name))
+        }
+        // npm drops the `@` of scoped names
+        "tgz" | "tar" => {
+            let ident = ident.strip_prefix('@').unwrap_or(&ident);
+            let version = stem.strip_prefix(ident)?.strip_prefix('-')?;
+            version
+                .starts_with(|c: char| c.is_ascii_digit())
+                .then(|| "package".to_string())
+        }
+        _ => None,
+    }
+}
+
 fn merge_results(results: Vec<ResolveResultVc>) -> ResolveResultVc {
     match results.len() {
         0 => ResolveResult::unresolveable().into(),
@@ -683,17 +779,8 @@ pub async fn resolve(
             ResolveResult::unresolveable().into()
         }
         Request::Empty => ResolveResult::unresolveable().into(),
-        Request::PackageInternal { path: _ } => {
-            let issue: ResolvingIssueVc = ResolvingIssue {
-                request_type: "package internal import: not implemented yet".to_string(),
-                request,
-                context,
-                resolve_options: options,
-                error_message: Some("package internal imports are not implemented yet".to_string()),
-            }
-            .into();
-            issue.as_issue().emit();
-            ResolveResult::unresolveable().into()
+        Request::PackageInternal { path } => {
+            resolve_package_internal_request(context, request, options, options_value, path).await?
         }
         Request::Uri {
             protocol,
@@ -797,6 +884,73 @@ async fn resolve_into_folder(
     Ok(ResolveResult::unresolveable().into())
 }
 
+async fn resolve_package_internal_request(
+    context: FileSystemPathVc,
+    request: RequestVc,
+    options: ResolveOptionsVc,
+    options_value: &ResolveOptions,
+    path: &Pattern,
+) -> Result<ResolveResultVc> {
+    let path = if let Some(path) = path.clone().into_string() {
+        path
+    } else {
+        let issue: ResolvingIssueVc = ResolvingIssue {
+            request_type: "package internal import: dynamic requests are not supported".to_string(),
+            request,
+            context,
+            resolve_options: options,
+            error_message: Some(
+                "package internal imports can't be resolved from dynamic requests".to_string(),
+            ),
+        }
+        .into();
+        issue.as_issue().emit();
+        return Ok(ResolveResult::unresolveable().into());
+    };
+
+    let (package_json_path, refs) = match &*find_context_file(context, "package.json").await? {
+        FindContextFileResult::Found(package_json_path, refs) => (*package_json_path, refs),
+        FindContextFileResult::NotFound(refs) => {
+            return Ok(ResolveResult::Unresolveable(refs.clone()).into());
+        }
+    };
+    let package_json = package_json_path.read_json();
+    for resolve_in in options_value.in_package.iter() {
+        if let ResolveInPackage::ImportsField {
+            field,
+            conditions,
+            unspecified_conditions,
+        } = resolve_in
+        {
+            if let ImportsFieldResult::Some(imports_field) =
+                &*imports_field(package_json_path, package_json, field).await?
+            {
+                let result = handle_imports_field(
+                    package_json_path,
+                    request,
+                    options,
+                    imports_field,
+                    &path,
+                    conditions,
+                    unspecified_conditions,
+                )
+                .await?;
+                if refs.is_empty() {
+                    return Ok(result);
+                }
+                let mut result = result.await?.clone();
+                for reference in refs.iter() {
+                    result.add_reference(*reference);
+                }
+                return Ok(result.into());
+            }
+        }
+    }
+    let mut refs = refs.clone();
+    refs.push(AffectingResolvingAssetReferenceVc::new(package_json_path).into());
+    Ok(ResolveResult::Unresolveable(refs).into())
+}
+
 async fn resolve_module_request(
     context: FileSystemPathVc,
     options: ResolveOptionsVc,
@@ -1018,6 +1172,7 @@ async fn resolved(
                     }
                 }
             }
+            ResolveInPackage::ImportsField { .. } => {}
         }
     }
     if let Some(resolved_map) = resolved_map {
@@ -1052,26 +1207,11 @@ fn handle_exports_field(
     conditions: &BTreeMap<String, ConditionValue>,
     unspecified_conditions: &ConditionValue,
 ) -> Result<ResolveResultVc> {
-    let mut results = Vec::new();
-    let mut conditions_state = HashMap::new();
     let values = exports_field
         .lookup(path)
         .map(AliasMatch::try_into_self)
         .collect::<Result<Vec<Cow<'_, ExportsValue>>>>()?;
-    for value in values.iter() {
-        if value.add_results(
-            conditions,
-            unspecified_conditions,
-            &mut conditions_state,
-            &mut results,
-        ) {
-            break;
-        }
-    }
-    {
-        let mut duplicates_set = HashSet::new();
-        results.retain(|item| duplicates_set.insert(*item));
-    }
+    let results = conditional_results(&values, conditions, unspecified_conditions);
     let mut resolved_results = Vec::new();
     for path in results {
         if let Some(path) = normalize_path(path) {
@@ -1086,6 +1226,96 @@ fn handle_exports_field(
     ))
 }
 
+async fn handle_imports_field(
+    package_json: FileSystemPathVc,
+    request: RequestVc,
+    options: ResolveOptionsVc,
+    imports_field: &ImportsField,
+    path: &str,
+    conditions: &BTreeMap<String, ConditionValue>,
+    unspecified_conditions: &ConditionValue,
+) -> Result<ResolveResultVc> {
+    let values = imports_field
+        .lookup(path)
+        .map(AliasMatch::try_into_self)
+        .collect::<Result<Vec<Cow<'_, ExportsValue>>>>()?;
+    let results = conditional_results(&values, conditions, unspecified_conditions);
+    let package_path = package_json.parent();
+    let options_value = options.await?;
+    let mut resolved_results = Vec::new();
+    for target in results {
+        // Targets are either exact files of the package or requests to other
+        // packages
+        match ImportsTarget::parse(target) {
+            Some(ImportsTarget::Path(target)) => {
+                let mut refs = Vec::new();
+                let result =
+                    if let Some(fs_path) = exists(package_path.join(target), &mut refs).await? {
+                        resolved(
+                            fs_path,
+                            package_path,
+                            RequestVc::parse(Value::new(format!("./{}", target).into())),
+                            &options_value,
+                            options,
+                        )
+                        .await?
+                    } else {
+                        ResolveResult::Unresolveable(refs).into()
+                    };
+                resolved_results.push(result);
+            }
+            Some(ImportsTarget::Package(target)) => {
+                let request = RequestVc::parse(Value::new(target.to_string().into()));
+                resolved_results.push(resolve(package_path, request, options));
+            }
+            None => {
+                let issue: ResolvingIssueVc = ResolvingIssue {
+                    request_type: "package internal import".to_string(),
+                    request,
+                    context: package_json,
+                    resolve_options: options,
+                    error_message: Some(format!(
+                        "invalid target \"{}\" for \"{}\" in an imports field. Targets must be \
+                         files within the package starting with \"./\" or requests to other \
+                         packages.",
+                        target, path
+                    )),
+                }
+                .into();
+                issue.as_issue().emit();
+            }
+        }
+    }
+    Ok(merge_results_with_references(
+        resolved_results,
+        vec![AffectingResolvingAssetReferenceVc::new(package_json).into()],
+    ))
+}
+
+/// Collects the targets of the matching values of an "exports" or "imports"
+/// field that apply to the given conditions.
+fn conditional_results<'a>(
+    values: &'a [Cow<'_, ExportsValue>],
+    conditions: &BTreeMap<String, ConditionValue>,
+    unspecified_conditions: &ConditionValue,
+) -> Vec<&'a str> {
+    let mut results = Vec::new();
+    let mut conditions_state = HashMap::new();
+    for value in values.iter() {
+        if value.add_results(
+            conditions,
+            unspecified_conditions,
+            &mut conditions_state,
+            &mut results,
+        ) {
+            break;
+        }
+    }
+    let mut duplicates_set = HashSet::new();
+    results.retain(|item| duplicates_set.insert(*item));
+    results
+}
+
 #[turbo_tasks::value]
 pub struct AffectingResolvingAssetReference {
     path: FileSystemPathVc,
@@ -1154,3 +1384,39 @@ pub async fn handle_resolve_error(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::package_archive_subdirectory;
+
+    #[rstest]
+    #[case::yarn(
+        "react-npm-18.2.0-1eb4b49c4b-88e38092da.zip",
+        "react",
+        Some("node_modules/react")
+    )]
+    #[case::yarn_scoped(
+        "@babel-core-npm-7.20.5-1a2b3c4d5e-6f7a8b9c0d.zip",
+        "@babel/core",
+        Some("node_modules/@babel/core")
+    )]
+    #[case::npm("react-18.2.0.tgz", "react", Some("package"))]
+    #[case::npm_tar("react-18.2.0.tar", "react", Some("package"))]
+    #[case::npm_scoped("babel-core-7.20.5.tgz", "@babel/core", Some("package"))]
+    #[case::other_package("react-dom-npm-18.2.0-1eb4b49c4b-88e38092da.zip", "react", None)]
+    #[case::other_package_npm("react-dom-18.2.0.tgz", "react", None)]
+    #[case::other_extension("react-18.2.0.json", "react", None)]
+    #[case::zip_without_npm("react-18.2.0.zip", "react", None)]
+    fn archive_names(
+        #[case] file_name: &str,
+        #[case] package_name: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(
+            package_archive_subdirectory(file_name, package_name).as_deref(),
+            expected
+        );
+    }
+}
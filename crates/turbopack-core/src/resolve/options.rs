@@ -28,6 +28,11 @@ pub enum ResolveModules {
     Nested(FileSystemPathVc, Vec<String>),
     /// look into that directory
     Path(FileSystemPathVc),
+    /// look into the package archives in that directory, e. g. the
+    /// `.yarn/cache` of Yarn PnP. Archives are named like the zip archives of
+    /// Yarn (`@scope-name-npm-<version>-<hash>.zip`) or like the tarballs of
+    /// `npm pack` (`scope-name-<version>.tgz`).
+    Archives(FileSystemPathVc),
     /// lookup versions based on lockfile in the registry filesystem
     /// registry filesystem is assumed to have structure like
     /// @scope/module/version/<path-in-package>
@@ -77,6 +82,14 @@ pub enum ResolveIntoPackage {
 pub enum ResolveInPackage {
     /// Using a alias field which allows to map requests
     AliasField(String),
+    /// Using the [imports] field, which maps `#`-prefixed requests.
+    ///
+    /// [imports]: https://nodejs.org/api/packages.html#imports
+    ImportsField {
+        field: String,
+        conditions: BTreeMap<String, ConditionValue>,
+        unspecified_conditions: ConditionValue,
+    },
 }
 
 #[turbo_tasks::value(shared)]
@@ -1,6 +1,7 @@
+use anyhow::Result;
 use turbo_tasks_fs::FileSystemPathVc;
 
-use super::{options::ResolveOptionsVc, parse::RequestVc, ResolveResultVc};
+use super::{options::ResolveOptionsVc, parse::RequestVc, resolve, ResolveResultVc};
 use crate::context::AssetContextVc;
 
 /// A location where resolving can occur from. It carries some meta information
@@ -32,6 +33,32 @@ impl ResolveOriginVc {
             .resolve_asset(self.origin_path(), request, options)
     }
 
+    /// Resolve to an asset that is referenced by url from that origin, e. g.
+    /// `new URL("./file", import.meta.url)`. The resolved assets are emitted
+    /// as they are (see
+    /// [AssetContext::process_url](crate::context::AssetContext::process_url)).
+    #[turbo_tasks::function]
+    pub async fn resolve_url(
+        self,
+        request: RequestVc,
+        options: ResolveOptionsVc,
+    ) -> Result<ResolveResultVc> {
+        let context = self.context();
+        let result = resolve(
+            self.origin_path().parent().resolve().await?,
+            request,
+            options,
+        );
+        Ok(result
+            .await?
+            .map(
+                |asset| context.process_url(asset).resolve(),
+                |reference| async move { Ok(reference) },
+            )
+            .await?
+            .into())
+    }
+
     /// Get the resolve options that apply for this origin.
     #[turbo_tasks::function]
     pub fn resolve_options(self) -> ResolveOptionsVc {
@@ -26,6 +26,14 @@ pub enum SourceMap {
     Sectioned(#[turbo_tasks(trace_ignore)] SectionedSourceMap),
 }
 
+#[turbo_tasks::value_impl]
+impl GenerateSourceMap for SourceMap {
+    #[turbo_tasks::function]
+    fn generate_source_map(self_vc: SourceMapVc) -> SourceMapVc {
+        self_vc
+    }
+}
+
 /// A token represents a mapping in a source map. It may either be Synthetic,
 /// meaning it was generated by some build tool and doesn't represent a location
 /// in a user-authored source file, or it is Original, meaning it represents a
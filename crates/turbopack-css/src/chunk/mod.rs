@@ -11,10 +11,12 @@ use turbo_tasks_hash::{encode_hex, Xxh3Hash64Hasher};
 use turbopack_core::{
     asset::{Asset, AssetContentVc, AssetVc},
     chunk::{
-        chunk_content, chunk_content_split,
+        chunk_content, chunk_content_split, content_hash_placeholder_path,
+        content_hashed_chunk_path,
         optimize::{ChunkOptimizerVc, OptimizableChunk, OptimizableChunkVc},
-        Chunk, ChunkContentResult, ChunkGroupReferenceVc, ChunkGroupVc, ChunkItem, ChunkItemVc,
-        ChunkReferenceVc, ChunkVc, ChunkableAssetVc, ChunkingContextVc, FromChunkableAsset,
+        with_content_hash, Chunk, ChunkContentResult, ChunkGroupReferenceVc, ChunkGroupVc,
+        ChunkItem, ChunkItemVc, ChunkReferenceVc, ChunkVc, ChunkableAssetVc, ChunkingContextVc,
+        FromChunkableAsset,
     },
     reference::{AssetReferenceVc, AssetReferencesVc},
 };
@@ -22,7 +24,9 @@ use turbopack_ecmascript::utils::FormatIter;
 use writer::{expand_imports, WriterWithIndent};
 
 use self::optimize::CssChunkOptimizerVc;
-use crate::{embed::CssEmbeddableVc, util::stringify_str, ImportAssetReferenceVc};
+use crate::{
+    embed::CssEmbeddableVc, minify::minify_css, util::stringify_str, ImportAssetReferenceVc,
+};
 
 #[turbo_tasks::value]
 pub struct CssChunk {
@@ -46,6 +50,80 @@ impl CssChunkVc {
         Self::new_normalized(context, CssChunkPlaceablesVc::cell(vec![entry]))
     }
 
+    /// The path of the chunk as it's named by the chunking context.
+    #[turbo_tasks::function]
+    async fn chunk_path(self) -> Result<FileSystemPathVc> {
+        let this = self.await?;
+        let mut hasher = Xxh3Hash64Hasher::new();
+
+        let main_entries = this.main_entries.await?;
+        let mut main_entries = main_entries.iter();
+        let mut needs_hash = false;
+        let main_entry = main_entries
+            .next()
+            .ok_or_else(|| anyhow!("Chunk must have at least one entry"))?;
+        for entry in main_entries {
+            let path = entry.path().to_string().await?;
+            hasher.write_value(path);
+            needs_hash = true;
+        }
+
+        let hash = hasher.finish();
+        let mut path = main_entry.path();
+        if needs_hash {
+            path = path.append_to_stem(&format!(".{}", encode_hex(hash)))
+        }
+
+        Ok(this.context.chunk_path(path, ".css"))
+    }
+
+    /// The path the content of the chunk is generated at. Chunks that are
+    /// named by the hash of their content are generated at a placeholder path,
+    /// which is replaced afterwards.
+    #[turbo_tasks::function]
+    async fn content_path(self) -> Result<FileSystemPathVc> {
+        let path = self.chunk_path();
+        Ok(
+            if *self.await?.context.is_content_hashing_enabled().await? {
+                content_hash_placeholder_path(path, ".css")
+            } else {
+                path
+            },
+        )
+    }
+
+    /// Generates the content of the chunk as if it was placed at `path`.
+    #[turbo_tasks::function]
+    async fn content_with_path(self, path: FileSystemPathVc) -> Result<AssetContentVc> {
+        let this = self.await?;
+        let chunk_name = path.to_string();
+
+        let mut body = "".to_owned();
+        let mut writer = WriterWithIndent::new(&mut body);
+        let mut external_imports = IndexSet::new();
+        for entry in this.main_entries.await?.iter() {
+            let entry_placeable = CssChunkPlaceableVc::cast_from(entry);
+            let entry_content = entry_placeable.as_chunk_item(this.context).content();
+
+            for external_import in expand_imports(&mut writer, entry_content).await? {
+                external_imports.insert(external_import.await?.to_owned());
+            }
+        }
+
+        let mut code = format!("/* chunk {} */\n", chunk_name.await?);
+        for external_import in external_imports {
+            writeln!(code, "@import {};", stringify_str(&external_import))?;
+        }
+
+        code += &body;
+
+        if *this.context.is_minification_enabled().await? {
+            code = minify_css(path, code).await?;
+        }
+
+        Ok(File::from(code).into())
+    }
+
     /// Return the most specific directory which contains all elements of the
     /// chunk.
     #[turbo_tasks::function]
@@ -179,57 +257,21 @@ impl ValueToString for CssChunk {
 impl Asset for CssChunk {
     #[turbo_tasks::function]
     async fn path(self_vc: CssChunkVc) -> Result<FileSystemPathVc> {
-        let this = self_vc.await?;
-        let mut hasher = Xxh3Hash64Hasher::new();
-
-        let main_entries = this.main_entries.await?;
-        let mut main_entries = main_entries.iter();
-        let mut needs_hash = false;
-        let main_entry = main_entries
-            .next()
-            .ok_or_else(|| anyhow!("Chunk must have at least one entry"))?;
-        for entry in main_entries {
-            let path = entry.path().to_string().await?;
-            hasher.write_value(path);
-            needs_hash = true;
+        let path = self_vc.chunk_path();
+        if *self_vc.await?.context.is_content_hashing_enabled().await? {
+            let content = self_vc.content_with_path(self_vc.content_path());
+            return Ok(content_hashed_chunk_path(path, content, ".css"));
         }
-
-        let hash = hasher.finish();
-        let mut path = main_entry.path();
-        if needs_hash {
-            path = path.append_to_stem(&format!(".{}", encode_hex(hash)))
-        }
-
-        Ok(this.context.chunk_path(path, ".css"))
+        Ok(path)
     }
 
     #[turbo_tasks::function]
     async fn content(self_vc: CssChunkVc) -> Result<AssetContentVc> {
-        let this = self_vc.await?;
-
-        let path = self_vc.path();
-        let chunk_name = path.to_string();
-
-        let mut body = "".to_owned();
-        let mut writer = WriterWithIndent::new(&mut body);
-        let mut external_imports = IndexSet::new();
-        for entry in this.main_entries.await?.iter() {
-            let entry_placeable = CssChunkPlaceableVc::cast_from(entry);
-            let entry_content = entry_placeable.as_chunk_item(this.context).content();
-
-            for external_import in expand_imports(&mut writer, entry_content).await? {
-                external_imports.insert(external_import.await?.to_owned());
-            }
-        }
-
-        let mut code = format!("/* chunk {} */\n", chunk_name.await?);
-        for external_import in external_imports {
-            writeln!(code, "@import {};", stringify_str(&external_import))?;
+        let content = self_vc.content_with_path(self_vc.content_path());
+        if *self_vc.await?.context.is_content_hashing_enabled().await? {
+            return Ok(with_content_hash(content));
         }
-
-        code += &body;
-
-        Ok(File::from(code).into())
+        Ok(content)
     }
 
     #[turbo_tasks::function]
@@ -310,4 +352,11 @@ impl FromChunkableAsset for CssChunkItemVc {
     ) -> Result<Option<(Self, ChunkableAssetVc)>> {
         Ok(None)
     }
+
+    async fn from_isolated_asset(
+        _context: ChunkingContextVc,
+        _asset: ChunkableAssetVc,
+    ) -> Result<Option<Self>> {
+        Ok(None)
+    }
 }
@@ -8,6 +8,7 @@ mod asset;
 pub mod chunk;
 mod code_gen;
 pub mod embed;
+mod minify;
 mod module_asset;
 pub(crate) mod parse;
 mod path_visitor;
@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use swc_core::{
+    common::{errors::Handler, FileName, SourceMap},
+    css::{
+        ast::Stylesheet,
+        codegen::{
+            writer::basic::{BasicCssWriter, BasicCssWriterConfig},
+            CodeGenerator, CodegenConfig, Emit,
+        },
+        minifier::minify,
+        parser::{parse_file, parser::ParserConfig},
+    },
+};
+use turbo_tasks::ValueToString;
+use turbo_tasks_fs::{File, FileSystemPathVc};
+use turbopack_core::virtual_asset::VirtualAssetVc;
+use turbopack_swc_utils::emitter::IssueEmitter;
+
+/// Minifies the generated code of a css chunk at `path` with the swc css
+/// minifier. Errors while parsing the code are reported as issues and leave the
+/// code unminified.
+pub(crate) async fn minify_css(path: FileSystemPathVc, code: String) -> Result<String> {
+    let name = path.to_string().await?;
+    let source_map: Arc<SourceMap> = Default::default();
+    // Issues point into the unminified code
+    let source = VirtualAssetVc::new(path, File::from(code.clone()).into());
+    let handler = Handler::with_emitter(
+        true,
+        false,
+        box IssueEmitter {
+            source: source.into(),
+            source_map: source_map.clone(),
+            title: Some("Minifying css chunk failed".to_string()),
+        },
+    );
+    Ok(minify_stylesheet(&source_map, &handler, &name, &code)?.unwrap_or(code))
+}
+
+/// Returns the minified code, or `None` when the code can't be parsed. Parse
+/// errors are emitted to the `handler`.
+fn minify_stylesheet(
+    source_map: &SourceMap,
+    handler: &Handler,
+    path: &str,
+    code: &str,
+) -> Result<Option<String>> {
+    let fm = source_map.new_source_file(FileName::Custom(path.to_string()), code.to_string());
+
+    let mut errors = Vec::new();
+    let stylesheet = parse_file::<Stylesheet>(&fm, ParserConfig::default(), &mut errors);
+    let mut has_errors = false;
+    for e in errors {
+        e.to_diagnostics(handler).emit();
+        has_errors = true;
+    }
+    let mut stylesheet = match stylesheet {
+        Ok(stylesheet) if !has_errors => stylesheet,
+        Ok(_) => return Ok(None),
+        Err(e) => {
+            e.to_diagnostics(handler).emit();
+            return Ok(None);
+        }
+    };
+
+    minify(&mut stylesheet, Default::default());
+
+    let mut code_string = String::new();
+    let mut code_gen = CodeGenerator::new(
+        BasicCssWriter::new(&mut code_string, None, BasicCssWriterConfig::default()),
+        CodegenConfig { minify: true },
+    );
+    code_gen.emit(&stylesheet)?;
+
+    Ok(Some(code_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_core::common::{
+        errors::{DiagnosticBuilder, Emitter, Handler},
+        SourceMap,
+    };
+
+    use super::minify_stylesheet;
+
+    struct NoopEmitter;
+
+    impl Emitter for NoopEmitter {
+        fn emit(&mut self, _db: &DiagnosticBuilder<'_>) {}
+    }
+
+    fn minify(code: &str) -> (Option<String>, bool) {
+        let source_map = SourceMap::default();
+        let handler = Handler::with_emitter(true, false, box NoopEmitter);
+        let minified = minify_stylesheet(&source_map, &handler, "chunk.css", code).unwrap();
+        (minified, handler.has_errors())
+    }
+
+    #[test]
+    fn minifies() {
+        let (minified, has_errors) = minify("/* chunk */\n.a {\n  color: #ff0000;\n}\n");
+        assert!(!has_errors);
+        assert_eq!(minified.as_deref(), Some(".a{color:red}"));
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let (minified, has_errors) = minify(".a..b { color: red; }");
+        assert!(has_errors);
+        assert_eq!(minified, None);
+    }
+}
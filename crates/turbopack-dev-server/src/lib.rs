@@ -25,17 +25,19 @@ use anyhow::{bail, Context, Result};
 use futures::{StreamExt, TryStreamExt};
 use hyper::{
     header::HeaderName,
+    http::response::Builder,
     service::{make_service_fn, service_fn},
     Request, Response, Server,
 };
 use mime_guess::mime;
 use source::{Body, Bytes};
 use turbo_tasks::{
-    run_once, trace::TraceRawVcs, util::FormatDuration, RawVc, TransientValue, TurboTasksApi, Value,
+    run_once_with_priority, trace::TraceRawVcs, util::FormatDuration, RawVc,
+    StronglyConsistentReader, TaskPriority, TransientValue, TurboTasksApi, Value,
 };
 use turbo_tasks_fs::{FileContent, FileContentReadRef};
 use turbopack_cli_utils::issue::{ConsoleUi, ConsoleUiVc};
-use turbopack_core::asset::AssetContent;
+use turbopack_core::{asset::AssetContent, issue::IssueSeverity};
 
 use self::{
     source::{
@@ -46,6 +48,14 @@ use self::{
 };
 use crate::source::{ContentSourceData, HeaderValue};
 
+/// How long a request waits by default for its content to be recomputed before
+/// it's served with the content computed for an earlier request
+pub const DEFAULT_CONTENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Set on responses with content that might be outdated, as recomputing it
+/// took longer than the content timeout passed to [DevServer::listen]
+const STALE_HEADER: &str = "x-turbopack-stale";
+
 pub trait SourceProvider: Send + Clone + 'static {
     /// must call a turbo-tasks function internally
     fn get_source(&self) -> ContentSourceVc;
@@ -127,24 +137,48 @@ async fn get_from_source(
     .cell())
 }
 
+fn response_builder(stale: bool) -> Builder {
+    let builder = Response::builder();
+    if stale {
+        builder.header(STALE_HEADER, "1")
+    } else {
+        builder
+    }
+}
+
 async fn process_request_with_content_source(
     path: &str,
     mut resolved_source: ContentSourceVc,
     mut asset_path: Cow<'_, str>,
     mut request: Request<hyper::Body>,
     console_ui: ConsoleUiVc,
+    reader: &StronglyConsistentReader<GetFromSourceResult>,
 ) -> Result<Response<hyper::Body>> {
     let mut data = ContentSourceData::default();
+    let mut stale = false;
     loop {
         let content_source_result = get_from_source(resolved_source, &asset_path, Value::new(data));
-        handle_issues(
-            content_source_result,
-            path,
-            "get content from source",
-            console_ui,
-        )
-        .await?;
-        match &*content_source_result.strongly_consistent().await? {
+        let result = reader.read(content_source_result).await?;
+        if result.stale {
+            // Issues are reported once the recomputation has completed
+            console_ui.await?.log(
+                IssueSeverity::Warning,
+                "stale",
+                &format!(
+                    "/{path} is still being recomputed, serving the content of an earlier request"
+                ),
+            );
+            stale = true;
+        } else {
+            handle_issues(
+                content_source_result,
+                path,
+                "get content from source",
+                console_ui,
+            )
+            .await?;
+        }
+        match &*result.value {
             GetFromSourceResult::Static(file) => {
                 if let FileContent::Content(content) = &**file {
                     let content_type = content.content_type().map_or_else(
@@ -169,7 +203,7 @@ async fn process_request_with_content_source(
 
                     let content = content.content();
                     let bytes = content.read();
-                    return Ok(Response::builder()
+                    return Ok(response_builder(stale)
                         .status(200)
                         .header("Content-Type", content_type)
                         .header("Content-Length", content.len().to_string())
@@ -177,7 +211,7 @@ async fn process_request_with_content_source(
                 }
             }
             GetFromSourceResult::HttpProxy(proxy_result) => {
-                let mut response = Response::builder().status(proxy_result.status);
+                let mut response = response_builder(stale).status(proxy_result.status);
                 let headers = response.headers_mut().expect("headers must be defined");
 
                 for [name, value] in proxy_result.headers.array_chunks() {
@@ -197,27 +231,36 @@ async fn process_request_with_content_source(
             }
             GetFromSourceResult::NotFound => {}
         }
-        return Ok(Response::builder().status(404).body(hyper::Body::empty())?);
+        return Ok(response_builder(stale)
+            .status(404)
+            .body(hyper::Body::empty())?);
     }
 }
 
 impl DevServer {
+    /// Starts the server. When the content of a request isn't recomputed
+    /// within `content_timeout`, the content of an earlier request is served
+    /// instead, if there is one. `None` always waits for the recomputation.
     pub fn listen(
         turbo_tasks: Arc<dyn TurboTasksApi>,
         source_provider: impl SourceProvider + Clone + Send + Sync,
         addr: SocketAddr,
         console_ui: Arc<ConsoleUi>,
+        content_timeout: Option<Duration>,
     ) -> Result<Self, anyhow::Error> {
+        let reader = Arc::new(StronglyConsistentReader::new(content_timeout));
         let make_svc = make_service_fn(move |_| {
             let tt = turbo_tasks.clone();
             let source_provider = source_provider.clone();
             let console_ui = console_ui.clone();
+            let reader = reader.clone();
             async move {
                 let handler = move |request: Request<hyper::Body>| {
                     let console_ui = console_ui.clone();
                     let start = Instant::now();
                     let tt = tt.clone();
                     let source_provider = source_provider.clone();
+                    let reader = reader.clone();
                     let future = async move {
                         if hyper_tungstenite::is_upgrade_request(&request) {
                             let uri = request.uri();
@@ -248,7 +291,9 @@ impl DevServer {
                                 .body(hyper::Body::empty())?);
                         }
 
-                        run_once(tt, async move {
+                        // Somebody is waiting for the response, so it's computed before
+                        // background work
+                        run_once_with_priority(tt, TaskPriority::High, async move {
                             let console_ui = (*console_ui).clone().cell();
                             let uri = request.uri();
                             let path = uri.path();
@@ -264,6 +309,7 @@ impl DevServer {
                                 asset_path,
                                 request,
                                 console_ui,
+                                &reader,
                             )
                             .await?;
                             let status = response.status().as_u16();
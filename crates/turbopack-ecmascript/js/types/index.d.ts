@@ -61,6 +61,8 @@ interface TurbopackContext {
   c: ModuleCache;
   l: LoadChunk;
   p: Partial<NodeJS.Process> & Pick<NodeJS.Process, "env">;
+  h: Hot;
+  k: ChunkPath;
 }
 
 type ModuleFactory = (
@@ -46,6 +46,20 @@ pub enum Effect {
         span: Span,
         ast_path: Vec<AstParentKind>,
     },
+    /// `new URL(input, import.meta.url)`
+    Url {
+        input: JsValue,
+        ast_path: Vec<AstParentKind>,
+        span: Span,
+    },
+    /// `new Worker(new URL(input, import.meta.url))` or the same with
+    /// `SharedWorker`. The `span` is the one of the inner `new URL(...)`, which
+    /// is also reported as [Effect::Url].
+    Worker {
+        input: JsValue,
+        ast_path: Vec<AstParentKind>,
+        span: Span,
+    },
 }
 
 impl Effect {
@@ -94,6 +108,18 @@ impl Effect {
                 span: _,
                 ast_path: _,
             } => {}
+            Effect::Url {
+                input,
+                ast_path: _,
+                span: _,
+            }
+            | Effect::Worker {
+                input,
+                ast_path: _,
+                span: _,
+            } => {
+                input.normalize();
+            }
         }
     }
 }
@@ -433,6 +459,36 @@ struct Analyzer<'a> {
     cur_fn_return_values: Option<Vec<JsValue>>,
 }
 
+/// Returns the `input` of a `new URL(input, import.meta.url)` expression.
+fn url_input(n: &NewExpr) -> Option<&Expr> {
+    if let Some(
+        [ExprOrSpread {
+            spread: None,
+            expr: input,
+        }, ExprOrSpread {
+            spread: None,
+            expr: base,
+        }],
+    ) = n.args.as_deref()
+    {
+        if let Expr::Member(MemberExpr {
+            obj:
+                box Expr::MetaProp(MetaPropExpr {
+                    kind: MetaPropKind::ImportMeta,
+                    ..
+                }),
+            prop: MemberProp::Ident(prop),
+            ..
+        }) = unparen(base)
+        {
+            if &*prop.sym == "url" {
+                return Some(input);
+            }
+        }
+    }
+    None
+}
+
 pub fn as_parent_path(ast_path: &AstNodePath<AstParentNodeRef<'_>>) -> Vec<AstParentKind> {
     ast_path.iter().map(|n| n.kind()).collect()
 }
@@ -713,6 +769,54 @@ impl Analyzer<'_> {
         }
     }
 
+    fn check_new_expr_for_effects<'ast: 'r, 'r>(
+        &mut self,
+        n: &'ast NewExpr,
+        ast_path: &AstNodePath<AstParentNodeRef<'r>>,
+    ) {
+        // Only the `new URL(input, import.meta.url)` and
+        // `new Worker(new URL(input, import.meta.url))` patterns are effects
+        let callee = match unparen(&n.callee) {
+            Expr::Ident(ident) if is_unresolved(ident, self.eval_context.unresolved_mark) => {
+                &*ident.sym
+            }
+            _ => return,
+        };
+        match callee {
+            "URL" => {
+                if let Some(input) = url_input(n) {
+                    self.data.effects.push(Effect::Url {
+                        input: self.eval_context.eval(input),
+                        ast_path: as_parent_path(ast_path),
+                        span: n.span(),
+                    });
+                }
+            }
+            "Worker" | "SharedWorker" => {
+                if let Some(ExprOrSpread {
+                    spread: None,
+                    expr: box Expr::New(url),
+                }) = n.args.as_ref().and_then(|args| args.first())
+                {
+                    let is_url = matches!(
+                        unparen(&url.callee),
+                        Expr::Ident(ident)
+                            if &*ident.sym == "URL"
+                                && is_unresolved(ident, self.eval_context.unresolved_mark)
+                    );
+                    if let Some(input) = url_input(url).filter(|_| is_url) {
+                        self.data.effects.push(Effect::Worker {
+                            input: self.eval_context.eval(input),
+                            ast_path: as_parent_path(ast_path),
+                            span: url.span(),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
     fn check_member_expr_for_effects<'ast: 'r, 'r>(
         &mut self,
         member_expr: &'ast MemberExpr,
@@ -807,6 +911,15 @@ impl VisitAstPath for Analyzer<'_> {
         }
     }
 
+    fn visit_new_expr<'ast: 'r, 'r>(
+        &mut self,
+        new_expr: &'ast NewExpr,
+        ast_path: &mut AstNodePath<AstParentNodeRef<'r>>,
+    ) {
+        self.check_new_expr_for_effects(new_expr, ast_path);
+        new_expr.visit_children_with_path(self, ast_path);
+    }
+
     fn visit_member_expr<'ast: 'r, 'r>(
         &mut self,
         member_expr: &'ast MemberExpr,
@@ -1,6 +1,7 @@
 pub mod loader;
 pub(crate) mod optimize;
 pub mod source_map;
+pub mod worker;
 
 use std::{fmt::Write, io::Write as _, slice::Iter};
 
@@ -8,22 +9,24 @@ use anyhow::{anyhow, bail, Result};
 use indexmap::{IndexMap, IndexSet};
 use serde::{Deserialize, Serialize};
 use turbo_tasks::{
-    primitives::{JsonValueVc, StringReadRef, StringVc, StringsVc, UsizeVc},
+    primitives::{BoolVc, JsonValueVc, StringReadRef, StringVc, StringsVc, UsizeVc},
     trace::TraceRawVcs,
     TryJoinIterExt, ValueToString, ValueToStringVc,
 };
 use turbo_tasks_fs::{
-    embed_file, rope::Rope, File, FileContent, FileSystemPathOptionVc, FileSystemPathVc,
+    embed_file, rope::Rope, File, FileContent, FileContentVc, FileSystemPathOptionVc,
+    FileSystemPathVc,
 };
 use turbo_tasks_hash::{encode_hex, hash_xxh3_hash64, Xxh3Hash64Hasher};
 use turbopack_core::{
     asset::{Asset, AssetContentVc, AssetVc},
     chunk::{
-        chunk_content, chunk_content_split,
+        chunk_content, chunk_content_split, content_hash_placeholder_path,
+        content_hashed_chunk_path,
         optimize::{ChunkOptimizerVc, OptimizableChunk, OptimizableChunkVc},
-        Chunk, ChunkContentResult, ChunkGroupReferenceVc, ChunkGroupVc, ChunkItem, ChunkItemVc,
-        ChunkReferenceVc, ChunkVc, ChunkableAsset, ChunkableAssetVc, ChunkingContextVc,
-        FromChunkableAsset, ModuleId, ModuleIdReadRef, ModuleIdVc, ModuleIdsVc,
+        with_content_hash, Chunk, ChunkContentResult, ChunkGroupReferenceVc, ChunkGroupVc,
+        ChunkItem, ChunkItemVc, ChunkReferenceVc, ChunkVc, ChunkableAsset, ChunkableAssetVc,
+        ChunkingContextVc, FromChunkableAsset, ModuleId, ModuleIdReadRef, ModuleIdVc, ModuleIdsVc,
     },
     code_builder::{Code, CodeBuilder, CodeReadRef, CodeVc},
     introspect::{
@@ -42,8 +45,10 @@ use self::{
     loader::{ManifestChunkAssetVc, ManifestLoaderItemVc},
     optimize::EcmascriptChunkOptimizerVc,
     source_map::EcmascriptChunkSourceMapAssetReferenceVc,
+    worker::{WorkerEntryAssetVc, WorkerLoaderItemVc},
 };
 use crate::{
+    minify::minify,
     parse::ParseResultSourceMapVc,
     references::esm::EsmExportsVc,
     utils::{stringify_module_id, stringify_str, FormatIter},
@@ -343,6 +348,9 @@ pub struct EcmascriptChunkContent {
     chunk_path: FileSystemPathVc,
     output_root: FileSystemPathVc,
     evaluate: Option<EcmascriptChunkContentEvaluateVc>,
+    hot_module_replacement: bool,
+    production_runtime: bool,
+    minify: bool,
 }
 
 #[turbo_tasks::value(transparent)]
@@ -447,11 +455,17 @@ impl EcmascriptChunkContentVc {
         let chunk_content = chunk_content.await?;
         let module_factories = chunk_content.chunk_items.to_entry_snapshot().await?;
         let output_root = context.output_root();
+        let hot_module_replacement = *context.is_hot_module_replacement_enabled().await?;
+        let production_runtime = *context.is_production_runtime_enabled().await?;
+        let minify = *context.is_minification_enabled().await?;
         Ok(EcmascriptChunkContent {
             module_factories,
             chunk_path,
             output_root,
             evaluate,
+            hot_module_replacement,
+            production_runtime,
+            minify,
         }
         .cell())
     }
@@ -513,6 +527,8 @@ async fn module_factory(content: EcmascriptChunkItemContentVc) -> Result<CodeVc>
         "l: __turbopack_load__",
         "p: process",
         "g: global",
+        "h: __turbopack_hot__",
+        "k: __turbopack_chunk_path__",
         // HACK
         "__dirname",
     ];
@@ -540,6 +556,36 @@ async fn module_factory(content: EcmascriptChunkItemContentVc) -> Result<CodeVc>
     Ok(code.build().cell())
 }
 
+/// Returns the code of the runtime. The production runtime is the development
+/// runtime without the parts between `// #if hmr` and `// #endif`, which are
+/// only needed to apply hot module replacement updates.
+#[turbo_tasks::function]
+async fn runtime_code(production: bool) -> Result<FileContentVc> {
+    let runtime_code = embed_file!("js/src/runtime.js");
+    if !production {
+        return Ok(runtime_code);
+    }
+    let code = match &*runtime_code.await? {
+        FileContent::NotFound => return Err(anyhow!("runtime code is not found")),
+        FileContent::Content(file) => file.content().to_str()?.into_owned(),
+    };
+    let mut production_code = String::with_capacity(code.len());
+    let mut in_hmr_block = false;
+    for line in code.split_inclusive('\n') {
+        match line.trim() {
+            "// #if hmr" if !in_hmr_block => in_hmr_block = true,
+            "// #endif" if in_hmr_block => in_hmr_block = false,
+            "// #if hmr" | "// #endif" => bail!("unbalanced `{}` in the runtime", line.trim()),
+            _ if in_hmr_block => {}
+            _ => production_code.push_str(line),
+        }
+    }
+    if in_hmr_block {
+        bail!("unterminated `// #if hmr` in the runtime");
+    }
+    Ok(File::from(production_code).into())
+}
+
 #[derive(Serialize)]
 #[serde(tag = "type")]
 struct EcmascriptChunkUpdate<'a> {
@@ -625,7 +671,7 @@ impl EcmascriptChunkContentVc {
         }
         code += "]);\n";
         if this.evaluate.is_some() {
-            let runtime_code = embed_file!("js/src/runtime.js").await?;
+            let runtime_code = runtime_code(this.production_runtime).await?;
             match &*runtime_code {
                 FileContent::NotFound => return Err(anyhow!("runtime code is not found")),
                 FileContent::Content(file) => code.push_source(file.content(), None),
@@ -640,9 +686,20 @@ impl EcmascriptChunkContentVc {
         Ok(code.build().cell())
     }
 
+    #[turbo_tasks::function]
+    async fn minified_code(self) -> Result<CodeVc> {
+        let this = self.await?;
+        let code = self.code();
+        Ok(if this.minify {
+            minify(this.chunk_path, code)
+        } else {
+            code
+        })
+    }
+
     #[turbo_tasks::function]
     async fn content(self) -> Result<AssetContentVc> {
-        let code = self.code().await?;
+        let code = self.minified_code().await?;
         Ok(File::from(code.source_code().clone()).into())
     }
 }
@@ -665,6 +722,14 @@ impl VersionedContent for EcmascriptChunkContent {
         from_version: VersionVc,
     ) -> Result<UpdateVc> {
         let to_version = self_vc.version();
+        if !self_vc.await?.hot_module_replacement {
+            // Without HMR there is no runtime on the client that could apply a
+            // partial update.
+            return Ok(Update::Total(TotalUpdate {
+                to: to_version.into(),
+            })
+            .cell());
+        }
         let from_version =
             if let Some(from) = EcmascriptChunkVersionVc::resolve_from(from_version).await? {
                 from
@@ -737,7 +802,7 @@ impl VersionedContent for EcmascriptChunkContent {
 impl GenerateSourceMap for EcmascriptChunkContent {
     #[turbo_tasks::function]
     fn generate_source_map(self_vc: EcmascriptChunkContentVc) -> SourceMapVc {
-        self_vc.code().generate_source_map()
+        self_vc.minified_code().generate_source_map()
     }
 }
 
@@ -881,23 +946,48 @@ impl EcmascriptChunkVc {
         let evaluate = this
             .evaluate
             .map(|evaluate| evaluate.content(this.context, self));
-        let chunk_path = self.path();
         let content = EcmascriptChunkContentVc::new(
             this.context,
             this.main_entries,
             this.omit_entries,
-            chunk_path,
+            self.content_path(),
             evaluate,
         );
         Ok(content)
     }
-}
 
-#[turbo_tasks::value_impl]
-impl Asset for EcmascriptChunk {
+    /// Whether the chunk is named by the hash of its content.
     #[turbo_tasks::function]
-    async fn path(self_vc: EcmascriptChunkVc) -> Result<FileSystemPathVc> {
-        let this = self_vc.await?;
+    async fn is_content_hashed(self) -> Result<BoolVc> {
+        let this = self.await?;
+        // Manifest chunks keep their path, as dynamic imports can be cyclic and the
+        // path of an importing chunk would depend on its own content otherwise.
+        let is_manifest = match this.main_entries.await?.as_slice() {
+            [entry] => ManifestChunkAssetVc::resolve_from(*entry).await?.is_some(),
+            _ => false,
+        };
+        Ok(BoolVc::cell(
+            !is_manifest && *this.context.is_content_hashing_enabled().await?,
+        ))
+    }
+
+    /// The path the content of the chunk is generated at. Chunks that are
+    /// named by the hash of their content are generated at a placeholder path,
+    /// which is replaced afterwards.
+    #[turbo_tasks::function]
+    async fn content_path(self) -> Result<FileSystemPathVc> {
+        let path = self.chunk_path();
+        Ok(if *self.is_content_hashed().await? {
+            content_hash_placeholder_path(path, ".js")
+        } else {
+            path
+        })
+    }
+
+    /// The path of the chunk as it's named by the chunking context.
+    #[turbo_tasks::function]
+    async fn chunk_path(self) -> Result<FileSystemPathVc> {
+        let this = self.await?;
 
         // All information that makes the chunk unique need to be encoded in the path.
         // As we can't make the path that long, we split info into "hashed info" and
@@ -908,7 +998,7 @@ impl Asset for EcmascriptChunk {
 
         // evalute only contributes to the hashed info
         if let Some(evaluate) = this.evaluate {
-            let evaluate = evaluate.content(this.context, self_vc).await?;
+            let evaluate = evaluate.content(this.context, self).await?;
             for path in evaluate.chunks_server_paths.await?.iter() {
                 hasher.write_ref(path);
                 need_hash = true;
@@ -931,7 +1021,7 @@ impl Asset for EcmascriptChunk {
                 hasher.write_value(path);
                 need_hash = true;
             }
-            if let &Some(common_parent) = &*self_vc.common_parent().await? {
+            if let &Some(common_parent) = &*self.common_parent().await? {
                 common_parent
             } else {
                 let main_entry = main_entries
@@ -951,10 +1041,27 @@ impl Asset for EcmascriptChunk {
 
         Ok(this.context.chunk_path(path, ".js"))
     }
+}
 
+#[turbo_tasks::value_impl]
+impl Asset for EcmascriptChunk {
     #[turbo_tasks::function]
-    fn content(self_vc: EcmascriptChunkVc) -> AssetContentVc {
-        self_vc.chunk_content().content()
+    async fn path(self_vc: EcmascriptChunkVc) -> Result<FileSystemPathVc> {
+        let path = self_vc.chunk_path();
+        if *self_vc.is_content_hashed().await? {
+            let content = self_vc.chunk_content().content();
+            return Ok(content_hashed_chunk_path(path, content, ".js"));
+        }
+        Ok(path)
+    }
+
+    #[turbo_tasks::function]
+    async fn content(self_vc: EcmascriptChunkVc) -> Result<AssetContentVc> {
+        let content = self_vc.chunk_content().content();
+        if *self_vc.is_content_hashed().await? {
+            return Ok(with_content_hash(content));
+        }
+        Ok(content)
     }
 
     #[turbo_tasks::function]
@@ -1155,6 +1262,14 @@ impl FromChunkableAsset for EcmascriptChunkItemVc {
             chunk.into(),
         )))
     }
+
+    async fn from_isolated_asset(
+        context: ChunkingContextVc,
+        asset: ChunkableAssetVc,
+    ) -> Result<Option<Self>> {
+        let entry = WorkerEntryAssetVc::new(asset, context.worker_context());
+        Ok(Some(WorkerLoaderItemVc::new(context, entry).into()))
+    }
 }
 
 #[turbo_tasks::value(transparent)]
@@ -0,0 +1,192 @@
+use std::io::Write as _;
+
+use anyhow::{bail, Result};
+use turbo_tasks::{primitives::StringVc, ValueToString, ValueToStringVc};
+use turbo_tasks_fs::{File, FileSystemPathVc};
+use turbopack_core::{
+    asset::{Asset, AssetContentVc, AssetVc},
+    chunk::{
+        ChunkGroupVc, ChunkItem, ChunkItemVc, ChunkReferenceVc, ChunkableAssetVc,
+        ChunkingContextVc, ChunksVc,
+    },
+    reference::{AssetReferencesVc, SingleAssetReferenceVc},
+};
+
+use crate::{
+    chunk::{
+        EcmascriptChunkItem, EcmascriptChunkItemContent, EcmascriptChunkItemContentVc,
+        EcmascriptChunkItemVc, EcmascriptChunkPlaceableVc, EcmascriptChunkVc,
+    },
+    utils::stringify_str,
+};
+
+/// The worker loader item is shipped in the same chunk that creates the
+/// worker with `new Worker(new URL(...))`. It exports the url of the
+/// [WorkerEntryAsset], which the url expression has been rewritten to.
+///
+/// The worker itself doesn't share any chunks or modules with the referencing
+/// chunk group.
+#[turbo_tasks::value]
+pub struct WorkerLoaderItem {
+    context: ChunkingContextVc,
+    entry: WorkerEntryAssetVc,
+}
+
+#[turbo_tasks::value_impl]
+impl WorkerLoaderItemVc {
+    #[turbo_tasks::function]
+    pub fn new(context: ChunkingContextVc, entry: WorkerEntryAssetVc) -> Self {
+        Self::cell(WorkerLoaderItem { context, entry })
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ValueToString for WorkerLoaderItem {
+    #[turbo_tasks::function]
+    async fn to_string(&self) -> Result<StringVc> {
+        Ok(StringVc::cell(format!(
+            "{} (worker loader)",
+            self.entry.await?.asset.path().to_string().await?
+        )))
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ChunkItem for WorkerLoaderItem {
+    #[turbo_tasks::function]
+    async fn references(&self) -> Result<AssetReferencesVc> {
+        Ok(AssetReferencesVc::cell(vec![SingleAssetReferenceVc::new(
+            self.entry.into(),
+            StringVc::cell(format!("worker entry {}", self.entry.path().await?)),
+        )
+        .into()]))
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl EcmascriptChunkItem for WorkerLoaderItem {
+    #[turbo_tasks::function]
+    fn chunking_context(&self) -> ChunkingContextVc {
+        self.context
+    }
+
+    #[turbo_tasks::function]
+    async fn content(&self) -> Result<EcmascriptChunkItemContentVc> {
+        let path = server_path(self.context, self.entry.path()).await?;
+        Ok(EcmascriptChunkItemContent {
+            inner_code: format!(
+                "__turbopack_export_value__({path});",
+                path = stringify_str(&format!("/{path}"))
+            )
+            .into(),
+            ..Default::default()
+        }
+        .into())
+    }
+}
+
+/// The script a web worker is started with. It loads all chunks of the
+/// isolated chunk group of the worker, with `importScripts` in classic workers
+/// and with `import()` in module workers. The chunk group contains its own
+/// runtime and evaluates the worker module once all chunks are loaded.
+///
+/// The `chunking_context` is the worker context of the referencing chunking
+/// context (see [turbopack_core::chunk::ChunkingContext::worker_context]). Hot
+/// module replacement is not available in workers, as nothing connects the
+/// worker runtime to the update server. Changes to the worker apply when the
+/// worker is created again.
+#[turbo_tasks::value(shared)]
+pub struct WorkerEntryAsset {
+    pub asset: ChunkableAssetVc,
+    pub chunking_context: ChunkingContextVc,
+}
+
+#[turbo_tasks::value_impl]
+impl WorkerEntryAssetVc {
+    #[turbo_tasks::function]
+    pub fn new(asset: ChunkableAssetVc, chunking_context: ChunkingContextVc) -> Self {
+        Self::cell(WorkerEntryAsset {
+            asset,
+            chunking_context,
+        })
+    }
+
+    #[turbo_tasks::function]
+    async fn chunks(self) -> Result<ChunksVc> {
+        let this = self.await?;
+        let chunk_group =
+            if let Some(placeable) = EcmascriptChunkPlaceableVc::resolve_from(this.asset).await? {
+                ChunkGroupVc::from_chunk(
+                    EcmascriptChunkVc::new_evaluate(this.chunking_context, placeable, None).into(),
+                )
+            } else {
+                ChunkGroupVc::from_asset(this.asset, this.chunking_context)
+            };
+        Ok(chunk_group.chunks())
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl Asset for WorkerEntryAsset {
+    #[turbo_tasks::function]
+    fn path(&self) -> FileSystemPathVc {
+        self.chunking_context
+            .chunk_path(self.asset.path(), ".worker.js")
+    }
+
+    #[turbo_tasks::function]
+    async fn content(self_vc: WorkerEntryAssetVc) -> Result<AssetContentVc> {
+        let this = self_vc.await?;
+        let mut code = Vec::new();
+        writeln!(code, "const chunks = [")?;
+        for chunk in self_vc.chunks().await?.iter() {
+            let path = server_path(this.chunking_context, chunk.path()).await?;
+            writeln!(code, "    {},", stringify_str(&format!("/{path}")))?;
+        }
+        writeln!(code, "];")?;
+        // Module workers don't support `importScripts`. Top-level `this` is only
+        // undefined in modules.
+        writeln!(
+            code,
+            r#"if (this === undefined) {{
+    (async () => {{
+        for (const chunk of chunks) {{
+            await import(chunk);
+        }}
+    }})();
+}} else {{
+    importScripts(...chunks);
+}}"#
+        )?;
+        Ok(File::from(String::from_utf8(code)?).into())
+    }
+
+    #[turbo_tasks::function]
+    async fn references(self_vc: WorkerEntryAssetVc) -> Result<AssetReferencesVc> {
+        Ok(AssetReferencesVc::cell(
+            self_vc
+                .chunks()
+                .await?
+                .iter()
+                .copied()
+                .map(ChunkReferenceVc::new)
+                .map(Into::into)
+                .collect(),
+        ))
+    }
+}
+
+/// Returns the path of an output file relative to the output root, which is
+/// the path the dev server serves it from.
+async fn server_path(context: ChunkingContextVc, path: FileSystemPathVc) -> Result<String> {
+    let output_root = context.output_root().await?;
+    if let Some(server_path) = output_root.get_path_to(&*path.await?) {
+        Ok(server_path.to_string())
+    } else {
+        bail!(
+            "path {} is not in output root {}",
+            path.to_string().await?,
+            context.output_root().to_string().await?
+        );
+    }
+}
@@ -12,7 +12,7 @@ pub mod failed_to_analyse {
         pub const NODE_EXPRESS: &str = "TP1103";
         pub const NODE_RESOLVE_FROM: &str = "TP1104";
         pub const NODE_PROTOBUF_LOADER: &str = "TP1105";
+        pub const NEW_URL_IMPORT_META: &str = "TP1107";
         pub const AMD_DEFINE: &str = "TP1200";
-        pub const IMPORT_META: &str = "TP1106";
     }
 }
@@ -12,6 +12,7 @@ pub mod chunk_group_files_asset;
 pub mod code_gen;
 mod errors;
 pub mod magic_identifier;
+pub(crate) mod minify;
 pub(crate) mod parse;
 mod path_visitor;
 pub(crate) mod references;
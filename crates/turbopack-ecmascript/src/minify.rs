@@ -0,0 +1,199 @@
+use std::{io::Write, sync::Arc};
+
+use anyhow::{bail, Result};
+use sourcemap::{DecodedMap, SourceMap as CrateMap};
+use swc_core::{
+    base::{try_with_handler, Compiler, HandlerOpts},
+    common::{
+        errors::ColorConfig, source_map::DefaultSourceMapGenConfig, BytePos, FileName, Globals,
+        LineCol, Mark, SourceMap, GLOBALS,
+    },
+    ecma::{
+        ast::{EsVersion, Program},
+        codegen::{text_writer::JsWriter, Emitter},
+        minifier::{
+            optimize,
+            option::{CompressOptions, ExtraOptions, MangleOptions, MinifyOptions},
+        },
+        parser::{lexer::Lexer, Parser, StringInput, Syntax},
+        transforms::base::{fixer::fixer, hygiene::hygiene, resolver},
+        visit::FoldWith,
+    },
+};
+use turbo_tasks_fs::FileSystemPathVc;
+use turbopack_core::{
+    code_builder::{CodeBuilder, CodeVc},
+    source_map::{GenerateSourceMap, SourceMapVc},
+};
+
+/// Minifies already generated chunk code with the swc minifier.
+/// The source map of the input is chained into the source map of the minified
+/// code.
+#[turbo_tasks::function]
+pub async fn minify(path: FileSystemPathVc, code: CodeVc) -> Result<CodeVc> {
+    let original_map = if code.await?.has_source_map() {
+        let map = code.generate_source_map().to_rope().await?;
+        Some(match sourcemap::decode(map.read())? {
+            DecodedMap::Regular(map) => map,
+            DecodedMap::Index(map) => map.flatten()?,
+            _ => bail!("unexpected format of the chunk source map"),
+        })
+    } else {
+        None
+    };
+
+    let path = path.await?;
+    let code = code.await?;
+    let (minified, map) = minify_source(
+        &path.path,
+        code.source_code().to_str()?.into_owned(),
+        original_map.as_ref(),
+    )?;
+
+    let mut builder = CodeBuilder::default();
+    match map {
+        Some(map) => {
+            builder.push_source(&minified.into(), Some(SourceMapVc::new_regular(map).into()));
+            // The minifier drops comments, including the one linking the source map
+            write!(builder, "\n\n//# sourceMappingURL={}.map", path.file_name())?;
+        }
+        None => builder.push_source(&minified.into(), None),
+    }
+    Ok(builder.build().cell())
+}
+
+/// Minifies `source` and returns the minified code, and its source map chained
+/// with `original_map` if one is passed.
+fn minify_source(
+    path: &str,
+    source: String,
+    original_map: Option<&CrateMap>,
+) -> Result<(String, Option<CrateMap>)> {
+    let cm = Arc::new(SourceMap::default());
+    let compiler = Arc::new(Compiler::new(cm.clone()));
+    let fm = compiler
+        .cm
+        .new_source_file(FileName::Custom(path.to_string()), source);
+
+    let lexer = Lexer::new(
+        Syntax::default(),
+        EsVersion::latest(),
+        StringInput::from(&*fm),
+        None,
+    );
+    let mut parser = Parser::new_from(lexer);
+
+    let program = try_with_handler(
+        cm.clone(),
+        HandlerOpts {
+            color: ColorConfig::Never,
+            skip_filename: false,
+        },
+        |handler| {
+            GLOBALS.set(&Globals::new(), || {
+                let program = match parser.parse_program() {
+                    Ok(program) => program,
+                    Err(err) => {
+                        err.into_diagnostic(handler).emit();
+                        bail!("failed to parse source code")
+                    }
+                };
+                let unresolved_mark = Mark::new();
+                let top_level_mark = Mark::new();
+
+                Ok(compiler.run_transform(handler, false, || {
+                    let program =
+                        program.fold_with(&mut resolver(unresolved_mark, top_level_mark, false));
+                    let program = optimize(
+                        program,
+                        cm.clone(),
+                        None,
+                        None,
+                        &MinifyOptions {
+                            compress: Some(CompressOptions {
+                                ..Default::default()
+                            }),
+                            mangle: Some(MangleOptions {
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        },
+                        &ExtraOptions {
+                            top_level_mark,
+                            unresolved_mark,
+                        },
+                    );
+                    program
+                        .fold_with(&mut hygiene())
+                        .fold_with(&mut fixer(None))
+                }))
+            })
+        },
+    )?;
+
+    let (minified, mappings) = print_program(cm.clone(), program)?;
+    let map = original_map.map(|original_map| {
+        cm.build_source_map_with_config(&mappings, Some(original_map), DefaultSourceMapGenConfig)
+    });
+    Ok((minified, map))
+}
+
+fn print_program(
+    cm: Arc<SourceMap>,
+    program: Program,
+) -> Result<(String, Vec<(BytePos, LineCol)>)> {
+    let mut bytes = Vec::new();
+    let mut mappings = Vec::new();
+    {
+        let mut emitter = Emitter {
+            cfg: swc_core::ecma::codegen::Config {
+                minify: true,
+                ..Default::default()
+            },
+            cm: cm.clone(),
+            comments: None,
+            wr: JsWriter::new(cm, "\n", &mut bytes, Some(&mut mappings)),
+        };
+
+        emitter.emit_program(&program)?;
+    }
+
+    Ok((String::from_utf8(bytes)?, mappings))
+}
+
+#[cfg(test)]
+mod tests {
+    use sourcemap::SourceMapBuilder;
+
+    use super::minify_source;
+
+    const SOURCE: &str = "function add(first, second) {
+  return first + second;
+}
+console.log(add(1, 2));
+";
+
+    #[test]
+    fn minifies() {
+        let (minified, map) = minify_source("chunk.js", SOURCE.to_string(), None).unwrap();
+        assert!(minified.len() < SOURCE.len());
+        assert!(!minified.contains("second"));
+        assert!(map.is_none());
+    }
+
+    #[test]
+    fn chains_source_map() {
+        // Maps every line of the chunk to the line 10 lines below in `input.js`
+        let mut builder = SourceMapBuilder::new(None);
+        for line in 0..4 {
+            builder.add(line, 0, line + 10, 0, Some("input.js"), None);
+        }
+        let original_map = builder.into_sourcemap();
+
+        let (_, map) = minify_source("chunk.js", SOURCE.to_string(), Some(&original_map)).unwrap();
+        let map = map.unwrap();
+        let token = map.lookup_token(0, 0).unwrap();
+        assert_eq!(token.get_source(), Some("input.js"));
+        assert_eq!(token.get_src_line(), 13);
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use swc_core::{
+    common::DUMMY_SP,
+    ecma::ast::{Expr, Ident, Stmt},
+    quote, quote_expr,
+};
+use turbopack_core::{chunk::ChunkingContextVc, environment::EnvironmentVc};
+
+use super::base::insert_hoisted_stmt;
+use crate::{
+    code_gen::{CodeGenerateable, CodeGenerateableVc, CodeGeneration, CodeGenerationVc},
+    create_visitor, magic_identifier,
+    references::AstPathVc,
+};
+
+fn import_meta_ident() -> Ident {
+    Ident::new(magic_identifier::encode("import.meta").into(), DUMMY_SP)
+}
+
+/// Declares the `import.meta` object of a module. Every `import.meta`
+/// expression of the module is replaced with it by an [ImportMetaRef].
+///
+/// `import.meta.url` is the url of the chunk the module has been loaded from,
+/// which is only known at runtime: the served url in the browser, the `file:`
+/// url of the chunk for Node.js targets and a `blob:` url for edge functions,
+/// which don't have a file system.
+/// `import.meta.env` and `import.meta.hot` map to `process.env` and the hot
+/// module replacement api of the module.
+#[turbo_tasks::value(shared)]
+#[derive(Hash, Debug)]
+pub struct ImportMetaBinding {
+    pub environment: EnvironmentVc,
+}
+
+#[turbo_tasks::value_impl]
+impl ImportMetaBindingVc {
+    #[turbo_tasks::function]
+    pub fn new(environment: EnvironmentVc) -> Self {
+        ImportMetaBinding { environment }.cell()
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl CodeGenerateable for ImportMetaBinding {
+    #[turbo_tasks::function]
+    async fn code_generation(&self, _context: ChunkingContextVc) -> Result<CodeGenerationVc> {
+        // `__turbopack_chunk_path__` is the path of the chunk relative to the
+        // output root, as registered with the runtime
+        let url = if *self.environment.is_browser().await? {
+            quote_expr!("new URL(\"/\" + __turbopack_chunk_path__, location.href).href")
+        } else if *self.environment.is_edge_function().await? {
+            quote_expr!("\"blob:\" + __turbopack_chunk_path__")
+        } else {
+            quote_expr!("__turbopack_external_require__(\"url\").pathToFileURL(__filename).href")
+        };
+
+        let visitor = create_visitor!(visit_mut_program(program: &mut Program) {
+            let stmt: Stmt = quote!(
+                "const $name = { get url() { return $url; }, env: process.env, hot: __turbopack_hot__ };"
+                    as Stmt,
+                name = import_meta_ident(),
+                url: Expr = *url.clone(),
+            );
+            insert_hoisted_stmt(program, stmt);
+        });
+
+        Ok(CodeGeneration {
+            visitors: vec![visitor],
+        }
+        .into())
+    }
+}
+
+/// Replaces an `import.meta` expression with the object declared by the
+/// [ImportMetaBinding] of the module.
+#[turbo_tasks::value(shared)]
+#[derive(Hash, Debug)]
+pub struct ImportMetaRef {
+    pub ast_path: AstPathVc,
+}
+
+#[turbo_tasks::value_impl]
+impl ImportMetaRefVc {
+    #[turbo_tasks::function]
+    pub fn new(ast_path: AstPathVc) -> Self {
+        ImportMetaRef { ast_path }.cell()
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl CodeGenerateable for ImportMetaRef {
+    #[turbo_tasks::function]
+    async fn code_generation(&self, _context: ChunkingContextVc) -> Result<CodeGenerationVc> {
+        let visitor = create_visitor!(self.ast_path.await?, visit_mut_expr(expr: &mut Expr) {
+            *expr = Expr::Ident(import_meta_ident());
+        });
+
+        Ok(CodeGeneration {
+            visitors: vec![visitor],
+        }
+        .into())
+    }
+}
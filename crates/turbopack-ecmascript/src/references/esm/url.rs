@@ -0,0 +1,178 @@
+use anyhow::{bail, Result};
+use swc_core::{
+    ecma::ast::{Expr, NewExpr},
+    quote_expr,
+};
+use turbo_tasks::{primitives::StringVc, ValueToString, ValueToStringVc};
+use turbo_tasks_fs::FileSystemPathVc;
+use turbopack_core::{
+    chunk::{ChunkItem, ChunkableAssetReference, ChunkableAssetReferenceVc, ChunkingContextVc},
+    environment::EnvironmentVc,
+    reference::{AssetReference, AssetReferenceVc},
+    resolve::{origin::ResolveOriginVc, parse::RequestVc, ResolveResultVc},
+};
+
+use super::base::{ReferencedAsset, ReferencedAssetVc};
+use crate::{
+    chunk::EcmascriptChunkPlaceableVc,
+    code_gen::{CodeGenerateable, CodeGenerateableVc, CodeGeneration, CodeGenerationVc},
+    create_visitor,
+    references::AstPathVc,
+    resolve::url_resolve,
+    utils::module_id_to_lit,
+};
+
+/// A reference to a file by `new URL("./file", import.meta.url)`. The file is
+/// emitted as static asset and the expression is rewritten to its output url.
+#[turbo_tasks::value]
+#[derive(Hash, Debug)]
+pub struct UrlAssetReference {
+    pub origin: ResolveOriginVc,
+    pub request: RequestVc,
+    pub environment: EnvironmentVc,
+    pub ast_path: AstPathVc,
+}
+
+#[turbo_tasks::value_impl]
+impl UrlAssetReferenceVc {
+    #[turbo_tasks::function]
+    pub fn new(
+        origin: ResolveOriginVc,
+        request: RequestVc,
+        environment: EnvironmentVc,
+        ast_path: AstPathVc,
+    ) -> Self {
+        UrlAssetReference {
+            origin,
+            request,
+            environment,
+            ast_path,
+        }
+        .cell()
+    }
+
+    #[turbo_tasks::function]
+    async fn get_referenced_asset(self) -> Result<ReferencedAssetVc> {
+        let assets = self.resolve_reference().primary_assets();
+        for asset in assets.await?.iter() {
+            if let Some(placeable) = EcmascriptChunkPlaceableVc::resolve_from(asset).await? {
+                return Ok(ReferencedAsset::Some(placeable).cell());
+            }
+        }
+        Ok(ReferencedAsset::None.cell())
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl AssetReference for UrlAssetReference {
+    #[turbo_tasks::function]
+    fn resolve_reference(&self) -> ResolveResultVc {
+        url_resolve(self.origin, self.request)
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ValueToString for UrlAssetReference {
+    #[turbo_tasks::function]
+    async fn to_string(&self) -> Result<StringVc> {
+        Ok(StringVc::cell(format!(
+            "new URL({})",
+            self.request.to_string().await?,
+        )))
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ChunkableAssetReference for UrlAssetReference {}
+
+#[turbo_tasks::value_impl]
+impl CodeGenerateable for UrlAssetReference {
+    #[turbo_tasks::function]
+    async fn code_generation(
+        self_vc: UrlAssetReferenceVc,
+        context: ChunkingContextVc,
+    ) -> Result<CodeGenerationVc> {
+        let this = self_vc.await?;
+        let mut visitors = Vec::new();
+
+        if let ReferencedAsset::Some(asset) = &*self_vc.get_referenced_asset().await? {
+            let visitor = if *this.environment.is_browser().await? {
+                // The referenced module exports the absolute path of the emitted
+                // file, which is resolved relative to `import.meta.url` as it's
+                // served from the same origin.
+                let id = asset.as_chunk_item(context).id().await?;
+                create_visitor!(exact this.ast_path.await?, visit_mut_new_expr(new_expr: &mut NewExpr) {
+                    if let Some(input) = new_expr.args.as_mut().and_then(|args| args.get_mut(0)) {
+                        input.expr = quote_expr!(
+                            "__turbopack_require__($id)",
+                            id: Expr = module_id_to_lit(&id)
+                        );
+                    }
+                })
+            } else {
+                // Otherwise the emitted file is resolved relative to the output
+                // root, which is derived at runtime from the chunk the module
+                // has been loaded from.
+                let path = match emitted_path(*asset, context).await? {
+                    Some(path) => path,
+                    None => return Ok(CodeGeneration { visitors }.into()),
+                };
+                let output_root = context.output_root();
+                let relative = match output_root.await?.get_path_to(&*path.await?) {
+                    Some(relative) => relative.to_string(),
+                    None => bail!(
+                        "emitted file {} is not in output root {}",
+                        path.to_string().await?,
+                        output_root.to_string().await?
+                    ),
+                };
+                if *this.environment.is_edge_function().await? {
+                    // Edge functions don't have a file system, the emitted file
+                    // is addressed by a `blob:` url like `import.meta.url`
+                    let url = format!("blob:{relative}");
+                    create_visitor!(exact this.ast_path.await?, visit_mut_new_expr(new_expr: &mut NewExpr) {
+                        if let Some(args) = &mut new_expr.args {
+                            args.truncate(1);
+                            if let Some(input) = args.get_mut(0) {
+                                input.expr = box Expr::Lit(url.clone().into());
+                            }
+                        }
+                    })
+                } else {
+                    // `__turbopack_chunk_path__` is relative to the output root
+                    create_visitor!(exact this.ast_path.await?, visit_mut_new_expr(new_expr: &mut NewExpr) {
+                        if let Some(args) = &mut new_expr.args {
+                            if let Some(input) = args.get_mut(0) {
+                                input.expr = box Expr::Lit(relative.clone().into());
+                            }
+                            if let Some(base) = args.get_mut(1) {
+                                base.expr = quote_expr!(
+                                    "__turbopack_external_require__(\"url\").pathToFileURL(__filename.slice(0, -__turbopack_chunk_path__.length))"
+                                );
+                            }
+                        }
+                    })
+                }
+            };
+            visitors.push(visitor);
+        }
+
+        Ok(CodeGeneration { visitors }.into())
+    }
+}
+
+/// Returns the path of the file that is emitted for the `asset`, which is
+/// referenced by its chunk item.
+async fn emitted_path(
+    asset: EcmascriptChunkPlaceableVc,
+    context: ChunkingContextVc,
+) -> Result<Option<FileSystemPathVc>> {
+    let references = asset.as_chunk_item(context).references().await?;
+    for reference in references.iter() {
+        let assets = reference.resolve_reference().primary_assets().await?;
+        if let Some(asset) = assets.first() {
+            return Ok(Some(asset.path()));
+        }
+    }
+    Ok(None)
+}
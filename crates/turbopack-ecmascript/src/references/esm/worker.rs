@@ -0,0 +1,121 @@
+use anyhow::Result;
+use swc_core::{
+    ecma::ast::{Expr, ExprOrSpread, NewExpr},
+    quote_expr,
+};
+use turbo_tasks::{primitives::StringVc, ValueToString, ValueToStringVc};
+use turbopack_core::{
+    chunk::{
+        ChunkableAssetReference, ChunkableAssetReferenceVc, ChunkableAssetVc, ChunkingContextVc,
+        ChunkingType, ChunkingTypeOptionVc, FromChunkableAsset,
+    },
+    reference::{AssetReference, AssetReferenceVc},
+    resolve::{origin::ResolveOriginVc, parse::RequestVc, ResolveResultVc},
+};
+
+use crate::{
+    chunk::{EcmascriptChunkItem, EcmascriptChunkItemVc},
+    code_gen::{CodeGenerateable, CodeGenerateableVc, CodeGeneration, CodeGenerationVc},
+    create_visitor,
+    references::AstPathVc,
+    resolve::esm_resolve,
+    utils::module_id_to_lit,
+};
+
+/// A reference to the entry module of a web worker by
+/// `new Worker(new URL("./worker", import.meta.url))` or the same with
+/// `SharedWorker`. The module is the entry of an isolated chunk group and the
+/// url is rewritten to the script that loads it.
+#[turbo_tasks::value]
+#[derive(Hash, Debug)]
+pub struct WorkerAssetReference {
+    pub origin: ResolveOriginVc,
+    pub request: RequestVc,
+    pub ast_path: AstPathVc,
+}
+
+#[turbo_tasks::value_impl]
+impl WorkerAssetReferenceVc {
+    #[turbo_tasks::function]
+    pub fn new(origin: ResolveOriginVc, request: RequestVc, ast_path: AstPathVc) -> Self {
+        WorkerAssetReference {
+            origin,
+            request,
+            ast_path,
+        }
+        .cell()
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl AssetReference for WorkerAssetReference {
+    #[turbo_tasks::function]
+    fn resolve_reference(&self) -> ResolveResultVc {
+        esm_resolve(self.origin, self.request)
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ValueToString for WorkerAssetReference {
+    #[turbo_tasks::function]
+    async fn to_string(&self) -> Result<StringVc> {
+        Ok(StringVc::cell(format!(
+            "new Worker({})",
+            self.request.to_string().await?,
+        )))
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ChunkableAssetReference for WorkerAssetReference {
+    #[turbo_tasks::function]
+    fn chunking_type(&self, _context: ChunkingContextVc) -> ChunkingTypeOptionVc {
+        ChunkingTypeOptionVc::cell(Some(ChunkingType::Isolated))
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl CodeGenerateable for WorkerAssetReference {
+    #[turbo_tasks::function]
+    async fn code_generation(
+        self_vc: WorkerAssetReferenceVc,
+        context: ChunkingContextVc,
+    ) -> Result<CodeGenerationVc> {
+        let this = self_vc.await?;
+        let mut visitors = Vec::new();
+
+        let assets = self_vc.resolve_reference().primary_assets();
+        for asset in assets.await?.iter() {
+            let chunkable = if let Some(chunkable) = ChunkableAssetVc::resolve_from(asset).await? {
+                chunkable
+            } else {
+                continue;
+            };
+            let loader = if let Some(loader) =
+                EcmascriptChunkItemVc::from_isolated_asset(context, chunkable).await?
+            {
+                loader
+            } else {
+                continue;
+            };
+            let id = loader.id().await?;
+
+            visitors.push(
+                create_visitor!(exact this.ast_path.await?, visit_mut_new_expr(new_expr: &mut NewExpr) {
+                    let arg = new_expr.args.as_mut().and_then(|args| args.first_mut());
+                    if let Some(ExprOrSpread { expr: box Expr::New(url), .. }) = arg {
+                        if let Some(input) = url.args.as_mut().and_then(|args| args.first_mut()) {
+                            input.expr = quote_expr!(
+                                "__turbopack_require__($id)",
+                                id: Expr = module_id_to_lit(&id)
+                            );
+                        }
+                    }
+                }),
+            );
+            break;
+        }
+
+        Ok(CodeGeneration { visitors }.into())
+    }
+}
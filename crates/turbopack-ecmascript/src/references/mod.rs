@@ -8,7 +8,7 @@ pub mod typescript;
 pub mod util;
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     future::Future,
     mem::take,
     pin::Pin,
@@ -51,7 +51,8 @@ use self::{
     cjs::CjsAssetReferenceVc,
     esm::{
         export::EsmExport, EsmAssetReferenceVc, EsmAsyncAssetReferenceVc, EsmExports,
-        EsmModuleItemVc,
+        EsmModuleItemVc, ImportMetaBindingVc, ImportMetaRefVc, UrlAssetReferenceVc,
+        WorkerAssetReferenceVc,
     },
     node::{DirAssetReferenceVc, PackageJsonReferenceVc},
     raw::SourceAssetReferenceVc,
@@ -1062,6 +1063,14 @@ pub(crate) async fn analyze_ecmascript_module(
             let linker = |value| value_visitor(source, origin, value, environment);
             let effects = take(&mut var_graph.effects);
             let link_value = |value| link(&var_graph, value, &linker, &cache);
+            let mut first_import_meta = true;
+            // The `new URL(...)` of a `new Worker(...)` is handled by the worker
+            // reference
+            let mut worker_url_spans = HashSet::new();
+            // Outside of the browser the `import.meta.url` of a `new URL(...)` is
+            // replaced by the url reference and doesn't need to be bound
+            let is_browser = *environment.is_browser().await?;
+            let mut rewritten_url_spans = Vec::new();
 
             for effect in effects.into_iter() {
                 match effect {
@@ -1157,14 +1166,69 @@ pub(crate) async fn analyze_ecmascript_module(
                             }
                         }
                     }
-                    Effect::ImportMeta { span, ast_path: _ } => {
-                        handler.span_warn_with_code(
-                            span,
-                            "import.meta is not yet supported",
-                            DiagnosticId::Error(
-                                errors::failed_to_analyse::ecmascript::IMPORT_META.to_string(),
-                            ),
-                        );
+                    Effect::ImportMeta { span, ast_path } => {
+                        if rewritten_url_spans
+                            .iter()
+                            .any(|url_span: &Span| url_span.contains(span))
+                        {
+                            continue;
+                        }
+                        if first_import_meta {
+                            first_import_meta = false;
+                            analysis.add_code_gen(ImportMetaBindingVc::new(environment));
+                        }
+
+                        analysis.add_code_gen(ImportMetaRefVc::new(AstPathVc::cell(ast_path)));
+                    }
+                    Effect::Url {
+                        input,
+                        ast_path,
+                        span,
+                    } => {
+                        if worker_url_spans.contains(&span) {
+                            continue;
+                        }
+                        let input = link_value(input).await?;
+                        if let Some(request) = input.as_str() {
+                            analysis.add_reference(UrlAssetReferenceVc::new(
+                                origin,
+                                RequestVc::parse(Value::new(request.to_string().into())),
+                                environment,
+                                AstPathVc::cell(ast_path),
+                            ));
+                            if !is_browser {
+                                rewritten_url_spans.push(span);
+                            }
+                        } else {
+                            let (args, hints) = JsValue::explain_args(&[input], 10, 2);
+                            handler.span_warn_with_code(
+                                span,
+                                &format!(
+                                    "new URL({args}, import.meta.url) is not statically \
+                                     analyse-able{hints}",
+                                ),
+                                DiagnosticId::Error(
+                                    errors::failed_to_analyse::ecmascript::NEW_URL_IMPORT_META
+                                        .to_string(),
+                                ),
+                            );
+                        }
+                    }
+                    Effect::Worker {
+                        input,
+                        ast_path,
+                        span,
+                    } => {
+                        // Non-constant inputs are reported by the `new URL(...)` effect
+                        let input = link_value(input).await?;
+                        if let Some(request) = input.as_str() {
+                            worker_url_spans.insert(span);
+                            analysis.add_reference(WorkerAssetReferenceVc::new(
+                                origin,
+                                RequestVc::parse(Value::new(request.to_string().into())),
+                                AstPathVc::cell(ast_path),
+                            ));
+                        }
                     }
                 }
             }
@@ -3,7 +3,9 @@ pub mod node_native_binding;
 use anyhow::Result;
 use turbopack_core::resolve::{
     handle_resolve_error,
-    options::{ConditionValue, ResolveIntoPackage, ResolveOptions, ResolveOptionsVc},
+    options::{
+        ConditionValue, ResolveInPackage, ResolveIntoPackage, ResolveOptions, ResolveOptionsVc,
+    },
     origin::ResolveOriginVc,
     parse::RequestVc,
     ResolveResultVc,
@@ -21,6 +23,15 @@ pub async fn apply_esm_specific_options(options: ResolveOptionsVc) -> Result<Res
             ResolveIntoPackage::MainField(_) | ResolveIntoPackage::Default(_) => {}
         }
     }
+    for item in options.in_package.iter_mut() {
+        match item {
+            ResolveInPackage::ImportsField { conditions, .. } => {
+                conditions.insert("import".to_string(), ConditionValue::Set);
+                conditions.insert("require".to_string(), ConditionValue::Unset);
+            }
+            ResolveInPackage::AliasField(_) => {}
+        }
+    }
     Ok(options.into())
 }
 
@@ -36,6 +47,15 @@ pub async fn apply_cjs_specific_options(options: ResolveOptionsVc) -> Result<Res
             ResolveIntoPackage::MainField(_) | ResolveIntoPackage::Default(_) => {}
         }
     }
+    for item in options.in_package.iter_mut() {
+        match item {
+            ResolveInPackage::ImportsField { conditions, .. } => {
+                conditions.insert("import".to_string(), ConditionValue::Unset);
+                conditions.insert("require".to_string(), ConditionValue::Set);
+            }
+            ResolveInPackage::AliasField(_) => {}
+        }
+    }
     Ok(options.into())
 }
 
@@ -61,3 +81,11 @@ async fn specific_resolve(
 
     handle_resolve_error(result, request_type, origin, request, options).await
 }
+
+#[turbo_tasks::function]
+pub async fn url_resolve(origin: ResolveOriginVc, request: RequestVc) -> Result<ResolveResultVc> {
+    let options = origin.resolve_options();
+    let result = origin.resolve_url(request, options);
+
+    handle_resolve_error(result, "url request", origin, request, options).await
+}
@@ -11,8 +11,8 @@ use turbopack_core::{
     resolve::{
         handle_resolve_error,
         options::{
-            ConditionValue, ImportMap, ImportMapVc, ImportMapping, ResolveIntoPackage,
-            ResolveModules, ResolveOptionsVc,
+            ConditionValue, ImportMap, ImportMapVc, ImportMapping, ResolveInPackage,
+            ResolveIntoPackage, ResolveModules, ResolveOptionsVc,
         },
         origin::ResolveOriginVc,
         parse::{Request, RequestVc},
@@ -320,6 +320,11 @@ async fn apply_typescript_types_options(
             }
         })
         .collect();
+    for item in resolve_options.in_package.iter_mut() {
+        if let ResolveInPackage::ImportsField { conditions, .. } = item {
+            conditions.insert("types".to_string(), ConditionValue::Set);
+        }
+    }
     resolve_options
         .into_package
         .push(ResolveIntoPackage::MainField("types".to_string()));
@@ -30,7 +30,10 @@ use turbopack_core::{
     asset::{AssetContent, AssetContentVc, AssetVc},
     chunk::{dev::DevChunkingContextVc, ChunkableAssetVc},
     context::AssetContextVc,
-    environment::{BrowserEnvironment, EnvironmentIntention, EnvironmentVc, ExecutionEnvironment},
+    environment::{
+        BrowserEnvironment, EdgeFunctionEnvironment, EnvironmentIntention, EnvironmentVc,
+        ExecutionEnvironment, NodeJsEnvironment, EDGE_RESOLVE_CONDITIONS,
+    },
     issue::IssueVc,
     reference::all_referenced_assets,
     source_asset::SourceAssetVc,
@@ -64,6 +67,21 @@ struct SnapshotOptions {
     browserslist: String,
     #[serde(default = "default_entry")]
     entry: String,
+    #[serde(default)]
+    environment: SnapshotEnvironment,
+}
+
+#[derive(Debug, Deserialize)]
+enum SnapshotEnvironment {
+    Browser,
+    NodeJs,
+    EdgeFunction,
+}
+
+impl Default for SnapshotEnvironment {
+    fn default() -> Self {
+        SnapshotEnvironment::Browser
+    }
 }
 
 impl Default for SnapshotOptions {
@@ -71,6 +89,7 @@ impl Default for SnapshotOptions {
         SnapshotOptions {
             browserslist: default_browserslist(),
             entry: default_entry(),
+            environment: Default::default(),
         }
     }
 }
@@ -140,19 +159,42 @@ async fn run_test(resource: String) -> Result<FileSystemPathVc> {
 
     let runtime_entries = maybe_load_env(project_fs.into(), fs_path).await?;
 
-    let env = EnvironmentVc::new(
-        Value::new(ExecutionEnvironment::Browser(
-            // TODO: load more from options.json
-            BrowserEnvironment {
-                dom: true,
-                web_worker: false,
-                service_worker: false,
-                browserslist_query: options.browserslist.to_owned(),
-            }
-            .into(),
-        )),
-        Value::new(EnvironmentIntention::Client),
-    );
+    let env = match options.environment {
+        SnapshotEnvironment::Browser => EnvironmentVc::new(
+            Value::new(ExecutionEnvironment::Browser(
+                // TODO: load more from options.json
+                BrowserEnvironment {
+                    dom: true,
+                    web_worker: false,
+                    service_worker: false,
+                    browserslist_query: options.browserslist.to_owned(),
+                }
+                .into(),
+            )),
+            Value::new(EnvironmentIntention::Client),
+        ),
+        SnapshotEnvironment::NodeJs => EnvironmentVc::new(
+            Value::new(ExecutionEnvironment::NodeJsLambda(
+                NodeJsEnvironment::default().into(),
+            )),
+            Value::new(EnvironmentIntention::ServerRendering),
+        ),
+        SnapshotEnvironment::EdgeFunction => EnvironmentVc::new(
+            Value::new(ExecutionEnvironment::EdgeFunction(
+                EdgeFunctionEnvironment::default().into(),
+            )),
+            Value::new(EnvironmentIntention::Api),
+        ),
+    };
+
+    let mut custom_conditions = vec!["development".to_string()];
+    if matches!(options.environment, SnapshotEnvironment::EdgeFunction) {
+        custom_conditions.extend(
+            EDGE_RESOLVE_CONDITIONS
+                .iter()
+                .map(|condition| condition.to_string()),
+        );
+    }
 
     let context: AssetContextVc = ModuleAssetContextVc::new(
         TransitionsByNameVc::cell(HashMap::new()),
@@ -168,7 +210,7 @@ async fn run_test(resource: String) -> Result<FileSystemPathVc> {
             enable_typescript: true,
             enable_react: true,
             enable_node_modules: true,
-            custom_conditions: vec!["development".to_string()],
+            custom_conditions,
             ..Default::default()
         }
         .cell(),
@@ -322,18 +364,26 @@ async fn maybe_load_env(
 
 async fn expected(dir: FileSystemPathVc) -> Result<HashSet<FileSystemPathVc>> {
     let mut expected = HashSet::new();
-    let entries = dir.read_dir().await?;
-    if let DirectoryContent::Entries(entries) = &*entries {
-        for (file, entry) in entries {
-            match entry {
-                DirectoryEntry::File(file) => {
-                    expected.insert(*file);
+    let mut dirs = vec![dir];
+    while let Some(dir) = dirs.pop() {
+        let entries = dir.read_dir().await?;
+        if let DirectoryContent::Entries(entries) = &*entries {
+            for (file, entry) in entries {
+                match entry {
+                    DirectoryEntry::File(file) => {
+                        expected.insert(*file);
+                    }
+                    // Chunks of other layers, e. g. of workers, are placed in
+                    // subdirectories
+                    DirectoryEntry::Directory(dir) => {
+                        dirs.push(*dir);
+                    }
+                    _ => bail!(
+                        "expected file at {}, found {:?}",
+                        file,
+                        FileSystemEntryType::from(entry)
+                    ),
                 }
-                _ => bail!(
-                    "expected file at {}, found {:?}",
-                    file,
-                    FileSystemEntryType::from(entry)
-                ),
             }
         }
     }
@@ -1,13 +1,13 @@
 (self.TURBOPACK = self.TURBOPACK || []).push(["output/crates_turbopack-tests_tests_snapshot_basic_async_chunk_input_index_9be35c.js", {
 
-"[project]/crates/turbopack-tests/tests/snapshot/basic/async_chunk/input/index.js (ecmascript)": (function({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, __dirname, m: module, e: exports }) { !function() {
+"[project]/crates/turbopack-tests/tests/snapshot/basic/async_chunk/input/index.js (ecmascript)": (function({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, h: __turbopack_hot__, k: __turbopack_chunk_path__, __dirname, m: module, e: exports }) { !function() {
 
 __turbopack_require__("[project]/crates/turbopack-tests/tests/snapshot/basic/async_chunk/input/import.js/manifest-loader.js")(__turbopack_import__).then(({ foo  })=>{
     foo(true);
 });
 
 }.call(this) }),
-"[project]/crates/turbopack-tests/tests/snapshot/basic/async_chunk/input/import.js/manifest-loader.js": (({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, __dirname }) => (() => {
+"[project]/crates/turbopack-tests/tests/snapshot/basic/async_chunk/input/import.js/manifest-loader.js": (({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, h: __turbopack_hot__, k: __turbopack_chunk_path__, __dirname }) => (() => {
 
 
 __turbopack_export_value__((__turbopack_import__) => {
@@ -21,6 +21,11 @@ __turbopack_export_value__((__turbopack_import__) => {
     instantiateRuntimeModule("[project]/crates/turbopack-tests/tests/snapshot/basic/async_chunk/input/index.js (ecmascript)");
 }]);
 (() => {
+  // This is the development runtime. Chunking contexts without hot module
+  // replacement, e. g. of production builds, use it without the parts between
+  // `// #if hmr` and `// #endif`, which only register chunks and instantiate
+  // modules.
+  //
   // When a chunk is executed, it will either register itself with the current
   // instance of the runtime, or it will push itself onto the list of pending
   // chunks (`self.TURBOPACK`).
@@ -63,6 +68,10 @@ __turbopack_export_value__((__turbopack_import__) => {
   /** @typedef {import('../types/runtime').Loader} Loader */
   /** @typedef {import('../types/runtime').ModuleEffect} ModuleEffect */
 
+  // Top-level `this` is only undefined when the chunk has been loaded as a
+  // module, e. g. in a module worker.
+  const isModule = this === undefined;
+
   /** @type {ChunkRegistration[]} */
   const chunksToRegister = self.TURBOPACK;
   /** @type {Array<Runnable>} */
@@ -71,6 +80,13 @@ __turbopack_export_value__((__turbopack_import__) => {
   const moduleFactories = { __proto__: null };
   /** @type {Object.<ModuleId, Module>} */
   const moduleCache = { __proto__: null };
+  /**
+   * Maps module IDs to the chunk their factory has been registered from.
+   * Modules derive `import.meta.url` from it.
+   *
+   * @type {Object.<ModuleId, ChunkPath>}
+   */
+  const moduleChunkPaths = { __proto__: null };
   /**
    * Contains the IDs of all chunks that have been loaded.
    *
@@ -83,6 +99,7 @@ __turbopack_export_value__((__turbopack_import__) => {
    * @type {Map<ChunkPath, Loader>}
    */
   const chunkLoaders = new Map();
+  // #if hmr
   /**
    * Maps module IDs to persisted data between executions of their hot module
    * implementation (`hot.data`).
@@ -110,6 +127,7 @@ __turbopack_export_value__((__turbopack_import__) => {
    * from chunk A, but still exists in chunk B.
    */
   const moduleChunksMap = new Map();
+  // #endif
   const hOP = Object.prototype.hasOwnProperty;
   const _process =
     typeof process !== "undefined"
@@ -272,6 +290,32 @@ __turbopack_export_value__((__turbopack_import__) => {
     chunkLoaders.set(chunkPath, chunkLoader);
 
     if (typeof document === "undefined") {
+      if (typeof EdgeRuntime === "string") {
+        // Edge functions can't load scripts at runtime. Their host evaluates
+        // all chunks upfront, so a chunk that isn't registered yet is not
+        // part of the function. CSS chunks have no effect outside the browser.
+        if (chunkPath.endsWith(".css")) {
+          onLoad();
+        } else {
+          onError();
+        }
+        return chunkLoader;
+      }
+      if (typeof importScripts === "function" && chunkPath.endsWith(".js")) {
+        // Web workers load chunks with `importScripts`, module workers don't
+        // support it and import them instead. The chunk is marked as loaded
+        // once it registers itself in `registerChunk`.
+        if (isModule) {
+          import(`/${chunkPath}`).catch(onError);
+        } else {
+          try {
+            importScripts(`/${chunkPath}`);
+          } catch (err) {
+            onError();
+          }
+        }
+        return chunkLoader;
+      }
       throw new Error(
         "Loading chunks outside the browser is not currently supported. If using next/dynamic, try opting out of ssr for now: https://nextjs.org/docs/advanced-features/dynamic-import#with-no-ssr"
       );
@@ -351,20 +395,25 @@ __turbopack_export_value__((__turbopack_import__) => {
       );
     }
 
+    // #if hmr
     const hotData = moduleHotData.get(id);
     const { hot, hotState } = createModuleHot(hotData);
+    // #endif
 
     /** @type {Module} */
     const module = {
       exports: {},
       loaded: false,
       id,
+      interopNamespace: undefined,
+      // #if hmr
       parents: [],
       children: [],
-      interopNamespace: undefined,
       hot,
+      // #endif
     };
     moduleCache[id] = module;
+    // #if hmr
     moduleHotState.set(module, hotState);
 
     if (sourceType === SourceType.Runtime) {
@@ -375,6 +424,7 @@ __turbopack_export_value__((__turbopack_import__) => {
       // No need to add this module as a child of the parent module here, this
       // has already been taken care of in `getOrInstantiateModuleFromParent`.
     }
+    // #endif
 
     runModuleExecutionHooks(module, () => {
       moduleFactory.call(module.exports, {
@@ -389,6 +439,10 @@ __turbopack_export_value__((__turbopack_import__) => {
         l: loadChunk,
         p: _process,
         g: globalThis,
+        // #if hmr
+        h: hot,
+        // #endif
+        k: moduleChunkPaths[id],
         __dirname: module.id.replace(/(^|\/)[\/]+$/, ""),
       });
     });
@@ -411,13 +465,16 @@ __turbopack_export_value__((__turbopack_import__) => {
    * @param {() => void} executeModule
    */
   function runModuleExecutionHooks(module, executeModule) {
+    // #if hmr
     const cleanupReactRefreshIntercept =
       typeof self.$RefreshInterceptModuleExecution$ === "function"
         ? self.$RefreshInterceptModuleExecution$(module.id)
         : () => {};
+    // #endif
 
     executeModule();
 
+    // #if hmr
     if ("$RefreshHelpers$" in self) {
       // This pattern can also be used to register the exports of
       // a module with the React Refresh runtime.
@@ -428,6 +485,7 @@ __turbopack_export_value__((__turbopack_import__) => {
     }
 
     cleanupReactRefreshIntercept();
+    // #endif
   }
 
   /**
@@ -438,22 +496,28 @@ __turbopack_export_value__((__turbopack_import__) => {
    * @returns {Module}
    */
   function getOrInstantiateModuleFromParent(id, sourceModule) {
+    // #if hmr
     if (!sourceModule.hot.active) {
       console.warn(
         `Unexpected import of module ${id} from module ${sourceModule.id}, which was deleted by an HMR update`
       );
     }
+    // #endif
 
     const module = moduleCache[id];
 
+    // #if hmr
     if (sourceModule.children.indexOf(id) === -1) {
       sourceModule.children.push(id);
     }
+    // #endif
 
     if (module) {
+      // #if hmr
       if (module.parents.indexOf(sourceModule.id) === -1) {
         module.parents.push(sourceModule.id);
       }
+      // #endif
 
       return module;
     }
@@ -461,6 +525,7 @@ __turbopack_export_value__((__turbopack_import__) => {
     return instantiateModule(id, SourceType.Parent, sourceModule.id);
   }
 
+  // #if hmr
   /**
    * This is adapted from https://github.com/vercel/next.js/blob/3466862d9dc9c8bb3131712134d38757b918d1c0/packages/react-refresh-utils/internal/ReactRefreshModule.runtime.ts
    *
@@ -694,6 +759,7 @@ __turbopack_export_value__((__turbopack_import__) => {
     // Update module factories.
     for (const [moduleId, factory] of newModuleFactories.entries()) {
       moduleFactories[moduleId] = factory;
+      moduleChunkPaths[moduleId] = chunkPath;
       addModuleToChunk(moduleId, chunkPath);
     }
 
@@ -956,6 +1022,7 @@ __turbopack_export_value__((__turbopack_import__) => {
     moduleChunksMap.delete(moduleId);
     return true;
   }
+  // #endif
 
   /**
    * Instantiates a runtime module.
@@ -969,6 +1036,7 @@ __turbopack_export_value__((__turbopack_import__) => {
     return instantiateModule(moduleId, SourceType.Runtime);
   }
 
+  // #if hmr
   /**
    * Subscribes to chunk updates from the update server and applies them.
    *
@@ -981,6 +1049,7 @@ __turbopack_export_value__((__turbopack_import__) => {
       handleApply.bind(null, chunkPath),
     ]);
   }
+  // #endif
 
   function markChunkAsLoaded(chunkPath) {
     loadedChunks.add(chunkPath);
@@ -1009,19 +1078,26 @@ __turbopack_export_value__((__turbopack_import__) => {
    */
   function registerChunk([chunkPath, chunkModules, ...run]) {
     markChunkAsLoaded(chunkPath);
+    // #if hmr
     subscribeToChunkUpdates(chunkPath);
+    // #endif
     for (const [moduleId, moduleFactory] of Object.entries(chunkModules)) {
       if (!moduleFactories[moduleId]) {
         moduleFactories[moduleId] = moduleFactory;
+        moduleChunkPaths[moduleId] = chunkPath;
       }
+      // #if hmr
       addModuleToChunk(moduleId, chunkPath);
+      // #endif
     }
     runnable.push(...run);
     runnable = runnable.filter((r) => r(runtime));
   }
 
+  // #if hmr
   self.TURBOPACK_CHUNK_UPDATE_LISTENERS =
     self.TURBOPACK_CHUNK_UPDATE_LISTENERS || [];
+  // #endif
   self.TURBOPACK = { push: registerChunk };
   chunksToRegister.forEach(registerChunk);
 })();
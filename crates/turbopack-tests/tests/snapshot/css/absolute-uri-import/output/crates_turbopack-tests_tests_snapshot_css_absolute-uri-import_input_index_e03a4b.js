@@ -1,6 +1,6 @@
 (self.TURBOPACK = self.TURBOPACK || []).push(["output/crates_turbopack-tests_tests_snapshot_css_absolute-uri-import_input_index_e03a4b.js", {
 
-"[project]/crates/turbopack-tests/tests/snapshot/css/absolute-uri-import/input/index.js (ecmascript)": (function({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, __dirname, m: module, e: exports }) { !function() {
+"[project]/crates/turbopack-tests/tests/snapshot/css/absolute-uri-import/input/index.js (ecmascript)": (function({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, h: __turbopack_hot__, k: __turbopack_chunk_path__, __dirname, m: module, e: exports }) { !function() {
 
 ;
 
@@ -10,6 +10,11 @@
     instantiateRuntimeModule("[project]/crates/turbopack-tests/tests/snapshot/css/absolute-uri-import/input/index.js (ecmascript)");
 }]);
 (() => {
+  // This is the development runtime. Chunking contexts without hot module
+  // replacement, e. g. of production builds, use it without the parts between
+  // `// #if hmr` and `// #endif`, which only register chunks and instantiate
+  // modules.
+  //
   // When a chunk is executed, it will either register itself with the current
   // instance of the runtime, or it will push itself onto the list of pending
   // chunks (`self.TURBOPACK`).
@@ -52,6 +57,10 @@
   /** @typedef {import('../types/runtime').Loader} Loader */
   /** @typedef {import('../types/runtime').ModuleEffect} ModuleEffect */
 
+  // Top-level `this` is only undefined when the chunk has been loaded as a
+  // module, e. g. in a module worker.
+  const isModule = this === undefined;
+
   /** @type {ChunkRegistration[]} */
   const chunksToRegister = self.TURBOPACK;
   /** @type {Array<Runnable>} */
@@ -60,6 +69,13 @@
   const moduleFactories = { __proto__: null };
   /** @type {Object.<ModuleId, Module>} */
   const moduleCache = { __proto__: null };
+  /**
+   * Maps module IDs to the chunk their factory has been registered from.
+   * Modules derive `import.meta.url` from it.
+   *
+   * @type {Object.<ModuleId, ChunkPath>}
+   */
+  const moduleChunkPaths = { __proto__: null };
   /**
    * Contains the IDs of all chunks that have been loaded.
    *
@@ -72,6 +88,7 @@
    * @type {Map<ChunkPath, Loader>}
    */
   const chunkLoaders = new Map();
+  // #if hmr
   /**
    * Maps module IDs to persisted data between executions of their hot module
    * implementation (`hot.data`).
@@ -99,6 +116,7 @@
    * from chunk A, but still exists in chunk B.
    */
   const moduleChunksMap = new Map();
+  // #endif
   const hOP = Object.prototype.hasOwnProperty;
   const _process =
     typeof process !== "undefined"
@@ -261,6 +279,32 @@
     chunkLoaders.set(chunkPath, chunkLoader);
 
     if (typeof document === "undefined") {
+      if (typeof EdgeRuntime === "string") {
+        // Edge functions can't load scripts at runtime. Their host evaluates
+        // all chunks upfront, so a chunk that isn't registered yet is not
+        // part of the function. CSS chunks have no effect outside the browser.
+        if (chunkPath.endsWith(".css")) {
+          onLoad();
+        } else {
+          onError();
+        }
+        return chunkLoader;
+      }
+      if (typeof importScripts === "function" && chunkPath.endsWith(".js")) {
+        // Web workers load chunks with `importScripts`, module workers don't
+        // support it and import them instead. The chunk is marked as loaded
+        // once it registers itself in `registerChunk`.
+        if (isModule) {
+          import(`/${chunkPath}`).catch(onError);
+        } else {
+          try {
+            importScripts(`/${chunkPath}`);
+          } catch (err) {
+            onError();
+          }
+        }
+        return chunkLoader;
+      }
       throw new Error(
         "Loading chunks outside the browser is not currently supported. If using next/dynamic, try opting out of ssr for now: https://nextjs.org/docs/advanced-features/dynamic-import#with-no-ssr"
       );
@@ -340,20 +384,25 @@
       );
     }
 
+    // #if hmr
     const hotData = moduleHotData.get(id);
     const { hot, hotState } = createModuleHot(hotData);
+    // #endif
 
     /** @type {Module} */
     const module = {
       exports: {},
       loaded: false,
       id,
+      interopNamespace: undefined,
+      // #if hmr
       parents: [],
       children: [],
-      interopNamespace: undefined,
       hot,
+      // #endif
     };
     moduleCache[id] = module;
+    // #if hmr
     moduleHotState.set(module, hotState);
 
     if (sourceType === SourceType.Runtime) {
@@ -364,6 +413,7 @@
       // No need to add this module as a child of the parent module here, this
       // has already been taken care of in `getOrInstantiateModuleFromParent`.
     }
+    // #endif
 
     runModuleExecutionHooks(module, () => {
       moduleFactory.call(module.exports, {
@@ -378,6 +428,10 @@
         l: loadChunk,
         p: _process,
         g: globalThis,
+        // #if hmr
+        h: hot,
+        // #endif
+        k: moduleChunkPaths[id],
         __dirname: module.id.replace(/(^|\/)[\/]+$/, ""),
       });
     });
@@ -400,13 +454,16 @@
    * @param {() => void} executeModule
    */
   function runModuleExecutionHooks(module, executeModule) {
+    // #if hmr
     const cleanupReactRefreshIntercept =
       typeof self.$RefreshInterceptModuleExecution$ === "function"
         ? self.$RefreshInterceptModuleExecution$(module.id)
         : () => {};
+    // #endif
 
     executeModule();
 
+    // #if hmr
     if ("$RefreshHelpers$" in self) {
       // This pattern can also be used to register the exports of
       // a module with the React Refresh runtime.
@@ -417,6 +474,7 @@
     }
 
     cleanupReactRefreshIntercept();
+    // #endif
   }
 
   /**
@@ -427,22 +485,28 @@
    * @returns {Module}
    */
   function getOrInstantiateModuleFromParent(id, sourceModule) {
+    // #if hmr
     if (!sourceModule.hot.active) {
       console.warn(
         `Unexpected import of module ${id} from module ${sourceModule.id}, which was deleted by an HMR update`
       );
     }
+    // #endif
 
     const module = moduleCache[id];
 
+    // #if hmr
     if (sourceModule.children.indexOf(id) === -1) {
       sourceModule.children.push(id);
     }
+    // #endif
 
     if (module) {
+      // #if hmr
       if (module.parents.indexOf(sourceModule.id) === -1) {
         module.parents.push(sourceModule.id);
       }
+      // #endif
 
       return module;
     }
@@ -450,6 +514,7 @@
     return instantiateModule(id, SourceType.Parent, sourceModule.id);
   }
 
+  // #if hmr
   /**
    * This is adapted from https://github.com/vercel/next.js/blob/3466862d9dc9c8bb3131712134d38757b918d1c0/packages/react-refresh-utils/internal/ReactRefreshModule.runtime.ts
    *
@@ -683,6 +748,7 @@
     // Update module factories.
     for (const [moduleId, factory] of newModuleFactories.entries()) {
       moduleFactories[moduleId] = factory;
+      moduleChunkPaths[moduleId] = chunkPath;
       addModuleToChunk(moduleId, chunkPath);
     }
 
@@ -945,6 +1011,7 @@
     moduleChunksMap.delete(moduleId);
     return true;
   }
+  // #endif
 
   /**
    * Instantiates a runtime module.
@@ -958,6 +1025,7 @@
     return instantiateModule(moduleId, SourceType.Runtime);
   }
 
+  // #if hmr
   /**
    * Subscribes to chunk updates from the update server and applies them.
    *
@@ -970,6 +1038,7 @@
       handleApply.bind(null, chunkPath),
     ]);
   }
+  // #endif
 
   function markChunkAsLoaded(chunkPath) {
     loadedChunks.add(chunkPath);
@@ -998,19 +1067,26 @@
    */
   function registerChunk([chunkPath, chunkModules, ...run]) {
     markChunkAsLoaded(chunkPath);
+    // #if hmr
     subscribeToChunkUpdates(chunkPath);
+    // #endif
     for (const [moduleId, moduleFactory] of Object.entries(chunkModules)) {
       if (!moduleFactories[moduleId]) {
         moduleFactories[moduleId] = moduleFactory;
+        moduleChunkPaths[moduleId] = chunkPath;
       }
+      // #if hmr
       addModuleToChunk(moduleId, chunkPath);
+      // #endif
     }
     runnable.push(...run);
     runnable = runnable.filter((r) => r(runtime));
   }
 
+  // #if hmr
   self.TURBOPACK_CHUNK_UPDATE_LISTENERS =
     self.TURBOPACK_CHUNK_UPDATE_LISTENERS || [];
+  // #endif
   self.TURBOPACK = { push: registerChunk };
   chunksToRegister.forEach(registerChunk);
 })();
@@ -1,6 +1,6 @@
 (self.TURBOPACK = self.TURBOPACK || []).push(["output/crates_turbopack-tests_tests_snapshot_css_css_input_index_907a50.js", {
 
-"[project]/crates/turbopack-tests/tests/snapshot/css/css/input/index.js (ecmascript)": (({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, __dirname }) => (() => {
+"[project]/crates/turbopack-tests/tests/snapshot/css/css/input/index.js (ecmascript)": (({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, h: __turbopack_hot__, k: __turbopack_chunk_path__, __dirname }) => (() => {
 
 var __TURBOPACK__imported__module__$5b$project$5d2f$crates$2f$turbopack$2d$tests$2f$tests$2f$snapshot$2f$css$2f$css$2f$input$2f$node_modules$2f$foo$2f$style$2e$module$2e$css__ = __turbopack_import__("[project]/crates/turbopack-tests/tests/snapshot/css/css/input/node_modules/foo/style.module.css (css module)");
 var __TURBOPACK__imported__module__$5b$project$5d2f$crates$2f$turbopack$2d$tests$2f$tests$2f$snapshot$2f$css$2f$css$2f$input$2f$style$2e$module$2e$css__ = __turbopack_import__("[project]/crates/turbopack-tests/tests/snapshot/css/css/input/style.module.css (css module)");
@@ -13,7 +13,7 @@ var __TURBOPACK__imported__module__$5b$project$5d2f$crates$2f$turbopack$2d$tests
 console.log(__TURBOPACK__imported__module__$5b$project$5d2f$crates$2f$turbopack$2d$tests$2f$tests$2f$snapshot$2f$css$2f$css$2f$input$2f$style$2e$module$2e$css__["default"], __TURBOPACK__imported__module__$5b$project$5d2f$crates$2f$turbopack$2d$tests$2f$tests$2f$snapshot$2f$css$2f$css$2f$input$2f$node_modules$2f$foo$2f$style$2e$module$2e$css__["default"]);
 
 })()),
-"[project]/crates/turbopack-tests/tests/snapshot/css/css/input/style.module.css (css module)": (({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, __dirname }) => (() => {
+"[project]/crates/turbopack-tests/tests/snapshot/css/css/input/style.module.css (css module)": (({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, h: __turbopack_hot__, k: __turbopack_chunk_path__, __dirname }) => (() => {
 
 __turbopack_export_value__({
   "inner": "inner◽[project]/crates/turbopack-tests/tests/snapshot/css/css/input/style.module.css",
@@ -26,6 +26,11 @@ __turbopack_export_value__({
     instantiateRuntimeModule("[project]/crates/turbopack-tests/tests/snapshot/css/css/input/index.js (ecmascript)");
 }]);
 (() => {
+  // This is the development runtime. Chunking contexts without hot module
+  // replacement, e. g. of production builds, use it without the parts between
+  // `// #if hmr` and `// #endif`, which only register chunks and instantiate
+  // modules.
+  //
   // When a chunk is executed, it will either register itself with the current
   // instance of the runtime, or it will push itself onto the list of pending
   // chunks (`self.TURBOPACK`).
@@ -68,6 +73,10 @@ __turbopack_export_value__({
   /** @typedef {import('../types/runtime').Loader} Loader */
   /** @typedef {import('../types/runtime').ModuleEffect} ModuleEffect */
 
+  // Top-level `this` is only undefined when the chunk has been loaded as a
+  // module, e. g. in a module worker.
+  const isModule = this === undefined;
+
   /** @type {ChunkRegistration[]} */
   const chunksToRegister = self.TURBOPACK;
   /** @type {Array<Runnable>} */
@@ -76,6 +85,13 @@ __turbopack_export_value__({
   const moduleFactories = { __proto__: null };
   /** @type {Object.<ModuleId, Module>} */
   const moduleCache = { __proto__: null };
+  /**
+   * Maps module IDs to the chunk their factory has been registered from.
+   * Modules derive `import.meta.url` from it.
+   *
+   * @type {Object.<ModuleId, ChunkPath>}
+   */
+  const moduleChunkPaths = { __proto__: null };
   /**
    * Contains the IDs of all chunks that have been loaded.
    *
@@ -88,6 +104,7 @@ __turbopack_export_value__({
    * @type {Map<ChunkPath, Loader>}
    */
   const chunkLoaders = new Map();
+  // #if hmr
   /**
    * Maps module IDs to persisted data between executions of their hot module
    * implementation (`hot.data`).
@@ -115,6 +132,7 @@ __turbopack_export_value__({
    * from chunk A, but still exists in chunk B.
    */
   const moduleChunksMap = new Map();
+  // #endif
   const hOP = Object.prototype.hasOwnProperty;
   const _process =
     typeof process !== "undefined"
@@ -277,6 +295,32 @@ __turbopack_export_value__({
     chunkLoaders.set(chunkPath, chunkLoader);
 
     if (typeof document === "undefined") {
+      if (typeof EdgeRuntime === "string") {
+        // Edge functions can't load scripts at runtime. Their host evaluates
+        // all chunks upfront, so a chunk that isn't registered yet is not
+        // part of the function. CSS chunks have no effect outside the browser.
+        if (chunkPath.endsWith(".css")) {
+          onLoad();
+        } else {
+          onError();
+        }
+        return chunkLoader;
+      }
+      if (typeof importScripts === "function" && chunkPath.endsWith(".js")) {
+        // Web workers load chunks with `importScripts`, module workers don't
+        // support it and import them instead. The chunk is marked as loaded
+        // once it registers itself in `registerChunk`.
+        if (isModule) {
+          import(`/${chunkPath}`).catch(onError);
+        } else {
+          try {
+            importScripts(`/${chunkPath}`);
+          } catch (err) {
+            onError();
+          }
+        }
+        return chunkLoader;
+      }
       throw new Error(
         "Loading chunks outside the browser is not currently supported. If using next/dynamic, try opting out of ssr for now: https://nextjs.org/docs/advanced-features/dynamic-import#with-no-ssr"
       );
@@ -356,20 +400,25 @@ __turbopack_export_value__({
       );
     }
 
+    // #if hmr
     const hotData = moduleHotData.get(id);
     const { hot, hotState } = createModuleHot(hotData);
+    // #endif
 
     /** @type {Module} */
     const module = {
       exports: {},
       loaded: false,
       id,
+      interopNamespace: undefined,
+      // #if hmr
       parents: [],
       children: [],
-      interopNamespace: undefined,
       hot,
+      // #endif
     };
     moduleCache[id] = module;
+    // #if hmr
     moduleHotState.set(module, hotState);
 
     if (sourceType === SourceType.Runtime) {
@@ -380,6 +429,7 @@ __turbopack_export_value__({
       // No need to add this module as a child of the parent module here, this
       // has already been taken care of in `getOrInstantiateModuleFromParent`.
     }
+    // #endif
 
     runModuleExecutionHooks(module, () => {
       moduleFactory.call(module.exports, {
@@ -394,6 +444,10 @@ __turbopack_export_value__({
         l: loadChunk,
         p: _process,
         g: globalThis,
+        // #if hmr
+        h: hot,
+        // #endif
+        k: moduleChunkPaths[id],
         __dirname: module.id.replace(/(^|\/)[\/]+$/, ""),
       });
     });
@@ -416,13 +470,16 @@ __turbopack_export_value__({
    * @param {() => void} executeModule
    */
   function runModuleExecutionHooks(module, executeModule) {
+    // #if hmr
     const cleanupReactRefreshIntercept =
       typeof self.$RefreshInterceptModuleExecution$ === "function"
         ? self.$RefreshInterceptModuleExecution$(module.id)
         : () => {};
+    // #endif
 
     executeModule();
 
+    // #if hmr
     if ("$RefreshHelpers$" in self) {
       // This pattern can also be used to register the exports of
       // a module with the React Refresh runtime.
@@ -433,6 +490,7 @@ __turbopack_export_value__({
     }
 
     cleanupReactRefreshIntercept();
+    // #endif
   }
 
   /**
@@ -443,22 +501,28 @@ __turbopack_export_value__({
    * @returns {Module}
    */
   function getOrInstantiateModuleFromParent(id, sourceModule) {
+    // #if hmr
     if (!sourceModule.hot.active) {
       console.warn(
         `Unexpected import of module ${id} from module ${sourceModule.id}, which was deleted by an HMR update`
       );
     }
+    // #endif
 
     const module = moduleCache[id];
 
+    // #if hmr
     if (sourceModule.children.indexOf(id) === -1) {
       sourceModule.children.push(id);
     }
+    // #endif
 
     if (module) {
+      // #if hmr
       if (module.parents.indexOf(sourceModule.id) === -1) {
         module.parents.push(sourceModule.id);
       }
+      // #endif
 
       return module;
     }
@@ -466,6 +530,7 @@ __turbopack_export_value__({
     return instantiateModule(id, SourceType.Parent, sourceModule.id);
   }
 
+  // #if hmr
   /**
    * This is adapted from https://github.com/vercel/next.js/blob/3466862d9dc9c8bb3131712134d38757b918d1c0/packages/react-refresh-utils/internal/ReactRefreshModule.runtime.ts
    *
@@ -699,6 +764,7 @@ __turbopack_export_value__({
     // Update module factories.
     for (const [moduleId, factory] of newModuleFactories.entries()) {
       moduleFactories[moduleId] = factory;
+      moduleChunkPaths[moduleId] = chunkPath;
       addModuleToChunk(moduleId, chunkPath);
     }
 
@@ -961,6 +1027,7 @@ __turbopack_export_value__({
     moduleChunksMap.delete(moduleId);
     return true;
   }
+  // #endif
 
   /**
    * Instantiates a runtime module.
@@ -974,6 +1041,7 @@ __turbopack_export_value__({
     return instantiateModule(moduleId, SourceType.Runtime);
   }
 
+  // #if hmr
   /**
    * Subscribes to chunk updates from the update server and applies them.
    *
@@ -986,6 +1054,7 @@ __turbopack_export_value__({
       handleApply.bind(null, chunkPath),
     ]);
   }
+  // #endif
 
   function markChunkAsLoaded(chunkPath) {
     loadedChunks.add(chunkPath);
@@ -1014,19 +1083,26 @@ __turbopack_export_value__({
    */
   function registerChunk([chunkPath, chunkModules, ...run]) {
     markChunkAsLoaded(chunkPath);
+    // #if hmr
     subscribeToChunkUpdates(chunkPath);
+    // #endif
     for (const [moduleId, moduleFactory] of Object.entries(chunkModules)) {
       if (!moduleFactories[moduleId]) {
         moduleFactories[moduleId] = moduleFactory;
+        moduleChunkPaths[moduleId] = chunkPath;
       }
+      // #if hmr
       addModuleToChunk(moduleId, chunkPath);
+      // #endif
     }
     runnable.push(...run);
     runnable = runnable.filter((r) => r(runtime));
   }
 
+  // #if hmr
   self.TURBOPACK_CHUNK_UPDATE_LISTENERS =
     self.TURBOPACK_CHUNK_UPDATE_LISTENERS || [];
+  // #endif
   self.TURBOPACK = { push: registerChunk };
   chunksToRegister.forEach(registerChunk);
 })();
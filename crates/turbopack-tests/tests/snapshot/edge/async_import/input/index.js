@@ -0,0 +1,3 @@
+import("./import").then(({ foo }) => {
+  foo(true);
+});
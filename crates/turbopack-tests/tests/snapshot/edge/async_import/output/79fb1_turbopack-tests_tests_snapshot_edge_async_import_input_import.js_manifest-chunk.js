@@ -0,0 +1,12 @@
+(self.TURBOPACK = self.TURBOPACK || []).push(["output/79fb1_turbopack-tests_tests_snapshot_edge_async_import_input_import.js_manifest-chunk.js", {
+
+"[project]/crates/turbopack-tests/tests/snapshot/edge/async_import/input/import.js/manifest-chunk.js": (({ r: __turbopack_require__, x: __turbopack_external_require__, i: __turbopack_import__, s: __turbopack_esm__, v: __turbopack_export_value__, c: __turbopack_cache__, l: __turbopack_load__, p: process, g: global, h: __turbopack_hot__, k: __turbopack_chunk_path__, __dirname }) => (() => {
+
+const chunks = [
+    "output/crates_turbopack-tests_tests_snapshot_edge_async_import_input_import.js",
+    "output/7ae72_foo_index.js",
+];
+
+__turbopack_export_value__(Promise.all(chunks.map(__turbopack_load__)));
+})()),
+}]);
\ No newline at end of file
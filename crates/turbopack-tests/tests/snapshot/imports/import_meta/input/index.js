@@ -0,0 +1,3 @@
+console.log(import.meta.url);
+console.log(import.meta.env);
+console.log(import.meta.hot);
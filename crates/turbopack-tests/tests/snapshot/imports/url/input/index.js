@@ -0,0 +1,2 @@
+const asset = new URL("./asset.txt", import.meta.url);
+console.log(asset);
@@ -0,0 +1,12 @@
+const chunks = [
+    "/output/worker/crates_turbopack-tests_tests_snapshot_workers_basic_input_worker_3ccc04.js",
+];
+if (this === undefined) {
+    (async () => {
+        for (const chunk of chunks) {
+            await import(chunk);
+        }
+    })();
+} else {
+    importScripts(...chunks);
+}
@@ -34,7 +34,10 @@ use turbopack_core::{
     asset::AssetVc,
     context::{AssetContext, AssetContextVc},
     environment::EnvironmentVc,
-    issue::{unsupported_module::UnsupportedModuleIssue, Issue, IssueVc},
+    issue::{
+        unsupported_module::{UnsupportedModuleIssue, UnsupportedNodeBuiltinIssue},
+        Issue, IssueVc,
+    },
     reference::all_referenced_assets,
     resolve::{
         options::ResolveOptionsVc,
@@ -58,6 +61,7 @@ use turbopack_json::JsonModuleAssetVc;
 use turbopack_static::StaticModuleAssetVc;
 
 use self::{
+    resolve::is_node_builtin,
     resolve_options_context::ResolveOptionsContextVc,
     transition::{TransitionVc, TransitionsByNameVc},
 };
@@ -307,6 +311,13 @@ impl AssetContext for ModuleAssetContext {
         let context_path = origin_path.parent().resolve().await?;
 
         let result = resolve(context_path, request, resolve_options);
+        error_on_unsupported_node_builtins(
+            self_vc.await?.environment,
+            request,
+            origin_path,
+            result,
+        )
+        .await?;
         let result = self_vc.process_resolve_result(result);
 
         if *self_vc.is_typescript_resolving_enabled().await? {
@@ -362,6 +373,11 @@ impl AssetContext for ModuleAssetContext {
         }
     }
 
+    #[turbo_tasks::function]
+    fn process_url(self_vc: ModuleAssetContextVc, asset: AssetVc) -> AssetVc {
+        StaticModuleAssetVc::new(asset, self_vc.into()).into()
+    }
+
     #[turbo_tasks::function]
     async fn with_transition(&self, transition: &str) -> Result<AssetContextVc> {
         Ok(
@@ -551,6 +567,38 @@ async fn warn_on_unsupported_modules(
     Ok(())
 }
 
+/// Edge functions don't provide Node.js builtins, so importing one only works
+/// when a package polyfills it.
+async fn error_on_unsupported_node_builtins(
+    environment: EnvironmentVc,
+    request: RequestVc,
+    origin_path: FileSystemPathVc,
+    result: ResolveResultVc,
+) -> Result<()> {
+    if !*environment.is_edge_function().await? {
+        return Ok(());
+    }
+    let module = match &*request.await? {
+        Request::Module { module, .. } => module.clone(),
+        Request::Uri {
+            protocol,
+            remainder,
+        } => format!("{protocol}{remainder}"),
+        _ => return Ok(()),
+    };
+    if is_node_builtin(&module) && *result.is_unresolveable().await? {
+        UnsupportedNodeBuiltinIssue {
+            context: origin_path,
+            module,
+        }
+        .cell()
+        .as_issue()
+        .emit();
+    }
+
+    Ok(())
+}
+
 pub fn register() {
     turbo_tasks::register();
     turbo_tasks_fs::register();
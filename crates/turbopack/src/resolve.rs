@@ -1,7 +1,7 @@
 use std::collections::BTreeMap;
 
 use anyhow::Result;
-use turbo_tasks_fs::FileSystemPathVc;
+use turbo_tasks_fs::{archive::ArchiveFileSystemVc, FileSystemEntryType, FileSystemPathVc};
 use turbopack_core::resolve::{
     find_context_file,
     options::{
@@ -71,6 +71,31 @@ const NODE_EXTERNALS: [&str; 51] = [
     "pnpapi",
 ];
 
+/// Returns whether `module` is a Node.js builtin, e. g. `fs` or
+/// `node:fs/promises`.
+pub(crate) fn is_node_builtin(module: &str) -> bool {
+    let module = module.strip_prefix("node:").unwrap_or(module);
+    module != "pnpapi" && NODE_EXTERNALS.contains(&module)
+}
+
+/// Packages are resolved from `node_modules`, or from the archives in
+/// `.yarn/cache` when the project uses Yarn Plug'n'Play.
+///
+/// The root of a package inside of an archive is the root of the archive, so
+/// its dependencies are resolved from the archives next to it instead.
+async fn node_modules(root: FileSystemPathVc) -> Result<Vec<ResolveModules>> {
+    let mut modules = vec![ResolveModules::Nested(
+        root,
+        vec!["node_modules".to_string()],
+    )];
+    if let Some(archive_fs) = ArchiveFileSystemVc::resolve_from(root.await?.fs).await? {
+        modules.push(ResolveModules::Archives(archive_fs.archive().parent()));
+    } else if *root.join(".pnp.cjs").get_type().await? == FileSystemEntryType::File {
+        modules.push(ResolveModules::Archives(root.join(".yarn/cache")));
+    }
+    Ok(modules)
+}
+
 #[turbo_tasks::function]
 async fn base_resolve_options(
     context: FileSystemPathVc,
@@ -110,6 +135,52 @@ async fn base_resolve_options(
     }
     let import_map = import_map.cell();
 
+    let conditions = {
+        let mut conditions: BTreeMap<String, ConditionValue> = [
+            ("import".to_string(), ConditionValue::Unknown),
+            ("require".to_string(), ConditionValue::Unknown),
+        ]
+        .into_iter()
+        .collect();
+        if opt.browser {
+            conditions.insert("browser".to_string(), ConditionValue::Set);
+        }
+        if opt.module {
+            conditions.insert("module".to_string(), ConditionValue::Set);
+        }
+        if let Some(environment) = emulating {
+            for condition in environment.resolve_conditions().await?.iter() {
+                conditions.insert(condition.to_string(), ConditionValue::Set);
+            }
+        }
+        for condition in opt.custom_conditions.iter() {
+            conditions.insert(condition.to_string(), ConditionValue::Set);
+        }
+        // Infer some well known conditions
+        let dev = conditions.get("development").cloned();
+        let prod = conditions.get("production").cloned();
+        if prod.is_none() {
+            conditions.insert(
+                "production".to_string(),
+                if matches!(dev, Some(ConditionValue::Set)) {
+                    ConditionValue::Unset
+                } else {
+                    ConditionValue::Unknown
+                },
+            );
+        }
+        if dev.is_none() {
+            conditions.insert(
+                "development".to_string(),
+                if matches!(prod, Some(ConditionValue::Set)) {
+                    ConditionValue::Unset
+                } else {
+                    ConditionValue::Unknown
+                },
+            );
+        }
+        conditions
+    };
     Ok(ResolveOptions {
         extensions: if let Some(environment) = emulating {
             environment.resolve_extensions().await?.clone_value()
@@ -133,20 +204,14 @@ async fn base_resolve_options(
         },
         modules: if let Some(environment) = emulating {
             if *environment.resolve_node_modules().await? {
-                vec![ResolveModules::Nested(
-                    root,
-                    vec!["node_modules".to_string()],
-                )]
+                node_modules(root).await?
             } else {
                 Vec::new()
             }
         } else {
             let mut mods = Vec::new();
             if opt.enable_node_modules {
-                mods.push(ResolveModules::Nested(
-                    root,
-                    vec!["node_modules".to_string()],
-                ));
+                mods.extend(node_modules(root).await?);
             }
             mods
         },
@@ -154,52 +219,7 @@ async fn base_resolve_options(
             let mut resolve_into = Vec::new();
             resolve_into.push(ResolveIntoPackage::ExportsField {
                 field: "exports".to_string(),
-                conditions: {
-                    let mut conditions: BTreeMap<String, ConditionValue> = [
-                        ("import".to_string(), ConditionValue::Unknown),
-                        ("require".to_string(), ConditionValue::Unknown),
-                    ]
-                    .into_iter()
-                    .collect();
-                    if opt.browser {
-                        conditions.insert("browser".to_string(), ConditionValue::Set);
-                    }
-                    if opt.module {
-                        conditions.insert("module".to_string(), ConditionValue::Set);
-                    }
-                    if let Some(environment) = emulating {
-                        for condition in environment.resolve_conditions().await?.iter() {
-                            conditions.insert(condition.to_string(), ConditionValue::Set);
-                        }
-                    }
-                    for condition in opt.custom_conditions.iter() {
-                        conditions.insert(condition.to_string(), ConditionValue::Set);
-                    }
-                    // Infer some well known conditions
-                    let dev = conditions.get("development").cloned();
-                    let prod = conditions.get("production").cloned();
-                    if prod.is_none() {
-                        conditions.insert(
-                            "production".to_string(),
-                            if matches!(dev, Some(ConditionValue::Set)) {
-                                ConditionValue::Unset
-                            } else {
-                                ConditionValue::Unknown
-                            },
-                        );
-                    }
-                    if dev.is_none() {
-                        conditions.insert(
-                            "development".to_string(),
-                            if matches!(prod, Some(ConditionValue::Set)) {
-                                ConditionValue::Unset
-                            } else {
-                                ConditionValue::Unknown
-                            },
-                        );
-                    }
-                    conditions
-                },
+                conditions: conditions.clone(),
                 unspecified_conditions: ConditionValue::Unset,
             });
             if opt.browser {
@@ -217,6 +237,11 @@ async fn base_resolve_options(
             if opt.browser {
                 resolve_in.push(ResolveInPackage::AliasField("browser".to_string()));
             }
+            resolve_in.push(ResolveInPackage::ImportsField {
+                field: "imports".to_string(),
+                conditions,
+                unspecified_conditions: ConditionValue::Unset,
+            });
             resolve_in
         },
         import_map: Some(import_map),
@@ -264,3 +289,19 @@ pub async fn resolve_options(
 
     Ok(resolve_options)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_node_builtin;
+
+    #[test]
+    fn detects_node_builtins() {
+        assert!(is_node_builtin("fs"));
+        assert!(is_node_builtin("fs/promises"));
+        assert!(is_node_builtin("node:fs"));
+        assert!(is_node_builtin("node:stream/web"));
+        assert!(!is_node_builtin("pnpapi"));
+        assert!(!is_node_builtin("buffer-polyfill"));
+        assert!(!is_node_builtin("node:foo"));
+    }
+}
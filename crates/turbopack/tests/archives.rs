@@ -0,0 +1,81 @@
+use anyhow::Result;
+use turbo_tasks::{TurboTasks, Value, ValueToString};
+use turbo_tasks_fs::{virtual_fs::VirtualFileSystemVc, FileSystemPathVc, FileSystemVc};
+use turbo_tasks_memory::MemoryBackend;
+use turbopack::{register, resolve_options, resolve_options_context::ResolveOptionsContext};
+use turbopack_core::{
+    asset::Asset,
+    resolve::{parse::RequestVc, pattern::Pattern, resolve},
+};
+
+/// A package tarball like the ones of `npm pack`, without compression
+fn package(name: &str, index: &str) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    let package_json = format!(r#"{{"name":"{name}","main":"index.js"}}"#);
+    for (path, content) in [
+        ("package/package.json", package_json.as_str()),
+        ("package/index.js", index),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, path, content.as_bytes())?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+async fn resolve_package(context: FileSystemPathVc, name: &str) -> Result<Vec<FileSystemPathVc>> {
+    let options = resolve_options(
+        context,
+        ResolveOptionsContext {
+            enable_node_modules: true,
+            ..Default::default()
+        }
+        .cell(),
+    );
+    let request = RequestVc::parse(Value::new(Pattern::Constant(name.to_string())));
+    let assets = resolve(context, request, options).primary_assets().await?;
+    Ok(assets.iter().map(|asset| asset.path()).collect())
+}
+
+#[tokio::test]
+async fn resolves_dependencies_of_archived_packages() {
+    register();
+    let tt = TurboTasks::new(MemoryBackend::new());
+    tt.run_once(async {
+        let vfs = VirtualFileSystemVc::new("project");
+        {
+            let vfs = vfs.await?;
+            vfs.write_file(".pnp.cjs", "")?;
+            vfs.write_file(
+                ".yarn/cache/react-dom-18.2.0.tar",
+                package("react-dom", "require(\"scheduler\");\n")?,
+            )?;
+            vfs.write_file(
+                ".yarn/cache/scheduler-0.23.0.tar",
+                package("scheduler", "")?,
+            )?;
+        }
+        let fs: FileSystemVc = vfs.into();
+
+        let react_dom = resolve_package(fs.root(), "react-dom").await?;
+        assert_eq!(react_dom.len(), 1);
+        assert_eq!(
+            *react_dom[0].to_string().await?,
+            "[[project]/.yarn/cache/react-dom-18.2.0.tar]/index.js"
+        );
+
+        // The root of react-dom is the root of its archive, which doesn't
+        // contain scheduler
+        let scheduler = resolve_package(react_dom[0].parent(), "scheduler").await?;
+        assert_eq!(scheduler.len(), 1);
+        assert_eq!(
+            *scheduler[0].to_string().await?,
+            "[[project]/.yarn/cache/scheduler-0.23.0.tar]/index.js"
+        );
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
@@ -281,6 +281,27 @@ fn node_file_trace_rocksdb(#[case] input: CaseInput) {
     );
 }
 
+#[cfg(feature = "test_persistent_cache_logdb")]
+#[apply(test_cases)]
+fn node_file_trace_logdb(#[case] input: CaseInput) {
+    use turbo_tasks_logdb::LogDbPersistedGraph;
+    use turbo_tasks_memory::MemoryBackendWithPersistedGraph;
+
+    node_file_trace(
+        input,
+        "logdb",
+        false,
+        2,
+        240,
+        |directory_path| {
+            TurboTasks::new(MemoryBackendWithPersistedGraph::new(
+                LogDbPersistedGraph::new(directory_path.join(".db")).unwrap(),
+            ))
+        },
+        |_| {},
+    );
+}
+
 #[cfg(feature = "bench_against_node_nft")]
 #[apply(test_cases)]
 fn bench_against_node_nft_st(#[case] input: CaseInput) {